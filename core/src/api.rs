@@ -0,0 +1,397 @@
+use serde::Deserialize;
+
+use crate::models::{
+    DiaryEntryResponse, DiaryEntrySummary, DiaryListResponse, ErrorResponse, HeldResponse,
+    OverwriteResponse, QuotaExceededResponse, RateLimitedResponse, TodayEmptyResponse,
+    VersionDetailResponse, VersionListResponse, VersionSummary,
+};
+use crate::overwrite::OverwriteCheck;
+use crate::rate_limit::{RateLimitDecision, RateLimitPolicy};
+use crate::reply::Reply;
+use crate::store::DiaryStore;
+use crate::validation::{is_valid_date, MAX_CONTENT_LENGTH};
+use crate::verification::VerificationFailure;
+
+/// POST /api/today の本文
+#[derive(Deserialize)]
+pub struct PostTodayRequest {
+    pub content: String,
+    /// 確認方式ごとのトークン（旧クライアントのturnstile_tokenも受け付ける）
+    #[serde(alias = "turnstile_token")]
+    pub verification_token: Option<String>,
+    /// 人には見えない入力欄（自動投稿が埋めがち）
+    pub website: Option<String>,
+    /// ページを開いた時刻を署名したトークン
+    pub form_token: Option<String>,
+    /// 既存の日記の大半を消す保存であることを書き手が確認済みか
+    #[serde(default)]
+    pub confirm_overwrite: bool,
+}
+
+/// GET /api/today - 今日の日記
+pub async fn today_reply<S: DiaryStore>(store: &S, today: &str) -> serde_json::Result<Reply> {
+    match store.get_entry(today).await {
+        Ok(Some(entry)) => Reply::json(200, &DiaryEntryResponse::from_entry(&entry, true)),
+        Ok(None) => {
+            let response = TodayEmptyResponse {
+                date: today.to_string(),
+                content: None,
+                can_edit: true,
+            };
+            Reply::json(200, &response)
+        }
+        Err(e) => {
+            log::error!("Failed to get today's entry: {:?}", e);
+            Reply::json(500, &ErrorResponse::internal_error())
+        }
+    }
+}
+
+/// GET /api/entries - 過去の日記一覧
+pub async fn entries_reply<S: DiaryStore>(store: &S, today: &str) -> serde_json::Result<Reply> {
+    match store.list_entries_before(today, 100).await {
+        Ok(entries) => {
+            let summaries: Vec<DiaryEntrySummary> = entries
+                .iter()
+                .map(DiaryEntrySummary::from_entry)
+                .collect();
+            Reply::json(200, &DiaryListResponse { entries: summaries })
+        }
+        Err(e) => {
+            log::error!("Failed to list entries: {:?}", e);
+            Reply::json(500, &ErrorResponse::internal_error())
+        }
+    }
+}
+
+/// GET /api/entries/:date - 特定日の日記
+pub async fn entry_reply<S: DiaryStore>(
+    store: &S,
+    date: Option<&str>,
+    today: &str,
+) -> serde_json::Result<Reply> {
+    let Some(date) = date else {
+        return Reply::json(400, &ErrorResponse::bad_request("Date parameter required"));
+    };
+
+    // 日付の形式を検証
+    if !is_valid_date(date) {
+        let body = ErrorResponse::bad_request("Invalid date format. Use YYYY-MM-DD.");
+        return Reply::json(400, &body);
+    }
+
+    match store.get_entry(date).await {
+        Ok(Some(entry)) => {
+            let can_edit = date == today;
+            Reply::json(200, &DiaryEntryResponse::from_entry(&entry, can_edit))
+        }
+        Ok(None) => Reply::json(404, &ErrorResponse::not_found()),
+        Err(e) => {
+            log::error!("Failed to get entry: {:?}", e);
+            Reply::json(500, &ErrorResponse::internal_error())
+        }
+    }
+}
+
+/// GET /api/admin/entries/:date/versions - 今の内容と版の一覧（管理者用）
+pub async fn versions_reply<S: DiaryStore>(
+    store: &S,
+    date: Option<&str>,
+) -> serde_json::Result<Reply> {
+    let Some(date) = date else {
+        return Reply::json(400, &ErrorResponse::bad_request("Date parameter required"));
+    };
+    if !is_valid_date(date) {
+        let body = ErrorResponse::bad_request("Invalid date format. Use YYYY-MM-DD.");
+        return Reply::json(400, &body);
+    }
+
+    let loaded = match store.get_entry(date).await {
+        Ok(current) => store.list_versions(date).await.map(|v| (current, v)),
+        Err(e) => Err(e),
+    };
+    match loaded {
+        Ok((current, versions)) => {
+            let response = VersionListResponse {
+                entry_date: date.to_string(),
+                current_content: current.map(|e| e.content),
+                versions: versions.iter().map(VersionSummary::from_version).collect(),
+            };
+            Reply::json(200, &response)
+        }
+        Err(e) => {
+            log::error!("Failed to list versions: {:?}", e);
+            Reply::json(500, &ErrorResponse::internal_error())
+        }
+    }
+}
+
+/// GET /api/admin/entries/:date/versions/:version - 特定の版（管理者用）
+pub async fn version_reply<S: DiaryStore>(
+    store: &S,
+    date: Option<&str>,
+    version: Option<&str>,
+) -> serde_json::Result<Reply> {
+    let Some(date) = date else {
+        return Reply::json(400, &ErrorResponse::bad_request("Date parameter required"));
+    };
+    let Some(version) = version.and_then(|v| v.parse::<i32>().ok()) else {
+        return Reply::json(400, &ErrorResponse::bad_request("Invalid version number"));
+    };
+    if !is_valid_date(date) {
+        let body = ErrorResponse::bad_request("Invalid date format. Use YYYY-MM-DD.");
+        return Reply::json(400, &body);
+    }
+
+    match store.get_version(date, version).await {
+        Ok(Some(v)) => {
+            let response = VersionDetailResponse {
+                entry_date: v.entry_date,
+                version_number: v.version_number,
+                content: v.content,
+                created_at: v.created_at,
+            };
+            Reply::json(200, &response)
+        }
+        Ok(None) => Reply::json(404, &ErrorResponse::not_found()),
+        Err(e) => {
+            log::error!("Failed to get version: {:?}", e);
+            Reply::json(500, &ErrorResponse::internal_error())
+        }
+    }
+}
+
+/// 判定結果のRateLimit-*ヘッダーを付ける
+fn with_rate_limit_headers(
+    mut reply: Reply,
+    decision: &RateLimitDecision,
+    policy: &RateLimitPolicy,
+) -> Reply {
+    for (name, value) in decision.header_values(policy) {
+        reply = reply.with_header(name, value);
+    }
+    reply
+}
+
+/// レート制限で拒否された場合の429（許可されていればNone）
+pub fn rate_limited_reply(
+    decision: &RateLimitDecision,
+    policy: &RateLimitPolicy,
+) -> serde_json::Result<Option<Reply>> {
+    let RateLimitDecision::Limited {
+        retry_after,
+        reason,
+    } = decision
+    else {
+        return Ok(None);
+    };
+    let body = RateLimitedResponse::new(reason.as_str(), *retry_after);
+    let reply = Reply::json(429, &body)?;
+    Ok(Some(with_rate_limit_headers(reply, decision, policy)))
+}
+
+/// 本文をJSONとして解釈できなかった場合の400
+pub fn invalid_json_reply() -> serde_json::Result<Reply> {
+    Reply::json(400, &ErrorResponse::bad_request("Invalid JSON"))
+}
+
+/// APIキーが未登録・失効済みの場合の401（人間確認にはフォールバックしない）
+pub fn invalid_api_key_reply() -> serde_json::Result<Reply> {
+    let body = ErrorResponse::new("Invalid or revoked API key", "INVALID_API_KEY");
+    Reply::json(401, &body)
+}
+
+/// 保存前の確認に失敗した場合の応答
+pub fn verification_failed_reply(failure: &VerificationFailure) -> serde_json::Result<Reply> {
+    let body = ErrorResponse::new(failure.message, failure.code);
+    Reply::json(failure.status, &body)
+}
+
+/// 本文が長すぎる場合の400
+pub fn content_too_long_reply() -> serde_json::Result<Reply> {
+    Reply::json(
+        400,
+        &ErrorResponse::bad_request(format!(
+            "Content too long. Maximum {} characters allowed.",
+            MAX_CONTENT_LENGTH
+        )),
+    )
+}
+
+/// 大きく消す保存を止める場合の応答（確認がなければ409、待機中なら429）
+pub fn overwrite_reply(check: &OverwriteCheck) -> serde_json::Result<Option<Reply>> {
+    match *check {
+        OverwriteCheck::Allowed | OverwriteCheck::Confirmed { .. } => Ok(None),
+        OverwriteCheck::NeedsConfirmation { deleted_ratio } => {
+            let body = OverwriteResponse::confirmation_required(deleted_ratio);
+            Reply::json(409, &body).map(Some)
+        }
+        OverwriteCheck::Cooldown {
+            deleted_ratio,
+            retry_after,
+        } => {
+            let body = OverwriteResponse::cooldown(deleted_ratio, retry_after);
+            let reply = Reply::json(429, &body)?;
+            Ok(Some(reply.with_header("Retry-After", retry_after.to_string())))
+        }
+    }
+}
+
+/// スパムと判定した保存を拒否する場合の400
+pub fn spam_rejected_reply() -> serde_json::Result<Reply> {
+    let body = ErrorResponse::new("Save rejected as suspected spam", "SPAM_DETECTED");
+    Reply::json(400, &body)
+}
+
+/// APIキーの1日の上限回数に達した場合の429
+pub fn quota_exceeded_reply(daily_quota: i64) -> serde_json::Result<Reply> {
+    Reply::json(429, &QuotaExceededResponse::new(daily_quota))
+}
+
+/// 内容フィルターで拒否された場合の422
+pub fn content_rejected_reply() -> serde_json::Result<Reply> {
+    let body = ErrorResponse::new("Content rejected by filter", "CONTENT_REJECTED");
+    Reply::json(422, &body)
+}
+
+/// 内容フィルターで保留した場合の202
+pub fn held_reply(today: String) -> serde_json::Result<Reply> {
+    Reply::json(202, &HeldResponse::new(today))
+}
+
+/// 保存できた場合の201（残りの書き込み回数をヘッダーで伝える）
+pub fn saved_reply(
+    today: String,
+    content: String,
+    decision: &RateLimitDecision,
+    policy: &RateLimitPolicy,
+) -> serde_json::Result<Reply> {
+    let response = DiaryEntryResponse {
+        date: today,
+        content,
+        can_edit: true,
+    };
+    let reply = Reply::json(201, &response)?;
+    Ok(with_rate_limit_headers(reply, decision, policy))
+}
+
+/// スキーマが古く書き込めない場合の503（マイグレーションを当てるまで）
+pub fn schema_outdated_reply() -> serde_json::Result<Reply> {
+    let body = ErrorResponse::new(
+        "Database schema is out of date; writes are disabled until migrations are applied",
+        "SCHEMA_OUTDATED",
+    );
+    Reply::json(503, &body)
+}
+
+/// 保存に失敗した場合の500
+pub fn internal_error_reply() -> serde_json::Result<Reply> {
+    Reply::json(500, &ErrorResponse::internal_error())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rate_limit::LimitReason;
+    use crate::store::{block_on, MemoryStore};
+
+    #[test]
+    fn test_today_flow() {
+        let store = MemoryStore::default();
+
+        let reply = block_on(today_reply(&store, "2025-01-15")).unwrap();
+        assert_eq!(reply.status, 200);
+        assert_eq!(reply.field("date"), "2025-01-15");
+        assert!(reply.field("content").is_null());
+
+        block_on(store.upsert_entry("2025-01-15", "今日は晴れ")).unwrap();
+        let reply = block_on(today_reply(&store, "2025-01-15")).unwrap();
+        assert_eq!(reply.field("content"), "今日は晴れ");
+        assert_eq!(reply.field("can_edit"), true);
+    }
+
+    #[test]
+    fn test_entries_flow_hides_today() {
+        let store = MemoryStore::default();
+        block_on(store.upsert_entry("2025-01-14", "昨日の日記")).unwrap();
+        block_on(store.upsert_entry("2025-01-15", "今日の日記")).unwrap();
+
+        let reply = block_on(entries_reply(&store, "2025-01-15")).unwrap();
+        assert_eq!(reply.status, 200);
+        let entries = reply.field("entries").as_array().unwrap();
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0]["date"], "2025-01-14");
+
+        // 日付が変わると今日の日記も一覧に入る
+        let reply = block_on(entries_reply(&store, "2025-01-16")).unwrap();
+        assert_eq!(reply.field("entries").as_array().unwrap().len(), 2);
+    }
+
+    #[test]
+    fn test_entry_flow() {
+        let store = MemoryStore::default();
+        block_on(store.upsert_entry("2025-01-14", "昨日の日記")).unwrap();
+
+        let reply = block_on(entry_reply(&store, Some("2025-01-14"), "2025-01-15")).unwrap();
+        assert_eq!(reply.status, 200);
+        assert_eq!(reply.field("content"), "昨日の日記");
+        assert_eq!(reply.field("can_edit"), false);
+
+        let reply = block_on(entry_reply(&store, Some("2025-01-14"), "2025-01-14")).unwrap();
+        assert_eq!(reply.field("can_edit"), true);
+
+        let reply = block_on(entry_reply(&store, Some("2025-01-13"), "2025-01-15")).unwrap();
+        assert_eq!(reply.status, 404);
+        assert_eq!(reply.field("code"), "NOT_FOUND");
+
+        for date in [None, Some("2025-02-30"), Some("../admin")] {
+            let reply = block_on(entry_reply(&store, date, "2025-01-15")).unwrap();
+            assert_eq!(reply.status, 400);
+            assert_eq!(reply.field("code"), "BAD_REQUEST");
+        }
+    }
+
+    #[test]
+    fn test_versions_flow() {
+        let store = MemoryStore::default();
+        block_on(store.upsert_entry("2025-01-14", "一回目")).unwrap();
+        block_on(store.upsert_entry("2025-01-14", "二回目")).unwrap();
+
+        let reply = block_on(versions_reply(&store, Some("2025-01-14"))).unwrap();
+        assert_eq!(reply.status, 200);
+        assert_eq!(reply.field("current_content"), "二回目");
+        assert_eq!(reply.field("versions")[0]["preview"], "一回目");
+
+        let reply = block_on(version_reply(&store, Some("2025-01-14"), Some("1"))).unwrap();
+        assert_eq!(reply.field("content"), "一回目");
+        let reply = block_on(version_reply(&store, Some("2025-01-14"), Some("2"))).unwrap();
+        assert_eq!(reply.status, 404);
+        let reply = block_on(version_reply(&store, Some("2025-01-14"), Some("x"))).unwrap();
+        assert_eq!(reply.field("error"), "Invalid version number");
+        let reply = block_on(versions_reply(&store, Some("2025-13-01"))).unwrap();
+        assert_eq!(reply.status, 400);
+    }
+
+    #[test]
+    fn test_rate_limit_replies_carry_headers() {
+        let policy = RateLimitPolicy::default();
+        let allowed = RateLimitDecision::Allowed {
+            remaining: 4,
+            reset_after: 30,
+        };
+        assert!(rate_limited_reply(&allowed, &policy).unwrap().is_none());
+
+        let reply = saved_reply("2025-01-15".into(), "本文".into(), &allowed, &policy).unwrap();
+        assert_eq!(reply.status, 201);
+        assert_eq!(reply.header("RateLimit-Remaining"), Some("4"));
+
+        let limited = RateLimitDecision::Limited {
+            retry_after: 12,
+            reason: LimitReason::TooSoon,
+        };
+        let reply = rate_limited_reply(&limited, &policy).unwrap().unwrap();
+        assert_eq!(reply.status, 429);
+        assert_eq!(reply.header("Retry-After"), Some("12"));
+        assert_eq!(reply.field("reason"), "too_soon");
+    }
+}
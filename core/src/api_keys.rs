@@ -0,0 +1,77 @@
+use sha2::{Digest, Sha256};
+
+use crate::crypto::to_hex;
+
+/// 発行するキーの接頭辞（管理者トークンなどと見分けるため）
+pub const KEY_PREFIX: &str = "dk_";
+/// 画面に表示するキーの先頭部分の長さ
+const DISPLAY_PREFIX_LENGTH: usize = 11;
+
+/// 保存先に記録するキーのハッシュ（純粋関数）
+///
+/// キー自体が十分な乱数なので、パスワードのような伸長は行わない。
+pub fn hash_key(key: &str) -> String {
+    to_hex(&Sha256::digest(key.as_bytes()))
+}
+
+/// 一覧で見分けるためのキーの先頭部分（純粋関数）
+pub fn display_prefix(key: &str) -> String {
+    key.chars().take(DISPLAY_PREFIX_LENGTH).collect()
+}
+
+/// AuthorizationヘッダーからAPIキーを取り出す（純粋関数）
+///
+/// APIキーの形式でないものは、このAPIキーの認証の対象外としてNoneを返す。
+pub fn bearer_key(auth_header: Option<&str>) -> Option<&str> {
+    auth_header?
+        .strip_prefix("Bearer ")
+        .map(str::trim)
+        .filter(|t| t.starts_with(KEY_PREFIX))
+}
+
+/// 1日の上限回数が正しい範囲か（純粋関数）
+pub fn parse_daily_quota(value: &str, max: i64) -> Option<i64> {
+    value
+        .trim()
+        .parse::<i64>()
+        .ok()
+        .filter(|q| (1..=max).contains(q))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_hash_key() {
+        let hash = hash_key("dk_0123");
+        assert_eq!(hash.len(), 64);
+        assert_eq!(hash, hash_key("dk_0123"));
+        assert_ne!(hash, hash_key("dk_0124"));
+    }
+
+    #[test]
+    fn test_display_prefix() {
+        assert_eq!(display_prefix("dk_0123456789abcdef"), "dk_01234567");
+        assert_eq!(display_prefix("dk_01"), "dk_01");
+    }
+
+    #[test]
+    fn test_bearer_key() {
+        assert_eq!(bearer_key(Some("Bearer dk_abc")), Some("dk_abc"));
+        assert_eq!(bearer_key(Some("Bearer  dk_abc ")), Some("dk_abc"));
+        assert_eq!(bearer_key(Some("Bearer admin-token")), None);
+        assert_eq!(bearer_key(Some("Basic dk_abc")), None);
+        assert_eq!(bearer_key(None), None);
+    }
+
+    #[test]
+    fn test_parse_daily_quota() {
+        assert_eq!(parse_daily_quota("100", 1000), Some(100));
+        assert_eq!(parse_daily_quota(" 1 ", 1000), Some(1));
+        assert_eq!(parse_daily_quota("0", 1000), None);
+        assert_eq!(parse_daily_quota("1001", 1000), None);
+        assert_eq!(parse_daily_quota("-5", 1000), None);
+        assert_eq!(parse_daily_quota("abc", 1000), None);
+    }
+}
@@ -0,0 +1,618 @@
+use std::collections::BTreeSet;
+use std::fmt::Debug;
+use std::io::{Read, Write};
+
+use chrono::Datelike;
+use flate2::read::GzDecoder;
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::config::{parse_var as parse, FromVars};
+use crate::export::Export;
+use crate::import::{self, Baseline, ConflictPolicy, ImportOptions, ImportReport, MAX_IMPORT_RECORDS};
+use crate::models::ErrorResponse;
+use crate::reply::Reply;
+use crate::store::{ClearRange, DiaryStore};
+use crate::time::{is_valid_date, parse_date};
+
+/// スナップショットを置くキーの接頭辞
+pub const SNAPSHOT_PREFIX: &str = "snapshots/";
+
+/// スナップショットのキーの末尾（管理者用エクスポートと同じJSON Linesをgzipしたもの）
+const SNAPSHOT_SUFFIX: &str = ".jsonl.gz";
+
+/// 日付ごとのスナップショットのキー
+pub fn snapshot_key(date: &str) -> String {
+    format!("{}{}{}", SNAPSHOT_PREFIX, date, SNAPSHOT_SUFFIX)
+}
+
+/// キーからスナップショットの日付を取り出す（このアプリのキーでなければNone）
+pub fn snapshot_date(key: &str) -> Option<&str> {
+    let date = key.strip_prefix(SNAPSHOT_PREFIX)?.strip_suffix(SNAPSHOT_SUFFIX)?;
+    is_valid_date(date).then_some(date)
+}
+
+/// 保存済みのスナップショット
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct SnapshotInfo {
+    pub date: String,
+    pub key: String,
+    /// 圧縮後のバイト数
+    pub size: u64,
+}
+
+/// スナップショットの置き場所
+///
+/// WorkersではR2のバケット、テストではメモリ上の実装を使う。
+pub trait SnapshotStore {
+    /// 置き場所の障害
+    type Error: Debug;
+
+    /// SNAPSHOT_PREFIX以下のスナップショットの一覧（順不同）
+    async fn list_snapshots(&self) -> Result<Vec<SnapshotInfo>, Self::Error>;
+    async fn get_snapshot(&self, key: &str) -> Result<Option<Vec<u8>>, Self::Error>;
+    async fn put_snapshot(&self, key: &str, data: Vec<u8>) -> Result<(), Self::Error>;
+    async fn delete_snapshot(&self, key: &str) -> Result<(), Self::Error>;
+}
+
+/// スナップショットを残す期間（BACKUP_KEEP_*変数で変更できる）
+///
+/// 直近の毎日の分に加え、週ごと・月ごとに最も新しい1つを残す。
+#[derive(Debug, Clone, PartialEq)]
+pub struct RetentionPolicy {
+    /// 新しいものから残す日数
+    pub daily: usize,
+    /// 週ごとの最新を残す週数
+    pub weekly: usize,
+    /// 月ごとの最新を残す月数
+    pub monthly: usize,
+}
+
+impl Default for RetentionPolicy {
+    fn default() -> Self {
+        Self {
+            daily: 14,
+            weekly: 8,
+            monthly: 12,
+        }
+    }
+}
+
+impl FromVars for RetentionPolicy {
+    fn from_vars(get: impl Fn(&str) -> Option<String>) -> Self {
+        let default = Self::default();
+        Self {
+            // 作ったばかりのスナップショットを消さないよう、毎日の分は1以上にする
+            daily: parse(get("BACKUP_KEEP_DAILY"))
+                .filter(|v| *v >= 1)
+                .unwrap_or(default.daily),
+            weekly: parse(get("BACKUP_KEEP_WEEKLY")).unwrap_or(default.weekly),
+            monthly: parse(get("BACKUP_KEEP_MONTHLY")).unwrap_or(default.monthly),
+        }
+    }
+}
+
+impl RetentionPolicy {
+    /// 期限を過ぎたスナップショットの日付（純粋関数）
+    pub fn expired<'a>(&self, dates: &[&'a str]) -> Vec<&'a str> {
+        let mut sorted: Vec<&str> = dates.iter().copied().filter(|d| is_valid_date(d)).collect();
+        sorted.sort_unstable_by(|a, b| b.cmp(a));
+        sorted.dedup();
+
+        let mut keep: BTreeSet<&str> = sorted.iter().copied().take(self.daily).collect();
+        let mut weeks = BTreeSet::new();
+        let mut months = BTreeSet::new();
+        for &date in &sorted {
+            let Some(day) = parse_date(date) else {
+                continue;
+            };
+            let week = day.iso_week();
+            // 新しい順に見ているため、期間ごとに最初に出てきたものがその期間の最新
+            if weeks.len() < self.weekly && weeks.insert((week.year(), week.week())) {
+                keep.insert(date);
+            }
+            if months.len() < self.monthly && months.insert((day.year(), day.month())) {
+                keep.insert(date);
+            }
+        }
+        sorted.into_iter().filter(|d| !keep.contains(d)).collect()
+    }
+}
+
+/// すべての日記と版をgzipしたJSON Linesにする
+pub async fn create_snapshot<S: DiaryStore>(store: &S) -> Result<Vec<u8>, S::Error> {
+    let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+    let mut export = Export::full();
+    while let Some(chunk) = export.next_chunk(store).await? {
+        // 圧縮しながら書き出し、展開した全体をメモリに置かない
+        encoder
+            .write_all(&chunk)
+            .expect("writing to a Vec does not fail");
+    }
+    Ok(encoder.finish().expect("writing to a Vec does not fail"))
+}
+
+/// 毎晩のバックアップの結果
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct BackupReport {
+    pub key: String,
+    pub size: usize,
+    /// 保持期間を過ぎて消したキー
+    pub deleted: Vec<String>,
+}
+
+/// 今日のスナップショットを書き、保持期間を過ぎたものを消す
+///
+/// 同じ日に2回動いた場合は、その日のスナップショットを書き直す。
+pub async fn run_backup<S, B>(
+    store: &S,
+    snapshots: &B,
+    today: &str,
+    policy: &RetentionPolicy,
+) -> Result<BackupReport, S::Error>
+where
+    S: DiaryStore,
+    B: SnapshotStore<Error = S::Error>,
+{
+    let data = create_snapshot(store).await?;
+    let key = snapshot_key(today);
+    let size = data.len();
+    snapshots.put_snapshot(&key, data).await?;
+
+    let existing = snapshots.list_snapshots().await?;
+    let dates: Vec<&str> = existing.iter().map(|s| s.date.as_str()).collect();
+    let mut deleted = Vec::new();
+    for date in policy.expired(&dates) {
+        let key = snapshot_key(date);
+        snapshots.delete_snapshot(&key).await?;
+        deleted.push(key);
+    }
+    Ok(BackupReport { key, size, deleted })
+}
+
+/// POST /api/admin/restore の本文
+#[derive(Debug, Clone, Deserialize)]
+pub struct RestoreRequest {
+    /// 戻すスナップショットの日付
+    pub snapshot: String,
+    /// 指定すればその日の日記と版だけを戻す
+    pub date: Option<String>,
+    #[serde(default)]
+    pub dry_run: bool,
+    /// 今の日記と食い違う場合の扱い（既定は今の内容を版として残すversion）
+    #[serde(default = "default_restore_policy")]
+    pub on_conflict: ConflictPolicy,
+    /// 何行目から戻すか（大きなスナップショットは数回に分けて戻す）
+    #[serde(default)]
+    pub offset: usize,
+    /// 今の日記と版を消してから戻す（日付を指定すればその日だけ、しなければ今日より前のすべて）
+    #[serde(default)]
+    pub replace: bool,
+}
+
+fn default_restore_policy() -> ConflictPolicy {
+    ConflictPolicy::Version
+}
+
+/// 復元の結果
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct RestoreReport {
+    pub snapshot: String,
+    pub date: Option<String>,
+    pub replace: bool,
+    /// 対象の行数（日付を指定した場合はその日の分）
+    pub total: usize,
+    pub offset: usize,
+    /// 続きがあれば、次に指定するoffset
+    pub next_offset: Option<usize>,
+    #[serde(flatten)]
+    pub import: ImportReport,
+}
+
+impl RestoreReport {
+    /// 結果の応答（取り込めない行があれば400）
+    pub fn reply(&self) -> serde_json::Result<Reply> {
+        let status = if self.import.errors.is_empty() { 200 } else { 400 };
+        Reply::json(status, self)
+    }
+}
+
+/// 復元を始められなかった理由
+#[derive(Debug, Clone, PartialEq)]
+pub enum RestoreFailure {
+    InvalidRequest(String),
+    NotFound,
+    /// gzipやUTF-8として読めない
+    Corrupt,
+}
+
+impl RestoreFailure {
+    pub fn reply(&self) -> serde_json::Result<Reply> {
+        match self {
+            Self::InvalidRequest(message) => {
+                Reply::json(400, &ErrorResponse::bad_request(message.as_str()))
+            }
+            Self::NotFound => {
+                let body = ErrorResponse::new("Snapshot not found", "SNAPSHOT_NOT_FOUND");
+                Reply::json(404, &body)
+            }
+            Self::Corrupt => {
+                let body = ErrorResponse::new("Snapshot could not be read", "SNAPSHOT_CORRUPT");
+                Reply::json(500, &body)
+            }
+        }
+    }
+}
+
+/// 行が指す日付（日記のdate、版のentry_date）
+fn line_date(line: &str) -> Option<String> {
+    let value: Value = serde_json::from_str(line).ok()?;
+    let date = value.get("date").or_else(|| value.get("entry_date"))?;
+    date.as_str().map(str::to_string)
+}
+
+/// 日記の行の日付（版の行はNone）
+fn entry_line_date(line: &str) -> Option<String> {
+    let value: Value = serde_json::from_str(line).ok()?;
+    if value.get("type").is_some_and(|t| t != "entry") {
+        return None;
+    }
+    value.get("date")?.as_str().map(str::to_string)
+}
+
+/// スナップショットから日記と版を戻す
+///
+/// 取り込みはインポートと同じ規則で行い、D1の問い合わせの上限に収まる
+/// MAX_IMPORT_RECORDS行ずつを1つのまとまりとして書き込む。
+/// 残りがあればnext_offsetを返すので、なくなるまで繰り返し呼ぶ。
+/// 書きかけの今日と、それより後の日付の行は戻さない。
+///
+/// `replace`では最初のまとまりと同時に範囲の日記と版を消すため、
+/// スナップショットの後に書かれた日記も残らない。dry_runでは前のまとまりの日記が
+/// 書き込まれていないため、その日付をあるものとして数える。
+pub async fn restore<S, B>(
+    store: &S,
+    snapshots: &B,
+    request: &RestoreRequest,
+    today: &str,
+    now: &str,
+) -> Result<Result<RestoreReport, RestoreFailure>, S::Error>
+where
+    S: DiaryStore,
+    B: SnapshotStore<Error = S::Error>,
+{
+    if !is_valid_date(&request.snapshot) {
+        let message = format!("Invalid snapshot: {}", request.snapshot);
+        return Ok(Err(RestoreFailure::InvalidRequest(message)));
+    }
+    if let Some(date) = request.date.as_deref().filter(|d| !is_valid_date(d)) {
+        let message = format!("Invalid date: {}", date);
+        return Ok(Err(RestoreFailure::InvalidRequest(message)));
+    }
+
+    let Some(data) = snapshots.get_snapshot(&snapshot_key(&request.snapshot)).await? else {
+        return Ok(Err(RestoreFailure::NotFound));
+    };
+    let mut text = String::new();
+    if GzDecoder::new(data.as_slice()).read_to_string(&mut text).is_err() {
+        return Ok(Err(RestoreFailure::Corrupt));
+    }
+
+    let lines: Vec<&str> = text
+        .lines()
+        .filter(|line| !line.trim().is_empty())
+        .filter(|line| {
+            let date = line_date(line);
+            match &request.date {
+                Some(target) => date.as_deref() == Some(target.as_str()),
+                None => date.is_none_or(|date| date.as_str() < today),
+            }
+        })
+        .collect();
+    let total = lines.len();
+    let start = request.offset.min(total);
+    let end = (start + MAX_IMPORT_RECORDS).min(total);
+    let options = ImportOptions {
+        dry_run: request.dry_run,
+        on_conflict: request.on_conflict,
+    };
+    let clear = match &request.date {
+        Some(date) => ClearRange::Date(date.clone()),
+        None => ClearRange::Before(today.to_string()),
+    };
+    let baseline = Baseline {
+        // 消すのは最初のまとまりだけ（dry_runでは続きも消した後として数える）
+        clear: (request.replace && (start == 0 || request.dry_run)).then_some(clear),
+        earlier_entries: lines[..start].iter().filter_map(|l| entry_line_date(l)).collect(),
+    };
+    let body = lines[start..end].join("\n");
+    let report = import::run_onto(store, &body, options, &baseline, today, now).await?;
+
+    Ok(Ok(RestoreReport {
+        snapshot: request.snapshot.clone(),
+        date: request.date.clone(),
+        replace: request.replace,
+        total,
+        offset: start,
+        next_offset: (end < total).then_some(end),
+        import: report,
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::RefCell;
+    use std::collections::BTreeMap;
+    use std::convert::Infallible;
+
+    use super::*;
+    use crate::store::{block_on, MemoryStore};
+
+    /// テスト用のメモリ上のスナップショット置き場
+    #[derive(Default)]
+    struct MemorySnapshots {
+        objects: RefCell<BTreeMap<String, Vec<u8>>>,
+    }
+
+    impl SnapshotStore for MemorySnapshots {
+        type Error = Infallible;
+
+        async fn list_snapshots(&self) -> Result<Vec<SnapshotInfo>, Infallible> {
+            Ok(self
+                .objects
+                .borrow()
+                .iter()
+                .filter_map(|(key, data)| {
+                    Some(SnapshotInfo {
+                        date: snapshot_date(key)?.to_string(),
+                        key: key.clone(),
+                        size: data.len() as u64,
+                    })
+                })
+                .collect())
+        }
+
+        async fn get_snapshot(&self, key: &str) -> Result<Option<Vec<u8>>, Infallible> {
+            Ok(self.objects.borrow().get(key).cloned())
+        }
+
+        async fn put_snapshot(&self, key: &str, data: Vec<u8>) -> Result<(), Infallible> {
+            self.objects.borrow_mut().insert(key.to_string(), data);
+            Ok(())
+        }
+
+        async fn delete_snapshot(&self, key: &str) -> Result<(), Infallible> {
+            self.objects.borrow_mut().remove(key);
+            Ok(())
+        }
+    }
+
+    const TODAY: &str = "2025-02-01";
+    const NOW: &str = "2025-02-01T00:00:00+09:00";
+
+    fn request(snapshot: &str, date: Option<&str>) -> RestoreRequest {
+        RestoreRequest {
+            snapshot: snapshot.to_string(),
+            date: date.map(str::to_string),
+            dry_run: false,
+            on_conflict: ConflictPolicy::Version,
+            offset: 0,
+            replace: false,
+        }
+    }
+
+    fn restore_from(
+        store: &MemoryStore,
+        snapshots: &MemorySnapshots,
+        request: &RestoreRequest,
+    ) -> RestoreReport {
+        block_on(restore(store, snapshots, request, TODAY, NOW)).unwrap().unwrap()
+    }
+
+    #[test]
+    fn test_snapshot_keys() {
+        assert_eq!(snapshot_key("2025-01-15"), "snapshots/2025-01-15.jsonl.gz");
+        assert_eq!(snapshot_date("snapshots/2025-01-15.jsonl.gz"), Some("2025-01-15"));
+        assert_eq!(snapshot_date("snapshots/latest.jsonl.gz"), None);
+        assert_eq!(snapshot_date("other/2025-01-15.jsonl.gz"), None);
+    }
+
+    #[test]
+    fn test_retention_keeps_daily_weekly_and_monthly() {
+        let policy = RetentionPolicy {
+            daily: 3,
+            weekly: 2,
+            monthly: 2,
+        };
+        let start = parse_date("2024-12-01").unwrap();
+        let dates: Vec<String> = (0..60)
+            .map(|i| (start + chrono::Days::new(i)).format("%Y-%m-%d").to_string())
+            .collect();
+        let dates: Vec<&str> = dates.iter().map(String::as_str).collect();
+        let expired = policy.expired(&dates);
+        let kept: Vec<&str> = dates.iter().copied().filter(|d| !expired.contains(d)).collect();
+        // 2025-01-29は水曜日。直近3日、今週と先週の最新（日曜）、今月と先月の最新
+        assert_eq!(
+            kept,
+            ["2024-12-31", "2025-01-26", "2025-01-27", "2025-01-28", "2025-01-29"]
+        );
+
+        // 日付として読めないものは消さない
+        assert!(policy.expired(&["latest"]).is_empty());
+    }
+
+    #[test]
+    fn test_retention_policy_from_vars() {
+        let policy = RetentionPolicy::from_vars(|name| match name {
+            "BACKUP_KEEP_DAILY" => Some("0".to_string()),
+            "BACKUP_KEEP_WEEKLY" => Some("4".to_string()),
+            _ => None,
+        });
+        assert_eq!(policy.daily, RetentionPolicy::default().daily);
+        assert_eq!(policy.weekly, 4);
+    }
+
+    #[test]
+    fn test_backup_writes_snapshot_and_prunes() {
+        let store = MemoryStore::default();
+        block_on(store.upsert_entry("2025-01-14", "一回目")).unwrap();
+        block_on(store.upsert_entry("2025-01-14", "二回目")).unwrap();
+        let snapshots = MemorySnapshots::default();
+        block_on(snapshots.put_snapshot(&snapshot_key("2024-01-01"), Vec::new())).unwrap();
+
+        let policy = RetentionPolicy {
+            daily: 1,
+            weekly: 0,
+            monthly: 0,
+        };
+        let report = block_on(run_backup(&store, &snapshots, "2025-01-15", &policy)).unwrap();
+        assert_eq!(report.key, "snapshots/2025-01-15.jsonl.gz");
+        assert_eq!(report.deleted, ["snapshots/2024-01-01.jsonl.gz"]);
+
+        let data = block_on(snapshots.get_snapshot(&report.key)).unwrap().unwrap();
+        assert_eq!(data.len(), report.size);
+        let mut text = String::new();
+        GzDecoder::new(data.as_slice()).read_to_string(&mut text).unwrap();
+        assert_eq!(text.lines().count(), 2);
+        assert!(text.contains(r#""type":"version""#));
+    }
+
+    #[test]
+    fn test_restore_whole_database_and_single_day() {
+        let store = MemoryStore::default();
+        block_on(store.upsert_entry("2025-01-13", "十三日")).unwrap();
+        block_on(store.upsert_entry("2025-01-14", "十四日")).unwrap();
+        let snapshots = MemorySnapshots::default();
+        let policy = RetentionPolicy::default();
+        block_on(run_backup(&store, &snapshots, "2025-01-15", &policy)).unwrap();
+
+        // 荒らされた後で1日だけ戻す
+        block_on(store.upsert_entry("2025-01-13", "荒らし")).unwrap();
+        block_on(store.upsert_entry("2025-01-14", "荒らし")).unwrap();
+        let request = request("2025-01-15", Some("2025-01-14"));
+        let report = restore_from(&store, &snapshots, &request);
+        assert_eq!(report.total, 1);
+        assert_eq!(report.next_offset, None);
+        assert_eq!(report.import.entries.updated, 1);
+        let entry = block_on(store.get_entry("2025-01-14")).unwrap().unwrap();
+        assert_eq!(entry.content, "十四日");
+        let entry = block_on(store.get_entry("2025-01-13")).unwrap().unwrap();
+        assert_eq!(entry.content, "荒らし");
+
+        // 全体を戻すと、荒らされた内容は版として残る
+        let request = self::request("2025-01-15", None);
+        let report = restore_from(&store, &snapshots, &request);
+        assert_eq!(report.total, 2);
+        assert_eq!(report.reply().unwrap().field("applied"), true);
+        let entry = block_on(store.get_entry("2025-01-13")).unwrap().unwrap();
+        assert_eq!(entry.content, "十三日");
+        let versions = block_on(store.list_versions("2025-01-13")).unwrap();
+        assert_eq!(versions[0].content, "荒らし");
+    }
+
+    #[test]
+    fn test_restore_replace_rebuilds_database() {
+        let store = MemoryStore::default();
+        block_on(store.upsert_entry("2025-01-13", "十三日")).unwrap();
+        block_on(store.upsert_entry("2025-01-14", "十四日")).unwrap();
+        let snapshots = MemorySnapshots::default();
+        let policy = RetentionPolicy::default();
+        block_on(run_backup(&store, &snapshots, "2025-01-15", &policy)).unwrap();
+
+        // スナップショットの後に書かれた日記と版、書きかけの今日
+        block_on(store.upsert_entry("2025-01-13", "荒らし")).unwrap();
+        block_on(store.upsert_entry("2025-01-20", "後から書いた")).unwrap();
+        block_on(store.upsert_entry(TODAY, "今日")).unwrap();
+        block_on(store.record_save("2025-01-20", "writer")).unwrap();
+
+        let mut request = request("2025-01-15", None);
+        request.replace = true;
+        request.dry_run = true;
+        let report = restore_from(&store, &snapshots, &request);
+        assert_eq!(report.import.entries.created, 2);
+        assert!(block_on(store.get_entry("2025-01-20")).unwrap().is_some());
+
+        request.dry_run = false;
+        let report = restore_from(&store, &snapshots, &request);
+        assert!(report.import.applied);
+        assert!(block_on(store.get_entry("2025-01-20")).unwrap().is_none());
+        assert_eq!(block_on(store.save_activity("2025-01-20")).unwrap().writers, 0);
+        let entry = block_on(store.get_entry("2025-01-13")).unwrap().unwrap();
+        assert_eq!(entry.content, "十三日");
+        assert!(block_on(store.list_versions("2025-01-13")).unwrap().is_empty());
+        // 今日の日記は残す
+        let entry = block_on(store.get_entry(TODAY)).unwrap().unwrap();
+        assert_eq!(entry.content, "今日");
+
+        // 1日だけ置き換える
+        block_on(store.upsert_entry("2025-01-14", "荒らし")).unwrap();
+        let mut request = self::request("2025-01-15", Some("2025-01-14"));
+        request.replace = true;
+        restore_from(&store, &snapshots, &request);
+        assert!(block_on(store.list_versions("2025-01-14")).unwrap().is_empty());
+        let entry = block_on(store.get_entry("2025-01-14")).unwrap().unwrap();
+        assert_eq!(entry.content, "十四日");
+    }
+
+    #[test]
+    fn test_restore_pages_large_snapshots() {
+        let store = MemoryStore::default();
+        let start = parse_date("2024-01-01").unwrap();
+        for i in 0..(MAX_IMPORT_RECORDS as u64 + 20) {
+            let date = (start + chrono::Days::new(i)).format("%Y-%m-%d").to_string();
+            block_on(store.upsert_entry(&date, "本文")).unwrap();
+        }
+        // 版の行は日記の行の後に書き出されるため、最初の日記とは別のまとまりに入る
+        block_on(store.upsert_entry("2024-01-01", "書き直した")).unwrap();
+        let snapshots = MemorySnapshots::default();
+        block_on(run_backup(&store, &snapshots, "2025-01-15", &RetentionPolicy::default()))
+            .unwrap();
+
+        // dry_runでは前のまとまりの日記を書き込まないが、その版も数えられる
+        let target = MemoryStore::default();
+        let mut request = request("2025-01-15", None);
+        request.dry_run = true;
+        request.offset = MAX_IMPORT_RECORDS;
+        let report = restore_from(&target, &snapshots, &request);
+        assert!(report.import.errors.is_empty());
+        assert_eq!(report.import.versions.created, 1);
+
+        let mut request = self::request("2025-01-15", None);
+        let report = restore_from(&target, &snapshots, &request);
+        assert_eq!(report.import.entries.created, MAX_IMPORT_RECORDS);
+        assert_eq!(report.next_offset, Some(MAX_IMPORT_RECORDS));
+
+        request.offset = report.next_offset.unwrap();
+        let report = restore_from(&target, &snapshots, &request);
+        assert_eq!(report.import.entries.created, 20);
+        assert_eq!(report.import.versions.created, 1);
+        assert_eq!(report.next_offset, None);
+    }
+
+    #[test]
+    fn test_restore_failures() {
+        let store = MemoryStore::default();
+        let snapshots = MemorySnapshots::default();
+        let restore = |request: &RestoreRequest| {
+            block_on(restore(&store, &snapshots, request, TODAY, NOW)).unwrap().unwrap_err()
+        };
+
+        assert_eq!(restore(&request("2025-01-15", None)), RestoreFailure::NotFound);
+        assert!(matches!(
+            restore(&request("latest", None)),
+            RestoreFailure::InvalidRequest(_)
+        ));
+        assert!(matches!(
+            restore(&request("2025-01-15", Some("2025-13-01"))),
+            RestoreFailure::InvalidRequest(_)
+        ));
+
+        block_on(snapshots.put_snapshot(&snapshot_key("2025-01-15"), b"not gzip".to_vec()))
+            .unwrap();
+        let failure = restore(&request("2025-01-15", None));
+        assert_eq!(failure, RestoreFailure::Corrupt);
+        assert_eq!(failure.reply().unwrap().status, 500);
+    }
+}
@@ -0,0 +1,14 @@
+use std::str::FromStr;
+
+/// 環境変数のような「名前から文字列を引く」関数で組み立てる設定
+///
+/// 値の取得元（Workersの変数、プロセスの環境変数など）は呼び出し側が決める。
+pub trait FromVars: Sized {
+    /// 未設定・不正な値は既定値を使う
+    fn from_vars(get: impl Fn(&str) -> Option<String>) -> Self;
+}
+
+/// 前後の空白を除いて値を解釈する（解釈できなければNone）
+pub fn parse_var<T: FromStr>(value: Option<String>) -> Option<T> {
+    value.and_then(|v| v.trim().parse().ok())
+}
@@ -0,0 +1,243 @@
+use regex_lite::{Regex, RegexBuilder};
+
+use crate::config::FromVars;
+use crate::models::{ContentFilter, FilterAction, FilterKind, MAX_FILTER_PATTERN_LENGTH};
+
+/// 既定で1回の保存に含められるリンクの数
+const DEFAULT_MAX_LINKS: usize = 3;
+/// 正規表現のコンパイル後の大きさの上限（巨大なパターンでWorkerのメモリを使い切らないように）
+const REGEX_SIZE_LIMIT: usize = 256 * 1024;
+
+/// 本文中のリンク数の制限（FILTER_MAX_LINKS・FILTER_LINK_ACTION変数で設定する）
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct LinkPolicy {
+    pub max_links: usize,
+    pub action: FilterAction,
+}
+
+impl Default for LinkPolicy {
+    fn default() -> Self {
+        Self {
+            max_links: DEFAULT_MAX_LINKS,
+            action: FilterAction::Hold,
+        }
+    }
+}
+
+impl FromVars for LinkPolicy {
+    /// 解釈できない値は既定値にする
+    fn from_vars(get: impl Fn(&str) -> Option<String>) -> Self {
+        let default = Self::default();
+        Self {
+            max_links: get("FILTER_MAX_LINKS")
+                .and_then(|v| v.trim().parse().ok())
+                .unwrap_or(default.max_links),
+            action: get("FILTER_LINK_ACTION")
+                .and_then(|v| FilterAction::parse(v.trim()))
+                .unwrap_or(default.action),
+        }
+    }
+}
+
+/// 照合できる形に変換した内容フィルター
+pub struct CompiledFilter {
+    id: i64,
+    matcher: Matcher,
+    action: FilterAction,
+}
+
+enum Matcher {
+    /// 小文字にしたNGワード
+    Word(String),
+    Regex(Regex),
+}
+
+/// 正規表現をコンパイルする（登録時の検証にも使う）
+pub fn compile_regex(pattern: &str) -> std::result::Result<Regex, String> {
+    RegexBuilder::new(pattern)
+        .size_limit(REGEX_SIZE_LIMIT)
+        .build()
+        .map_err(|e| e.to_string())
+}
+
+/// 登録しようとしているフィルターを検証する（純粋関数）
+pub fn validate_pattern(kind: FilterKind, pattern: &str) -> std::result::Result<(), String> {
+    if pattern.trim().is_empty() {
+        return Err("パターンを入力してください".to_string());
+    }
+    if pattern.chars().count() > MAX_FILTER_PATTERN_LENGTH {
+        return Err(format!(
+            "パターンは{}文字以内にしてください",
+            MAX_FILTER_PATTERN_LENGTH
+        ));
+    }
+    if kind == FilterKind::Regex {
+        compile_regex(pattern).map_err(|e| format!("正規表現が正しくありません: {}", e))?;
+    }
+    Ok(())
+}
+
+/// 照合できる形に変換する（壊れた正規表現は読み飛ばす）
+pub fn compile(filters: &[ContentFilter]) -> Vec<CompiledFilter> {
+    filters
+        .iter()
+        .filter_map(|f| {
+            let matcher = match f.kind {
+                FilterKind::Word => Matcher::Word(f.pattern.to_lowercase()),
+                FilterKind::Regex => Matcher::Regex(compile_regex(&f.pattern).ok()?),
+            };
+            Some(CompiledFilter {
+                id: f.id,
+                matcher,
+                action: f.action,
+            })
+        })
+        .collect()
+}
+
+/// 本文中のリンクの数（純粋関数）
+pub fn count_links(content: &str) -> usize {
+    let lower = content.to_ascii_lowercase();
+    lower.matches("http://").count() + lower.matches("https://").count()
+}
+
+/// 内容フィルターの判定結果
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Verdict {
+    /// 該当したものの中で最も重い扱い（該当なしならNone）
+    pub action: Option<FilterAction>,
+    /// 該当した理由（管理画面に表示する）
+    pub reasons: Vec<String>,
+}
+
+impl Verdict {
+    fn add(&mut self, action: FilterAction, reason: String) {
+        self.action = self.action.max(Some(action));
+        self.reasons.push(reason);
+    }
+
+    /// モデレーションキューに保存する形の理由
+    pub fn reasons_text(&self) -> String {
+        self.reasons.join("\n")
+    }
+}
+
+/// 本文をフィルターと照合する（純粋関数）
+pub fn evaluate(content: &str, filters: &[CompiledFilter], links: &LinkPolicy) -> Verdict {
+    let mut verdict = Verdict::default();
+    let lower = content.to_lowercase();
+
+    for filter in filters {
+        let reason = match &filter.matcher {
+            Matcher::Word(word) if lower.contains(word.as_str()) => {
+                format!("NGワード「{}」（#{}）", word, filter.id)
+            }
+            Matcher::Regex(regex) if regex.is_match(content) => {
+                format!("正規表現 {}（#{}）", regex.as_str(), filter.id)
+            }
+            _ => continue,
+        };
+        verdict.add(filter.action, reason);
+    }
+
+    let link_count = count_links(content);
+    if link_count > links.max_links {
+        verdict.add(
+            links.action,
+            format!("リンクが{}件（上限{}件）", link_count, links.max_links),
+        );
+    }
+
+    verdict
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn filter(id: i64, kind: FilterKind, pattern: &str, action: FilterAction) -> ContentFilter {
+        ContentFilter {
+            id,
+            kind,
+            pattern: pattern.to_string(),
+            action,
+            created_by: "alice".to_string(),
+            created_at: "2025-01-15T10:00:00Z".to_string(),
+        }
+    }
+
+    fn link_policy(max_links: Option<&str>, action: Option<&str>) -> LinkPolicy {
+        LinkPolicy::from_vars(|name| match name {
+            "FILTER_MAX_LINKS" => max_links.map(str::to_string),
+            "FILTER_LINK_ACTION" => action.map(str::to_string),
+            _ => None,
+        })
+    }
+
+    #[test]
+    fn test_link_policy_from_vars() {
+        assert_eq!(link_policy(None, None), LinkPolicy::default());
+        assert_eq!(
+            link_policy(Some(" 0 "), Some("reject")),
+            LinkPolicy {
+                max_links: 0,
+                action: FilterAction::Reject,
+            }
+        );
+        assert_eq!(link_policy(Some("-1"), Some("ban")), LinkPolicy::default());
+    }
+
+    #[test]
+    fn test_count_links() {
+        assert_eq!(count_links("リンクなし"), 0);
+        assert_eq!(
+            count_links("HTTPS://a.example と http://b.example/?q=https://c"),
+            3
+        );
+    }
+
+    #[test]
+    fn test_validate_pattern() {
+        assert!(validate_pattern(FilterKind::Word, "spam").is_ok());
+        assert!(validate_pattern(FilterKind::Word, "a(b").is_ok());
+        assert!(validate_pattern(FilterKind::Regex, "a(b").is_err());
+        assert!(validate_pattern(FilterKind::Regex, r"(?i)casino\d+").is_ok());
+        assert!(validate_pattern(FilterKind::Word, "  ").is_err());
+        let long = "a".repeat(MAX_FILTER_PATTERN_LENGTH + 1);
+        assert!(validate_pattern(FilterKind::Word, &long).is_err());
+    }
+
+    #[test]
+    fn test_evaluate_takes_most_severe_action() {
+        let filters = compile(&[
+            filter(1, FilterKind::Word, "Spam", FilterAction::Flag),
+            filter(2, FilterKind::Regex, r"casino\d+", FilterAction::Reject),
+            filter(3, FilterKind::Regex, "(", FilterAction::Reject),
+        ]);
+        assert_eq!(filters.len(), 2);
+        let links = LinkPolicy::default();
+
+        assert_eq!(evaluate("今日は晴れ", &filters, &links), Verdict::default());
+
+        let verdict = evaluate("SPAMです", &filters, &links);
+        assert_eq!(verdict.action, Some(FilterAction::Flag));
+        assert_eq!(verdict.reasons, vec!["NGワード「spam」（#1）".to_string()]);
+
+        let verdict = evaluate("spam casino777", &filters, &links);
+        assert_eq!(verdict.action, Some(FilterAction::Reject));
+        assert_eq!(verdict.reasons.len(), 2);
+    }
+
+    #[test]
+    fn test_evaluate_link_limit() {
+        let links = LinkPolicy {
+            max_links: 1,
+            action: FilterAction::Hold,
+        };
+        assert_eq!(evaluate("https://a.example", &[], &links).action, None);
+
+        let verdict = evaluate("https://a.example https://b.example", &[], &links);
+        assert_eq!(verdict.action, Some(FilterAction::Hold));
+        assert_eq!(verdict.reasons_text(), "リンクが2件（上限1件）");
+    }
+}
@@ -0,0 +1,53 @@
+//! 乱数を使わないハッシュと比較の小さな道具
+
+use sha2::{Digest, Sha256};
+
+/// バイト列を小文字の16進文字列に変換（純粋関数）
+pub fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+/// 秘密値を比較（純粋関数）
+///
+/// 一致する接頭辞の長さで処理時間が変わらないよう、全バイトを比較する。
+pub fn constant_time_eq(a: &str, b: &str) -> bool {
+    let (a, b) = (a.as_bytes(), b.as_bytes());
+    if a.len() != b.len() {
+        return false;
+    }
+    a.iter().zip(b).fold(0u8, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+
+/// 日記本文の変更前後を比較するためのハッシュ（純粋関数）
+pub fn content_hash(content: &str) -> String {
+    to_hex(&Sha256::digest(content.as_bytes()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_to_hex() {
+        assert_eq!(to_hex(&[0x00, 0x0f, 0xab, 0xff]), "000fabff");
+        assert_eq!(to_hex(&[]), "");
+    }
+
+    #[test]
+    fn test_constant_time_eq() {
+        assert!(constant_time_eq("secret123", "secret123"));
+        assert!(!constant_time_eq("secret124", "secret123"));
+        assert!(!constant_time_eq("secret", "secret123"));
+        assert!(!constant_time_eq("", "secret123"));
+        assert!(constant_time_eq("", ""));
+    }
+
+    #[test]
+    fn test_content_hash() {
+        assert_eq!(
+            content_hash(""),
+            "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855"
+        );
+        assert_ne!(content_hash("a"), content_hash("b"));
+    }
+}
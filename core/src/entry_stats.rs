@@ -0,0 +1,323 @@
+//! 確定した日記ごとの分析（/api/entries/:date/stats と日記のページの表示）
+
+use chrono::Days;
+use hmac::{Hmac, Mac};
+use serde::Serialize;
+use sha2::Sha256;
+
+use crate::crypto::to_hex;
+use crate::models::{DiaryEntry, ErrorResponse};
+use crate::reply::Reply;
+use crate::store::DiaryStore;
+use crate::time::{parse_date, parse_iso8601_unix};
+use crate::validation::is_valid_date;
+
+/// 書き手のハッシュの長さ（16進文字数）
+const WRITER_HASH_LENGTH: usize = 32;
+
+/// 書き手のハッシュを人数にまとめるまでの日数
+pub const SAVE_RETENTION_DAYS: u64 = 7;
+
+/// 保存の記録に残す書き手のハッシュ（純粋関数）
+///
+/// レート制限に使うクライアントのキーに日付を混ぜ、別の日の書き手と結び付けられないようにする。
+/// 鍵付きのHMACにして、IPアドレスなどの全数探索でハッシュから書き手を割り出せないようにする。
+pub fn writer_hash(key: &str, day: &str, client: &str) -> String {
+    let mut mac =
+        Hmac::<Sha256>::new_from_slice(key.as_bytes()).expect("HMAC accepts any key length");
+    mac.update(format!("{}|{}", day, client).as_bytes());
+    let mut hex = to_hex(&mac.finalize().into_bytes());
+    hex.truncate(WRITER_HASH_LENGTH);
+    hex
+}
+
+/// 保持期間を過ぎた書き手の記録を人数にまとめる（毎日呼ぶ）
+pub async fn compact_saves<S: DiaryStore>(store: &S, today: &str) -> Result<(), S::Error> {
+    let retention = Days::new(SAVE_RETENTION_DAYS);
+    let Some(cutoff) = parse_date(today).and_then(|d| d.checked_sub_days(retention)) else {
+        return Ok(());
+    };
+    store.compact_saves(&cutoff.format("%Y-%m-%d").to_string()).await
+}
+
+/// 保存の記録から集めた、1日分の書き込みの様子
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SaveActivity {
+    /// 版の数（内容が変わった保存の回数）
+    pub versions: i64,
+    /// 書き手の記録にある人数
+    pub writers: i64,
+    /// 最新の版が作られた日時（最後の文章に書き換えられた日時）
+    pub last_changed_at: Option<String>,
+}
+
+/// 文字の種類ごとの割合（空白と絵文字の修飾は数えない）
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct ScriptRatios {
+    pub hiragana: f64,
+    pub katakana: f64,
+    pub kanji: f64,
+    pub latin: f64,
+    pub emoji: f64,
+    pub other: f64,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Script {
+    Hiragana,
+    Katakana,
+    Kanji,
+    Latin,
+    Emoji,
+    Other,
+}
+
+/// 文字の種類（数えない文字はNone）
+fn classify(c: char) -> Option<Script> {
+    let script = match c {
+        // ZWJ・異体字セレクタ・肌の色・タグなど、絵文字を組み立てる部品
+        '\u{200D}' | '\u{20E3}' | '\u{FE0E}' | '\u{FE0F}' | '\u{1F3FB}'..='\u{1F3FF}'
+        | '\u{E0020}'..='\u{E007F}' => return None,
+        c if c.is_whitespace() => return None,
+        '\u{3041}'..='\u{309F}' => Script::Hiragana,
+        '\u{30A0}'..='\u{30FF}' | '\u{31F0}'..='\u{31FF}' | '\u{FF66}'..='\u{FF9F}' => {
+            Script::Katakana
+        }
+        '\u{3005}' | '\u{3006}' | '\u{3400}'..='\u{4DBF}' | '\u{4E00}'..='\u{9FFF}'
+        | '\u{F900}'..='\u{FAFF}' | '\u{20000}'..='\u{2FA1F}' => Script::Kanji,
+        'A'..='Z' | 'a'..='z' | '\u{FF21}'..='\u{FF3A}' | '\u{FF41}'..='\u{FF5A}' => Script::Latin,
+        '\u{00C0}'..='\u{024F}' if c != '\u{00D7}' && c != '\u{00F7}' => Script::Latin,
+        '\u{1F000}'..='\u{1FAFF}' | '\u{2600}'..='\u{27BF}' | '\u{231A}' | '\u{231B}'
+        | '\u{23E9}'..='\u{23FA}' | '\u{2B50}' | '\u{2B55}' => Script::Emoji,
+        _ => Script::Other,
+    };
+    Some(script)
+}
+
+/// 小数第3位に丸めた割合
+fn ratio(count: usize, total: usize) -> f64 {
+    if total == 0 {
+        return 0.0;
+    }
+    (count as f64 / total as f64 * 1000.0).round() / 1000.0
+}
+
+impl ScriptRatios {
+    pub fn of(content: &str) -> Self {
+        let mut counts = [0usize; 6];
+        for script in content.chars().filter_map(classify) {
+            counts[script as usize] += 1;
+        }
+        let total = counts.iter().sum();
+        Self {
+            hiragana: ratio(counts[Script::Hiragana as usize], total),
+            katakana: ratio(counts[Script::Katakana as usize], total),
+            kanji: ratio(counts[Script::Kanji as usize], total),
+            latin: ratio(counts[Script::Latin as usize], total),
+            emoji: ratio(counts[Script::Emoji as usize], total),
+            other: ratio(counts[Script::Other as usize], total),
+        }
+    }
+}
+
+/// GET /api/entries/:date/stats の応答
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct EntryStats {
+    pub date: String,
+    pub characters: usize,
+    pub lines: usize,
+    pub scripts: ScriptRatios,
+    /// 内容が変わった保存の回数（最初の保存を含む）
+    pub saves: i64,
+    /// 書いた人の数（記録を始める前の日はnull）
+    pub writers: Option<i64>,
+    pub first_saved_at: String,
+    pub last_saved_at: String,
+    /// 最後の文章に書き換えられた日時
+    pub final_text_since: String,
+    /// 最後の文章が日付が変わるまで残った秒数
+    pub survived_seconds: Option<i64>,
+}
+
+/// 日付が変わる時刻（翌日のJST 0時）のUNIX秒
+fn day_end_unix(date: &str) -> Option<i64> {
+    let next = parse_date(date)?.checked_add_days(Days::new(1))?;
+    let midnight = next.and_hms_opt(0, 0, 0)?.and_utc().timestamp();
+    Some(midnight - 9 * 3600)
+}
+
+impl EntryStats {
+    pub fn build(entry: &DiaryEntry, activity: &SaveActivity) -> Self {
+        let final_text_since = activity
+            .last_changed_at
+            .clone()
+            .unwrap_or_else(|| entry.created_at.clone());
+        let survived_seconds = parse_iso8601_unix(&final_text_since)
+            .zip(day_end_unix(&entry.date))
+            .map(|(since, end)| (end - since).max(0));
+        Self {
+            date: entry.date.clone(),
+            characters: entry.content.chars().count(),
+            lines: entry.content.lines().count(),
+            scripts: ScriptRatios::of(&entry.content),
+            saves: activity.versions + 1,
+            writers: (activity.writers > 0).then_some(activity.writers),
+            first_saved_at: entry.created_at.clone(),
+            last_saved_at: entry.updated_at.clone(),
+            final_text_since,
+            survived_seconds,
+        }
+    }
+
+    /// 保存先から読む（日記がなければNone）
+    pub async fn load<S: DiaryStore>(store: &S, date: &str) -> Result<Option<Self>, S::Error> {
+        let Some(entry) = store.get_entry(date).await? else {
+            return Ok(None);
+        };
+        let activity = store.save_activity(date).await?;
+        Ok(Some(Self::build(&entry, &activity)))
+    }
+}
+
+/// GET /api/entries/:date/stats - 確定した日記の分析
+pub async fn entry_stats_reply<S: DiaryStore>(
+    store: &S,
+    date: Option<&str>,
+    today: &str,
+) -> serde_json::Result<Reply> {
+    let Some(date) = date else {
+        return Reply::json(400, &ErrorResponse::bad_request("Date parameter required"));
+    };
+    if !is_valid_date(date) {
+        let body = ErrorResponse::bad_request("Invalid date format. Use YYYY-MM-DD.");
+        return Reply::json(400, &body);
+    }
+    // 書きかけの今日の日記は分析しない
+    if date >= today {
+        return Reply::json(404, &ErrorResponse::not_found());
+    }
+
+    match EntryStats::load(store, date).await {
+        Ok(Some(stats)) => Reply::json(200, &stats),
+        Ok(None) => Reply::json(404, &ErrorResponse::not_found()),
+        Err(e) => {
+            log::error!("Failed to load entry stats: {:?}", e);
+            Reply::json(500, &ErrorResponse::internal_error())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::store::{block_on, MemoryStore};
+
+    const KEY: &str = "writer-key";
+
+    #[test]
+    fn test_writer_hash_changes_by_day_and_key() {
+        let a = writer_hash(KEY, "2025-01-15", "192.0.2.1");
+        assert_eq!(a.len(), WRITER_HASH_LENGTH);
+        assert_eq!(a, writer_hash(KEY, "2025-01-15", "192.0.2.1"));
+        assert_ne!(a, writer_hash(KEY, "2025-01-16", "192.0.2.1"));
+        // 鍵を知らなければ同じハッシュを作れない
+        assert_ne!(a, writer_hash("other", "2025-01-15", "192.0.2.1"));
+        assert!(!a.contains("192"));
+    }
+
+    #[test]
+    fn test_compact_saves_keeps_writer_counts() {
+        let store = MemoryStore::default();
+        let saves = [
+            ("2025-01-01", "a"),
+            ("2025-01-01", "b"),
+            ("2025-01-01", "a"),
+            ("2025-01-10", "a"),
+        ];
+        for (date, client) in saves {
+            block_on(store.record_save(date, &writer_hash(KEY, date, client))).unwrap();
+        }
+
+        block_on(compact_saves(&store, "2025-01-15")).unwrap();
+        assert_eq!(block_on(store.save_activity("2025-01-01")).unwrap().writers, 2);
+        assert_eq!(block_on(store.save_activity("2025-01-10")).unwrap().writers, 1);
+        // 保持期間内の記録はハッシュのまま残す
+        block_on(compact_saves(&store, "2025-01-15")).unwrap();
+        assert_eq!(block_on(store.save_activity("2025-01-01")).unwrap().writers, 2);
+        block_on(compact_saves(&store, "2025-01-20")).unwrap();
+        assert_eq!(block_on(store.save_activity("2025-01-10")).unwrap().writers, 1);
+    }
+
+    #[test]
+    fn test_script_ratios() {
+        let ratios = ScriptRatios::of("ひらがなカナ漢字 ABC 👍🏻");
+        assert_eq!(ratios.hiragana, 0.333);
+        assert_eq!(ratios.katakana, 0.167);
+        assert_eq!(ratios.kanji, 0.167);
+        assert_eq!(ratios.latin, 0.25);
+        assert_eq!(ratios.emoji, 0.083);
+        assert_eq!(ratios.other, 0.0);
+        // 絵文字をつなぐZWJは数えない
+        let family = ScriptRatios::of("👨\u{200D}👩\u{200D}👧!");
+        assert_eq!(family.emoji, 0.75);
+        assert_eq!(ScriptRatios::of(" \n"), ScriptRatios::default());
+    }
+
+    #[test]
+    fn test_build() {
+        let entry = DiaryEntry {
+            date: "2025-01-14".to_string(),
+            content: "一行目\n二行目".to_string(),
+            created_at: "2025-01-14T01:00:00+00:00".to_string(),
+            updated_at: "2025-01-14T14:00:00+00:00".to_string(),
+        };
+        let activity = SaveActivity {
+            versions: 2,
+            writers: 0,
+            last_changed_at: Some("2025-01-14T13:00:00+00:00".to_string()),
+        };
+        let stats = EntryStats::build(&entry, &activity);
+        assert_eq!(stats.characters, 7);
+        assert_eq!(stats.lines, 2);
+        assert_eq!(stats.saves, 3);
+        assert_eq!(stats.writers, None);
+        // JSTの22時に書き換えられ、日付が変わるまで2時間残った
+        assert_eq!(stats.survived_seconds, Some(2 * 3600));
+        assert_eq!(stats.final_text_since, "2025-01-14T13:00:00+00:00");
+
+        let stats = EntryStats::build(&entry, &SaveActivity::default());
+        assert_eq!(stats.saves, 1);
+        assert_eq!(stats.survived_seconds, Some(14 * 3600));
+    }
+
+    #[test]
+    fn test_entry_stats_flow() {
+        let store = MemoryStore::default();
+        store.set_now("2025-01-14T01:00:00+00:00");
+        block_on(store.upsert_entry("2025-01-14", "一回目")).unwrap();
+        block_on(store.record_save("2025-01-14", &writer_hash(KEY, "2025-01-14", "a"))).unwrap();
+        store.set_now("2025-01-14T02:00:00+00:00");
+        block_on(store.upsert_entry("2025-01-14", "二回目")).unwrap();
+        block_on(store.record_save("2025-01-14", &writer_hash(KEY, "2025-01-14", "b"))).unwrap();
+        block_on(store.upsert_entry("2025-01-14", "二回目")).unwrap();
+        block_on(store.record_save("2025-01-14", &writer_hash(KEY, "2025-01-14", "b"))).unwrap();
+        block_on(store.upsert_entry("2025-01-15", "今日")).unwrap();
+
+        let reply = block_on(entry_stats_reply(&store, Some("2025-01-14"), "2025-01-15")).unwrap();
+        assert_eq!(reply.status, 200);
+        assert_eq!(reply.field("saves"), 2);
+        assert_eq!(reply.field("writers"), 2);
+        assert_eq!(reply.field("final_text_since"), "2025-01-14T02:00:00+00:00");
+        assert_eq!(reply.field("survived_seconds"), 13 * 3600);
+
+        for (date, status) in [
+            (Some("2025-01-15"), 404),
+            (Some("2025-01-13"), 404),
+            (Some("2025-02-30"), 400),
+            (None, 400),
+        ] {
+            let reply = block_on(entry_stats_reply(&store, date, "2025-01-15")).unwrap();
+            assert_eq!(reply.status, status);
+        }
+    }
+}
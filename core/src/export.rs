@@ -0,0 +1,383 @@
+use serde::{Deserialize, Serialize};
+
+use crate::models::{DiaryEntry, DiaryVersion};
+use crate::store::DiaryStore;
+use crate::time::parse_iso8601_unix;
+
+/// 1回の問い合わせで読む件数
+///
+/// 全件を一度に読まず、ページごとに書き出してWorkersのメモリに載せきらないようにする。
+pub const PAGE_SIZE: i32 = 100;
+
+/// Markdownの束の中で日記を置くディレクトリ
+const MARKDOWN_DIR: &str = "darekagakaku";
+
+/// tarのブロックの大きさ
+const TAR_BLOCK: usize = 512;
+
+/// 書き出しの形式
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExportFormat {
+    /// 1行に1日分のJSON
+    Jsonl,
+    /// 日付・本文・作成日時・更新日時の表
+    Csv,
+    /// 1日1ファイルのMarkdownをまとめたtar
+    Markdown,
+    /// 日記と版を`type`で区別したJSON Lines（管理者用）
+    Full,
+}
+
+impl ExportFormat {
+    pub fn content_type(&self) -> &'static str {
+        match self {
+            Self::Jsonl | Self::Full => "application/x-ndjson; charset=utf-8",
+            Self::Csv => "text/csv; charset=utf-8",
+            Self::Markdown => "application/x-tar",
+        }
+    }
+
+    /// ダウンロード時のファイル名
+    pub fn file_name(&self) -> &'static str {
+        match self {
+            Self::Jsonl => "darekagakaku-entries.jsonl",
+            Self::Csv => "darekagakaku-entries.csv",
+            Self::Markdown => "darekagakaku-entries.tar",
+            Self::Full => "darekagakaku-full.jsonl",
+        }
+    }
+
+    /// 応答に付けるヘッダー
+    pub fn headers(&self) -> [(&'static str, String); 2] {
+        [
+            ("Content-Type", self.content_type().to_string()),
+            (
+                "Content-Disposition",
+                format!("attachment; filename=\"{}\"", self.file_name()),
+            ),
+        ]
+    }
+
+    /// 先頭に書くもの
+    fn header(&self) -> Vec<u8> {
+        match self {
+            // Excelで開いても文字化けしないようBOMを付ける
+            Self::Csv => "\u{feff}date,content,created_at,updated_at\r\n".into(),
+            Self::Jsonl | Self::Markdown | Self::Full => Vec::new(),
+        }
+    }
+
+    /// 末尾に書くもの
+    fn footer(&self) -> Vec<u8> {
+        match self {
+            Self::Markdown => vec![0; TAR_BLOCK * 2],
+            Self::Jsonl | Self::Csv | Self::Full => Vec::new(),
+        }
+    }
+
+    /// 1日分の日記
+    fn entry(&self, entry: &DiaryEntry, out: &mut Vec<u8>) {
+        match self {
+            Self::Jsonl => push_json_line(out, entry),
+            Self::Csv => {
+                let fields = [
+                    &entry.date,
+                    &entry.content,
+                    &entry.created_at,
+                    &entry.updated_at,
+                ];
+                let row: Vec<String> = fields.iter().map(|f| csv_field(f)).collect();
+                out.extend_from_slice(row.join(",").as_bytes());
+                out.extend_from_slice(b"\r\n");
+            }
+            Self::Markdown => {
+                let name = format!("{}/{}.md", MARKDOWN_DIR, entry.date);
+                let mtime = parse_iso8601_unix(&entry.updated_at).unwrap_or(0);
+                push_tar_file(out, &name, markdown(entry).as_bytes(), mtime);
+            }
+            Self::Full => push_json_line(out, &ExportRecord::Entry(entry.clone())),
+        }
+    }
+}
+
+/// 管理者用のエクスポートの1行
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ExportRecord {
+    Entry(DiaryEntry),
+    Version(DiaryVersion),
+}
+
+fn push_json_line(out: &mut Vec<u8>, value: &impl Serialize) {
+    // 文字列のキーだけの構造体なので失敗しない
+    if serde_json::to_writer(&mut *out, value).is_ok() {
+        out.push(b'\n');
+    }
+}
+
+/// 表計算ソフトが数式として解釈する先頭の文字
+const FORMULA_PREFIXES: &[char] = &['=', '+', '-', '@', '\t', '\r'];
+
+/// RFC 4180の形でCSVの1項目を書く（本文には改行やカンマが入るため常に囲む）
+///
+/// 数式として実行されないよう、数式の記号で始まる項目は先頭に`'`を付ける。
+fn csv_field(value: &str) -> String {
+    let escaped = value.replace('"', "\"\"");
+    if value.starts_with(FORMULA_PREFIXES) {
+        format!("\"'{}\"", escaped)
+    } else {
+        format!("\"{}\"", escaped)
+    }
+}
+
+/// 1日分のMarkdown
+fn markdown(entry: &DiaryEntry) -> String {
+    format!("# {}\n\n{}\n", entry.date, entry.content)
+}
+
+/// tar（ustar）の1ファイル分を書く
+fn push_tar_file(out: &mut Vec<u8>, name: &str, data: &[u8], mtime: i64) {
+    let mut header = [0u8; TAR_BLOCK];
+    let mut put = |offset: usize, value: &[u8]| {
+        header[offset..offset + value.len()].copy_from_slice(value);
+    };
+    put(0, name.as_bytes());
+    put(100, b"0000644\0");
+    put(108, b"0000000\0");
+    put(116, b"0000000\0");
+    put(124, format!("{:011o}\0", data.len()).as_bytes());
+    put(136, format!("{:011o}\0", mtime.max(0)).as_bytes());
+    // チェックサムは自分の欄を空白とみなして計算する
+    put(148, b"        ");
+    put(156, b"0");
+    put(257, b"ustar\0");
+    put(263, b"00");
+    let checksum: u32 = header.iter().map(|&b| u32::from(b)).sum();
+    header[148..156].copy_from_slice(format!("{:06o}\0 ", checksum).as_bytes());
+
+    out.extend_from_slice(&header);
+    out.extend_from_slice(data);
+    let padding = (TAR_BLOCK - data.len() % TAR_BLOCK) % TAR_BLOCK;
+    out.resize(out.len() + padding, 0);
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Phase {
+    Header,
+    Entries { after: String },
+    Versions { after: i64 },
+    Footer,
+    Done,
+}
+
+/// ページごとに読み進めながら書き出す
+///
+/// `next_chunk`を呼ぶたびに1ページ分のバイト列を返し、最後まで書き出したらNoneを返す。
+/// Workersではこれをストリームにつなぎ、全件を一度にメモリに置かない。
+pub struct Export {
+    format: ExportFormat,
+    before: Option<String>,
+    phase: Phase,
+}
+
+impl Export {
+    /// 確定した日記（`today`より前の日付）だけを書き出す
+    pub fn finalized(format: ExportFormat, today: &str) -> Self {
+        Self {
+            format,
+            before: Some(today.to_string()),
+            phase: Phase::Header,
+        }
+    }
+
+    /// 今日の日記と版を含めてすべて書き出す（管理者用）
+    pub fn full() -> Self {
+        Self {
+            format: ExportFormat::Full,
+            before: None,
+            phase: Phase::Header,
+        }
+    }
+
+    pub fn format(&self) -> ExportFormat {
+        self.format
+    }
+
+    /// 次のまとまりを書き出す
+    pub async fn next_chunk<S: DiaryStore>(
+        &mut self,
+        store: &S,
+    ) -> Result<Option<Vec<u8>>, S::Error> {
+        let mut out = Vec::new();
+        match &self.phase {
+            Phase::Header => {
+                out = self.format.header();
+                self.phase = Phase::Entries {
+                    after: String::new(),
+                };
+            }
+            Phase::Entries { after } => {
+                let page = store.list_entries_after(after, PAGE_SIZE).await?;
+                let mut next = after.clone();
+                let mut reached_end = page.len() < PAGE_SIZE as usize;
+                for entry in &page {
+                    if self.before.as_deref().is_some_and(|before| entry.date.as_str() >= before) {
+                        reached_end = true;
+                        break;
+                    }
+                    self.format.entry(entry, &mut out);
+                    next = entry.date.clone();
+                }
+                self.phase = match (reached_end, self.format) {
+                    (false, _) => Phase::Entries { after: next },
+                    (true, ExportFormat::Full) => Phase::Versions { after: 0 },
+                    (true, _) => Phase::Footer,
+                };
+            }
+            Phase::Versions { after } => {
+                let page = store.list_versions_after(*after, PAGE_SIZE).await?;
+                for version in &page {
+                    push_json_line(&mut out, &ExportRecord::Version(version.clone()));
+                }
+                self.phase = match page.last() {
+                    Some(last) if page.len() == PAGE_SIZE as usize => {
+                        Phase::Versions { after: last.id }
+                    }
+                    _ => Phase::Footer,
+                };
+            }
+            Phase::Footer => {
+                out = self.format.footer();
+                self.phase = Phase::Done;
+            }
+            Phase::Done => return Ok(None),
+        }
+        Ok(Some(out))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::store::{block_on, MemoryStore};
+
+    fn export_all(store: &MemoryStore, mut export: Export) -> Vec<u8> {
+        let mut out = Vec::new();
+        while let Some(chunk) = block_on(export.next_chunk(store)).unwrap() {
+            out.extend(chunk);
+        }
+        out
+    }
+
+    fn store_with(dates: &[&str]) -> MemoryStore {
+        let store = MemoryStore::default();
+        for date in dates {
+            block_on(store.upsert_entry(date, &format!("{}の日記", date))).unwrap();
+        }
+        store
+    }
+
+    #[test]
+    fn test_jsonl_export_skips_today() {
+        let store = store_with(&["2025-01-13", "2025-01-14", "2025-01-15"]);
+        let out = export_all(&store, Export::finalized(ExportFormat::Jsonl, "2025-01-15"));
+        let lines: Vec<DiaryEntry> = String::from_utf8(out)
+            .unwrap()
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        let dates: Vec<&str> = lines.iter().map(|e| e.date.as_str()).collect();
+        assert_eq!(dates, ["2025-01-13", "2025-01-14"]);
+        assert_eq!(lines[0].content, "2025-01-13の日記");
+    }
+
+    #[test]
+    fn test_export_pages_through_all_entries() {
+        let store = MemoryStore::default();
+        let start = chrono::NaiveDate::from_ymd_opt(2024, 1, 1).unwrap();
+        let dates: Vec<String> = (0..250)
+            .map(|i| (start + chrono::Days::new(i)).format("%Y-%m-%d").to_string())
+            .collect();
+        for date in &dates {
+            block_on(store.upsert_entry(date, "本文")).unwrap();
+        }
+
+        let out = export_all(&store, Export::finalized(ExportFormat::Jsonl, "2099-01-01"));
+        let text = String::from_utf8(out).unwrap();
+        assert_eq!(text.lines().count(), 250);
+        assert!(text.lines().last().unwrap().contains(dates.last().unwrap()));
+    }
+
+    #[test]
+    fn test_csv_export_quotes_fields() {
+        let store = MemoryStore::default();
+        block_on(store.upsert_entry("2025-01-14", "一行目, \"引用\"\n二行目")).unwrap();
+        let out = export_all(&store, Export::finalized(ExportFormat::Csv, "2025-01-15"));
+        let text = String::from_utf8(out).unwrap();
+        let mut lines = text.split("\r\n");
+        assert_eq!(lines.next(), Some("\u{feff}date,content,created_at,updated_at"));
+        assert_eq!(
+            lines.next(),
+            Some(concat!(
+                "\"2025-01-14\",\"一行目, \"\"引用\"\"\n二行目\",",
+                "\"2025-01-15T00:00:00+00:00\",\"2025-01-15T00:00:00+00:00\""
+            ))
+        );
+    }
+
+    #[test]
+    fn test_csv_field_neutralizes_formulas() {
+        assert_eq!(csv_field("=HYPERLINK(\"x\")"), "\"'=HYPERLINK(\"\"x\"\")\"");
+        assert_eq!(csv_field("+1"), "\"'+1\"");
+        assert_eq!(csv_field("-1"), "\"'-1\"");
+        assert_eq!(csv_field("@SUM(A1)"), "\"'@SUM(A1)\"");
+        assert_eq!(csv_field("\tタブ"), "\"'\tタブ\"");
+        // 途中の記号や日付はそのまま
+        assert_eq!(csv_field("1+1=2"), "\"1+1=2\"");
+        assert_eq!(csv_field("2025-01-14"), "\"2025-01-14\"");
+    }
+
+    #[test]
+    fn test_markdown_export_is_a_tar_of_days() {
+        let store = store_with(&["2025-01-13", "2025-01-14"]);
+        let out = export_all(&store, Export::finalized(ExportFormat::Markdown, "2025-01-15"));
+        // 1ファイルにつきヘッダーと本文で2ブロック、最後に空のブロックが2つ
+        assert_eq!(out.len(), TAR_BLOCK * 6);
+        assert_eq!(&out[..28], b"darekagakaku/2025-01-13.md\0\0");
+        assert_eq!(&out[257..262], b"ustar");
+
+        let body = "# 2025-01-13\n\n2025-01-13の日記\n";
+        assert_eq!(&out[124..136], format!("{:011o}\0", body.len()).as_bytes());
+        assert_eq!(&out[TAR_BLOCK..TAR_BLOCK + body.len()], body.as_bytes());
+
+        // チェックサムは欄を空白として数えたヘッダーの合計
+        let mut header = out[..TAR_BLOCK].to_vec();
+        let stored = String::from_utf8(header[148..154].to_vec()).unwrap();
+        header[148..156].copy_from_slice(b"        ");
+        let sum: u32 = header.iter().map(|&b| u32::from(b)).sum();
+        assert_eq!(u32::from_str_radix(&stored, 8).unwrap(), sum);
+        assert!(out[TAR_BLOCK * 4..].iter().all(|&b| b == 0));
+    }
+
+    #[test]
+    fn test_full_export_includes_today_and_versions() {
+        let store = store_with(&["2025-01-14", "2025-01-15"]);
+        block_on(store.upsert_entry("2025-01-15", "書き直した")).unwrap();
+
+        let out = export_all(&store, Export::full());
+        let records: Vec<ExportRecord> = String::from_utf8(out)
+            .unwrap()
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        assert_eq!(records.len(), 3);
+        assert!(matches!(&records[1], ExportRecord::Entry(e) if e.content == "書き直した"));
+        match &records[2] {
+            ExportRecord::Version(v) => {
+                assert_eq!(v.entry_date, "2025-01-15");
+                assert_eq!(v.content, "2025-01-15の日記");
+            }
+            other => panic!("expected a version, got {:?}", other),
+        }
+    }
+}
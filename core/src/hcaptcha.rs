@@ -0,0 +1,159 @@
+use serde::Deserialize;
+
+use crate::models::encode_query_value;
+use crate::verification::VerificationFailure;
+
+/// 既定の検証エンドポイント（互換サービスはHCAPTCHA_VERIFY_URLで差し替える）
+pub const DEFAULT_VERIFY_URL: &str = "https://api.hcaptcha.com/siteverify";
+
+/// siteverifyの応答
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct HCaptchaResponse {
+    pub success: bool,
+    #[serde(rename = "error-codes", default)]
+    pub error_codes: Vec<String>,
+    #[serde(default)]
+    pub hostname: Option<String>,
+}
+
+const fn failure(code: &'static str, message: &'static str, status: u16) -> VerificationFailure {
+    VerificationFailure {
+        code,
+        message,
+        status,
+    }
+}
+
+const EXPIRED: VerificationFailure = failure(
+    "HCAPTCHA_EXPIRED",
+    "hCaptcha token expired or already used",
+    400,
+);
+pub const INVALID_TOKEN: VerificationFailure =
+    failure("HCAPTCHA_INVALID_TOKEN", "Invalid hCaptcha token", 400);
+const MISCONFIGURED: VerificationFailure = failure(
+    "HCAPTCHA_MISCONFIGURED",
+    "hCaptcha verification unavailable",
+    500,
+);
+const HOSTNAME_MISMATCH: VerificationFailure = failure(
+    "HCAPTCHA_HOSTNAME_MISMATCH",
+    "hCaptcha token was issued for another site",
+    400,
+);
+const FAILED: VerificationFailure =
+    failure("HCAPTCHA_FAILED", "hCaptcha verification failed", 400);
+/// siteverifyに問い合わせできなかった
+pub const UNAVAILABLE: VerificationFailure = failure(
+    "HCAPTCHA_UNAVAILABLE",
+    "hCaptcha verification unavailable",
+    503,
+);
+
+/// siteverifyに送る本文（application/x-www-form-urlencoded、純粋関数）
+pub fn request_body(secret: &str, token: &str, ip: Option<&str>, site_key: Option<&str>) -> String {
+    let mut pairs = vec![("secret", secret), ("response", token)];
+    if let Some(ip) = ip {
+        pairs.push(("remoteip", ip));
+    }
+    if let Some(site_key) = site_key {
+        pairs.push(("sitekey", site_key));
+    }
+    pairs
+        .iter()
+        .map(|(k, v)| format!("{}={}", k, encode_query_value(v)))
+        .collect::<Vec<_>>()
+        .join("&")
+}
+
+/// siteverifyの応答を検証結果に変換（純粋関数）
+pub fn check_response(
+    resp: &HCaptchaResponse,
+    hostname: Option<&str>,
+) -> Result<(), VerificationFailure> {
+    if !resp.success {
+        let has = |code: &str| resp.error_codes.iter().any(|c| c == code);
+        return Err(if has("invalid-or-already-seen-response") {
+            EXPIRED
+        } else if has("missing-input-secret") || has("invalid-input-secret") {
+            MISCONFIGURED
+        } else if has("missing-input-response") || has("invalid-input-response") {
+            INVALID_TOKEN
+        } else {
+            FAILED
+        });
+    }
+
+    if let Some(hostname) = hostname {
+        let matches = resp
+            .hostname
+            .as_deref()
+            .is_some_and(|h| h.eq_ignore_ascii_case(hostname));
+        if !matches {
+            return Err(HOSTNAME_MISMATCH);
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn response(success: bool, codes: &[&str], hostname: Option<&str>) -> HCaptchaResponse {
+        HCaptchaResponse {
+            success,
+            error_codes: codes.iter().map(|c| c.to_string()).collect(),
+            hostname: hostname.map(|h| h.to_string()),
+        }
+    }
+
+    #[test]
+    fn test_hcaptcha_response_deserialization() {
+        let json =
+            r#"{"success": false, "error-codes": ["invalid-input-response"], "credit": false}"#;
+        let resp: HCaptchaResponse = serde_json::from_str(json).unwrap();
+        assert_eq!(resp, response(false, &["invalid-input-response"], None));
+    }
+
+    #[test]
+    fn test_check_response() {
+        let host = Some("darekagakaku.day");
+        assert_eq!(
+            check_response(&response(true, &[], Some("darekagakaku.day")), host),
+            Ok(())
+        );
+        assert_eq!(check_response(&response(true, &[], None), None), Ok(()));
+        assert_eq!(
+            check_response(&response(true, &[], Some("evil.example")), host),
+            Err(HOSTNAME_MISMATCH)
+        );
+        assert_eq!(
+            check_response(
+                &response(false, &["invalid-or-already-seen-response"], None),
+                host
+            ),
+            Err(EXPIRED)
+        );
+        assert_eq!(
+            check_response(&response(false, &["invalid-input-secret"], None), host),
+            Err(MISCONFIGURED)
+        );
+        assert_eq!(
+            check_response(&response(false, &["sitekey-secret-mismatch"], None), host),
+            Err(FAILED)
+        );
+    }
+
+    #[test]
+    fn test_request_body() {
+        assert_eq!(
+            request_body("0x12", "a+b/c=", None, None),
+            "secret=0x12&response=a%2Bb%2Fc%3D"
+        );
+        assert_eq!(
+            request_body("s", "t", Some("192.0.2.1"), Some("site")),
+            "secret=s&response=t&remoteip=192.0.2.1&sitekey=site"
+        );
+    }
+}
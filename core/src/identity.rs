@@ -0,0 +1,40 @@
+use serde::{Deserialize, Serialize};
+
+use crate::models::AdminRole;
+
+/// フォームでCSRFトークンを送るフィールド名
+pub const CSRF_FIELD_NAME: &str = "csrf_token";
+/// fetchでCSRFトークンを送るヘッダー名
+pub const CSRF_HEADER_NAME: &str = "X-CSRF-Token";
+/// ADMIN_TOKENでログインした場合の管理者名
+pub const BOOTSTRAP_USERNAME: &str = "bootstrap";
+
+/// 認証済みの管理者
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AdminIdentity {
+    /// adminsテーブルのID（ADMIN_TOKENによるブートストラップ管理者はNone）
+    pub admin_id: Option<i64>,
+    pub username: String,
+    pub role: AdminRole,
+    /// 状態を変更するリクエストに要求するCSRFトークン
+    /// （Bearerトークンのようにブラウザが自動送信しない認証ではNone）
+    #[serde(default)]
+    pub csrf_token: Option<String>,
+}
+
+impl AdminIdentity {
+    /// ADMIN_TOKENで認証されたスーパーユーザー
+    pub fn bootstrap() -> Self {
+        Self {
+            admin_id: None,
+            username: BOOTSTRAP_USERNAME.to_string(),
+            role: AdminRole::Superuser,
+            csrf_token: None,
+        }
+    }
+
+    /// 指定した権限以上を持つか
+    pub fn has_role(&self, required: AdminRole) -> bool {
+        self.role >= required
+    }
+}
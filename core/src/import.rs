@@ -0,0 +1,635 @@
+use std::collections::{BTreeSet, HashMap};
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::models::{DiaryEntry, DiaryVersion};
+use crate::reply::Reply;
+use crate::store::{ClearRange, DiaryStore, ImportWrite};
+use crate::time::parse_iso8601_unix;
+use crate::validation::{
+    is_content_too_long, is_valid_date, normalize_content, MAX_CONTENT_LENGTH,
+};
+
+/// D1で1回の呼び出しに発行できる問い合わせの数
+pub const QUERY_BUDGET: usize = 1000;
+
+/// 1行の取り込みで発行する問い合わせの数の上限
+///
+/// 既存の日記と版の読み出しに1つずつ、版を残して日記を置き換える書き込みに2つを使う。
+pub const QUERIES_PER_RECORD: usize = 4;
+
+/// 認証や監査ログなど、取り込み以外に残しておく問い合わせの数
+///
+/// リストアで取り込む前に範囲を消す3つの問い合わせもここに含める。
+pub const RESERVED_QUERIES: usize = 100;
+
+/// 1回のインポートで受け付ける行数
+///
+/// D1の問い合わせの上限に収まるよう、大きなファイルは分けて送る。
+pub const MAX_IMPORT_RECORDS: usize = (QUERY_BUDGET - RESERVED_QUERIES) / QUERIES_PER_RECORD;
+
+/// 既にある日記と同じ日付を取り込むときの扱い
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ConflictPolicy {
+    /// 既存の日記を残す
+    #[default]
+    Skip,
+    /// 版を残さずに置き換える
+    Overwrite,
+    /// 通常の保存と同じく、既存の内容を版として残してから置き換える
+    Version,
+}
+
+impl ConflictPolicy {
+    pub fn parse(value: &str) -> Option<Self> {
+        match value {
+            "skip" => Some(Self::Skip),
+            "overwrite" => Some(Self::Overwrite),
+            "version" => Some(Self::Version),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Skip => "skip",
+            Self::Overwrite => "overwrite",
+            Self::Version => "version",
+        }
+    }
+}
+
+/// インポートの指定（クエリ文字列の`dry_run`と`on_conflict`）
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct ImportOptions {
+    pub dry_run: bool,
+    pub on_conflict: ConflictPolicy,
+}
+
+impl ImportOptions {
+    /// クエリ文字列のパラメータから作る（知らない扱いの指定はエラー）
+    pub fn from_query<'a>(
+        pairs: impl Iterator<Item = (&'a str, &'a str)>,
+    ) -> Result<Self, String> {
+        let mut options = Self::default();
+        for (key, value) in pairs {
+            let value = value.trim();
+            match key {
+                "dry_run" => options.dry_run = matches!(value, "1" | "true" | ""),
+                "on_conflict" => {
+                    options.on_conflict = ConflictPolicy::parse(value).ok_or_else(|| {
+                        format!("Unknown on_conflict: {} (use skip, overwrite or version)", value)
+                    })?;
+                }
+                _ => {}
+            }
+        }
+        Ok(options)
+    }
+}
+
+/// 取り込み先の前提（リストアで分けて取り込むときに使う）
+#[derive(Debug, Clone, Default)]
+pub struct Baseline {
+    /// 取り込む前に消す範囲（範囲内の今の日記と版はないものとして数える）
+    pub clear: Option<ClearRange>,
+    /// 前の分割で取り込んだ日記の日付（dry_runでは書き込まれていないため、あるものとして扱う）
+    pub earlier_entries: BTreeSet<String>,
+}
+
+/// 日記の行（公開のエクスポートの行と、管理者用の`type: entry`の行）
+#[derive(Debug, Deserialize)]
+struct EntryLine {
+    date: String,
+    content: String,
+    created_at: Option<String>,
+    updated_at: Option<String>,
+}
+
+/// 版の行（管理者用のエクスポートの`type: version`の行、idは無視する）
+#[derive(Debug, Deserialize)]
+struct VersionLine {
+    entry_date: String,
+    content: String,
+    #[serde(default)]
+    version_number: i32,
+    created_at: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum TypedLine {
+    Entry(EntryLine),
+    Version(VersionLine),
+}
+
+/// 取り込めなかった行（行番号は1から、0はファイル全体）
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ImportError {
+    pub line: usize,
+    pub error: String,
+}
+
+/// 日記の取り込み結果の件数
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct EntryCounts {
+    pub created: usize,
+    pub updated: usize,
+    /// 既存と同じ内容だった
+    pub unchanged: usize,
+    /// on_conflict=skipで既存を残した
+    pub skipped: usize,
+}
+
+/// 版の取り込み結果の件数
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct VersionCounts {
+    pub created: usize,
+    /// 同じ内容・日時の版が既にある、または日記を取り込まなかった
+    pub skipped: usize,
+}
+
+/// インポートの結果
+///
+/// 1行でも取り込めない行があれば何も書き込まず、`errors`に理由を並べる。
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ImportReport {
+    pub dry_run: bool,
+    pub on_conflict: ConflictPolicy,
+    /// 実際に書き込んだか
+    pub applied: bool,
+    pub entries: EntryCounts,
+    pub versions: VersionCounts,
+    pub errors: Vec<ImportError>,
+}
+
+impl ImportReport {
+    /// 結果の応答（取り込めない行があれば400）
+    pub fn reply(&self) -> serde_json::Result<Reply> {
+        let status = if self.errors.is_empty() { 200 } else { 400 };
+        Reply::json(status, self)
+    }
+}
+
+/// 検証を済ませた取り込み内容
+#[derive(Debug, Default)]
+struct Plan {
+    entries: Vec<EntryLine>,
+    versions: Vec<VersionLine>,
+    errors: Vec<ImportError>,
+}
+
+fn check_timestamp(value: &Option<String>, name: &str) -> Result<(), String> {
+    match value {
+        Some(value) if parse_iso8601_unix(value).is_none() => {
+            Err(format!("Invalid {}: {}", name, value))
+        }
+        _ => Ok(()),
+    }
+}
+
+fn check_content(content: &str) -> Result<String, String> {
+    let content = normalize_content(content);
+    if is_content_too_long(&content) {
+        return Err(format!(
+            "Content too long. Maximum {} characters allowed.",
+            MAX_CONTENT_LENGTH
+        ));
+    }
+    Ok(content)
+}
+
+/// 書きかけの今日と未来の日付は取り込まない（今日の日記は投稿でだけ書く）
+fn check_past(date: &str, today: &str) -> Result<(), String> {
+    if date >= today {
+        return Err(format!("Only dates before {} can be imported: {}", today, date));
+    }
+    Ok(())
+}
+
+fn check_entry(mut line: EntryLine, today: &str) -> Result<EntryLine, String> {
+    if !is_valid_date(&line.date) {
+        return Err(format!("Invalid date: {}", line.date));
+    }
+    check_past(&line.date, today)?;
+    line.content = check_content(&line.content)?;
+    check_timestamp(&line.created_at, "created_at")?;
+    check_timestamp(&line.updated_at, "updated_at")?;
+    Ok(line)
+}
+
+fn check_version(mut line: VersionLine, today: &str) -> Result<VersionLine, String> {
+    if !is_valid_date(&line.entry_date) {
+        return Err(format!("Invalid entry_date: {}", line.entry_date));
+    }
+    check_past(&line.entry_date, today)?;
+    line.content = check_content(&line.content)?;
+    check_timestamp(&line.created_at, "created_at")?;
+    Ok(line)
+}
+
+/// JSON Linesを読み、post_todayと同じ規則で検証する（空行は無視する）
+fn parse(body: &str, today: &str) -> Plan {
+    let mut plan = Plan::default();
+    let mut dates = BTreeSet::new();
+    let mut records = 0;
+    for (index, text) in body.lines().enumerate() {
+        let line = index + 1;
+        if text.trim().is_empty() {
+            continue;
+        }
+        records += 1;
+        let value: Value = match serde_json::from_str(text) {
+            Ok(value) => value,
+            Err(e) => {
+                plan.errors.push(ImportError {
+                    line,
+                    error: format!("Invalid JSON: {}", e),
+                });
+                continue;
+            }
+        };
+        // typeのない行は公開のエクスポートの日記として読む
+        let parsed = if value.get("type").is_some() {
+            serde_json::from_value(value)
+        } else {
+            serde_json::from_value(value).map(TypedLine::Entry)
+        };
+        let checked = match parsed {
+            Ok(TypedLine::Entry(entry)) => check_entry(entry, today).and_then(|entry| {
+                if dates.insert(entry.date.clone()) {
+                    plan.entries.push(entry);
+                    Ok(())
+                } else {
+                    Err(format!("Duplicate date: {}", entry.date))
+                }
+            }),
+            Ok(TypedLine::Version(version)) => {
+                check_version(version, today).map(|version| plan.versions.push(version))
+            }
+            Err(e) => Err(format!("Invalid record: {}", e)),
+        };
+        if let Err(error) = checked {
+            plan.errors.push(ImportError { line, error });
+        }
+    }
+    if records > MAX_IMPORT_RECORDS {
+        plan.errors.insert(
+            0,
+            ImportError {
+                line: 0,
+                error: format!(
+                    "Too many records ({}). Split the file into parts of at most {} lines.",
+                    records, MAX_IMPORT_RECORDS
+                ),
+            },
+        );
+    }
+    plan
+}
+
+/// JSON Linesの日記と版を取り込む
+///
+/// 日記を先に、版は日付と元の版番号の順に取り込む。同じ内容・作成日時の版が既にあれば
+/// 取り込まないため、同じファイルを2回流しても版は増えない。
+/// 書き込みは最後にまとめて行い、途中で失敗しても一部だけが書き込まれることはない。
+pub async fn run<S: DiaryStore>(
+    store: &S,
+    body: &str,
+    options: ImportOptions,
+    today: &str,
+    now: &str,
+) -> Result<ImportReport, S::Error> {
+    run_onto(store, body, options, &Baseline::default(), today, now).await
+}
+
+/// 前提を指定して取り込む
+///
+/// `baseline.clear`があれば、その範囲を消す書き込みを先頭に加え、同じまとまりで書き込む。
+pub async fn run_onto<S: DiaryStore>(
+    store: &S,
+    body: &str,
+    options: ImportOptions,
+    baseline: &Baseline,
+    today: &str,
+    now: &str,
+) -> Result<ImportReport, S::Error> {
+    let cleared = |date: &str| baseline.clear.as_ref().is_some_and(|c| c.covers(date));
+    let mut plan = parse(body, today);
+    let mut report = ImportReport {
+        dry_run: options.dry_run,
+        on_conflict: options.on_conflict,
+        applied: false,
+        entries: EntryCounts::default(),
+        versions: VersionCounts::default(),
+        errors: Vec::new(),
+    };
+    if !plan.errors.is_empty() {
+        report.errors = plan.errors;
+        return Ok(report);
+    }
+
+    // 書き込む前に、版が指す日記があるかを確かめる
+    let mut existing: HashMap<String, Option<DiaryEntry>> = HashMap::new();
+    for date in plan
+        .entries
+        .iter()
+        .map(|e| &e.date)
+        .chain(plan.versions.iter().map(|v| &v.entry_date))
+    {
+        if !existing.contains_key(date) {
+            let entry = if cleared(date) {
+                None
+            } else {
+                store.get_entry(date).await?
+            };
+            existing.insert(date.clone(), entry);
+        }
+    }
+    let imported: BTreeSet<&str> = plan.entries.iter().map(|e| e.date.as_str()).collect();
+    for version in &plan.versions {
+        let date = version.entry_date.as_str();
+        let earlier = baseline.earlier_entries.contains(date);
+        if existing[date].is_none() && !imported.contains(date) && !earlier {
+            report.errors.push(ImportError {
+                line: 0,
+                error: format!("Version refers to a missing entry: {}", date),
+            });
+        }
+    }
+    if !report.errors.is_empty() {
+        return Ok(report);
+    }
+
+    let mut writes: Vec<ImportWrite> =
+        baseline.clear.iter().cloned().map(ImportWrite::Clear).collect();
+    let mut skipped_dates = BTreeSet::new();
+    for line in &plan.entries {
+        let current = &existing[&line.date];
+        let Some(current) = current else {
+            report.entries.created += 1;
+            let created_at = line.created_at.as_deref().unwrap_or(now);
+            writes.push(ImportWrite::Entry(DiaryEntry {
+                date: line.date.clone(),
+                content: line.content.clone(),
+                created_at: created_at.to_string(),
+                updated_at: line.updated_at.as_deref().unwrap_or(created_at).to_string(),
+            }));
+            continue;
+        };
+        if current.content == line.content {
+            report.entries.unchanged += 1;
+            continue;
+        }
+        let entry = match options.on_conflict {
+            ConflictPolicy::Skip => {
+                report.entries.skipped += 1;
+                skipped_dates.insert(line.date.as_str());
+                continue;
+            }
+            ConflictPolicy::Overwrite => DiaryEntry {
+                date: line.date.clone(),
+                content: line.content.clone(),
+                created_at: line.created_at.clone().unwrap_or(current.created_at.clone()),
+                updated_at: line.updated_at.as_deref().unwrap_or(now).to_string(),
+            },
+            ConflictPolicy::Version => DiaryEntry {
+                date: line.date.clone(),
+                content: line.content.clone(),
+                created_at: current.created_at.clone(),
+                updated_at: line.updated_at.as_deref().unwrap_or(now).to_string(),
+            },
+        };
+        report.entries.updated += 1;
+        if options.on_conflict == ConflictPolicy::Version {
+            writes.push(ImportWrite::Version {
+                date: line.date.clone(),
+                content: current.content.clone(),
+                created_at: now.to_string(),
+            });
+        }
+        writes.push(ImportWrite::Entry(entry));
+    }
+
+    plan.versions
+        .sort_by_key(|v| (v.entry_date.clone(), v.version_number));
+    let mut known: HashMap<String, Vec<DiaryVersion>> = HashMap::new();
+    for line in &plan.versions {
+        let date = line.entry_date.as_str();
+        let created_at = line.created_at.as_deref().unwrap_or(now);
+        if skipped_dates.contains(date) {
+            report.versions.skipped += 1;
+            continue;
+        }
+        if !known.contains_key(date) {
+            let versions = if cleared(date) {
+                Vec::new()
+            } else {
+                store.list_versions(date).await?
+            };
+            known.insert(date.to_string(), versions);
+        }
+        let versions = known.get_mut(date).unwrap();
+        if versions
+            .iter()
+            .any(|v| v.content == line.content && v.created_at == created_at)
+        {
+            report.versions.skipped += 1;
+            continue;
+        }
+        report.versions.created += 1;
+        writes.push(ImportWrite::Version {
+            date: date.to_string(),
+            content: line.content.clone(),
+            created_at: created_at.to_string(),
+        });
+        versions.push(DiaryVersion {
+            id: 0,
+            entry_date: date.to_string(),
+            content: line.content.clone(),
+            version_number: 0,
+            created_at: created_at.to_string(),
+        });
+    }
+
+    if !options.dry_run {
+        store.write_import(&writes).await?;
+        report.applied = true;
+    }
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::export::{Export, ExportFormat};
+    use crate::store::{block_on, MemoryStore};
+
+    const TODAY: &str = "2025-02-01";
+    const NOW: &str = "2025-02-01T00:00:00+09:00";
+
+    fn options(on_conflict: ConflictPolicy, dry_run: bool) -> ImportOptions {
+        ImportOptions {
+            dry_run,
+            on_conflict,
+        }
+    }
+
+    fn import(store: &MemoryStore, body: &str, options: ImportOptions) -> ImportReport {
+        block_on(run(store, body, options, TODAY, NOW)).unwrap()
+    }
+
+    #[test]
+    fn test_options_from_query() {
+        let pairs = [("dry_run", "1"), ("on_conflict", "version")];
+        let options = ImportOptions::from_query(pairs.into_iter()).unwrap();
+        assert_eq!(options, self::options(ConflictPolicy::Version, true));
+
+        assert_eq!(
+            ImportOptions::from_query(std::iter::empty()),
+            Ok(ImportOptions::default())
+        );
+        assert!(ImportOptions::from_query([("on_conflict", "merge")].into_iter()).is_err());
+    }
+
+    #[test]
+    fn test_import_creates_entries_with_timestamps() {
+        let store = MemoryStore::default();
+        let body = concat!(
+            r#"{"date":"2025-01-13","content":"一日目\r\nです","#,
+            r#""created_at":"2025-01-13T09:00:00+09:00","#,
+            r#""updated_at":"2025-01-13T22:00:00+09:00"}"#,
+            "\n\n",
+            r#"{"type":"entry","date":"2025-01-14","content":"二日目"}"#,
+            "\n",
+        );
+        let report = import(&store, body, ImportOptions::default());
+        assert!(report.applied);
+        assert_eq!(report.entries.created, 2);
+
+        let entry = block_on(store.get_entry("2025-01-13")).unwrap().unwrap();
+        assert_eq!(entry.content, "一日目\nです");
+        assert_eq!(entry.created_at, "2025-01-13T09:00:00+09:00");
+        assert_eq!(entry.updated_at, "2025-01-13T22:00:00+09:00");
+        let entry = block_on(store.get_entry("2025-01-14")).unwrap().unwrap();
+        assert_eq!(entry.created_at, NOW);
+    }
+
+    #[test]
+    fn test_import_rejects_whole_file_on_bad_line() {
+        let store = MemoryStore::default();
+        let long = "あ".repeat(MAX_CONTENT_LENGTH + 1);
+        let body = [
+            r#"{"date":"2025-01-13","content":"正しい行"}"#.to_string(),
+            r#"{"date":"2025-02-30","content":"存在しない日"}"#.to_string(),
+            format!(r#"{{"date":"2025-01-14","content":"{}"}}"#, long),
+            "{".to_string(),
+            r#"{"date":"2025-01-13","content":"重複"}"#.to_string(),
+            r#"{"type":"comment","text":"知らない種類"}"#.to_string(),
+            r#"{"date":"2025-02-01","content":"書きかけの今日"}"#.to_string(),
+            r#"{"type":"version","entry_date":"2025-03-01","content":"未来"}"#.to_string(),
+        ]
+        .join("\n");
+        let report = import(&store, &body, ImportOptions::default());
+        assert!(!report.applied);
+        let lines: Vec<usize> = report.errors.iter().map(|e| e.line).collect();
+        assert_eq!(lines, [2, 3, 4, 5, 6, 7, 8]);
+        assert!(report.errors[5].error.contains("before 2025-02-01"));
+        assert_eq!(report.reply().unwrap().status, 400);
+        assert!(block_on(store.get_entry("2025-01-13")).unwrap().is_none());
+
+        let line = format!("{}\n", r#"{"date":"2025-01-13","content":"a"}"#);
+        let body = line.repeat(MAX_IMPORT_RECORDS + 1);
+        let report = import(&store, &body, ImportOptions::default());
+        assert_eq!(report.errors[0].line, 0);
+    }
+
+    #[test]
+    fn test_conflict_policies() {
+        let body = r#"{"date":"2025-01-14","content":"取り込んだ内容"}"#;
+        let setup = || {
+            let store = MemoryStore::default();
+            block_on(store.upsert_entry("2025-01-14", "元の内容")).unwrap();
+            store
+        };
+        let content = |store: &MemoryStore| {
+            block_on(store.get_entry("2025-01-14")).unwrap().unwrap().content
+        };
+        let version_count = |store: &MemoryStore| {
+            block_on(store.list_versions("2025-01-14")).unwrap().len()
+        };
+
+        let store = setup();
+        let report = import(&store, body, options(ConflictPolicy::Skip, false));
+        assert_eq!(report.entries.skipped, 1);
+        assert_eq!(content(&store), "元の内容");
+
+        let store = setup();
+        let report = import(&store, body, options(ConflictPolicy::Overwrite, false));
+        assert_eq!(report.entries.updated, 1);
+        assert_eq!(content(&store), "取り込んだ内容");
+        assert_eq!(version_count(&store), 0);
+
+        let store = setup();
+        import(&store, body, options(ConflictPolicy::Version, false));
+        assert_eq!(content(&store), "取り込んだ内容");
+        let versions = block_on(store.list_versions("2025-01-14")).unwrap();
+        assert_eq!(versions[0].content, "元の内容");
+
+        // 同じ内容は数えるだけ
+        let report = import(&store, body, options(ConflictPolicy::Overwrite, false));
+        assert_eq!(report.entries.unchanged, 1);
+    }
+
+    #[test]
+    fn test_dry_run_writes_nothing() {
+        let store = MemoryStore::default();
+        block_on(store.upsert_entry("2025-01-14", "元の内容")).unwrap();
+        let body = concat!(
+            r#"{"date":"2025-01-13","content":"新しい日"}"#,
+            "\n",
+            r#"{"date":"2025-01-14","content":"上書き"}"#,
+        );
+        let report = import(&store, body, options(ConflictPolicy::Overwrite, true));
+        assert!(!report.applied);
+        assert_eq!(report.entries.created, 1);
+        assert_eq!(report.entries.updated, 1);
+        assert!(block_on(store.get_entry("2025-01-13")).unwrap().is_none());
+        assert_eq!(report.reply().unwrap().field("dry_run"), true);
+    }
+
+    #[test]
+    fn test_full_export_round_trips() {
+        let source = MemoryStore::default();
+        block_on(source.upsert_entry("2025-01-14", "一回目")).unwrap();
+        block_on(source.upsert_entry("2025-01-14", "二回目")).unwrap();
+        block_on(source.upsert_entry("2025-01-15", "今日")).unwrap();
+        let mut export = Export::full();
+        assert_eq!(export.format(), ExportFormat::Full);
+        let mut body = Vec::new();
+        while let Some(chunk) = block_on(export.next_chunk(&source)).unwrap() {
+            body.extend(chunk);
+        }
+        let body = String::from_utf8(body).unwrap();
+
+        let target = MemoryStore::default();
+        let report = import(&target, &body, ImportOptions::default());
+        assert_eq!(report.entries.created, 2);
+        assert_eq!(report.versions.created, 1);
+        let versions = block_on(target.list_versions("2025-01-14")).unwrap();
+        assert_eq!(versions[0].content, "一回目");
+
+        // もう一度流しても版は増えない
+        let report = import(&target, &body, ImportOptions::default());
+        assert_eq!(report.entries.unchanged, 2);
+        assert_eq!(report.versions.skipped, 1);
+        assert_eq!(block_on(target.list_versions("2025-01-14")).unwrap().len(), 1);
+
+        // 日記のない日付の版は取り込まない
+        let orphan = r#"{"type":"version","entry_date":"2024-12-31","content":"迷子"}"#;
+        let report = import(&target, orphan, ImportOptions::default());
+        assert!(report.errors[0].error.contains("missing entry"));
+    }
+}
@@ -0,0 +1,39 @@
+//! 誰かが書く日記のうち、実行環境に依存しない部分
+//!
+//! ドメインの型、入力の検証、HTMLとRSSの組み立て、時刻の計算に加えて、
+//! 保存先やレート制限の状態を抽象化したリクエストの処理をまとめる。
+//! Cloudflare Workersのバインディングには依存せず、ネイティブでビルド・テストできる。
+
+// 保存先のトレイトはWorkersのシングルスレッドでも使うためSendを要求しない。
+// ネイティブのサーバーは具体的な型で呼び出すので、futureがSendかはそこで判定される。
+#![allow(async_fn_in_trait)]
+
+pub mod api;
+pub mod api_keys;
+pub mod backup;
+pub mod config;
+pub mod content_filter;
+pub mod crypto;
+pub mod entry_stats;
+pub mod export;
+pub mod feed;
+pub mod hcaptcha;
+pub mod identity;
+pub mod import;
+pub mod migrations;
+pub mod models;
+pub mod moderation;
+pub mod overwrite;
+pub mod pages;
+pub mod pow;
+pub mod rate_limit;
+pub mod reply;
+pub mod spam;
+pub mod stats;
+pub mod store;
+pub mod templates;
+pub mod time;
+pub mod turnstile;
+pub mod validation;
+pub mod verification;
+pub mod write;
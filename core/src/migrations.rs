@@ -0,0 +1,149 @@
+//! 番号付きのスキーママイグレーション
+//!
+//! SQLはリポジトリ直下のmigrations/に置き、wranglerの`d1 migrations apply`と
+//! ネイティブのサーバーの両方から同じファイルを当てる。適用済みの記録には
+//! wranglerと同じ形のschema_migrationsテーブルを使う。
+
+/// 適用済みのマイグレーションを記録するテーブル（wrangler.tomlのmigrations_table）
+pub const MIGRATIONS_TABLE: &str = "schema_migrations";
+
+/// 記録用のテーブル（wranglerが作るものと同じ列）
+pub const CREATE_MIGRATIONS_TABLE: &str = "CREATE TABLE IF NOT EXISTS schema_migrations (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    name TEXT UNIQUE,
+    applied_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP
+)";
+
+/// 1つのマイグレーション
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Migration {
+    /// migrations/内のファイル名（wranglerの記録と同じ）
+    pub name: &'static str,
+    pub sql: &'static str,
+}
+
+/// すべてのマイグレーション（ファイル名の順）
+pub const MIGRATIONS: &[Migration] = &[
+    Migration {
+        name: "0001_initial.sql",
+        sql: include_str!("../../migrations/0001_initial.sql"),
+    },
+    Migration {
+        name: "0002_stats.sql",
+        sql: include_str!("../../migrations/0002_stats.sql"),
+    },
+    Migration {
+        name: "0003_entry_saves.sql",
+        sql: include_str!("../../migrations/0003_entry_saves.sql"),
+    },
+    Migration {
+        name: "0004_pow_challenges.sql",
+        sql: include_str!("../../migrations/0004_pow_challenges.sql"),
+    },
+    Migration {
+        name: "0005_stats_deletes.sql",
+        sql: include_str!("../../migrations/0005_stats_deletes.sql"),
+    },
+    Migration {
+        name: "0006_entry_writer_counts.sql",
+        sql: include_str!("../../migrations/0006_entry_writer_counts.sql"),
+    },
+];
+
+/// データベースのスキーマがこのビルドと合っているか
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SchemaStatus {
+    /// すべて適用済み
+    Current,
+    /// 未適用のマイグレーションがある（書き込みは受け付けない）
+    Behind { pending: Vec<&'static str> },
+    /// このビルドが知らないマイグレーションが適用されている（新しい版からの巻き戻し）
+    Ahead { unknown: Vec<String> },
+}
+
+impl SchemaStatus {
+    /// 書き込みを受け付けてよいか
+    ///
+    /// 新しい版のマイグレーションは列やテーブルを足すだけにしているため、
+    /// 知らない記録があっても書き込みは止めない。
+    pub fn is_writable(&self) -> bool {
+        !matches!(self, Self::Behind { .. })
+    }
+}
+
+/// 適用済みの記録に含まれないマイグレーション
+pub fn pending(applied: &[String]) -> Vec<&'static Migration> {
+    MIGRATIONS
+        .iter()
+        .filter(|m| !applied.iter().any(|name| name == m.name))
+        .collect()
+}
+
+/// 適用済みの記録からスキーマの状態を判定する
+pub fn status(applied: &[String]) -> SchemaStatus {
+    let pending: Vec<&str> = pending(applied).iter().map(|m| m.name).collect();
+    if !pending.is_empty() {
+        return SchemaStatus::Behind { pending };
+    }
+    let unknown: Vec<String> = applied
+        .iter()
+        .filter(|name| !MIGRATIONS.iter().any(|m| m.name == name.as_str()))
+        .cloned()
+        .collect();
+    if unknown.is_empty() {
+        SchemaStatus::Current
+    } else {
+        SchemaStatus::Ahead { unknown }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn names(names: &[&str]) -> Vec<String> {
+        names.iter().map(|n| n.to_string()).collect()
+    }
+
+    #[test]
+    fn test_migrations_match_directory() {
+        // ファイルを足したのにここへの登録を忘れると、wranglerとサーバーで食い違う
+        let dir = concat!(env!("CARGO_MANIFEST_DIR"), "/../migrations");
+        let mut files: Vec<String> = std::fs::read_dir(dir)
+            .unwrap()
+            .map(|e| e.unwrap().file_name().into_string().unwrap())
+            .filter(|name| name.ends_with(".sql"))
+            .collect();
+        files.sort();
+        let registered: Vec<&str> = MIGRATIONS.iter().map(|m| m.name).collect();
+        assert_eq!(files, registered);
+
+        for name in registered {
+            let (number, _) = name.split_once('_').unwrap();
+            assert_eq!(number.len(), 4);
+            assert!(number.chars().all(|c| c.is_ascii_digit()));
+        }
+    }
+
+    #[test]
+    fn test_status() {
+        let all: Vec<&str> = MIGRATIONS.iter().map(|m| m.name).collect();
+        assert_eq!(status(&names(&all)), SchemaStatus::Current);
+
+        let empty = status(&[]);
+        assert_eq!(empty, SchemaStatus::Behind { pending: all.clone() });
+        assert!(!empty.is_writable());
+        assert_eq!(pending(&[]).len(), MIGRATIONS.len());
+
+        let mut newer = names(&all);
+        newer.push("9999_future.sql".to_string());
+        let ahead = status(&newer);
+        assert_eq!(
+            ahead,
+            SchemaStatus::Ahead {
+                unknown: names(&["9999_future.sql"])
+            }
+        );
+        assert!(ahead.is_writable());
+    }
+}
@@ -0,0 +1,927 @@
+use serde::{Deserialize, Serialize};
+
+/// 日記エントリのデータ構造
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DiaryEntry {
+    pub date: String,
+    pub content: String,
+    pub created_at: String,
+    pub updated_at: String,
+}
+
+/// APIレスポンス用の日記エントリ
+#[derive(Debug, Serialize)]
+pub struct DiaryEntryResponse {
+    pub date: String,
+    pub content: String,
+    pub can_edit: bool,
+}
+
+impl DiaryEntryResponse {
+    pub fn from_entry(entry: &DiaryEntry, can_edit: bool) -> Self {
+        Self {
+            date: entry.date.clone(),
+            content: entry.content.clone(),
+            can_edit,
+        }
+    }
+}
+
+/// 今日の日記がない場合のレスポンス
+#[derive(Debug, Serialize)]
+pub struct TodayEmptyResponse {
+    pub date: String,
+    pub content: Option<String>,
+    pub can_edit: bool,
+}
+
+/// エラーレスポンス
+#[derive(Debug, Serialize)]
+pub struct ErrorResponse {
+    pub error: String,
+    pub code: String,
+}
+
+impl ErrorResponse {
+    pub fn new(error: impl Into<String>, code: impl Into<String>) -> Self {
+        Self {
+            error: error.into(),
+            code: code.into(),
+        }
+    }
+
+    pub fn not_found() -> Self {
+        Self::new("Entry not found", "NOT_FOUND")
+    }
+
+    pub fn bad_request(message: impl Into<String>) -> Self {
+        Self::new(message, "BAD_REQUEST")
+    }
+
+    pub fn internal_error() -> Self {
+        Self::new("Internal server error", "INTERNAL_ERROR")
+    }
+}
+
+/// レート制限で拒否したときのレスポンス
+#[derive(Debug, Serialize)]
+pub struct RateLimitedResponse {
+    pub error: String,
+    pub code: String,
+    /// 制限された理由（exhausted / cooldown / too_soon）
+    pub reason: String,
+    /// 再試行できるまでの秒数
+    pub retry_after: i64,
+}
+
+impl RateLimitedResponse {
+    pub fn new(reason: impl Into<String>, retry_after: i64) -> Self {
+        Self {
+            error: "Too Many Requests".to_string(),
+            code: "RATE_LIMITED".to_string(),
+            reason: reason.into(),
+            retry_after,
+        }
+    }
+}
+
+/// プルーフ・オブ・ワークのチャレンジ
+#[derive(Debug, Serialize)]
+pub struct PowChallengeResponse {
+    pub challenge: String,
+    /// SHA-256の先頭に並ぶべき0のビット数
+    pub difficulty: u32,
+}
+
+/// JSON APIで画面遷移先を返すレスポンス
+#[derive(Debug, Serialize)]
+pub struct RedirectResponse {
+    pub redirect: String,
+}
+
+impl RedirectResponse {
+    pub fn new(location: impl Into<String>) -> Self {
+        Self {
+            redirect: location.into(),
+        }
+    }
+}
+
+/// 日記一覧レスポンス
+#[derive(Debug, Serialize)]
+pub struct DiaryListResponse {
+    pub entries: Vec<DiaryEntrySummary>,
+}
+
+/// 日記一覧用のサマリ
+#[derive(Debug, Serialize)]
+pub struct DiaryEntrySummary {
+    pub date: String,
+    pub preview: String,
+}
+
+impl DiaryEntrySummary {
+    pub fn from_entry(entry: &DiaryEntry) -> Self {
+        // 最初の100文字をプレビューとして使用
+        let preview = if entry.content.chars().count() > 100 {
+            let preview: String = entry.content.chars().take(100).collect();
+            format!("{}...", preview)
+        } else {
+            entry.content.clone()
+        };
+        Self {
+            date: entry.date.clone(),
+            preview,
+        }
+    }
+}
+
+/// バージョン履歴のデータ構造
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DiaryVersion {
+    pub id: i64,
+    pub entry_date: String,
+    pub content: String,
+    pub version_number: i32,
+    pub created_at: String,
+}
+
+/// バージョン一覧レスポンス
+#[derive(Debug, Serialize)]
+pub struct VersionListResponse {
+    pub entry_date: String,
+    pub current_content: Option<String>,
+    pub versions: Vec<VersionSummary>,
+}
+
+/// バージョンサマリ（一覧用）
+#[derive(Debug, Serialize)]
+pub struct VersionSummary {
+    pub version_number: i32,
+    pub created_at: String,
+    pub preview: String,
+}
+
+impl VersionSummary {
+    pub fn from_version(version: &DiaryVersion) -> Self {
+        let preview = if version.content.chars().count() > 100 {
+            let preview: String = version.content.chars().take(100).collect();
+            format!("{}...", preview)
+        } else {
+            version.content.clone()
+        };
+        Self {
+            version_number: version.version_number,
+            created_at: version.created_at.clone(),
+            preview,
+        }
+    }
+}
+
+/// 単一バージョンレスポンス
+#[derive(Debug, Serialize)]
+pub struct VersionDetailResponse {
+    pub entry_date: String,
+    pub version_number: i32,
+    pub content: String,
+    pub created_at: String,
+}
+
+/// 管理者の権限
+///
+/// 上位の権限は下位の権限でできることをすべて行える。
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum AdminRole {
+    /// バージョン履歴の閲覧のみ
+    Viewer,
+    /// 差し戻し・墨消しが可能
+    Moderator,
+    /// 日記の直接編集と管理者の管理が可能
+    Superuser,
+}
+
+impl AdminRole {
+    pub const ALL: [AdminRole; 3] = [AdminRole::Viewer, AdminRole::Moderator, AdminRole::Superuser];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            AdminRole::Viewer => "viewer",
+            AdminRole::Moderator => "moderator",
+            AdminRole::Superuser => "superuser",
+        }
+    }
+
+    pub fn parse(s: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|r| r.as_str() == s)
+    }
+
+    /// 画面表示用の名前
+    pub fn label(&self) -> &'static str {
+        match self {
+            AdminRole::Viewer => "閲覧者",
+            AdminRole::Moderator => "モデレーター",
+            AdminRole::Superuser => "スーパーユーザー",
+        }
+    }
+}
+
+/// 管理者のユーザー名の最大長
+pub const MAX_USERNAME_LENGTH: usize = 32;
+/// 管理者パスワードの最小長
+pub const MIN_PASSWORD_LENGTH: usize = 12;
+
+/// 墨消ししたバージョンに残す文言
+pub const REDACTED_CONTENT: &str = "（この版は管理者により墨消しされました）";
+
+/// ユーザー名が有効か（英数字・ハイフン・アンダースコアのみ）
+pub fn is_valid_username(username: &str) -> bool {
+    !username.is_empty()
+        && username.len() <= MAX_USERNAME_LENGTH
+        && username
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+}
+
+/// メールアドレスの最大長（RFC 5321）
+pub const MAX_EMAIL_LENGTH: usize = 254;
+
+/// メールアドレスとして最低限の形式を満たすか
+pub fn is_valid_email(email: &str) -> bool {
+    let Some((local, domain)) = email.split_once('@') else {
+        return false;
+    };
+    email.len() <= MAX_EMAIL_LENGTH
+        && !local.is_empty()
+        && domain.contains('.')
+        && !domain.starts_with('.')
+        && !domain.ends_with('.')
+        && !domain.contains('@')
+        && !email.chars().any(|c| c.is_whitespace() || c.is_control())
+}
+
+/// 管理者アカウントのデータ構造
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Admin {
+    pub id: i64,
+    pub username: String,
+    pub password_hash: String,
+    pub role: AdminRole,
+    pub totp_secret: Option<String>,
+    pub totp_last_step: Option<i64>,
+    pub email: Option<String>,
+    pub created_at: String,
+    pub updated_at: String,
+}
+
+impl Admin {
+    /// 二要素認証が有効か
+    pub fn totp_enabled(&self) -> bool {
+        self.totp_secret.is_some()
+    }
+}
+
+/// パスキーの表示名の最大長
+pub const MAX_PASSKEY_LABEL_LENGTH: usize = 64;
+
+/// 管理者のパスキー（WebAuthnクレデンシャル）
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AdminCredential {
+    pub id: i64,
+    pub admin_id: i64,
+    pub credential_id: String,
+    pub public_key: String,
+    pub sign_count: i64,
+    pub label: String,
+    pub created_at: String,
+    pub last_used_at: Option<String>,
+}
+
+/// APIキーの名前の最大長
+pub const MAX_API_KEY_LABEL_LENGTH: usize = 64;
+/// APIキー1つあたりの1日の保存回数の上限
+pub const MAX_API_KEY_DAILY_QUOTA: i64 = 1000;
+/// 発行フォームに初期表示する1日の保存回数
+pub const DEFAULT_API_KEY_DAILY_QUOTA: i64 = 100;
+
+/// スクリプトやAIエージェント向けのAPIキー（平文のキーは含まない）
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ApiKey {
+    pub id: i64,
+    pub label: String,
+    pub key_prefix: String,
+    pub daily_quota: i64,
+    pub created_by: String,
+    pub created_at: String,
+    pub last_used_at: Option<String>,
+    pub revoked_at: Option<String>,
+}
+
+impl ApiKey {
+    pub fn is_revoked(&self) -> bool {
+        self.revoked_at.is_some()
+    }
+}
+
+/// 管理画面に表示するAPIキーと今日の使用回数
+#[derive(Debug, Clone)]
+pub struct ApiKeyWithUsage {
+    pub key: ApiKey,
+    pub used_today: i64,
+}
+
+/// APIキーの1日の上限回数に達した場合のレスポンス
+#[derive(Debug, Serialize)]
+pub struct QuotaExceededResponse {
+    pub error: String,
+    pub code: String,
+    pub daily_quota: i64,
+}
+
+impl QuotaExceededResponse {
+    pub fn new(daily_quota: i64) -> Self {
+        Self {
+            error: "Daily quota for this API key exceeded".to_string(),
+            code: "API_KEY_QUOTA_EXCEEDED".to_string(),
+            daily_quota,
+        }
+    }
+}
+
+/// 既存の日記を大きく消す保存を受け付けなかった場合のレスポンス
+#[derive(Debug, Serialize)]
+pub struct OverwriteResponse {
+    pub error: String,
+    /// OVERWRITE_CONFIRMATION_REQUIRED / OVERWRITE_COOLDOWN
+    pub code: String,
+    /// 既存の内容のうち消える割合（0〜1）
+    pub deleted_ratio: f64,
+    /// 破壊的な保存ができるようになるまでの秒数（待機中のみ）
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub retry_after: Option<i64>,
+}
+
+impl OverwriteResponse {
+    pub fn confirmation_required(deleted_ratio: f64) -> Self {
+        Self {
+            error: "This save deletes most of the existing entry; confirm to continue"
+                .to_string(),
+            code: "OVERWRITE_CONFIRMATION_REQUIRED".to_string(),
+            deleted_ratio,
+            retry_after: None,
+        }
+    }
+
+    pub fn cooldown(deleted_ratio: f64, retry_after: i64) -> Self {
+        Self {
+            error: "The entry was saved moments ago; wait before deleting most of it".to_string(),
+            code: "OVERWRITE_COOLDOWN".to_string(),
+            deleted_ratio,
+            retry_after: Some(retry_after),
+        }
+    }
+}
+
+/// 内容フィルターの照合方法
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum FilterKind {
+    /// 大文字・小文字を区別しない部分一致
+    Word,
+    /// 正規表現
+    Regex,
+}
+
+impl FilterKind {
+    pub const ALL: [FilterKind; 2] = [FilterKind::Word, FilterKind::Regex];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            FilterKind::Word => "word",
+            FilterKind::Regex => "regex",
+        }
+    }
+
+    pub fn parse(s: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|k| k.as_str() == s)
+    }
+
+    /// 画面表示用の名前
+    pub fn label(&self) -> &'static str {
+        match self {
+            FilterKind::Word => "NGワード",
+            FilterKind::Regex => "正規表現",
+        }
+    }
+}
+
+/// 内容フィルターに該当した保存の扱い
+///
+/// 複数に該当した場合は最も重いものを採る。
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum FilterAction {
+    /// 保存して、モデレーションキューに載せる
+    Flag,
+    /// 公開せずに保留し、承認されたら保存する
+    Hold,
+    /// 保存を拒否する
+    Reject,
+}
+
+impl FilterAction {
+    pub const ALL: [FilterAction; 3] =
+        [FilterAction::Flag, FilterAction::Hold, FilterAction::Reject];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            FilterAction::Flag => "flag",
+            FilterAction::Hold => "hold",
+            FilterAction::Reject => "reject",
+        }
+    }
+
+    pub fn parse(s: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|a| a.as_str() == s)
+    }
+
+    /// 画面表示用の名前
+    pub fn label(&self) -> &'static str {
+        match self {
+            FilterAction::Flag => "保存して要確認",
+            FilterAction::Hold => "保留",
+            FilterAction::Reject => "拒否",
+        }
+    }
+}
+
+/// 内容フィルターのパターンの最大長
+pub const MAX_FILTER_PATTERN_LENGTH: usize = 200;
+
+/// 管理者が登録した内容フィルター
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ContentFilter {
+    pub id: i64,
+    pub kind: FilterKind,
+    pub pattern: String,
+    pub action: FilterAction,
+    pub created_by: String,
+    pub created_at: String,
+}
+
+/// モデレーションキューの項目の処理結果
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ModerationResolution {
+    /// 問題なしとして承認（保留中のものは公開する）
+    Approved,
+    /// 保存前の内容に戻した
+    Reverted,
+    /// 内容を墨消しした
+    Redacted,
+    /// 保留中のものを公開せずに破棄した
+    Discarded,
+}
+
+impl ModerationResolution {
+    pub const ALL: [ModerationResolution; 4] = [
+        ModerationResolution::Approved,
+        ModerationResolution::Reverted,
+        ModerationResolution::Redacted,
+        ModerationResolution::Discarded,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ModerationResolution::Approved => "approved",
+            ModerationResolution::Reverted => "reverted",
+            ModerationResolution::Redacted => "redacted",
+            ModerationResolution::Discarded => "discarded",
+        }
+    }
+
+    pub fn parse(s: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|r| r.as_str() == s)
+    }
+
+    /// その扱いの項目に使えるか（差し戻しは保存済み、破棄は保留中のものだけ）
+    pub fn applies_to(&self, action: FilterAction) -> bool {
+        match self {
+            ModerationResolution::Approved | ModerationResolution::Redacted => true,
+            ModerationResolution::Reverted => action == FilterAction::Flag,
+            ModerationResolution::Discarded => action == FilterAction::Hold,
+        }
+    }
+}
+
+/// モデレーションキューの項目（要確認で保存された版、または保留中の内容）
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ModerationItem {
+    pub id: i64,
+    pub entry_date: String,
+    pub content: String,
+    /// 保存または保留した時点の直前の内容（日記がまだなければNULL）
+    pub previous_content: Option<String>,
+    /// flag（保存済み）またはhold（保留中）
+    pub action: FilterAction,
+    /// 該当したフィルターの説明（改行区切り）
+    pub reasons: String,
+    pub created_at: String,
+    pub resolution: Option<ModerationResolution>,
+    pub resolved_by: Option<String>,
+    pub resolved_at: Option<String>,
+}
+
+/// モデレーションキューの未処理の項目（管理者用API）
+#[derive(Debug, Serialize)]
+pub struct ModerationListResponse {
+    pub items: Vec<ModerationItem>,
+}
+
+/// 保存が内容フィルターにより保留された場合のレスポンス
+#[derive(Debug, Serialize)]
+pub struct HeldResponse {
+    pub date: String,
+    pub code: String,
+    pub message: String,
+}
+
+impl HeldResponse {
+    pub fn new(date: impl Into<String>) -> Self {
+        Self {
+            date: date.into(),
+            code: "CONTENT_HELD".to_string(),
+            message: "Saved for review; it will be published once approved".to_string(),
+        }
+    }
+}
+
+/// 監査ログに記録する管理者の操作
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AuditAction {
+    Login,
+    LoginFailed,
+    Logout,
+    ViewVersions,
+    ViewVersion,
+    Revert,
+    Edit,
+    Redact,
+    AdminCreate,
+    AdminRoleChange,
+    AdminEmailChange,
+    AdminDelete,
+    TotpEnable,
+    TotpDisable,
+    RecoveryCodesRegenerate,
+    PasskeyRegister,
+    PasskeyDelete,
+    ApiKeyCreate,
+    ApiKeyRevoke,
+    FilterCreate,
+    FilterDelete,
+    ModerationApprove,
+    ModerationDiscard,
+    Export,
+    Import,
+    Restore,
+}
+
+impl AuditAction {
+    pub const ALL: [AuditAction; 26] = [
+        AuditAction::Login,
+        AuditAction::LoginFailed,
+        AuditAction::Logout,
+        AuditAction::ViewVersions,
+        AuditAction::ViewVersion,
+        AuditAction::Revert,
+        AuditAction::Edit,
+        AuditAction::Redact,
+        AuditAction::AdminCreate,
+        AuditAction::AdminRoleChange,
+        AuditAction::AdminEmailChange,
+        AuditAction::AdminDelete,
+        AuditAction::TotpEnable,
+        AuditAction::TotpDisable,
+        AuditAction::RecoveryCodesRegenerate,
+        AuditAction::PasskeyRegister,
+        AuditAction::PasskeyDelete,
+        AuditAction::ApiKeyCreate,
+        AuditAction::ApiKeyRevoke,
+        AuditAction::FilterCreate,
+        AuditAction::FilterDelete,
+        AuditAction::ModerationApprove,
+        AuditAction::ModerationDiscard,
+        AuditAction::Export,
+        AuditAction::Import,
+        AuditAction::Restore,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            AuditAction::Login => "login",
+            AuditAction::LoginFailed => "login_failed",
+            AuditAction::Logout => "logout",
+            AuditAction::ViewVersions => "view_versions",
+            AuditAction::ViewVersion => "view_version",
+            AuditAction::Revert => "revert",
+            AuditAction::Edit => "edit",
+            AuditAction::Redact => "redact",
+            AuditAction::AdminCreate => "admin_create",
+            AuditAction::AdminRoleChange => "admin_role_change",
+            AuditAction::AdminEmailChange => "admin_email_change",
+            AuditAction::AdminDelete => "admin_delete",
+            AuditAction::TotpEnable => "totp_enable",
+            AuditAction::TotpDisable => "totp_disable",
+            AuditAction::RecoveryCodesRegenerate => "recovery_codes_regenerate",
+            AuditAction::PasskeyRegister => "passkey_register",
+            AuditAction::PasskeyDelete => "passkey_delete",
+            AuditAction::ApiKeyCreate => "api_key_create",
+            AuditAction::ApiKeyRevoke => "api_key_revoke",
+            AuditAction::FilterCreate => "filter_create",
+            AuditAction::FilterDelete => "filter_delete",
+            AuditAction::ModerationApprove => "moderation_approve",
+            AuditAction::ModerationDiscard => "moderation_discard",
+            AuditAction::Export => "export",
+            AuditAction::Import => "import",
+            AuditAction::Restore => "restore",
+        }
+    }
+
+    pub fn parse(s: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|a| a.as_str() == s)
+    }
+
+    /// 画面表示用の名前
+    pub fn label(&self) -> &'static str {
+        match self {
+            AuditAction::Login => "ログイン",
+            AuditAction::LoginFailed => "ログイン失敗",
+            AuditAction::Logout => "ログアウト",
+            AuditAction::ViewVersions => "バージョン一覧の閲覧",
+            AuditAction::ViewVersion => "バージョンの閲覧",
+            AuditAction::Revert => "差し戻し",
+            AuditAction::Edit => "直接編集",
+            AuditAction::Redact => "墨消し",
+            AuditAction::AdminCreate => "管理者の作成",
+            AuditAction::AdminRoleChange => "権限の変更",
+            AuditAction::AdminEmailChange => "メールアドレスの変更",
+            AuditAction::AdminDelete => "管理者の削除",
+            AuditAction::TotpEnable => "二要素認証の有効化",
+            AuditAction::TotpDisable => "二要素認証の無効化",
+            AuditAction::RecoveryCodesRegenerate => "リカバリーコードの再発行",
+            AuditAction::PasskeyRegister => "パスキーの登録",
+            AuditAction::PasskeyDelete => "パスキーの削除",
+            AuditAction::ApiKeyCreate => "APIキーの発行",
+            AuditAction::ApiKeyRevoke => "APIキーの失効",
+            AuditAction::FilterCreate => "内容フィルターの追加",
+            AuditAction::FilterDelete => "内容フィルターの削除",
+            AuditAction::ModerationApprove => "要確認の承認",
+            AuditAction::ModerationDiscard => "保留中の内容の破棄",
+            AuditAction::Export => "全データのエクスポート",
+            AuditAction::Import => "日記のインポート",
+            AuditAction::Restore => "バックアップからの復元",
+        }
+    }
+}
+
+/// 監査ログに書き込む操作の内容
+#[derive(Debug, Clone, PartialEq)]
+pub struct AuditEvent {
+    pub action: AuditAction,
+    pub target_date: Option<String>,
+    pub target_version: Option<i32>,
+    pub target: Option<String>,
+    pub before_hash: Option<String>,
+    pub after_hash: Option<String>,
+}
+
+impl AuditEvent {
+    /// 対象を持たない操作
+    pub fn new(action: AuditAction) -> Self {
+        Self {
+            action,
+            target_date: None,
+            target_version: None,
+            target: None,
+            before_hash: None,
+            after_hash: None,
+        }
+    }
+}
+
+/// 監査ログの1件
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AuditLogEntry {
+    pub id: i64,
+    pub actor: String,
+    pub actor_id: Option<i64>,
+    pub action: AuditAction,
+    pub ip_hash: String,
+    pub target_date: Option<String>,
+    pub target_version: Option<i32>,
+    pub target: Option<String>,
+    pub before_hash: Option<String>,
+    pub after_hash: Option<String>,
+    pub created_at: String,
+}
+
+/// 監査ログの絞り込み条件
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct AuditLogFilter {
+    pub actor: Option<String>,
+    pub action: Option<AuditAction>,
+    /// 1始まりのページ番号
+    pub page: u32,
+}
+
+impl AuditLogFilter {
+    /// クエリ文字列のパラメータから絞り込み条件を作る（純粋関数）
+    ///
+    /// 不正な値は無視して絞り込まない。
+    pub fn from_query<'a>(pairs: impl Iterator<Item = (&'a str, &'a str)>) -> Self {
+        let mut filter = Self {
+            page: 1,
+            ..Self::default()
+        };
+        for (key, value) in pairs {
+            let value = value.trim();
+            match key {
+                "actor" if !value.is_empty() => filter.actor = Some(value.to_string()),
+                "action" => filter.action = AuditAction::parse(value),
+                "page" => filter.page = value.parse().ok().filter(|p| *p >= 1).unwrap_or(1),
+                _ => {}
+            }
+        }
+        filter
+    }
+
+    /// 絞り込み条件を保ったまま別のページを指すクエリ文字列
+    pub fn query_string(&self, page: u32) -> String {
+        let mut params = Vec::new();
+        if let Some(actor) = &self.actor {
+            params.push(format!("actor={}", encode_query_value(actor)));
+        }
+        if let Some(action) = self.action {
+            params.push(format!("action={}", action.as_str()));
+        }
+        params.push(format!("page={}", page));
+        params.join("&")
+    }
+}
+
+/// クエリ文字列やフォームの値をパーセントエンコード（純粋関数）
+pub fn encode_query_value(s: &str) -> String {
+    s.bytes()
+        .map(|b| match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' => {
+                (b as char).to_string()
+            }
+            _ => format!("%{:02X}", b),
+        })
+        .collect()
+}
+
+/// 監査ログ一覧のレスポンス
+#[derive(Debug, Serialize)]
+pub struct AuditLogResponse {
+    pub entries: Vec<AuditLogEntry>,
+    pub page: u32,
+    pub has_next: bool,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_diary_entry_summary_short_content() {
+        let entry = DiaryEntry {
+            date: "2025-01-15".to_string(),
+            content: "短い日記".to_string(),
+            created_at: "2025-01-15T00:00:00Z".to_string(),
+            updated_at: "2025-01-15T00:00:00Z".to_string(),
+        };
+        let summary = DiaryEntrySummary::from_entry(&entry);
+        assert_eq!(summary.preview, "短い日記");
+    }
+
+    #[test]
+    fn test_admin_role_ordering() {
+        assert!(AdminRole::Viewer < AdminRole::Moderator);
+        assert!(AdminRole::Moderator < AdminRole::Superuser);
+    }
+
+    #[test]
+    fn test_admin_role_parse() {
+        assert_eq!(AdminRole::parse("viewer"), Some(AdminRole::Viewer));
+        assert_eq!(AdminRole::parse("superuser"), Some(AdminRole::Superuser));
+        assert_eq!(AdminRole::parse("root"), None);
+        let role: AdminRole = serde_json::from_str(r#""moderator""#).unwrap();
+        assert_eq!(role, AdminRole::Moderator);
+    }
+
+    #[test]
+    fn test_is_valid_email() {
+        assert!(is_valid_email("alice@example.com"));
+        assert!(!is_valid_email("alice"));
+        assert!(!is_valid_email("@example.com"));
+        assert!(!is_valid_email("alice@localhost"));
+        assert!(!is_valid_email("alice@@example.com"));
+        assert!(!is_valid_email("alice smith@example.com"));
+    }
+
+    #[test]
+    fn test_is_valid_username() {
+        assert!(is_valid_username("alice"));
+        assert!(is_valid_username("mod_01-b"));
+        assert!(!is_valid_username(""));
+        assert!(!is_valid_username("名前"));
+        assert!(!is_valid_username("with space"));
+        assert!(!is_valid_username(&"a".repeat(33)));
+    }
+
+    #[test]
+    fn test_diary_entry_summary_long_content() {
+        let long_content = "あ".repeat(150);
+        let entry = DiaryEntry {
+            date: "2025-01-15".to_string(),
+            content: long_content,
+            created_at: "2025-01-15T00:00:00Z".to_string(),
+            updated_at: "2025-01-15T00:00:00Z".to_string(),
+        };
+        let summary = DiaryEntrySummary::from_entry(&entry);
+        assert!(summary.preview.ends_with("..."));
+        assert_eq!(summary.preview.chars().count(), 103); // 100 + "..."
+    }
+
+    #[test]
+    fn test_audit_action_roundtrip() {
+        for action in AuditAction::ALL {
+            assert_eq!(AuditAction::parse(action.as_str()), Some(action));
+            let json = serde_json::to_string(&action).unwrap();
+            assert_eq!(json, format!("\"{}\"", action.as_str()));
+        }
+        assert_eq!(AuditAction::parse("unknown"), None);
+    }
+
+    #[test]
+    fn test_audit_log_filter_from_query() {
+        let pairs = [("actor", "alice"), ("action", "revert"), ("page", "3")];
+        let filter = AuditLogFilter::from_query(pairs.into_iter());
+        assert_eq!(filter.actor.as_deref(), Some("alice"));
+        assert_eq!(filter.action, Some(AuditAction::Revert));
+        assert_eq!(filter.page, 3);
+
+        let pairs = [("actor", " "), ("action", "bogus"), ("page", "0")];
+        let filter = AuditLogFilter::from_query(pairs.into_iter());
+        assert_eq!(
+            filter,
+            AuditLogFilter {
+                page: 1,
+                ..AuditLogFilter::default()
+            }
+        );
+    }
+
+    #[test]
+    fn test_audit_log_filter_query_string() {
+        let filter = AuditLogFilter {
+            actor: Some("a b".to_string()),
+            action: Some(AuditAction::Login),
+            page: 1,
+        };
+        assert_eq!(filter.query_string(2), "actor=a%20b&action=login&page=2");
+    }
+
+    #[test]
+    fn test_filter_enums_roundtrip() {
+        for kind in FilterKind::ALL {
+            assert_eq!(FilterKind::parse(kind.as_str()), Some(kind));
+        }
+        for action in FilterAction::ALL {
+            assert_eq!(FilterAction::parse(action.as_str()), Some(action));
+            let json = serde_json::to_string(&action).unwrap();
+            assert_eq!(json, format!("\"{}\"", action.as_str()));
+        }
+        assert!(FilterAction::Reject > FilterAction::Hold);
+        assert!(FilterAction::Hold > FilterAction::Flag);
+        assert_eq!(FilterKind::parse("glob"), None);
+    }
+
+    #[test]
+    fn test_moderation_resolution_applies_to() {
+        use ModerationResolution::*;
+        assert!(Approved.applies_to(FilterAction::Flag));
+        assert!(Approved.applies_to(FilterAction::Hold));
+        assert!(Redacted.applies_to(FilterAction::Hold));
+        assert!(Reverted.applies_to(FilterAction::Flag));
+        assert!(!Reverted.applies_to(FilterAction::Hold));
+        assert!(Discarded.applies_to(FilterAction::Hold));
+        assert!(!Discarded.applies_to(FilterAction::Flag));
+        for resolution in ModerationResolution::ALL {
+            assert_eq!(ModerationResolution::parse(resolution.as_str()), Some(resolution));
+        }
+    }
+}
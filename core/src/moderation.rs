@@ -0,0 +1,286 @@
+use crate::crypto::content_hash;
+use crate::models::{
+    AuditAction, ErrorResponse, FilterAction, ModerationItem, ModerationListResponse,
+    ModerationResolution, REDACTED_CONTENT,
+};
+use crate::reply::Reply;
+use crate::store::{DiaryStore, ModerationStore};
+
+/// モデレーションキューに一度に表示する件数
+pub const MODERATION_PAGE_SIZE: i32 = 50;
+
+/// 項目を処理できなかった理由
+#[derive(Debug, Clone, PartialEq)]
+pub enum ResolveRejection {
+    /// 項目が見つからない
+    NotFound,
+    /// この項目にはその操作を行えない
+    NotApplicable,
+    /// すでに処理されている、またはその後の書き換えで反映できない（管理画面に表示する文言）
+    Conflict(&'static str),
+}
+
+impl ResolveRejection {
+    pub fn status(&self) -> u16 {
+        match self {
+            ResolveRejection::NotFound => 404,
+            ResolveRejection::NotApplicable => 400,
+            ResolveRejection::Conflict(_) => 409,
+        }
+    }
+
+    pub fn message(&self) -> &'static str {
+        match self {
+            ResolveRejection::NotFound => "項目が見つかりません",
+            ResolveRejection::NotApplicable => "この項目にはその操作を行えません",
+            ResolveRejection::Conflict(message) => message,
+        }
+    }
+
+    /// 管理者用APIの応答
+    pub fn reply(&self) -> serde_json::Result<Reply> {
+        let code = match self {
+            ResolveRejection::NotFound => "NOT_FOUND",
+            ResolveRejection::NotApplicable => "BAD_REQUEST",
+            ResolveRejection::Conflict(_) => "CONFLICT",
+        };
+        Reply::json(self.status(), &ErrorResponse::new(self.message(), code))
+    }
+}
+
+/// GET /api/admin/moderation - 未処理の項目（管理者用）
+pub async fn pending_reply<S: ModerationStore>(store: &S) -> serde_json::Result<Reply> {
+    match store.list_pending_moderation(MODERATION_PAGE_SIZE).await {
+        Ok(items) => Reply::json(200, &ModerationListResponse { items }),
+        Err(e) => {
+            log::error!("Failed to list moderation queue: {:?}", e);
+            Reply::json(500, &ErrorResponse::internal_error())
+        }
+    }
+}
+
+/// 処理した項目と、監査ログに残す内容
+#[derive(Debug, Clone)]
+pub struct Resolved {
+    pub item: ModerationItem,
+    pub action: AuditAction,
+    pub before_hash: Option<String>,
+    pub after_hash: Option<String>,
+}
+
+/// モデレーションキューの項目を処理する
+///
+/// 承認・差し戻し・墨消し・破棄に応じて日記と版を書き換える。
+/// 他の管理者と同時に処理しても二重に反映しないよう、書き換えの前に処理済みにする。
+pub async fn resolve<S>(
+    store: &S,
+    id: i64,
+    resolution: Option<ModerationResolution>,
+    resolved_by: &str,
+    today: &str,
+) -> Result<Result<Resolved, ResolveRejection>, <S as DiaryStore>::Error>
+where
+    S: DiaryStore + ModerationStore<Error = <S as DiaryStore>::Error>,
+{
+    let Some(item) = store.get_moderation_item(id).await? else {
+        return Ok(Err(ResolveRejection::NotFound));
+    };
+    let Some(resolution) = resolution.filter(|r| r.applies_to(item.action)) else {
+        return Ok(Err(ResolveRejection::NotApplicable));
+    };
+    if item.resolution.is_some() {
+        return Ok(Err(ResolveRejection::Conflict("この項目はすでに処理されています")));
+    }
+
+    // 要確認で保存された内容が今も公開されているか（その後に書き換えられていないか）
+    let current = store.get_entry(&item.entry_date).await?;
+    let still_current = current.as_ref().is_some_and(|e| e.content == item.content);
+    if resolution == ModerationResolution::Reverted && !still_current {
+        let error = "この保存の後に書き換えられています。バージョン履歴から対応してください";
+        return Ok(Err(ResolveRejection::Conflict(error)));
+    }
+    // 直前の内容がない（この保存で日記ができた）場合は、空の日記で上書きせず断る
+    if resolution == ModerationResolution::Reverted && item.previous_content.is_none() {
+        let error = "この保存より前の内容がないため差し戻せません。日記を編集してください";
+        return Ok(Err(ResolveRejection::Conflict(error)));
+    }
+    // 墨消しも公開中の内容を直前の内容に戻すため、戻す内容がなければ空にせず断る
+    // （日記を編集して公開中でなくなれば、版に残った内容だけを墨消しできる）
+    if resolution == ModerationResolution::Redacted
+        && item.action == FilterAction::Flag
+        && still_current
+        && item.previous_content.is_none()
+    {
+        let error = "この保存より前の内容がないため墨消しできません。日記を編集してから墨消ししてください";
+        return Ok(Err(ResolveRejection::Conflict(error)));
+    }
+    // 保留中の内容は、保留したときの内容のまま今日の日記であるときだけ反映する
+    if resolution == ModerationResolution::Approved && item.action == FilterAction::Hold {
+        if item.entry_date != today {
+            let error = "過去の日付の保留は反映できません。破棄してください";
+            return Ok(Err(ResolveRejection::Conflict(error)));
+        }
+        if current.as_ref().map(|e| &e.content) != item.previous_content.as_ref() {
+            let error = "保留の後に書き換えられているため反映できません。破棄してください";
+            return Ok(Err(ResolveRejection::Conflict(error)));
+        }
+    }
+
+    // 同時に処理された場合に二重に反映しないよう、先に処理済みにする
+    if !store.resolve_moderation_item(id, resolution, resolved_by).await? {
+        return Ok(Err(ResolveRejection::Conflict("この項目はすでに処理されています")));
+    }
+
+    let before_hash = current.as_ref().map(|e| content_hash(&e.content));
+    let mut after_hash = None;
+    let restored = item.previous_content.as_deref().unwrap_or_default();
+    let action = match (resolution, item.action) {
+        (ModerationResolution::Approved, FilterAction::Hold) => {
+            store.upsert_entry(&item.entry_date, &item.content).await?;
+            after_hash = Some(content_hash(&item.content));
+            AuditAction::ModerationApprove
+        }
+        (ModerationResolution::Approved, _) => AuditAction::ModerationApprove,
+        (ModerationResolution::Reverted, _) => {
+            // 差し戻し前の内容も新しいバージョンとして残る
+            store.upsert_entry(&item.entry_date, restored).await?;
+            after_hash = Some(content_hash(restored));
+            AuditAction::Revert
+        }
+        (ModerationResolution::Redacted, action) => {
+            if action == FilterAction::Flag {
+                if still_current {
+                    store.upsert_entry(&item.entry_date, restored).await?;
+                    after_hash = Some(content_hash(restored));
+                }
+                store
+                    .redact_versions_with_content(&item.entry_date, &item.content, REDACTED_CONTENT)
+                    .await?;
+            }
+            store.redact_moderation_content(id, REDACTED_CONTENT).await?;
+            AuditAction::Redact
+        }
+        (ModerationResolution::Discarded, _) => AuditAction::ModerationDiscard,
+    };
+
+    Ok(Ok(Resolved {
+        item,
+        action,
+        before_hash,
+        after_hash,
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::store::{block_on, MemoryStore};
+
+    const TODAY: &str = "2025-01-15";
+
+    fn resolve_item(
+        store: &MemoryStore,
+        id: i64,
+        resolution: ModerationResolution,
+    ) -> Result<Resolved, ResolveRejection> {
+        block_on(resolve(store, id, Some(resolution), "moderator", TODAY)).unwrap()
+    }
+
+    #[test]
+    fn test_approve_hold_publishes_content() {
+        let store = MemoryStore::default();
+        block_on(store.upsert_entry(TODAY, "前の内容")).unwrap();
+        let insert = store.insert_moderation_item(
+            TODAY,
+            "保留した内容",
+            Some("前の内容"),
+            FilterAction::Hold,
+            "NGワード",
+        );
+        block_on(insert).unwrap();
+
+        let resolved = resolve_item(&store, 1, ModerationResolution::Approved).unwrap();
+        assert_eq!(resolved.action, AuditAction::ModerationApprove);
+        assert_eq!(resolved.after_hash, Some(content_hash("保留した内容")));
+        let entry = block_on(store.get_entry(TODAY)).unwrap().unwrap();
+        assert_eq!(entry.content, "保留した内容");
+
+        // 二度目は処理しない
+        let rejection = resolve_item(&store, 1, ModerationResolution::Approved).unwrap_err();
+        assert_eq!(rejection.status(), 409);
+    }
+
+    #[test]
+    fn test_stale_hold_and_revert_are_refused() {
+        let store = MemoryStore::default();
+        let hold = store.insert_moderation_item(TODAY, "保留", None, FilterAction::Hold, "");
+        block_on(hold).unwrap();
+        block_on(store.upsert_entry(TODAY, "後から書いた")).unwrap();
+        let flag = store.insert_moderation_item(TODAY, "後から書いた", None, FilterAction::Flag, "");
+        block_on(flag).unwrap();
+
+        // 保留の後に書き換えられたものは反映しない
+        let rejection = resolve_item(&store, 1, ModerationResolution::Approved).unwrap_err();
+        assert_eq!(rejection.status(), 409);
+        // 直前の内容がなければ差し戻さない
+        let rejection = resolve_item(&store, 2, ModerationResolution::Reverted).unwrap_err();
+        assert_eq!(rejection.status(), 409);
+        // 保留中のものは差し戻せない
+        let rejection = resolve_item(&store, 1, ModerationResolution::Reverted).unwrap_err();
+        assert_eq!(rejection, ResolveRejection::NotApplicable);
+        let rejection = resolve_item(&store, 9, ModerationResolution::Approved).unwrap_err();
+        assert_eq!(rejection, ResolveRejection::NotFound);
+        assert_eq!(block_on(store.list_pending_moderation(10)).unwrap().len(), 2);
+    }
+
+    #[test]
+    fn test_redact_flagged_entry() {
+        let store = MemoryStore::default();
+        block_on(store.upsert_entry(TODAY, "前の内容")).unwrap();
+        block_on(store.upsert_entry(TODAY, "問題のある内容")).unwrap();
+        let flag = store.insert_moderation_item(
+            TODAY,
+            "問題のある内容",
+            Some("前の内容"),
+            FilterAction::Flag,
+            "",
+        );
+        block_on(flag).unwrap();
+
+        let resolved = resolve_item(&store, 1, ModerationResolution::Redacted).unwrap();
+        assert_eq!(resolved.action, AuditAction::Redact);
+        let entry = block_on(store.get_entry(TODAY)).unwrap().unwrap();
+        assert_eq!(entry.content, "前の内容");
+        // 差し戻しで版に残った問題の内容も墨消しする
+        let versions = block_on(store.list_versions(TODAY)).unwrap();
+        assert!(versions.iter().all(|v| v.content != "問題のある内容"));
+        assert_eq!(versions[0].content, REDACTED_CONTENT);
+        let item = block_on(store.get_moderation_item(1)).unwrap().unwrap();
+        assert_eq!(item.content, REDACTED_CONTENT);
+        assert_eq!(item.resolved_by.as_deref(), Some("moderator"));
+    }
+
+    #[test]
+    fn test_redact_without_previous_content() {
+        let store = MemoryStore::default();
+        block_on(store.upsert_entry(TODAY, "問題のある内容")).unwrap();
+        let flag =
+            store.insert_moderation_item(TODAY, "問題のある内容", None, FilterAction::Flag, "");
+        block_on(flag).unwrap();
+
+        // 公開中の日記を空にせず断る
+        let rejection = resolve_item(&store, 1, ModerationResolution::Redacted).unwrap_err();
+        assert_eq!(rejection.status(), 409);
+        let entry = block_on(store.get_entry(TODAY)).unwrap().unwrap();
+        assert_eq!(entry.content, "問題のある内容");
+
+        // 編集して公開中でなくなれば、版に残った内容を墨消しできる
+        block_on(store.upsert_entry(TODAY, "書き直した")).unwrap();
+        let resolved = resolve_item(&store, 1, ModerationResolution::Redacted).unwrap();
+        assert_eq!(resolved.after_hash, None);
+        let entry = block_on(store.get_entry(TODAY)).unwrap().unwrap();
+        assert_eq!(entry.content, "書き直した");
+        let versions = block_on(store.list_versions(TODAY)).unwrap();
+        assert_eq!(versions[0].content, REDACTED_CONTENT);
+    }
+}
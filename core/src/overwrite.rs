@@ -0,0 +1,250 @@
+use crate::config::{parse_var as parse, FromVars};
+
+/// 行単位の比較を行う最大の計算量（行数の積）。超えたら比較を諦め、消えたものとみなす
+const MAX_DIFF_CELLS: usize = 1_000_000;
+
+/// 既存の日記を大きく消す保存への歯止め（OVERWRITE_*変数で変更できる）
+#[derive(Debug, Clone, PartialEq)]
+pub struct OverwritePolicy {
+    /// この文字数に満たない日記は守らない
+    pub min_length: usize,
+    /// 既存の内容のうち、この割合を超えて消す保存を破壊的とみなす
+    pub max_deleted_ratio: f64,
+    /// 前回の保存からこの秒数が経つまで、破壊的な保存を受け付けない
+    pub cooldown_seconds: i64,
+    /// 破壊的な保存がレート制限で余分に消費する回数
+    pub extra_cost: u32,
+}
+
+impl Default for OverwritePolicy {
+    fn default() -> Self {
+        Self {
+            min_length: 200,
+            max_deleted_ratio: 0.5,
+            cooldown_seconds: 300,
+            extra_cost: 2,
+        }
+    }
+}
+
+impl FromVars for OverwritePolicy {
+    fn from_vars(get: impl Fn(&str) -> Option<String>) -> Self {
+        let default = Self::default();
+        Self {
+            min_length: parse(get("OVERWRITE_MIN_LENGTH")).unwrap_or(default.min_length),
+            max_deleted_ratio: parse(get("OVERWRITE_MAX_DELETED_RATIO"))
+                .filter(|v: &f64| (0.0..=1.0).contains(v))
+                .unwrap_or(default.max_deleted_ratio),
+            cooldown_seconds: parse(get("OVERWRITE_COOLDOWN_SECONDS"))
+                .filter(|v| *v >= 0)
+                .unwrap_or(default.cooldown_seconds),
+            extra_cost: parse(get("OVERWRITE_EXTRA_COST")).unwrap_or(default.extra_cost),
+        }
+    }
+}
+
+/// 2つの行の並びで共通する部分の文字数（行単位の最長共通部分列、純粋関数）
+fn common_line_chars(old: &[&str], new: &[&str]) -> Option<usize> {
+    if old.len().saturating_mul(new.len()) > MAX_DIFF_CELLS {
+        return None;
+    }
+    let mut prev = vec![0usize; new.len() + 1];
+    let mut row = vec![0usize; new.len() + 1];
+    for old_line in old {
+        for (j, new_line) in new.iter().enumerate() {
+            row[j + 1] = if old_line == new_line {
+                prev[j] + old_line.chars().count()
+            } else {
+                prev[j + 1].max(row[j])
+            };
+        }
+        std::mem::swap(&mut prev, &mut row);
+    }
+    Some(prev[new.len()])
+}
+
+/// 既存の内容のうち、新しい内容で消える文字数（純粋関数）
+///
+/// 先頭と末尾の一致部分を除いた残りを行単位で比べる。
+/// 行の中の一部だけを書き換えた場合は、その行全体を消したものとして数える。
+pub fn deleted_chars(old: &str, new: &str) -> usize {
+    let old_chars: Vec<char> = old.chars().collect();
+    let new_chars: Vec<char> = new.chars().collect();
+
+    let prefix = old_chars
+        .iter()
+        .zip(&new_chars)
+        .take_while(|(a, b)| a == b)
+        .count();
+    let max_suffix = old_chars.len().min(new_chars.len()) - prefix;
+    let suffix = old_chars
+        .iter()
+        .rev()
+        .zip(new_chars.iter().rev())
+        .take(max_suffix)
+        .take_while(|(a, b)| a == b)
+        .count();
+
+    let old_middle: String = old_chars[prefix..old_chars.len() - suffix].iter().collect();
+    let new_middle: String = new_chars[prefix..new_chars.len() - suffix].iter().collect();
+    // 改行は直前の行に含めて数える
+    let old_lines: Vec<&str> = old_middle.split_inclusive('\n').collect();
+    let new_lines: Vec<&str> = new_middle.split_inclusive('\n').collect();
+    let old_middle_len = old_chars.len() - prefix - suffix;
+
+    let kept = common_line_chars(&old_lines, &new_lines).unwrap_or(0);
+    old_middle_len.saturating_sub(kept)
+}
+
+/// 既存の内容のうち、新しい内容で消える割合（純粋関数）
+pub fn deleted_ratio(old: &str, new: &str) -> f64 {
+    let total = old.chars().count();
+    if total == 0 {
+        return 0.0;
+    }
+    deleted_chars(old, new) as f64 / total as f64
+}
+
+/// 破壊的な保存への判定
+#[derive(Debug, Clone, PartialEq)]
+pub enum OverwriteCheck {
+    /// 破壊的ではない
+    Allowed,
+    /// 破壊的だが、確認済みで受け付けられる
+    Confirmed { deleted_ratio: f64 },
+    /// 破壊的なため、書き手の確認が必要
+    NeedsConfirmation { deleted_ratio: f64 },
+    /// 前回の保存から間もないため、破壊的な保存を受け付けない
+    Cooldown { deleted_ratio: f64, retry_after: i64 },
+}
+
+/// 既存の内容を大きく消す保存かどうかを判定する（純粋関数）
+///
+/// `last_saved_at` は既存の日記が最後に保存された時刻（UNIX秒）。
+pub fn check(
+    current: Option<&str>,
+    new: &str,
+    confirmed: bool,
+    last_saved_at: Option<i64>,
+    now: i64,
+    policy: &OverwritePolicy,
+) -> OverwriteCheck {
+    let Some(current) = current else {
+        return OverwriteCheck::Allowed;
+    };
+    if current.chars().count() < policy.min_length {
+        return OverwriteCheck::Allowed;
+    }
+    let deleted_ratio = deleted_ratio(current, new);
+    if deleted_ratio <= policy.max_deleted_ratio {
+        return OverwriteCheck::Allowed;
+    }
+
+    // 書いている最中の日記がすぐに消されないよう、確認があっても待たせる
+    if let Some(last_saved_at) = last_saved_at {
+        let retry_after = last_saved_at + policy.cooldown_seconds - now;
+        if retry_after > 0 {
+            return OverwriteCheck::Cooldown {
+                deleted_ratio,
+                retry_after,
+            };
+        }
+    }
+
+    if confirmed {
+        OverwriteCheck::Confirmed { deleted_ratio }
+    } else {
+        OverwriteCheck::NeedsConfirmation { deleted_ratio }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn long_entry() -> String {
+        (1..=10)
+            .map(|i| format!("{}行目の日記です。今日はいろいろなことがありました。", i))
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+
+    #[test]
+    fn test_policy_from_vars() {
+        let policy = OverwritePolicy::from_vars(|name| match name {
+            "OVERWRITE_MIN_LENGTH" => Some("50".to_string()),
+            "OVERWRITE_MAX_DELETED_RATIO" => Some("1.5".to_string()),
+            "OVERWRITE_COOLDOWN_SECONDS" => Some("0".to_string()),
+            _ => None,
+        });
+        assert_eq!(
+            policy,
+            OverwritePolicy {
+                min_length: 50,
+                cooldown_seconds: 0,
+                ..OverwritePolicy::default()
+            }
+        );
+    }
+
+    #[test]
+    fn test_deleted_chars() {
+        assert_eq!(deleted_chars("", "追記"), 0);
+        assert_eq!(deleted_chars("あいうえお", "あいうえお、かきくけこ"), 0);
+        assert_eq!(deleted_chars("あいうえお", "あ"), 4);
+        assert_eq!(deleted_chars("あいうえお", ""), 5);
+        // 先頭と末尾を残して真ん中を消す
+        assert_eq!(deleted_chars("一\n二\n三\n四", "一\n四"), 4);
+        // 離れた2か所を書き換えても、消えたとみなすのは書き換えた行だけ
+        assert_eq!(deleted_chars("甲\n乙\n丙\n丁", "甲!\n乙\n丙\n丁!"), 2);
+        assert_eq!(deleted_chars("甲\n乙\n丙\n丁", "乙\n丙\n新"), 3);
+    }
+
+    #[test]
+    fn test_deleted_ratio() {
+        let entry = long_entry();
+        assert_eq!(deleted_ratio("", "x"), 0.0);
+        assert_eq!(deleted_ratio(&entry, &entry), 0.0);
+        assert!(deleted_ratio(&entry, "x") > 0.99);
+        let appended = format!("{}\n追記しました", entry);
+        assert_eq!(deleted_ratio(&entry, &appended), 0.0);
+    }
+
+    #[test]
+    fn test_check() {
+        let policy = OverwritePolicy::default();
+        let entry = long_entry();
+        let check = |current, new, confirmed, last_saved_at| {
+            super::check(current, new, confirmed, last_saved_at, 10_000, &policy)
+        };
+
+        assert_eq!(check(None, "x", false, None), OverwriteCheck::Allowed);
+        assert_eq!(check(Some("短い"), "x", false, None), OverwriteCheck::Allowed);
+        let appended = format!("{}\n追記", entry);
+        assert_eq!(
+            check(Some(&entry), &appended, false, Some(9_999)),
+            OverwriteCheck::Allowed
+        );
+
+        let ratio = deleted_ratio(&entry, "x");
+        assert_eq!(
+            check(Some(&entry), "x", false, Some(1_000)),
+            OverwriteCheck::NeedsConfirmation {
+                deleted_ratio: ratio
+            }
+        );
+        assert_eq!(
+            check(Some(&entry), "x", true, Some(1_000)),
+            OverwriteCheck::Confirmed {
+                deleted_ratio: ratio
+            }
+        );
+        assert_eq!(
+            check(Some(&entry), "x", true, Some(9_900)),
+            OverwriteCheck::Cooldown {
+                deleted_ratio: ratio,
+                retry_after: 200
+            }
+        );
+    }
+}
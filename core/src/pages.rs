@@ -0,0 +1,203 @@
+use crate::entry_stats::EntryStats;
+use crate::feed;
+use crate::models::DiaryEntrySummary;
+use crate::overwrite::OverwritePolicy;
+use crate::reply::Reply;
+use crate::stats::SiteStats;
+use crate::store::DiaryStore;
+use crate::templates;
+use crate::validation::is_valid_date;
+use crate::verification::VerifierWidget;
+
+/// トップページの表示に使う設定
+pub struct HomePage<'a> {
+    pub today: &'a str,
+    pub widget: &'a VerifierWidget,
+    pub form_token: Option<&'a str>,
+    pub overwrite: &'a OverwritePolicy,
+}
+
+impl HomePage<'_> {
+    pub async fn reply<S: DiaryStore>(&self, store: &S) -> Reply {
+        let entry = match store.get_entry(self.today).await {
+            Ok(entry) => entry,
+            Err(e) => {
+                log::error!("Failed to get today's entry: {:?}", e);
+                None
+            }
+        };
+
+        let html = templates::render_home(
+            self.today,
+            entry.as_ref(),
+            self.widget,
+            self.form_token,
+            self.overwrite,
+        );
+        Reply::html(200, html)
+    }
+}
+
+/// GET /entries - 過去の日記一覧
+pub async fn archive_reply<S: DiaryStore>(store: &S, today: &str) -> Reply {
+    let entries = match store.list_entries_before(today, 100).await {
+        Ok(entries) => entries,
+        Err(e) => {
+            log::error!("Failed to list entries: {:?}", e);
+            vec![]
+        }
+    };
+
+    let summaries: Vec<DiaryEntrySummary> = entries
+        .iter()
+        .map(DiaryEntrySummary::from_entry)
+        .collect();
+
+    Reply::html(200, templates::render_archive(&summaries))
+}
+
+/// GET /entries/:date - 特定日の日記
+pub async fn entry_page_reply<S: DiaryStore>(
+    store: &S,
+    date: Option<&str>,
+    today: &str,
+) -> Reply {
+    // 日付の形式を検証
+    let Some(date) = date.filter(|d| is_valid_date(d)) else {
+        return Reply::html(404, templates::render_not_found());
+    };
+
+    match store.get_entry(date).await {
+        Ok(Some(entry)) => {
+            let can_edit = date == today;
+            // 分析は確定した日記にだけ付け、読めなくても本文は表示する
+            let stats = if date < today {
+                match store.save_activity(date).await {
+                    Ok(activity) => Some(EntryStats::build(&entry, &activity)),
+                    Err(e) => {
+                        log::error!("Failed to load entry stats: {:?}", e);
+                        None
+                    }
+                }
+            } else {
+                None
+            };
+            Reply::html(200, templates::render_entry(&entry, can_edit, stats.as_ref()))
+        }
+        Ok(None) => Reply::html(404, templates::render_not_found()),
+        Err(e) => {
+            log::error!("Failed to get entry: {:?}", e);
+            Reply::html(500, templates::render_not_found())
+        }
+    }
+}
+
+/// GET /stats - サイト全体の統計
+pub async fn stats_page_reply<S: DiaryStore>(store: &S, today: &str) -> Reply {
+    match SiteStats::load(store, today).await {
+        Ok(stats) => Reply::html(200, templates::render_stats(&stats)),
+        Err(e) => {
+            log::error!("Failed to load stats: {:?}", e);
+            Reply::html(500, templates::render_not_found())
+        }
+    }
+}
+
+/// GET /feed - RSSフィード
+pub async fn feed_xml<S: DiaryStore>(store: &S, today: &str, base_url: &str) -> String {
+    // 今日の日記は編集中なので、過去の確定した日記のみをRSSに含める
+    let entries = match store.list_entries_before(today, 20).await {
+        Ok(entries) => entries,
+        Err(e) => {
+            log::error!("Failed to list entries for RSS: {:?}", e);
+            vec![]
+        }
+    };
+
+    feed::render_rss(&entries, base_url)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::store::{block_on, MemoryStore};
+
+    #[test]
+    fn test_home_flow() {
+        let store = MemoryStore::default();
+        let widget = VerifierWidget::NoOp;
+        let overwrite = OverwritePolicy::default();
+        let page = HomePage {
+            today: "2025-01-15",
+            widget: &widget,
+            form_token: Some("123.abc"),
+            overwrite: &overwrite,
+        };
+
+        let reply = block_on(page.reply(&store));
+        assert_eq!(reply.status, 200);
+        assert!(reply.html_body().contains("2025-01-15の日記"));
+        assert!(reply.html_body().contains(r#"value="123.abc""#));
+
+        block_on(store.upsert_entry("2025-01-15", "<b>誰か</b>の日記")).unwrap();
+        let reply = block_on(page.reply(&store));
+        assert!(reply.html_body().contains("&lt;b&gt;誰か&lt;/b&gt;の日記</textarea>"));
+    }
+
+    #[test]
+    fn test_archive_and_entry_flow() {
+        let store = MemoryStore::default();
+        block_on(store.upsert_entry("2025-01-14", "雪が降った")).unwrap();
+        block_on(store.upsert_entry("2025-01-15", "書きかけ")).unwrap();
+
+        let reply = block_on(archive_reply(&store, "2025-01-15"));
+        assert!(reply.html_body().contains(r#"href="/entries/2025-01-14""#));
+        assert!(!reply.html_body().contains("書きかけ"));
+
+        let reply = block_on(entry_page_reply(&store, Some("2025-01-14"), "2025-01-15"));
+        assert_eq!(reply.status, 200);
+        assert!(reply.html_body().contains("雪が降った"));
+        assert!(!reply.html_body().contains("編集する"));
+
+        assert!(reply.html_body().contains("<dd>5文字・1行</dd>"));
+        assert!(reply.html_body().contains("ひらがな 60% / カタカナ 0% / 漢字 40%"));
+
+        let reply = block_on(entry_page_reply(&store, Some("2025-01-15"), "2025-01-15"));
+        assert!(reply.html_body().contains("編集する"));
+        assert!(!reply.html_body().contains("entry-stats\""));
+
+        for date in [None, Some("2025-01-13"), Some("yesterday")] {
+            let reply = block_on(entry_page_reply(&store, date, "2025-01-15"));
+            assert_eq!(reply.status, 404);
+        }
+    }
+
+    #[test]
+    fn test_feed_flow_excludes_today() {
+        let store = MemoryStore::default();
+        block_on(store.upsert_entry("2025-01-14", "雪が降った")).unwrap();
+        block_on(store.upsert_entry("2025-01-15", "書きかけ")).unwrap();
+
+        let rss = block_on(feed_xml(&store, "2025-01-15", "https://darekagakaku.day"));
+        assert!(rss.contains("<link>https://darekagakaku.day/entries/2025-01-14</link>"));
+        assert!(!rss.contains("書きかけ"));
+    }
+
+    #[test]
+    fn test_stats_page_flow() {
+        let store = MemoryStore::default();
+        let reply = block_on(stats_page_reply(&store, "2025-01-15"));
+        assert!(reply.html_body().contains("まだ確定した日記はありません"));
+
+        block_on(store.upsert_entry("2025-01-13", "<b>一</b>")).unwrap();
+        block_on(store.upsert_entry("2025-01-13", "二")).unwrap();
+        let reply = block_on(stats_page_reply(&store, "2025-01-15"));
+        assert_eq!(reply.status, 200);
+        let html = reply.html_body();
+        assert!(html.contains("2025-01-13から1日分の日記"));
+        assert!(html.contains("誰も書かなかった日: 1日"));
+        assert!(html.contains(r#"<a href="/entries/2025-01-13">2025-01-13</a></td><td>1回"#));
+        let hour = r#"<td>9時</td><td>2</td><td class="bar"><span style="width: 100%">"#;
+        assert!(html.contains(hour));
+    }
+}
@@ -0,0 +1,254 @@
+//! プルーフ・オブ・ワークによる保存前の確認
+//!
+//! チャレンジの乱数は実行環境で作り、発行と検証の手順はWorkers版とネイティブのサーバーで共有する。
+
+use sha2::{Digest, Sha256};
+
+use crate::api;
+use crate::config::{parse_var, FromVars};
+use crate::models::PowChallengeResponse;
+use crate::rate_limit::{RateLimitStore, RateLimiter};
+use crate::reply::Reply;
+use crate::store::PowStore;
+use crate::time::Clock;
+use crate::verification::VerificationFailure;
+
+/// 既定の難易度（ハッシュの先頭に並ぶべき0のビット数）
+const DEFAULT_DIFFICULTY: u32 = 16;
+const MAX_DIFFICULTY: u32 = 32;
+/// チャレンジを解くまでの猶予
+const CHALLENGE_TTL_SECONDS: i64 = 300;
+/// チャレンジに使う乱数のバイト数
+pub const CHALLENGE_BYTES: usize = 16;
+/// 16バイトのチャレンジを16進にした長さ
+const CHALLENGE_LENGTH: usize = CHALLENGE_BYTES * 2;
+const MAX_NONCE_LENGTH: usize = 20;
+
+const EXPIRED: VerificationFailure = VerificationFailure {
+    code: "POW_EXPIRED",
+    message: "Proof-of-work challenge expired or already used",
+    status: 400,
+};
+const INVALID: VerificationFailure = VerificationFailure {
+    code: "POW_INVALID",
+    message: "Invalid proof-of-work solution",
+    status: 400,
+};
+
+/// チャレンジの発行の設定
+#[derive(Debug, Clone, PartialEq)]
+pub struct PowPolicy {
+    /// 新しく発行するチャレンジの難易度（POW_DIFFICULTY）
+    pub difficulty: u32,
+}
+
+impl Default for PowPolicy {
+    fn default() -> Self {
+        Self {
+            difficulty: DEFAULT_DIFFICULTY,
+        }
+    }
+}
+
+impl FromVars for PowPolicy {
+    fn from_vars(get: impl Fn(&str) -> Option<String>) -> Self {
+        Self {
+            difficulty: parse_var(get("POW_DIFFICULTY"))
+                .filter(|d| (1..=MAX_DIFFICULTY).contains(d))
+                .unwrap_or(DEFAULT_DIFFICULTY),
+        }
+    }
+}
+
+/// チャレンジの発行を書き込みとは別のバケツで数えるためのキー
+fn issue_bucket(client: &str) -> String {
+    format!("pow_issue:{}", client)
+}
+
+/// ハッシュの先頭に並ぶ0のビット数（純粋関数）
+fn leading_zero_bits(hash: &[u8]) -> u32 {
+    let mut bits = 0;
+    for byte in hash {
+        if *byte == 0 {
+            bits += 8;
+        } else {
+            bits += byte.leading_zeros();
+            break;
+        }
+    }
+    bits
+}
+
+/// 「チャレンジ:ナンス」のSHA-256が難易度を満たすか（純粋関数）
+fn is_valid_solution(challenge: &str, nonce: &str, difficulty: u32) -> bool {
+    let hash = Sha256::digest(format!("{}:{}", challenge, nonce).as_bytes());
+    leading_zero_bits(&hash) >= difficulty
+}
+
+/// トークンをチャレンジとナンスに分ける（純粋関数）
+fn parse_token(token: &str) -> Option<(&str, &str)> {
+    let (challenge, nonce) = token.split_once(':')?;
+    let valid_challenge =
+        challenge.len() == CHALLENGE_LENGTH && challenge.bytes().all(|b| b.is_ascii_hexdigit());
+    let valid_nonce = !nonce.is_empty()
+        && nonce.len() <= MAX_NONCE_LENGTH
+        && nonce.bytes().all(|b| b.is_ascii_digit());
+    (valid_challenge && valid_nonce).then_some((challenge, nonce))
+}
+
+/// POST /api/pow/challenge - プルーフ・オブ・ワークのチャレンジ発行
+///
+/// 誰でも呼べるため、書き込みと同じ方針でクライアントごとに発行を制限する。
+/// `challenge` は実行環境で作った乱数の16進で、発行先のクライアントのキーと結び付けて保存する。
+pub async fn issue_reply<S, L, C>(
+    store: &S,
+    limiter: &RateLimiter<'_, L, C>,
+    client: &str,
+    challenge: String,
+    policy: &PowPolicy,
+    now: i64,
+) -> serde_json::Result<Reply>
+where
+    S: PowStore,
+    L: RateLimitStore,
+    L::Error: std::fmt::Debug,
+    C: Clock,
+{
+    let decision = match limiter.acquire(&issue_bucket(client), 1).await {
+        Ok(decision) => decision,
+        Err(e) => {
+            log::error!("Failed to rate limit challenge: {:?}", e);
+            return api::internal_error_reply();
+        }
+    };
+    if let Some(reply) = api::rate_limited_reply(&decision, limiter.policy())? {
+        return Ok(reply);
+    }
+
+    // 発行時の難易度を保存し、設定を変えても解きかけのチャレンジは通す
+    let expires_at = now + CHALLENGE_TTL_SECONDS;
+    let inserted = store
+        .insert_pow_challenge(&challenge, client, policy.difficulty, expires_at, now)
+        .await;
+    if let Err(e) = inserted {
+        log::error!("Failed to store challenge: {:?}", e);
+        return api::internal_error_reply();
+    }
+    Reply::json(
+        200,
+        &PowChallengeResponse {
+            challenge,
+            difficulty: policy.difficulty,
+        },
+    )
+}
+
+/// クライアントが解いたチャレンジを検証する（チャレンジは1回限り）
+///
+/// `client` はチャレンジを発行したときと同じクライアントのキー。
+pub async fn verify_solution<S: PowStore>(
+    store: &S,
+    client: &str,
+    token: &str,
+    now: i64,
+) -> Result<Result<(), VerificationFailure>, S::Error> {
+    let Some((challenge, nonce)) = parse_token(token) else {
+        return Ok(Err(INVALID));
+    };
+
+    let Some(difficulty) = store.take_pow_challenge(challenge, client, now).await? else {
+        return Ok(Err(EXPIRED));
+    };
+
+    if !is_valid_solution(challenge, nonce, difficulty.min(MAX_DIFFICULTY)) {
+        return Ok(Err(INVALID));
+    }
+    Ok(Ok(()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rate_limit::RateLimitPolicy;
+    use crate::store::{block_on, MemoryStore};
+    use crate::time::FixedClock;
+
+    const CHALLENGE: &str = "00112233445566778899aabbccddeeff";
+
+    /// 難易度を満たすナンスを総当たりで探す（8ビットなら数百回程度で見つかる）
+    fn solve(challenge: &str, difficulty: u32) -> String {
+        (0..100_000u32)
+            .map(|n| n.to_string())
+            .find(|n| is_valid_solution(challenge, n, difficulty))
+            .expect("solution exists")
+    }
+
+    #[test]
+    fn test_leading_zero_bits() {
+        assert_eq!(leading_zero_bits(&[0xff]), 0);
+        assert_eq!(leading_zero_bits(&[0x0f, 0x00]), 4);
+        assert_eq!(leading_zero_bits(&[0x00, 0x01]), 15);
+        assert_eq!(leading_zero_bits(&[0x00, 0x00]), 16);
+    }
+
+    #[test]
+    fn test_is_valid_solution() {
+        let nonce = solve(CHALLENGE, 8);
+        let hash = Sha256::digest(format!("{}:{}", CHALLENGE, nonce).as_bytes());
+        assert_eq!(hash[0], 0);
+        assert!(!is_valid_solution(
+            "ffeeddccbbaa99887766554433221100",
+            &nonce,
+            MAX_DIFFICULTY
+        ));
+    }
+
+    #[test]
+    fn test_parse_token() {
+        assert_eq!(
+            parse_token(&format!("{}:123", CHALLENGE)),
+            Some((CHALLENGE, "123"))
+        );
+        assert_eq!(parse_token(CHALLENGE), None);
+        assert_eq!(parse_token(&format!("{}:", CHALLENGE)), None);
+        assert_eq!(parse_token(&format!("{}:12a", CHALLENGE)), None);
+        assert_eq!(parse_token("short:1"), None);
+        assert_eq!(
+            parse_token(&format!("{}:{}", CHALLENGE, "1".repeat(21))),
+            None
+        );
+        assert_eq!(parse_token(""), None);
+    }
+
+    #[test]
+    fn test_policy_from_vars() {
+        let policy = |value: &str| PowPolicy::from_vars(|_| Some(value.to_string()));
+        assert_eq!(policy(" 20 ").difficulty, 20);
+        assert_eq!(policy("0").difficulty, DEFAULT_DIFFICULTY);
+        assert_eq!(policy("33").difficulty, DEFAULT_DIFFICULTY);
+    }
+
+    #[test]
+    fn test_issue_and_verify() {
+        let store = MemoryStore::default();
+        let limiter = RateLimiter::new(&store, FixedClock(1000), RateLimitPolicy::default());
+        let policy = PowPolicy { difficulty: 8 };
+
+        let issue = issue_reply(&store, &limiter, "a", CHALLENGE.to_string(), &policy, 1000);
+        let reply = block_on(issue).unwrap();
+        assert_eq!(reply.status, 200);
+        let token = format!("{}:{}", CHALLENGE, solve(CHALLENGE, 8));
+
+        // 別のクライアントや期限切れでは使えない
+        let verify = |client, now| block_on(verify_solution(&store, client, &token, now)).unwrap();
+        assert_eq!(verify("b", 1000), Err(EXPIRED));
+        assert_eq!(verify("a", 1000 + CHALLENGE_TTL_SECONDS), Err(EXPIRED));
+        assert_eq!(verify("a", 1000), Ok(()));
+        // 1回使ったチャレンジは使えない
+        assert_eq!(verify("a", 1000), Err(EXPIRED));
+
+        let wrong = format!("{}:0", CHALLENGE);
+        block_on(store.insert_pow_challenge(CHALLENGE, "a", 32, 2000, 1000)).unwrap();
+        assert_eq!(block_on(verify_solution(&store, "a", &wrong, 1000)).unwrap(), Err(INVALID));
+    }
+}
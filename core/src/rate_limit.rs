@@ -0,0 +1,797 @@
+use std::net::IpAddr;
+
+use serde::{Deserialize, Serialize};
+
+use crate::config::{parse_var, FromVars};
+use crate::time::Clock;
+
+/// 状態を保持する最小秒数（KVのexpiration_ttlに指定できる最小値に合わせる）
+const MIN_TTL_SECONDS: i64 = 60;
+/// サイト全体の書き込み数を数える期間（秒）
+const GLOBAL_WINDOW_SECONDS: i64 = 60;
+/// サイト全体の毎分の上限の最大値
+///
+/// 全体の回数は1つのキーを読んで書き戻すため、KVの同じキーへの書き込み（毎秒1回程度）を超えない。
+pub const MAX_GLOBAL_PER_MINUTE: u32 = 60;
+
+/// 書き込みのレート制限ポリシー
+///
+/// トークンバケット方式で、`window_seconds` あたり `limit` 回のペースで補充し、
+/// 最大 `burst` 回まで続けて書き込める。
+#[derive(Debug, Clone, PartialEq)]
+pub struct RateLimitPolicy {
+    /// window_secondsあたりに許可する書き込み回数
+    pub limit: u32,
+    pub window_seconds: i64,
+    /// 続けて書き込める最大回数（バケツの容量）
+    pub burst: u32,
+    /// 同じクライアントからの連続した保存の最小間隔（秒）
+    pub min_interval_seconds: i64,
+    /// バケツを使い切ったときに課す最低限の待ち時間（秒）
+    pub cooldown_seconds: i64,
+    /// IPv6アドレスを同じクライアントとみなすプレフィックス長
+    pub ipv6_prefix_length: u8,
+    /// サイト全体で1分間に許可する書き込み回数（0なら無制限、最大MAX_GLOBAL_PER_MINUTE）
+    ///
+    /// KVは結果整合のため、同時に書き込まれると数え漏れる。上限は目安で、厳密には守られない。
+    pub global_per_minute: u32,
+}
+
+impl Default for RateLimitPolicy {
+    fn default() -> Self {
+        Self {
+            limit: 60,
+            window_seconds: 3600,
+            burst: 10,
+            min_interval_seconds: 3,
+            cooldown_seconds: 60,
+            ipv6_prefix_length: 64,
+            global_per_minute: 30,
+        }
+    }
+}
+
+impl FromVars for RateLimitPolicy {
+    fn from_vars(get: impl Fn(&str) -> Option<String>) -> Self {
+        let default = Self::default();
+        Self {
+            limit: parse_var(get("RATE_LIMIT_MAX"))
+                .filter(|v| *v > 0)
+                .unwrap_or(default.limit),
+            window_seconds: parse_var(get("RATE_LIMIT_WINDOW_SECONDS"))
+                .filter(|v| *v > 0)
+                .unwrap_or(default.window_seconds),
+            burst: parse_var(get("RATE_LIMIT_BURST"))
+                .filter(|v| *v > 0)
+                .unwrap_or(default.burst),
+            min_interval_seconds: parse_var(get("RATE_LIMIT_MIN_INTERVAL_SECONDS"))
+                .filter(|v| *v >= 0)
+                .unwrap_or(default.min_interval_seconds),
+            cooldown_seconds: parse_var(get("RATE_LIMIT_COOLDOWN_SECONDS"))
+                .filter(|v| *v >= 0)
+                .unwrap_or(default.cooldown_seconds),
+            ipv6_prefix_length: parse_var(get("RATE_LIMIT_IPV6_PREFIX"))
+                .filter(|v| (1..=128).contains(v))
+                .unwrap_or(default.ipv6_prefix_length),
+            global_per_minute: parse_var(get("RATE_LIMIT_GLOBAL_PER_MINUTE"))
+                .map(|v: u32| v.min(MAX_GLOBAL_PER_MINUTE))
+                .unwrap_or(default.global_per_minute),
+        }
+    }
+}
+
+impl RateLimitPolicy {
+    /// 1秒あたりに補充されるトークン数
+    fn refill_per_second(&self) -> f64 {
+        self.limit as f64 / self.window_seconds as f64
+    }
+}
+
+/// 制限された理由
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LimitReason {
+    /// バケツを使い切った
+    Exhausted,
+    /// 使い切った後の待機期間中
+    Cooldown,
+    /// 前回の保存から最小間隔が経っていない
+    TooSoon,
+    /// サイト全体の書き込みが上限に達した
+    Busy,
+}
+
+impl LimitReason {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Exhausted => "exhausted",
+            Self::Cooldown => "cooldown",
+            Self::TooSoon => "too_soon",
+            Self::Busy => "busy",
+        }
+    }
+}
+
+/// レート制限の判定結果
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum RateLimitDecision {
+    /// 許可（remainingはこの判定の後に続けて書き込める回数、
+    /// reset_afterはバケツが満杯に戻るまでの秒数）
+    Allowed { remaining: u32, reset_after: i64 },
+    Limited {
+        retry_after: i64,
+        reason: LimitReason,
+    },
+}
+
+impl RateLimitDecision {
+    /// RateLimit-*ヘッダーとRetry-Afterの値（純粋関数）
+    pub fn header_values(&self, policy: &RateLimitPolicy) -> Vec<(&'static str, String)> {
+        let mut values = vec![("RateLimit-Limit", policy.burst.to_string())];
+        match self {
+            Self::Allowed {
+                remaining,
+                reset_after,
+            } => {
+                values.push(("RateLimit-Remaining", remaining.to_string()));
+                values.push(("RateLimit-Reset", reset_after.to_string()));
+            }
+            Self::Limited { retry_after, .. } => {
+                values.push(("RateLimit-Remaining", "0".to_string()));
+                values.push(("RateLimit-Reset", retry_after.to_string()));
+                values.push(("Retry-After", retry_after.to_string()));
+            }
+        }
+        values
+    }
+}
+
+/// KVに保存するクライアントごとのバケツ
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+struct BucketState {
+    tokens: f64,
+    updated_at: i64,
+    last_write_at: Option<i64>,
+    blocked_until: i64,
+}
+
+impl BucketState {
+    fn full(policy: &RateLimitPolicy, now: i64) -> Self {
+        Self {
+            tokens: policy.burst as f64,
+            updated_at: now,
+            last_write_at: None,
+            blocked_until: 0,
+        }
+    }
+}
+
+/// 現在時刻まで補充したバケツを返す（純粋関数）
+fn refill(state: &BucketState, policy: &RateLimitPolicy, now: i64) -> BucketState {
+    let elapsed = (now - state.updated_at).max(0) as f64;
+    BucketState {
+        tokens: (state.tokens + elapsed * policy.refill_per_second()).min(policy.burst as f64),
+        updated_at: now.max(state.updated_at),
+        ..state.clone()
+    }
+}
+
+/// バケツが満杯に戻るまでの秒数（純粋関数）
+fn seconds_until_full(state: &BucketState, policy: &RateLimitPolicy) -> i64 {
+    let missing = (policy.burst as f64 - state.tokens).max(0.0);
+    (missing / policy.refill_per_second()).ceil() as i64
+}
+
+/// 書き込みの可否と、保存すべき新しい状態を返す（純粋関数）
+///
+/// `cost` が1以上なら許可と同時にその数のトークンを消費する（0なら判定だけ）。
+/// 残りが1つでもあれば許可し、足りない分はマイナスとして持ち越すため、
+/// 重い書き込みの後は次に書けるまでの時間が長くなる。
+/// バケツを使い切っていた場合は、待機期間を記録した状態を返す。
+fn evaluate(
+    state: Option<&BucketState>,
+    policy: &RateLimitPolicy,
+    now: i64,
+    cost: u32,
+) -> (RateLimitDecision, Option<BucketState>) {
+    let current = match state {
+        Some(state) => refill(state, policy, now),
+        None => BucketState::full(policy, now),
+    };
+
+    if current.blocked_until > now {
+        let decision = RateLimitDecision::Limited {
+            retry_after: current.blocked_until - now,
+            reason: LimitReason::Cooldown,
+        };
+        return (decision, None);
+    }
+
+    if let Some(last_write_at) = current.last_write_at {
+        let next_allowed = last_write_at + policy.min_interval_seconds;
+        if next_allowed > now {
+            let decision = RateLimitDecision::Limited {
+                retry_after: next_allowed - now,
+                reason: LimitReason::TooSoon,
+            };
+            return (decision, None);
+        }
+    }
+
+    if current.tokens < 1.0 {
+        let refill_wait = ((1.0 - current.tokens) / policy.refill_per_second()).ceil() as i64;
+        let wait = refill_wait.max(policy.cooldown_seconds).max(1);
+        let decision = RateLimitDecision::Limited {
+            retry_after: wait,
+            reason: LimitReason::Exhausted,
+        };
+        let blocked = BucketState {
+            blocked_until: now + wait,
+            ..current
+        };
+        return (decision, Some(blocked));
+    }
+
+    if cost == 0 {
+        let decision = RateLimitDecision::Allowed {
+            remaining: current.tokens.floor() as u32,
+            reset_after: seconds_until_full(&current, policy),
+        };
+        return (decision, None);
+    }
+
+    // 持ち越すマイナスはバケツ1杯分までにする
+    let consumed = BucketState {
+        tokens: (current.tokens - cost as f64).max(-(policy.burst as f64)),
+        last_write_at: Some(now),
+        ..current
+    };
+    let decision = RateLimitDecision::Allowed {
+        remaining: consumed.tokens.floor() as u32,
+        reset_after: seconds_until_full(&consumed, policy),
+    };
+    (decision, Some(consumed))
+}
+
+/// 状態を保持すべき秒数（純粋関数）
+///
+/// 期限切れで消えたバケツは満杯として扱われるため、満杯に戻るまで保持すれば十分。
+fn ttl_seconds(state: &BucketState, policy: &RateLimitPolicy, now: i64) -> u64 {
+    let until_full = seconds_until_full(state, policy);
+    let until_unblocked = state.blocked_until - now;
+    let until_interval = state
+        .last_write_at
+        .map_or(0, |t| t + policy.min_interval_seconds - now);
+    until_full
+        .max(until_unblocked)
+        .max(until_interval)
+        .max(MIN_TTL_SECONDS) as u64
+}
+
+/// サイト全体の書き込み数が上限に達していれば、次の期間までの待ち時間を返す（純粋関数）
+fn global_retry_after(count: u32, policy: &RateLimitPolicy, now: i64) -> Option<i64> {
+    (policy.global_per_minute > 0 && count >= policy.global_per_minute)
+        .then(|| GLOBAL_WINDOW_SECONDS - now.rem_euclid(GLOBAL_WINDOW_SECONDS))
+}
+
+/// IPアドレスを同じクライアントとみなす単位に正規化（純粋関数）
+///
+/// IPv6は指定長のプレフィックスにまとめ、アドレスを使い回す回避を防ぐ。
+/// IPv4射影アドレスはIPv4として扱う。解釈できない値はNone。
+pub fn client_network(ip: &str, ipv6_prefix_length: u8) -> Option<String> {
+    match ip.trim().parse::<IpAddr>().ok()? {
+        IpAddr::V4(v4) => Some(v4.to_string()),
+        IpAddr::V6(v6) => {
+            if let Some(v4) = v6.to_ipv4_mapped() {
+                return Some(v4.to_string());
+            }
+            let prefix = ipv6_prefix_length.clamp(1, 128);
+            let mask = u128::MAX << (128 - u32::from(prefix));
+            let network = std::net::Ipv6Addr::from(u128::from(v6) & mask);
+            Some(format!("{}/{}", network, prefix))
+        }
+    }
+}
+
+/// レート制限の状態の保存先
+///
+/// Workersでは期限付きで値を置けるKV、ネイティブのサーバーではプロセス内のメモリを使う。
+pub trait RateLimitStore {
+    /// 保存先の障害（状態の読み書きに使うJSONのエラーも含める）
+    type Error: From<serde_json::Error>;
+
+    async fn get_state(&self, key: &str) -> Result<Option<String>, Self::Error>;
+    async fn put_state(
+        &self,
+        key: &str,
+        value: String,
+        ttl_seconds: u64,
+    ) -> Result<(), Self::Error>;
+}
+
+/// クライアントごとの書き込みレート制限と、サイト全体の上限
+///
+/// 保存先の読み書きが原子的でない場合、同時に届いたリクエストが同じ残量を読むことはありうる。
+/// 1回の判定で読み込みと書き込みを1度ずつに抑え、その隙間を最小限にしている。
+/// KVは同じキーへの書き込みが毎秒1回程度に制限されるため、全体の上限は毎分60回以下にする。
+pub struct RateLimiter<'a, S, C> {
+    store: &'a S,
+    clock: C,
+    policy: RateLimitPolicy,
+}
+
+impl<'a, S: RateLimitStore, C: Clock> RateLimiter<'a, S, C> {
+    pub fn new(store: &'a S, clock: C, policy: RateLimitPolicy) -> Self {
+        Self {
+            store,
+            clock,
+            policy,
+        }
+    }
+
+    pub fn policy(&self) -> &RateLimitPolicy {
+        &self.policy
+    }
+
+    /// トークンを消費せずに書き込めるか判定
+    pub async fn check(&self, client: &str) -> Result<RateLimitDecision, S::Error> {
+        self.run(client, 0).await
+    }
+
+    /// 書き込めるなら `cost` 回分を消費する（疑わしい書き込みほど重くする）
+    pub async fn acquire(
+        &self,
+        client: &str,
+        cost: u32,
+    ) -> Result<RateLimitDecision, S::Error> {
+        self.run(client, cost.max(1)).await
+    }
+
+    async fn run(&self, client: &str, cost: u32) -> Result<RateLimitDecision, S::Error> {
+        let key = format!("rate_bucket:{}", client);
+        let now = self.clock.now();
+        // 壊れた値や旧形式の値は満杯のバケツとして扱う
+        let state = self
+            .store
+            .get_state(&key)
+            .await?
+            .and_then(|v| serde_json::from_str::<BucketState>(&v).ok());
+
+        let (decision, next) = evaluate(state.as_ref(), &self.policy, now, cost);
+
+        // クライアントとして許可できる場合だけ、サイト全体の上限を確かめる
+        let global_key = format!("rate_global:{}", now.div_euclid(GLOBAL_WINDOW_SECONDS));
+        let mut global_count = None;
+        let allowed = matches!(decision, RateLimitDecision::Allowed { .. });
+        if allowed && self.policy.global_per_minute > 0 {
+            let count: u32 = self
+                .store
+                .get_state(&global_key)
+                .await?
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(0);
+            if let Some(retry_after) = global_retry_after(count, &self.policy, now) {
+                return Ok(RateLimitDecision::Limited {
+                    retry_after,
+                    reason: LimitReason::Busy,
+                });
+            }
+            global_count = Some(count);
+        }
+
+        if let Some(next) = next {
+            let ttl = ttl_seconds(&next, &self.policy, now);
+            self.store
+                .put_state(&key, serde_json::to_string(&next)?, ttl)
+                .await?;
+        }
+        if let (true, Some(count)) = (cost > 0, global_count) {
+            self.store
+                .put_state(
+                    &global_key,
+                    (count + 1).to_string(),
+                    (GLOBAL_WINDOW_SECONDS * 2) as u64,
+                )
+                .await?;
+        }
+        Ok(decision)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::{Cell, RefCell};
+    use std::collections::HashMap;
+    use std::future::Future;
+
+    use crate::store::block_on;
+
+    #[derive(Default)]
+    struct FakeStore {
+        values: RefCell<HashMap<String, (String, u64)>>,
+    }
+
+    impl RateLimitStore for FakeStore {
+        type Error = serde_json::Error;
+
+        async fn get_state(&self, key: &str) -> serde_json::Result<Option<String>> {
+            Ok(self.values.borrow().get(key).map(|(v, _)| v.clone()))
+        }
+
+        async fn put_state(
+            &self,
+            key: &str,
+            value: String,
+            ttl_seconds: u64,
+        ) -> serde_json::Result<()> {
+            self.values
+                .borrow_mut()
+                .insert(key.to_string(), (value, ttl_seconds));
+            Ok(())
+        }
+    }
+
+    struct FakeClock(Cell<i64>);
+
+    impl FakeClock {
+        fn advance(&self, seconds: i64) {
+            self.0.set(self.0.get() + seconds);
+        }
+    }
+
+    impl Clock for &FakeClock {
+        fn now(&self) -> i64 {
+            self.0.get()
+        }
+    }
+
+    fn policy() -> RateLimitPolicy {
+        RateLimitPolicy {
+            limit: 60,
+            window_seconds: 3600,
+            burst: 3,
+            min_interval_seconds: 5,
+            cooldown_seconds: 120,
+            ipv6_prefix_length: 64,
+            global_per_minute: 0,
+        }
+    }
+
+    fn is_allowed(future: impl Future<Output = serde_json::Result<RateLimitDecision>>) -> bool {
+        matches!(
+            block_on(future).unwrap(),
+            RateLimitDecision::Allowed { .. }
+        )
+    }
+
+    fn allowed(remaining: u32, reset_after: i64) -> RateLimitDecision {
+        RateLimitDecision::Allowed {
+            remaining,
+            reset_after,
+        }
+    }
+
+    fn limited(retry_after: i64, reason: LimitReason) -> RateLimitDecision {
+        RateLimitDecision::Limited {
+            retry_after,
+            reason,
+        }
+    }
+
+    #[test]
+    fn test_policy_from_vars() {
+        let vars: HashMap<&str, &str> = [
+            ("RATE_LIMIT_MAX", "30"),
+            ("RATE_LIMIT_WINDOW_SECONDS", "600"),
+            ("RATE_LIMIT_BURST", " 5 "),
+            ("RATE_LIMIT_MIN_INTERVAL_SECONDS", "0"),
+            ("RATE_LIMIT_COOLDOWN_SECONDS", "10"),
+            ("RATE_LIMIT_IPV6_PREFIX", "48"),
+            ("RATE_LIMIT_GLOBAL_PER_MINUTE", "0"),
+        ]
+        .into_iter()
+        .collect();
+        let policy = RateLimitPolicy::from_vars(|name| vars.get(name).map(|v| v.to_string()));
+        assert_eq!(
+            policy,
+            RateLimitPolicy {
+                limit: 30,
+                window_seconds: 600,
+                burst: 5,
+                min_interval_seconds: 0,
+                cooldown_seconds: 10,
+                ipv6_prefix_length: 48,
+                global_per_minute: 0,
+            }
+        );
+    }
+
+    #[test]
+    fn test_policy_from_vars_clamps_global_ceiling() {
+        let policy = RateLimitPolicy::from_vars(|name| {
+            (name == "RATE_LIMIT_GLOBAL_PER_MINUTE").then(|| "1000".to_string())
+        });
+        assert_eq!(policy.global_per_minute, MAX_GLOBAL_PER_MINUTE);
+    }
+
+    #[test]
+    fn test_policy_from_vars_falls_back_to_default() {
+        let vars: HashMap<&str, &str> = [
+            ("RATE_LIMIT_MAX", "0"),
+            ("RATE_LIMIT_WINDOW_SECONDS", "abc"),
+            ("RATE_LIMIT_MIN_INTERVAL_SECONDS", "-1"),
+            ("RATE_LIMIT_IPV6_PREFIX", "129"),
+        ]
+        .into_iter()
+        .collect();
+        let policy = RateLimitPolicy::from_vars(|name| vars.get(name).map(|v| v.to_string()));
+        assert_eq!(policy, RateLimitPolicy::default());
+    }
+
+    #[test]
+    fn test_first_write_is_allowed() {
+        let store = FakeStore::default();
+        let clock = FakeClock(Cell::new(1_000));
+        let limiter = RateLimiter::new(&store, &clock, policy());
+
+        assert_eq!(
+            block_on(limiter.acquire("a", 1)).unwrap(),
+            allowed(2, 60)
+        );
+        // 別のクライアントには影響しない
+        assert_eq!(
+            block_on(limiter.acquire("b", 1)).unwrap(),
+            allowed(2, 60)
+        );
+    }
+
+    #[test]
+    fn test_check_does_not_consume() {
+        let store = FakeStore::default();
+        let clock = FakeClock(Cell::new(1_000));
+        let limiter = RateLimiter::new(&store, &clock, policy());
+
+        for _ in 0..5 {
+            assert_eq!(
+                block_on(limiter.check("a")).unwrap(),
+                allowed(3, 0)
+            );
+        }
+        assert!(store.values.borrow().is_empty());
+    }
+
+    #[test]
+    fn test_min_interval_between_writes() {
+        let store = FakeStore::default();
+        let clock = FakeClock(Cell::new(1_000));
+        let limiter = RateLimiter::new(&store, &clock, policy());
+
+        assert!(is_allowed(limiter.acquire("a", 1)));
+        clock.advance(2);
+        assert_eq!(
+            block_on(limiter.acquire("a", 1)).unwrap(),
+            limited(3, LimitReason::TooSoon)
+        );
+        clock.advance(3);
+        assert!(is_allowed(limiter.acquire("a", 1)));
+    }
+
+    #[test]
+    fn test_exhausted_bucket_starts_cooldown() {
+        let store = FakeStore::default();
+        let clock = FakeClock(Cell::new(1_000));
+        let limiter = RateLimiter::new(&store, &clock, policy());
+
+        for _ in 0..3 {
+            assert!(is_allowed(limiter.acquire("a", 1)));
+            clock.advance(5);
+        }
+        // 15秒で補充されたのは0.25回分なので、クールダウンの120秒が優先される
+        assert_eq!(
+            block_on(limiter.check("a")).unwrap(),
+            limited(120, LimitReason::Exhausted)
+        );
+        clock.advance(100);
+        assert_eq!(
+            block_on(limiter.acquire("a", 1)).unwrap(),
+            limited(20, LimitReason::Cooldown)
+        );
+        clock.advance(20);
+        assert!(is_allowed(limiter.acquire("a", 1)));
+    }
+
+    #[test]
+    fn test_refill_wait_longer_than_cooldown() {
+        let store = FakeStore::default();
+        let clock = FakeClock(Cell::new(1_000));
+        let policy = RateLimitPolicy {
+            cooldown_seconds: 0,
+            min_interval_seconds: 0,
+            ..policy()
+        };
+        let limiter = RateLimiter::new(&store, &clock, policy);
+
+        for _ in 0..3 {
+            assert!(is_allowed(limiter.acquire("a", 1)));
+        }
+        // 毎分1回のペースで補充される
+        assert_eq!(
+            block_on(limiter.acquire("a", 1)).unwrap(),
+            limited(60, LimitReason::Exhausted)
+        );
+        clock.advance(60);
+        assert_eq!(
+            block_on(limiter.acquire("a", 1)).unwrap(),
+            allowed(0, 180)
+        );
+    }
+
+    #[test]
+    fn test_weighted_cost_carries_debt() {
+        let store = FakeStore::default();
+        let clock = FakeClock(Cell::new(1_000));
+        let policy = RateLimitPolicy {
+            cooldown_seconds: 0,
+            min_interval_seconds: 0,
+            ..policy()
+        };
+        let limiter = RateLimiter::new(&store, &clock, policy);
+
+        // 残り3回分のところに5回分を課すと、2回分のマイナスを持ち越す
+        assert_eq!(
+            block_on(limiter.acquire("a", 5)).unwrap(),
+            allowed(0, 300)
+        );
+        assert_eq!(
+            block_on(limiter.check("a")).unwrap(),
+            limited(180, LimitReason::Exhausted)
+        );
+
+        // マイナスはバケツ1杯分までで、0回分は1回分として扱う
+        assert_eq!(
+            block_on(limiter.acquire("b", 100)).unwrap(),
+            allowed(0, 360)
+        );
+        assert_eq!(
+            block_on(limiter.acquire("c", 0)).unwrap(),
+            allowed(2, 60)
+        );
+    }
+
+    #[test]
+    fn test_bucket_refills_up_to_burst() {
+        let store = FakeStore::default();
+        let clock = FakeClock(Cell::new(1_000));
+        let limiter = RateLimiter::new(&store, &clock, policy());
+
+        for _ in 0..3 {
+            assert!(is_allowed(limiter.acquire("a", 1)));
+            clock.advance(5);
+        }
+        clock.advance(86_400);
+        assert_eq!(
+            block_on(limiter.check("a")).unwrap(),
+            allowed(3, 0)
+        );
+    }
+
+    #[test]
+    fn test_sustained_rate_matches_limit() {
+        let store = FakeStore::default();
+        let clock = FakeClock(Cell::new(1_000));
+        let limiter = RateLimiter::new(&store, &clock, policy());
+
+        // 1時間にわたって5秒ごとに書き込もうとしても、許可されるのは補充分とバースト分だけ
+        let mut allowed = 0;
+        for _ in 0..720 {
+            if is_allowed(limiter.acquire("a", 1)) {
+                allowed += 1;
+            }
+            clock.advance(5);
+        }
+        assert!(allowed <= 60 + 3, "allowed {} writes", allowed);
+        assert!(allowed >= 30, "allowed {} writes", allowed);
+    }
+
+    #[test]
+    fn test_stored_state_ttl() {
+        let store = FakeStore::default();
+        let clock = FakeClock(Cell::new(1_000));
+        let limiter = RateLimiter::new(&store, &clock, policy());
+
+        block_on(limiter.acquire("a", 1)).unwrap();
+        let (_, ttl) = store.values.borrow()["rate_bucket:a"].clone();
+        assert_eq!(ttl, MIN_TTL_SECONDS as u64);
+
+        block_on(limiter.acquire("b", 1)).unwrap();
+        clock.advance(5);
+        block_on(limiter.acquire("b", 1)).unwrap();
+        let (_, ttl) = store.values.borrow()["rate_bucket:b"].clone();
+        // 2回分の補充にかかる時間
+        assert_eq!(ttl, 115);
+    }
+
+    #[test]
+    fn test_header_values() {
+        let policy = policy();
+        assert_eq!(
+            allowed(2, 60).header_values(&policy),
+            vec![
+                ("RateLimit-Limit", "3".to_string()),
+                ("RateLimit-Remaining", "2".to_string()),
+                ("RateLimit-Reset", "60".to_string()),
+            ]
+        );
+        assert_eq!(
+            limited(120, LimitReason::Exhausted).header_values(&policy),
+            vec![
+                ("RateLimit-Limit", "3".to_string()),
+                ("RateLimit-Remaining", "0".to_string()),
+                ("RateLimit-Reset", "120".to_string()),
+                ("Retry-After", "120".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_global_ceiling() {
+        let store = FakeStore::default();
+        let clock = FakeClock(Cell::new(1_200));
+        let policy = RateLimitPolicy {
+            global_per_minute: 2,
+            ..policy()
+        };
+        let limiter = RateLimiter::new(&store, &clock, policy);
+
+        assert!(is_allowed(limiter.acquire("a", 1)));
+        assert!(is_allowed(limiter.acquire("b", 1)));
+        clock.advance(15);
+        assert_eq!(
+            block_on(limiter.acquire("c", 1)).unwrap(),
+            limited(45, LimitReason::Busy)
+        );
+        // 全体の上限で断られた分はクライアントのバケツから消費しない
+        assert!(!store.values.borrow().contains_key("rate_bucket:c"));
+
+        clock.advance(45);
+        assert_eq!(
+            block_on(limiter.acquire("c", 1)).unwrap(),
+            allowed(2, 60)
+        );
+    }
+
+    #[test]
+    fn test_client_network() {
+        assert_eq!(client_network("192.0.2.1", 64).as_deref(), Some("192.0.2.1"));
+        assert_eq!(
+            client_network("2001:db8:1:2:3:4:5:6", 64).as_deref(),
+            Some("2001:db8:1:2::/64")
+        );
+        assert_eq!(
+            client_network("2001:db8:1:2:3:4:5:6", 48).as_deref(),
+            Some("2001:db8:1::/48")
+        );
+        assert_eq!(
+            client_network("::ffff:192.0.2.1", 64).as_deref(),
+            Some("192.0.2.1")
+        );
+        assert_eq!(client_network("unknown", 64), None);
+        assert_eq!(client_network("", 64), None);
+    }
+
+    #[test]
+    fn test_corrupt_state_is_treated_as_full() {
+        let store = FakeStore::default();
+        store
+            .values
+            .borrow_mut()
+            .insert("rate_bucket:a".to_string(), ("42".to_string(), 60));
+        let clock = FakeClock(Cell::new(1_000));
+        let limiter = RateLimiter::new(&store, &clock, policy());
+
+        assert_eq!(
+            block_on(limiter.acquire("a", 1)).unwrap(),
+            allowed(2, 60)
+        );
+    }
+}
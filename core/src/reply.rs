@@ -0,0 +1,66 @@
+use serde::Serialize;
+
+/// ストアの内容だけで決まる応答
+///
+/// 実行環境ごとのレスポンス型（WorkersのResponse、axumのResponse）には依存せず、
+/// 変換はそれぞれのルーティングの境界で行う。
+#[derive(Debug, Clone, PartialEq)]
+pub struct Reply {
+    pub status: u16,
+    pub headers: Vec<(&'static str, String)>,
+    pub body: ReplyBody,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ReplyBody {
+    Json(serde_json::Value),
+    Html(String),
+}
+
+impl Reply {
+    pub fn json(status: u16, body: &impl Serialize) -> serde_json::Result<Self> {
+        Ok(Self {
+            status,
+            headers: vec![],
+            body: ReplyBody::Json(serde_json::to_value(body)?),
+        })
+    }
+
+    pub fn html(status: u16, body: String) -> Self {
+        Self {
+            status,
+            headers: vec![],
+            body: ReplyBody::Html(body),
+        }
+    }
+
+    /// ヘッダーを追加する
+    pub fn with_header(mut self, name: &'static str, value: impl Into<String>) -> Self {
+        self.headers.push((name, value.into()));
+        self
+    }
+
+    /// 指定した名前のヘッダーの値
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(n, _)| n.eq_ignore_ascii_case(name))
+            .map(|(_, v)| v.as_str())
+    }
+
+    /// JSON本文の項目（HTMLの応答ではpanicする）
+    pub fn field(&self, name: &str) -> &serde_json::Value {
+        match &self.body {
+            ReplyBody::Json(value) => &value[name],
+            ReplyBody::Html(_) => panic!("not a JSON reply"),
+        }
+    }
+
+    /// HTML本文（JSONの応答ではpanicする）
+    pub fn html_body(&self) -> &str {
+        match &self.body {
+            ReplyBody::Html(html) => html,
+            ReplyBody::Json(_) => panic!("not an HTML reply"),
+        }
+    }
+}
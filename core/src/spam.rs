@@ -0,0 +1,397 @@
+use std::fmt::Debug;
+
+use hmac::{Hmac, Mac};
+use serde::{Deserialize, Serialize};
+use sha2::Sha256;
+
+use crate::config::{parse_var, FromVars};
+use crate::crypto::{constant_time_eq, content_hash, to_hex};
+use crate::rate_limit::RateLimitStore;
+
+/// フォームトークンの署名の長さ（16進文字数）
+const FORM_TOKEN_SIGNATURE_LENGTH: usize = 32;
+/// フォームトークンを受け付ける期間（ページを開いたまま日付が変わる程度まで）
+const FORM_TOKEN_MAX_AGE_SECONDS: i64 = 86_400;
+/// 同じ内容について覚えておくクライアントの数
+const MAX_TRACKED_CLIENTS: usize = 20;
+/// スコアがこの値増えるごとに、レート制限で消費する回数を1つ増やす
+const SCORE_PER_EXTRA_COST: u32 = 25;
+
+/// スパム判定の設定（SPAM_*変数で変更できる）
+#[derive(Debug, Clone, PartialEq)]
+pub struct SpamPolicy {
+    /// 同じ内容をこの数以上のクライアントが送ったら疑わしいとみなす
+    pub duplicate_clients: usize,
+    /// 同じ内容を数える期間（秒）
+    pub duplicate_window_seconds: i64,
+    /// ページを開いてから保存するまでの最短時間（秒）
+    pub min_seconds: i64,
+    /// この値以上のスコアの保存は拒否する（0で拒否しない）
+    pub reject_score: u32,
+}
+
+impl Default for SpamPolicy {
+    fn default() -> Self {
+        Self {
+            duplicate_clients: 3,
+            duplicate_window_seconds: 600,
+            min_seconds: 3,
+            reject_score: 100,
+        }
+    }
+}
+
+impl FromVars for SpamPolicy {
+    fn from_vars(get: impl Fn(&str) -> Option<String>) -> Self {
+        let default = Self::default();
+        Self {
+            duplicate_clients: parse_var(get("SPAM_DUPLICATE_CLIENTS"))
+                .filter(|v| *v >= 2)
+                .unwrap_or(default.duplicate_clients),
+            duplicate_window_seconds: parse_var(get("SPAM_DUPLICATE_WINDOW_SECONDS"))
+                .filter(|v| *v >= 60)
+                .unwrap_or(default.duplicate_window_seconds),
+            min_seconds: parse_var(get("SPAM_MIN_SECONDS"))
+                .filter(|v| *v >= 0)
+                .unwrap_or(default.min_seconds),
+            reject_score: parse_var(get("SPAM_REJECT_SCORE")).unwrap_or(default.reject_score),
+        }
+    }
+}
+
+/// 自動投稿を疑わせる兆候
+#[derive(Debug, Clone, PartialEq)]
+pub enum SpamSignal {
+    /// 人には見えない入力欄が埋められていた
+    Honeypot,
+    /// フォームトークンがない、または正しくない
+    MissingFormToken,
+    /// ページを開いてから保存までが速すぎる
+    TooFast,
+    /// 同じ内容が短時間に複数のクライアントから送られた
+    Duplicate { clients: usize },
+}
+
+impl SpamSignal {
+    fn weight(&self) -> u32 {
+        match self {
+            SpamSignal::Honeypot => 100,
+            SpamSignal::Duplicate { .. } => 50,
+            SpamSignal::TooFast => 40,
+            SpamSignal::MissingFormToken => 30,
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            SpamSignal::Honeypot => "honeypot",
+            SpamSignal::MissingFormToken => "missing_form_token",
+            SpamSignal::TooFast => "too_fast",
+            SpamSignal::Duplicate { .. } => "duplicate",
+        }
+    }
+}
+
+/// 保存1回分の疑わしさ
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct SpamScore {
+    pub signals: Vec<SpamSignal>,
+}
+
+impl SpamScore {
+    pub fn score(&self) -> u32 {
+        self.signals.iter().map(SpamSignal::weight).sum()
+    }
+
+    /// レート制限で消費させる回数（疑わしいほど次に書けるまでが長くなる）
+    pub fn rate_limit_cost(&self) -> u32 {
+        1 + self.score() / SCORE_PER_EXTRA_COST
+    }
+
+    pub fn should_reject(&self, policy: &SpamPolicy) -> bool {
+        policy.reject_score > 0 && self.score() >= policy.reject_score
+    }
+
+    /// ログ用の兆候の一覧
+    pub fn describe(&self) -> String {
+        let names: Vec<&str> = self.signals.iter().map(SpamSignal::as_str).collect();
+        names.join(",")
+    }
+}
+
+fn sign(key: &str, issued_at: i64) -> String {
+    let mut mac =
+        Hmac::<Sha256>::new_from_slice(key.as_bytes()).expect("HMAC accepts any key length");
+    mac.update(format!("form:{}", issued_at).as_bytes());
+    let mut hex = to_hex(&mac.finalize().into_bytes());
+    hex.truncate(FORM_TOKEN_SIGNATURE_LENGTH);
+    hex
+}
+
+/// ページを開いた時刻を署名したフォームトークンを発行（純粋関数）
+pub fn issue_form_token(key: &str, now: i64) -> String {
+    format!("{}.{}", now, sign(key, now))
+}
+
+/// フォームトークンを検証し、ページを開いた時刻を返す（純粋関数）
+fn verify_form_token(token: &str, key: &str, now: i64) -> Option<i64> {
+    let (issued_at, signature) = token.split_once('.')?;
+    let issued_at: i64 = issued_at.parse().ok()?;
+    let fresh = issued_at <= now && now - issued_at <= FORM_TOKEN_MAX_AGE_SECONDS;
+    (fresh && constant_time_eq(signature, &sign(key, issued_at))).then_some(issued_at)
+}
+
+/// フォーム由来の兆候を調べる（純粋関数）
+///
+/// 署名の鍵が設定されていなければトークンは確かめない。
+fn form_signals(
+    honeypot: Option<&str>,
+    form_token: Option<&str>,
+    key: Option<&str>,
+    now: i64,
+    policy: &SpamPolicy,
+) -> Vec<SpamSignal> {
+    let mut signals = Vec::new();
+    if honeypot.is_some_and(|v| !v.trim().is_empty()) {
+        signals.push(SpamSignal::Honeypot);
+    }
+    if let Some(key) = key {
+        match form_token.and_then(|t| verify_form_token(t, key, now)) {
+            None => signals.push(SpamSignal::MissingFormToken),
+            Some(issued_at) if now - issued_at < policy.min_seconds => {
+                signals.push(SpamSignal::TooFast)
+            }
+            Some(_) => {}
+        }
+    }
+    signals
+}
+
+/// 空白の違いを無視して内容を比べるためのハッシュ（純粋関数）
+fn duplicate_hash(content: &str) -> String {
+    let normalized = content.split_whitespace().collect::<Vec<_>>().join(" ");
+    content_hash(&normalized)
+}
+
+/// 同じ内容を送ったクライアントの記録
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+struct DuplicateRecord {
+    /// クライアントキーと最後に送った時刻
+    clients: Vec<(String, i64)>,
+}
+
+impl DuplicateRecord {
+    /// 期間外の記録を捨ててクライアントを追加する（純粋関数）
+    fn record(mut self, client: &str, now: i64, policy: &SpamPolicy) -> Self {
+        self.clients
+            .retain(|(c, seen_at)| c != client && now - seen_at < policy.duplicate_window_seconds);
+        self.clients.push((client.to_string(), now));
+        let excess = self.clients.len().saturating_sub(MAX_TRACKED_CLIENTS);
+        self.clients.drain(..excess);
+        self
+    }
+}
+
+/// 同じ内容を送ったクライアントの数を記録して返す
+async fn track_duplicate<S: RateLimitStore>(
+    store: &S,
+    content: &str,
+    client: &str,
+    now: i64,
+    policy: &SpamPolicy,
+) -> std::result::Result<usize, S::Error> {
+    let key = format!("spam_dup:{}", duplicate_hash(content));
+    let record = store
+        .get_state(&key)
+        .await?
+        .and_then(|v| serde_json::from_str::<DuplicateRecord>(&v).ok())
+        .unwrap_or_default()
+        .record(client, now, policy);
+    let count = record.clients.len();
+    store
+        .put_state(
+            &key,
+            serde_json::to_string(&record)?,
+            policy.duplicate_window_seconds as u64,
+        )
+        .await?;
+    Ok(count)
+}
+
+/// 保存しようとしている内容とフォームから疑わしさを調べる
+pub struct SpamCheck<'a> {
+    pub content: &'a str,
+    /// 今の日記と同じ内容か（変更せずに保存し直しただけなら重複として数えない）
+    pub unchanged: bool,
+    pub client: &'a str,
+    pub honeypot: Option<&'a str>,
+    pub form_token: Option<&'a str>,
+    pub now: i64,
+}
+
+impl SpamCheck<'_> {
+    pub async fn score<S>(
+        &self,
+        store: &S,
+        key: Option<&str>,
+        policy: &SpamPolicy,
+    ) -> SpamScore
+    where
+        S: RateLimitStore,
+        S::Error: Debug,
+    {
+        let mut signals = form_signals(self.honeypot, self.form_token, key, self.now, policy);
+
+        if !self.unchanged && !self.content.trim().is_empty() {
+            // KVの障害で保存自体は止めない
+            match track_duplicate(store, self.content, self.client, self.now, policy).await {
+                Ok(clients) if clients >= policy.duplicate_clients => {
+                    signals.push(SpamSignal::Duplicate { clients })
+                }
+                Ok(_) => {}
+                Err(e) => log::error!("Failed to track duplicate content: {:?}", e),
+            }
+        }
+
+        SpamScore { signals }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    use crate::store::{block_on, MemoryStore};
+
+    #[test]
+    fn test_policy_from_vars() {
+        let vars: HashMap<&str, &str> = [
+            ("SPAM_DUPLICATE_CLIENTS", "5"),
+            ("SPAM_DUPLICATE_WINDOW_SECONDS", "30"),
+            ("SPAM_MIN_SECONDS", "0"),
+            ("SPAM_REJECT_SCORE", "0"),
+        ]
+        .into_iter()
+        .collect();
+        let policy = SpamPolicy::from_vars(|name| vars.get(name).map(|v| v.to_string()));
+        assert_eq!(
+            policy,
+            SpamPolicy {
+                duplicate_clients: 5,
+                duplicate_window_seconds: 600,
+                min_seconds: 0,
+                reject_score: 0,
+            }
+        );
+    }
+
+    #[test]
+    fn test_form_token() {
+        let token = issue_form_token("secret", 1_000);
+        assert_eq!(verify_form_token(&token, "secret", 1_010), Some(1_000));
+        assert_eq!(verify_form_token(&token, "other", 1_010), None);
+        // 未来の時刻や古すぎるトークンは受け付けない
+        assert_eq!(verify_form_token(&token, "secret", 999), None);
+        assert_eq!(verify_form_token(&token, "secret", 1_000 + 86_401), None);
+        let forged = token.replacen("1000", "900", 1);
+        assert_eq!(verify_form_token(&forged, "secret", 1_010), None);
+        assert_eq!(verify_form_token("garbage", "secret", 1_010), None);
+    }
+
+    #[test]
+    fn test_form_signals() {
+        let policy = SpamPolicy::default();
+        let token = issue_form_token("secret", 1_000);
+        let key = Some("secret");
+
+        assert!(form_signals(None, Some(&token), key, 1_010, &policy).is_empty());
+        assert_eq!(
+            form_signals(Some("http://spam"), Some(&token), key, 1_010, &policy),
+            vec![SpamSignal::Honeypot]
+        );
+        assert_eq!(
+            form_signals(Some(" "), Some(&token), key, 1_001, &policy),
+            vec![SpamSignal::TooFast]
+        );
+        assert_eq!(
+            form_signals(None, None, key, 1_010, &policy),
+            vec![SpamSignal::MissingFormToken]
+        );
+        // 鍵がなければトークンは確かめない
+        assert!(form_signals(None, None, None, 1_010, &policy).is_empty());
+    }
+
+    #[test]
+    fn test_duplicate_record() {
+        let policy = SpamPolicy::default();
+        let record = DuplicateRecord::default()
+            .record("a", 1_000, &policy)
+            .record("b", 1_100, &policy)
+            .record("a", 1_200, &policy);
+        assert_eq!(
+            record.clients,
+            vec![("b".to_string(), 1_100), ("a".to_string(), 1_200)]
+        );
+
+        // 期間外の記録は捨てる
+        let record = record.record("c", 1_700, &policy);
+        assert_eq!(
+            record.clients,
+            vec![("a".to_string(), 1_200), ("c".to_string(), 1_700)]
+        );
+
+        let record = (0..30).fold(DuplicateRecord::default(), |r, i| {
+            r.record(&i.to_string(), 1_000, &policy)
+        });
+        assert_eq!(record.clients.len(), MAX_TRACKED_CLIENTS);
+        assert_eq!(record.clients[0].0, "10");
+    }
+
+    #[test]
+    fn test_duplicate_content_across_clients() {
+        let store = MemoryStore::default();
+        let policy = SpamPolicy::default();
+        let check = |client, content, unchanged| SpamCheck {
+            content,
+            unchanged,
+            client,
+            honeypot: None,
+            form_token: None,
+            now: 1_000,
+        };
+
+        let score = |c: SpamCheck| block_on(c.score(&store, None, &policy));
+        assert_eq!(score(check("a", "買って", false)), SpamScore::default());
+        assert_eq!(score(check("b", " 買って\n", false)), SpamScore::default());
+        // 変更せずに保存し直しただけなら数えない
+        assert_eq!(score(check("c", "買って", true)), SpamScore::default());
+
+        let result = score(check("c", "買って", false));
+        assert_eq!(result.signals, vec![SpamSignal::Duplicate { clients: 3 }]);
+        assert_eq!(result.rate_limit_cost(), 3);
+        assert!(!result.should_reject(&policy));
+    }
+
+    #[test]
+    fn test_score() {
+        let policy = SpamPolicy::default();
+        let score = SpamScore {
+            signals: vec![SpamSignal::TooFast, SpamSignal::MissingFormToken],
+        };
+        assert_eq!(score.score(), 70);
+        assert_eq!(score.rate_limit_cost(), 3);
+        assert!(!score.should_reject(&policy));
+        assert_eq!(score.describe(), "too_fast,missing_form_token");
+
+        let honeypot = SpamScore {
+            signals: vec![SpamSignal::Honeypot],
+        };
+        assert!(honeypot.should_reject(&policy));
+        assert!(!honeypot.should_reject(&SpamPolicy {
+            reject_score: 0,
+            ..policy
+        }));
+        assert_eq!(SpamScore::default().rate_limit_cost(), 1);
+    }
+}
@@ -0,0 +1,281 @@
+//! サイト全体の統計（/stats）
+//!
+//! 集計はmigrations/0002_stats.sqlのトリガーが書き込みのたびに更新する表から読み、
+//! ここでは今日の書きかけの日記を除いて表示用の値にする。
+
+use chrono::{DateTime, Datelike, Days, Months, Timelike};
+use chrono_tz::Asia::Tokyo;
+use serde::{Deserialize, Serialize};
+
+use crate::models::ErrorResponse;
+use crate::reply::Reply;
+use crate::store::DiaryStore;
+use crate::time::parse_date;
+
+/// 上書きの多い日として並べる数
+pub const MOST_OVERWRITTEN_LIMIT: i32 = 10;
+
+/// 月ごとの集計
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub struct MonthTotals {
+    /// YYYY-MM
+    pub month: String,
+    pub entries: i64,
+    /// 最新の本文の文字数の合計
+    pub total_length: i64,
+}
+
+/// 日ごとの上書きの回数
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DayOverwrites {
+    pub date: String,
+    pub versions: i64,
+}
+
+/// 集計表の中身（今日の分も含む）
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct StatsTotals {
+    /// 古い順
+    pub months: Vec<MonthTotals>,
+    /// 最も古い日記の日付
+    pub first_date: Option<String>,
+    /// 上書きの多い順
+    pub most_overwritten: Vec<DayOverwrites>,
+    /// JSTの時ごとの保存回数
+    pub hours: [i64; 24],
+}
+
+/// ISO8601形式の日時のJSTでの時（集計表と同じく読めなければNone）
+pub fn hour_jst(timestamp: &str) -> Option<usize> {
+    let time = DateTime::parse_from_rfc3339(timestamp).ok()?;
+    Some(time.with_timezone(&Tokyo).hour() as usize)
+}
+
+/// 月ごとの統計
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct MonthStats {
+    pub month: String,
+    pub entries: i64,
+    /// 誰も書かなかった日の数
+    pub empty_days: i64,
+    pub average_length: f64,
+}
+
+/// GET /api/stats の応答
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct SiteStats {
+    pub first_date: Option<String>,
+    /// 確定した日記の数
+    pub total_entries: i64,
+    /// 確定した日記の平均の文字数
+    pub average_length: f64,
+    /// 最初の日記から昨日までに誰も書かなかった日の数
+    pub empty_days: i64,
+    /// 最初の日記の月から昨日の月まで、古い順
+    pub months: Vec<MonthStats>,
+    /// 上書きの多い確定した日
+    pub most_overwritten: Vec<DayOverwrites>,
+    /// JSTの時ごとの保存回数（今日の保存も含む）
+    pub hours: [i64; 24],
+}
+
+/// 小数第1位に丸めた平均
+fn average(total: i64, count: i64) -> f64 {
+    if count == 0 {
+        return 0.0;
+    }
+    (total as f64 / count as f64 * 10.0).round() / 10.0
+}
+
+impl SiteStats {
+    /// 集計表の中身から、今日の書きかけの日記を除いた統計を作る
+    pub fn build(totals: StatsTotals, today: &str, today_length: Option<i64>) -> Self {
+        let mut months = Vec::new();
+        let mut total_entries = 0;
+        let mut total_length = 0;
+        let mut empty_days = 0;
+
+        let yesterday = parse_date(today).and_then(|d| d.checked_sub_days(Days::new(1)));
+        let first = totals
+            .first_date
+            .as_deref()
+            .and_then(parse_date)
+            .filter(|first| Some(*first) <= yesterday);
+        if let (Some(first), Some(yesterday)) = (first, yesterday) {
+            let mut start = first.with_day(1).unwrap_or(first);
+            while start <= yesterday {
+                let month = start.format("%Y-%m").to_string();
+                let next = start.checked_add_months(Months::new(1));
+                let last = next
+                    .and_then(|n| n.pred_opt())
+                    .unwrap_or(start)
+                    .min(yesterday);
+                let from = start.max(first);
+
+                let found = totals.months.iter().find(|m| m.month == month);
+                let mut entries = found.map_or(0, |m| m.entries);
+                let mut length = found.map_or(0, |m| m.total_length);
+                if today.starts_with(&month) {
+                    if let Some(today_length) = today_length {
+                        entries -= 1;
+                        length -= today_length;
+                    }
+                }
+                let days = (last - from).num_days() + 1;
+                let empty = (days - entries).max(0);
+
+                months.push(MonthStats {
+                    month,
+                    entries,
+                    empty_days: empty,
+                    average_length: average(length, entries),
+                });
+                total_entries += entries;
+                total_length += length;
+                empty_days += empty;
+
+                let Some(next) = next else {
+                    break;
+                };
+                start = next;
+            }
+        }
+
+        let mut most_overwritten = totals.most_overwritten;
+        most_overwritten.retain(|d| d.date.as_str() < today);
+        most_overwritten.truncate(MOST_OVERWRITTEN_LIMIT as usize);
+
+        Self {
+            first_date: totals.first_date.filter(|d| d.as_str() < today),
+            total_entries,
+            average_length: average(total_length, total_entries),
+            empty_days,
+            months,
+            most_overwritten,
+            hours: totals.hours,
+        }
+    }
+
+    /// 保存先から読む
+    pub async fn load<S: DiaryStore>(store: &S, today: &str) -> Result<Self, S::Error> {
+        // 今日の分を除いても足りるよう、1件多く読む
+        let totals = store.stats_totals(MOST_OVERWRITTEN_LIMIT + 1).await?;
+        let today_entry = store.get_entry(today).await?;
+        let today_length = today_entry.map(|e| e.content.chars().count() as i64);
+        Ok(Self::build(totals, today, today_length))
+    }
+
+    /// 月ごとの最大の日記の数（グラフの幅の基準）
+    pub fn max_month_entries(&self) -> i64 {
+        self.months.iter().map(|m| m.entries).max().unwrap_or(0)
+    }
+
+    /// 時ごとの最大の保存回数（グラフの幅の基準）
+    pub fn max_hour_saves(&self) -> i64 {
+        self.hours.iter().copied().max().unwrap_or(0)
+    }
+}
+
+/// GET /api/stats - サイト全体の統計
+pub async fn stats_reply<S: DiaryStore>(store: &S, today: &str) -> serde_json::Result<Reply> {
+    match SiteStats::load(store, today).await {
+        Ok(stats) => Reply::json(200, &stats),
+        Err(e) => {
+            log::error!("Failed to load stats: {:?}", e);
+            Reply::json(500, &ErrorResponse::internal_error())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::store::{block_on, MemoryStore};
+
+    fn month(month: &str, entries: i64, total_length: i64) -> MonthTotals {
+        MonthTotals {
+            month: month.to_string(),
+            entries,
+            total_length,
+        }
+    }
+
+    #[test]
+    fn test_hour_jst() {
+        assert_eq!(hour_jst("2025-01-15T00:30:00+00:00"), Some(9));
+        assert_eq!(hour_jst("2025-01-15T15:00:00.123+00:00"), Some(0));
+        assert_eq!(hour_jst("2025-01-15T23:59:59+09:00"), Some(23));
+        assert_eq!(hour_jst("yesterday"), None);
+    }
+
+    #[test]
+    fn test_build_excludes_today_and_counts_empty_days() {
+        let totals = StatsTotals {
+            months: vec![month("2024-12", 2, 30), month("2025-02", 3, 60)],
+            first_date: Some("2024-12-30".to_string()),
+            most_overwritten: vec![
+                DayOverwrites {
+                    date: "2025-02-03".to_string(),
+                    versions: 9,
+                },
+                DayOverwrites {
+                    date: "2025-02-01".to_string(),
+                    versions: 4,
+                },
+            ],
+            hours: [1; 24],
+        };
+        let stats = SiteStats::build(totals, "2025-02-03", Some(20));
+
+        let months: Vec<(&str, i64, i64)> = stats
+            .months
+            .iter()
+            .map(|m| (m.month.as_str(), m.entries, m.empty_days))
+            .collect();
+        // 12月は30日から、1月は丸ごと空き、2月は昨日（2日）まで
+        assert_eq!(months, [("2024-12", 2, 0), ("2025-01", 0, 31), ("2025-02", 2, 0)]);
+        assert_eq!(stats.total_entries, 4);
+        assert_eq!(stats.empty_days, 31);
+        assert_eq!(stats.average_length, 17.5);
+        assert_eq!(stats.months[2].average_length, 20.0);
+        assert_eq!(stats.most_overwritten.len(), 1);
+        assert_eq!(stats.most_overwritten[0].date, "2025-02-01");
+        assert_eq!(stats.max_hour_saves(), 1);
+    }
+
+    #[test]
+    fn test_build_without_finalized_entries() {
+        let totals = StatsTotals {
+            months: vec![month("2025-01", 1, 5)],
+            first_date: Some("2025-01-15".to_string()),
+            ..StatsTotals::default()
+        };
+        let stats = SiteStats::build(totals, "2025-01-15", Some(5));
+        assert!(stats.months.is_empty());
+        assert_eq!(stats.first_date, None);
+        assert_eq!(stats.average_length, 0.0);
+    }
+
+    #[test]
+    fn test_stats_from_store() {
+        let store = MemoryStore::default();
+        store.set_now("2025-01-13T12:00:00+00:00");
+        block_on(store.upsert_entry("2025-01-13", "あいう")).unwrap();
+        store.set_now("2025-01-13T13:00:00+00:00");
+        block_on(store.upsert_entry("2025-01-13", "あいうえお")).unwrap();
+        store.set_now("2025-01-15T00:00:00+00:00");
+        block_on(store.upsert_entry("2025-01-15", "今日")).unwrap();
+
+        let reply = block_on(stats_reply(&store, "2025-01-15")).unwrap();
+        assert_eq!(reply.status, 200);
+        assert_eq!(reply.field("total_entries"), 1);
+        assert_eq!(reply.field("empty_days"), 1);
+        assert_eq!(reply.field("average_length"), 5.0);
+        assert_eq!(reply.field("most_overwritten")[0]["versions"], 1);
+        let hours = reply.field("hours");
+        // 作成と上書きのそれぞれを、JSTの時で数える
+        assert_eq!(hours[21], 1);
+        assert_eq!(hours[22], 1);
+        assert_eq!(hours[9], 1);
+    }
+}
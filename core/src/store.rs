@@ -0,0 +1,792 @@
+use std::cell::RefCell;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::convert::Infallible;
+use std::fmt::Debug;
+use std::future::Future;
+use std::task::{Context, Poll, Waker};
+
+use crate::api_keys::{display_prefix, hash_key};
+use crate::entry_stats::SaveActivity;
+use crate::models::{
+    ApiKey, ContentFilter, DiaryEntry, DiaryVersion, FilterAction, FilterKind, ModerationItem,
+    ModerationResolution,
+};
+use crate::rate_limit::RateLimitStore;
+use crate::stats::{hour_jst, DayOverwrites, MonthTotals, StatsTotals};
+
+/// リストアで取り込む前に消す日記と版の範囲
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ClearRange {
+    /// 指定日だけ
+    Date(String),
+    /// 指定日より前のすべて（書きかけの今日は残す）
+    Before(String),
+}
+
+impl ClearRange {
+    /// その日付が範囲に含まれるか
+    pub fn covers(&self, date: &str) -> bool {
+        match self {
+            Self::Date(d) => date == d,
+            Self::Before(before) => date < before.as_str(),
+        }
+    }
+}
+
+/// インポートで書き込む1件
+#[derive(Debug, Clone)]
+pub enum ImportWrite {
+    /// 範囲の日記・版・書き手の記録と人数を消す
+    Clear(ClearRange),
+    /// 日時を指定して日記を書き込む（版は残さない）
+    Entry(DiaryEntry),
+    /// 指定日の版を次の番号で追加する
+    Version {
+        date: String,
+        content: String,
+        created_at: String,
+    },
+}
+
+/// 日記とその版の保存先
+///
+/// WorkersではD1、ネイティブのサーバーではSQLite、テストではメモリ上の実装を使う。
+pub trait DiaryStore {
+    /// 保存先の障害
+    type Error: Debug;
+
+    /// 指定日の日記を取得
+    async fn get_entry(&self, date: &str) -> Result<Option<DiaryEntry>, Self::Error>;
+    /// 指定日の日記を作成または更新する（内容が変わる場合は直前の内容を版として残す）
+    async fn upsert_entry(&self, date: &str, content: &str) -> Result<(), Self::Error>;
+    /// 指定日の版の一覧（新しい順）
+    async fn list_versions(&self, date: &str) -> Result<Vec<DiaryVersion>, Self::Error>;
+    /// 指定日の特定の版
+    async fn get_version(
+        &self,
+        date: &str,
+        version: i32,
+    ) -> Result<Option<DiaryVersion>, Self::Error>;
+    /// 特定の版の内容を置き換える（墨消し）
+    async fn redact_version(
+        &self,
+        date: &str,
+        version: i32,
+        replacement: &str,
+    ) -> Result<(), Self::Error>;
+    /// 指定日より前の日記の一覧（新しい順）
+    async fn list_entries_before(
+        &self,
+        date: &str,
+        limit: i32,
+    ) -> Result<Vec<DiaryEntry>, Self::Error>;
+    /// 指定日より後の日記の一覧（古い順、エクスポートのページ送り用）
+    async fn list_entries_after(
+        &self,
+        date: &str,
+        limit: i32,
+    ) -> Result<Vec<DiaryEntry>, Self::Error>;
+    /// 指定したIDより後の版の一覧（IDの順、エクスポートのページ送り用）
+    async fn list_versions_after(
+        &self,
+        id: i64,
+        limit: i32,
+    ) -> Result<Vec<DiaryVersion>, Self::Error>;
+    /// インポートの書き込みを順に行う（すべて書き込むか、何も書き込まない）
+    async fn write_import(&self, writes: &[ImportWrite]) -> Result<(), Self::Error>;
+    /// 統計の集計表を読む（上書きの多い日はlimit件まで）
+    async fn stats_totals(&self, limit: i32) -> Result<StatsTotals, Self::Error>;
+    /// 保存した書き手のハッシュを記録する
+    async fn record_save(&self, date: &str, writer: &str) -> Result<(), Self::Error>;
+    /// 指定日の版の数・書き手の数・最後に書き換えられた日時
+    async fn save_activity(&self, date: &str) -> Result<SaveActivity, Self::Error>;
+    /// 指定日より前の書き手の記録を日ごとの人数にまとめ、ハッシュを消す
+    async fn compact_saves(&self, before: &str) -> Result<(), Self::Error>;
+}
+
+/// 内容フィルターとモデレーションキューの保存先
+pub trait ModerationStore {
+    /// 保存先の障害
+    type Error: Debug;
+
+    /// 登録されている内容フィルターの一覧（登録順）
+    async fn list_content_filters(&self) -> Result<Vec<ContentFilter>, Self::Error>;
+    /// 要確認の版や保留中の内容をキューに載せる
+    async fn insert_moderation_item(
+        &self,
+        date: &str,
+        content: &str,
+        previous_content: Option<&str>,
+        action: FilterAction,
+        reasons: &str,
+    ) -> Result<(), Self::Error>;
+    /// 未処理の項目の一覧（古い順）
+    async fn list_pending_moderation(&self, limit: i32)
+        -> Result<Vec<ModerationItem>, Self::Error>;
+    /// IDで項目を取得
+    async fn get_moderation_item(&self, id: i64) -> Result<Option<ModerationItem>, Self::Error>;
+    /// 未処理の項目を処理済みにする（他の管理者が先に処理していればfalse）
+    async fn resolve_moderation_item(
+        &self,
+        id: i64,
+        resolution: ModerationResolution,
+        resolved_by: &str,
+    ) -> Result<bool, Self::Error>;
+    /// キューに残る内容を墨消しする
+    async fn redact_moderation_content(
+        &self,
+        id: i64,
+        replacement: &str,
+    ) -> Result<(), Self::Error>;
+    /// 指定日の版のうち、指定した内容と一致するものをすべて墨消しする
+    async fn redact_versions_with_content(
+        &self,
+        date: &str,
+        content: &str,
+        replacement: &str,
+    ) -> Result<(), Self::Error>;
+}
+
+/// スクリプトやAIエージェント向けのAPIキーの保存先
+pub trait ApiKeyStore {
+    /// 保存先の障害
+    type Error: Debug;
+
+    /// キーのハッシュからAPIキーを取得（失効済みのものも返す）
+    async fn get_api_key_by_hash(&self, key_hash: &str) -> Result<Option<ApiKey>, Self::Error>;
+    /// 指定日の使用回数を1つ増やす（上限に達していればfalse）
+    async fn consume_api_key_quota(
+        &self,
+        key_id: i64,
+        date: &str,
+        daily_quota: i64,
+    ) -> Result<bool, Self::Error>;
+    /// APIキーによる書き込みを記録し、最終使用日時を更新する
+    async fn record_api_key_write(
+        &self,
+        key_id: i64,
+        date: &str,
+        content_hash: &str,
+    ) -> Result<(), Self::Error>;
+}
+
+/// プルーフ・オブ・ワークのチャレンジの保存先
+pub trait PowStore {
+    /// 保存先の障害
+    type Error: Debug;
+
+    /// チャレンジを保存し、期限切れのものを消す
+    async fn insert_pow_challenge(
+        &self,
+        challenge: &str,
+        client: &str,
+        difficulty: u32,
+        expires_at: i64,
+        now: i64,
+    ) -> Result<(), Self::Error>;
+    /// 有効なチャレンジを取り出して消し、発行時の難易度を返す（同じチャレンジは1回しか取り出せない）
+    async fn take_pow_challenge(
+        &self,
+        challenge: &str,
+        client: &str,
+        now: i64,
+    ) -> Result<Option<u32>, Self::Error>;
+}
+
+/// 待たずに完了するfutureを実行する
+///
+/// メモリ上のストアは即座に完了するため、1回のpollで結果が得られる。
+pub fn block_on<F: Future>(future: F) -> F::Output {
+    let mut future = std::pin::pin!(future);
+    let mut cx = Context::from_waker(Waker::noop());
+    match future.as_mut().poll(&mut cx) {
+        Poll::Ready(output) => output,
+        Poll::Pending => panic!("memory store should never be pending"),
+    }
+}
+
+/// テスト用のメモリ上のストア（保存時刻は `set_now` で進める）
+pub struct MemoryStore {
+    entries: RefCell<BTreeMap<String, DiaryEntry>>,
+    versions: RefCell<Vec<DiaryVersion>>,
+    /// 保存した書き手の記録（日付, 書き手）
+    saves: RefCell<Vec<(String, String)>>,
+    /// 記録をまとめた日ごとの書き手の人数
+    writer_counts: RefCell<BTreeMap<String, i64>>,
+    /// レート制限などの期限付きの値（期限は見ない）
+    state: RefCell<HashMap<String, String>>,
+    filters: RefCell<Vec<ContentFilter>>,
+    moderation: RefCell<Vec<ModerationItem>>,
+    /// APIキーとそのハッシュ
+    api_keys: RefCell<Vec<(String, ApiKey)>>,
+    /// APIキーごとの日別の使用回数
+    api_key_usage: RefCell<HashMap<(i64, String), i64>>,
+    /// APIキーによる書き込みの記録（キーID, 日付, 内容のハッシュ）
+    api_key_writes: RefCell<Vec<(i64, String, String)>>,
+    /// プルーフ・オブ・ワークのチャレンジ（チャレンジ, クライアント, 難易度, 失効時刻）
+    pow_challenges: RefCell<Vec<(String, String, u32, i64)>>,
+    now: RefCell<String>,
+}
+
+impl Default for MemoryStore {
+    fn default() -> Self {
+        Self {
+            entries: RefCell::default(),
+            versions: RefCell::default(),
+            saves: RefCell::default(),
+            writer_counts: RefCell::default(),
+            state: RefCell::default(),
+            filters: RefCell::default(),
+            moderation: RefCell::default(),
+            api_keys: RefCell::default(),
+            api_key_usage: RefCell::default(),
+            api_key_writes: RefCell::default(),
+            pow_challenges: RefCell::default(),
+            now: RefCell::new("2025-01-15T00:00:00+00:00".to_string()),
+        }
+    }
+}
+
+impl MemoryStore {
+    pub fn set_now(&self, now: &str) {
+        *self.now.borrow_mut() = now.to_string();
+    }
+
+    /// 内容フィルターを登録する
+    pub fn add_content_filter(&self, kind: FilterKind, pattern: &str, action: FilterAction) {
+        let mut filters = self.filters.borrow_mut();
+        let filter = ContentFilter {
+            id: filters.len() as i64 + 1,
+            kind,
+            pattern: pattern.to_string(),
+            action,
+            created_by: "admin".to_string(),
+            created_at: self.now.borrow().clone(),
+        };
+        filters.push(filter);
+    }
+
+    /// 平文のキーを指定してAPIキーを登録する
+    pub fn add_api_key(&self, key: &str, daily_quota: i64) -> ApiKey {
+        let mut api_keys = self.api_keys.borrow_mut();
+        let api_key = ApiKey {
+            id: api_keys.len() as i64 + 1,
+            label: format!("key {}", api_keys.len() + 1),
+            key_prefix: display_prefix(key),
+            daily_quota,
+            created_by: "admin".to_string(),
+            created_at: self.now.borrow().clone(),
+            last_used_at: None,
+            revoked_at: None,
+        };
+        api_keys.push((hash_key(key), api_key.clone()));
+        api_key
+    }
+
+    /// APIキーを失効させる
+    pub fn revoke_api_key(&self, id: i64) {
+        let now = self.now.borrow().clone();
+        let mut api_keys = self.api_keys.borrow_mut();
+        if let Some((_, api_key)) = api_keys.iter_mut().find(|(_, k)| k.id == id) {
+            api_key.revoked_at = Some(now);
+        }
+    }
+
+    /// モデレーションキューの項目（登録順）
+    pub fn moderation_items(&self) -> Vec<ModerationItem> {
+        self.moderation.borrow().clone()
+    }
+
+    /// APIキーによる書き込みの記録（キーID, 日付, 内容のハッシュ）
+    pub fn api_key_writes(&self) -> Vec<(i64, String, String)> {
+        self.api_key_writes.borrow().clone()
+    }
+
+    fn push_version(&self, date: &str, content: String, created_at: &str) {
+        let mut versions = self.versions.borrow_mut();
+        let version_number = versions
+            .iter()
+            .filter(|v| v.entry_date == date)
+            .map(|v| v.version_number)
+            .max()
+            .unwrap_or(0)
+            + 1;
+        let id = versions.iter().map(|v| v.id).max().unwrap_or(0) + 1;
+        versions.push(DiaryVersion {
+            id,
+            entry_date: date.to_string(),
+            content,
+            version_number,
+            created_at: created_at.to_string(),
+        });
+    }
+}
+
+impl RateLimitStore for MemoryStore {
+    type Error = serde_json::Error;
+
+    async fn get_state(&self, key: &str) -> serde_json::Result<Option<String>> {
+        Ok(self.state.borrow().get(key).cloned())
+    }
+
+    async fn put_state(
+        &self,
+        key: &str,
+        value: String,
+        _ttl_seconds: u64,
+    ) -> serde_json::Result<()> {
+        self.state.borrow_mut().insert(key.to_string(), value);
+        Ok(())
+    }
+}
+
+impl ModerationStore for MemoryStore {
+    type Error = Infallible;
+
+    async fn list_content_filters(&self) -> Result<Vec<ContentFilter>, Infallible> {
+        Ok(self.filters.borrow().clone())
+    }
+
+    async fn insert_moderation_item(
+        &self,
+        date: &str,
+        content: &str,
+        previous_content: Option<&str>,
+        action: FilterAction,
+        reasons: &str,
+    ) -> Result<(), Infallible> {
+        let mut moderation = self.moderation.borrow_mut();
+        let item = ModerationItem {
+            id: moderation.len() as i64 + 1,
+            entry_date: date.to_string(),
+            content: content.to_string(),
+            previous_content: previous_content.map(str::to_string),
+            action,
+            reasons: reasons.to_string(),
+            created_at: self.now.borrow().clone(),
+            resolution: None,
+            resolved_by: None,
+            resolved_at: None,
+        };
+        moderation.push(item);
+        Ok(())
+    }
+
+    async fn list_pending_moderation(&self, limit: i32) -> Result<Vec<ModerationItem>, Infallible> {
+        Ok(self
+            .moderation
+            .borrow()
+            .iter()
+            .filter(|item| item.resolution.is_none())
+            .take(limit.max(0) as usize)
+            .cloned()
+            .collect())
+    }
+
+    async fn get_moderation_item(&self, id: i64) -> Result<Option<ModerationItem>, Infallible> {
+        Ok(self.moderation.borrow().iter().find(|item| item.id == id).cloned())
+    }
+
+    async fn resolve_moderation_item(
+        &self,
+        id: i64,
+        resolution: ModerationResolution,
+        resolved_by: &str,
+    ) -> Result<bool, Infallible> {
+        let now = self.now.borrow().clone();
+        let mut moderation = self.moderation.borrow_mut();
+        let item = moderation
+            .iter_mut()
+            .find(|item| item.id == id && item.resolution.is_none());
+        let Some(item) = item else {
+            return Ok(false);
+        };
+        item.resolution = Some(resolution);
+        item.resolved_by = Some(resolved_by.to_string());
+        item.resolved_at = Some(now);
+        Ok(true)
+    }
+
+    async fn redact_moderation_content(
+        &self,
+        id: i64,
+        replacement: &str,
+    ) -> Result<(), Infallible> {
+        let mut moderation = self.moderation.borrow_mut();
+        if let Some(item) = moderation.iter_mut().find(|item| item.id == id) {
+            item.content = replacement.to_string();
+        }
+        Ok(())
+    }
+
+    async fn redact_versions_with_content(
+        &self,
+        date: &str,
+        content: &str,
+        replacement: &str,
+    ) -> Result<(), Infallible> {
+        let mut versions = self.versions.borrow_mut();
+        for version in versions
+            .iter_mut()
+            .filter(|v| v.entry_date == date && v.content == content)
+        {
+            version.content = replacement.to_string();
+        }
+        Ok(())
+    }
+}
+
+impl ApiKeyStore for MemoryStore {
+    type Error = Infallible;
+
+    async fn get_api_key_by_hash(&self, key_hash: &str) -> Result<Option<ApiKey>, Infallible> {
+        Ok(self
+            .api_keys
+            .borrow()
+            .iter()
+            .find(|(hash, _)| hash == key_hash)
+            .map(|(_, api_key)| api_key.clone()))
+    }
+
+    async fn consume_api_key_quota(
+        &self,
+        key_id: i64,
+        date: &str,
+        daily_quota: i64,
+    ) -> Result<bool, Infallible> {
+        let mut usage = self.api_key_usage.borrow_mut();
+        let count = usage.entry((key_id, date.to_string())).or_default();
+        if *count >= daily_quota {
+            return Ok(false);
+        }
+        *count += 1;
+        Ok(true)
+    }
+
+    async fn record_api_key_write(
+        &self,
+        key_id: i64,
+        date: &str,
+        content_hash: &str,
+    ) -> Result<(), Infallible> {
+        let now = self.now.borrow().clone();
+        let mut api_keys = self.api_keys.borrow_mut();
+        if let Some((_, api_key)) = api_keys.iter_mut().find(|(_, k)| k.id == key_id) {
+            api_key.last_used_at = Some(now);
+        }
+        let write = (key_id, date.to_string(), content_hash.to_string());
+        self.api_key_writes.borrow_mut().push(write);
+        Ok(())
+    }
+}
+
+impl PowStore for MemoryStore {
+    type Error = Infallible;
+
+    async fn insert_pow_challenge(
+        &self,
+        challenge: &str,
+        client: &str,
+        difficulty: u32,
+        expires_at: i64,
+        now: i64,
+    ) -> Result<(), Infallible> {
+        let mut challenges = self.pow_challenges.borrow_mut();
+        challenges.retain(|(_, _, _, expires_at)| *expires_at > now);
+        let challenge = (challenge.to_string(), client.to_string(), difficulty, expires_at);
+        challenges.push(challenge);
+        Ok(())
+    }
+
+    async fn take_pow_challenge(
+        &self,
+        challenge: &str,
+        client: &str,
+        now: i64,
+    ) -> Result<Option<u32>, Infallible> {
+        let mut challenges = self.pow_challenges.borrow_mut();
+        let position = challenges
+            .iter()
+            .position(|(c, k, _, expires_at)| c == challenge && k == client && *expires_at > now);
+        Ok(position.map(|i| challenges.remove(i).2))
+    }
+}
+
+impl DiaryStore for MemoryStore {
+    type Error = Infallible;
+
+    async fn get_entry(&self, date: &str) -> Result<Option<DiaryEntry>, Infallible> {
+        Ok(self.entries.borrow().get(date).cloned())
+    }
+
+    async fn upsert_entry(&self, date: &str, content: &str) -> Result<(), Infallible> {
+        let now = self.now.borrow().clone();
+        let mut entries = self.entries.borrow_mut();
+        match entries.get_mut(date) {
+            Some(entry) => {
+                if entry.content != content {
+                    let previous = std::mem::replace(&mut entry.content, content.to_string());
+                    self.push_version(date, previous, &now);
+                }
+                entry.updated_at = now;
+            }
+            None => {
+                let entry = DiaryEntry {
+                    date: date.to_string(),
+                    content: content.to_string(),
+                    created_at: now.clone(),
+                    updated_at: now,
+                };
+                entries.insert(date.to_string(), entry);
+            }
+        }
+        Ok(())
+    }
+
+    async fn list_versions(&self, date: &str) -> Result<Vec<DiaryVersion>, Infallible> {
+        let mut versions: Vec<DiaryVersion> = self
+            .versions
+            .borrow()
+            .iter()
+            .filter(|v| v.entry_date == date)
+            .cloned()
+            .collect();
+        versions.sort_by_key(|v| std::cmp::Reverse(v.version_number));
+        Ok(versions)
+    }
+
+    async fn get_version(
+        &self,
+        date: &str,
+        version: i32,
+    ) -> Result<Option<DiaryVersion>, Infallible> {
+        Ok(self
+            .versions
+            .borrow()
+            .iter()
+            .find(|v| v.entry_date == date && v.version_number == version)
+            .cloned())
+    }
+
+    async fn redact_version(
+        &self,
+        date: &str,
+        version: i32,
+        replacement: &str,
+    ) -> Result<(), Infallible> {
+        let mut versions = self.versions.borrow_mut();
+        let target = versions
+            .iter_mut()
+            .find(|v| v.entry_date == date && v.version_number == version);
+        if let Some(target) = target {
+            target.content = replacement.to_string();
+        }
+        Ok(())
+    }
+
+    async fn list_entries_before(
+        &self,
+        date: &str,
+        limit: i32,
+    ) -> Result<Vec<DiaryEntry>, Infallible> {
+        Ok(self
+            .entries
+            .borrow()
+            .range(..date.to_string())
+            .rev()
+            .take(limit.max(0) as usize)
+            .map(|(_, entry)| entry.clone())
+            .collect())
+    }
+
+    async fn list_entries_after(
+        &self,
+        date: &str,
+        limit: i32,
+    ) -> Result<Vec<DiaryEntry>, Infallible> {
+        Ok(self
+            .entries
+            .borrow()
+            .iter()
+            .filter(|(d, _)| d.as_str() > date)
+            .take(limit.max(0) as usize)
+            .map(|(_, entry)| entry.clone())
+            .collect())
+    }
+
+    async fn list_versions_after(
+        &self,
+        id: i64,
+        limit: i32,
+    ) -> Result<Vec<DiaryVersion>, Infallible> {
+        Ok(self
+            .versions
+            .borrow()
+            .iter()
+            .filter(|v| v.id > id)
+            .take(limit.max(0) as usize)
+            .cloned()
+            .collect())
+    }
+
+    async fn write_import(&self, writes: &[ImportWrite]) -> Result<(), Infallible> {
+        for write in writes {
+            match write {
+                ImportWrite::Clear(range) => {
+                    self.entries.borrow_mut().retain(|date, _| !range.covers(date));
+                    self.versions
+                        .borrow_mut()
+                        .retain(|v| !range.covers(&v.entry_date));
+                    self.saves.borrow_mut().retain(|(date, _)| !range.covers(date));
+                    self.writer_counts
+                        .borrow_mut()
+                        .retain(|date, _| !range.covers(date));
+                }
+                ImportWrite::Entry(entry) => {
+                    self.entries
+                        .borrow_mut()
+                        .insert(entry.date.clone(), entry.clone());
+                }
+                ImportWrite::Version {
+                    date,
+                    content,
+                    created_at,
+                } => self.push_version(date, content.clone(), created_at),
+            }
+        }
+        Ok(())
+    }
+
+    /// 集計表の代わりに、保存してある日記と版をその場で数える
+    async fn stats_totals(&self, limit: i32) -> Result<StatsTotals, Infallible> {
+        let entries = self.entries.borrow();
+        let versions = self.versions.borrow();
+        let mut totals = StatsTotals {
+            first_date: entries.keys().next().cloned(),
+            ..StatsTotals::default()
+        };
+
+        let mut months: BTreeMap<&str, MonthTotals> = BTreeMap::new();
+        for entry in entries.values() {
+            let month = months.entry(&entry.date[..7]).or_insert_with(|| MonthTotals {
+                month: entry.date[..7].to_string(),
+                entries: 0,
+                total_length: 0,
+            });
+            month.entries += 1;
+            month.total_length += entry.content.chars().count() as i64;
+        }
+        totals.months = months.into_values().collect();
+
+        let mut days: BTreeMap<&str, i64> = BTreeMap::new();
+        for version in versions.iter() {
+            *days.entry(&version.entry_date).or_default() += 1;
+        }
+        let mut days: Vec<DayOverwrites> = days
+            .into_iter()
+            .map(|(date, versions)| DayOverwrites {
+                date: date.to_string(),
+                versions,
+            })
+            .collect();
+        days.sort_by(|a, b| b.versions.cmp(&a.versions).then_with(|| a.date.cmp(&b.date)));
+        days.truncate(limit.max(0) as usize);
+        totals.most_overwritten = days;
+
+        let created = entries.values().map(|e| e.created_at.as_str());
+        let overwritten = versions.iter().map(|v| v.created_at.as_str());
+        for hour in created.chain(overwritten).filter_map(hour_jst) {
+            totals.hours[hour] += 1;
+        }
+        Ok(totals)
+    }
+
+    async fn record_save(&self, date: &str, writer: &str) -> Result<(), Infallible> {
+        let save = (date.to_string(), writer.to_string());
+        self.saves.borrow_mut().push(save);
+        Ok(())
+    }
+
+    async fn save_activity(&self, date: &str) -> Result<SaveActivity, Infallible> {
+        let versions = self.list_versions(date).await?;
+        let saves = self.saves.borrow();
+        let writers: BTreeSet<&str> = saves
+            .iter()
+            .filter(|(d, _)| d == date)
+            .map(|(_, writer)| writer.as_str())
+            .collect();
+        let compacted = self.writer_counts.borrow().get(date).copied().unwrap_or(0);
+        Ok(SaveActivity {
+            versions: versions.len() as i64,
+            writers: writers.len() as i64 + compacted,
+            last_changed_at: versions.first().map(|v| v.created_at.clone()),
+        })
+    }
+
+    async fn compact_saves(&self, before: &str) -> Result<(), Infallible> {
+        let mut saves = self.saves.borrow_mut();
+        let mut writers: BTreeMap<String, BTreeSet<String>> = BTreeMap::new();
+        for (date, writer) in saves.iter().filter(|(date, _)| date.as_str() < before) {
+            writers
+                .entry(date.clone())
+                .or_default()
+                .insert(writer.clone());
+        }
+        let mut counts = self.writer_counts.borrow_mut();
+        for (date, writers) in writers {
+            *counts.entry(date).or_insert(0) += writers.len() as i64;
+        }
+        saves.retain(|(date, _)| date.as_str() >= before);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_memory_store_keeps_versions() {
+        let store = MemoryStore::default();
+        block_on(store.upsert_entry("2025-01-15", "一回目")).unwrap();
+        store.set_now("2025-01-15T01:00:00+00:00");
+        block_on(store.upsert_entry("2025-01-15", "二回目")).unwrap();
+        // 同じ内容の保存では版を増やさない
+        block_on(store.upsert_entry("2025-01-15", "二回目")).unwrap();
+        block_on(store.upsert_entry("2025-01-15", "三回目")).unwrap();
+
+        let entry = block_on(store.get_entry("2025-01-15")).unwrap().unwrap();
+        assert_eq!(entry.content, "三回目");
+        assert_eq!(entry.created_at, "2025-01-15T00:00:00+00:00");
+        assert_eq!(entry.updated_at, "2025-01-15T01:00:00+00:00");
+
+        let versions = block_on(store.list_versions("2025-01-15")).unwrap();
+        let contents: Vec<_> = versions.iter().map(|v| v.content.as_str()).collect();
+        assert_eq!(contents, ["二回目", "一回目"]);
+        assert_eq!(versions[0].version_number, 2);
+
+        block_on(store.redact_version("2025-01-15", 1, "[削除]")).unwrap();
+        let version = block_on(store.get_version("2025-01-15", 1)).unwrap().unwrap();
+        assert_eq!(version.content, "[削除]");
+        assert!(block_on(store.get_version("2025-01-15", 3)).unwrap().is_none());
+    }
+
+    #[test]
+    fn test_memory_store_lists_entries_before_date() {
+        let store = MemoryStore::default();
+        for date in ["2025-01-13", "2025-01-14", "2025-01-15"] {
+            block_on(store.upsert_entry(date, date)).unwrap();
+        }
+
+        let dates = |before, limit| -> Vec<String> {
+            block_on(store.list_entries_before(before, limit))
+                .unwrap()
+                .into_iter()
+                .map(|e| e.date)
+                .collect()
+        };
+        assert_eq!(dates("2025-01-15", 100), ["2025-01-14", "2025-01-13"]);
+        assert_eq!(dates("2025-01-15", 1), ["2025-01-14"]);
+        assert!(dates("2025-01-13", 100).is_empty());
+    }
+}
@@ -0,0 +1,2204 @@
+use chrono::DateTime;
+use chrono_tz::Asia::Tokyo;
+
+use crate::content_filter::LinkPolicy;
+use crate::entry_stats::EntryStats;
+use crate::identity::{AdminIdentity, CSRF_FIELD_NAME, CSRF_HEADER_NAME};
+use crate::models::{
+    Admin, AdminCredential, AdminRole, ApiKeyWithUsage, AuditAction, AuditLogEntry, AuditLogFilter,
+    ContentFilter, DiaryEntry, DiaryEntrySummary, DiaryVersion, FilterAction, FilterKind,
+    ModerationItem, ModerationResolution, VersionSummary, DEFAULT_API_KEY_DAILY_QUOTA,
+    MAX_API_KEY_DAILY_QUOTA, MAX_API_KEY_LABEL_LENGTH, MAX_EMAIL_LENGTH, MAX_FILTER_PATTERN_LENGTH,
+    MAX_PASSKEY_LABEL_LENGTH,
+};
+use crate::overwrite::OverwritePolicy;
+use crate::stats::SiteStats;
+use crate::verification::{self, VerifierWidget};
+
+pub(crate) fn escape_common(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+fn escape_html(s: &str) -> String {
+    escape_common(s).replace('\'', "&#x27;")
+}
+
+fn html_head(title: &str) -> String {
+    format!(
+        r#"<!DOCTYPE html>
+<html lang="ja">
+<head>
+    <meta charset="UTF-8">
+    <meta name="viewport" content="width=device-width, initial-scale=1.0">
+    <title>{title} - 誰かが書く日記</title>
+    <link rel="alternate" type="application/rss+xml" title="誰かが書く日記 RSS" href="/feed">
+    <style>
+        * {{ box-sizing: border-box; margin: 0; padding: 0; }}
+        body {{
+            font-family: -apple-system, BlinkMacSystemFont, "Segoe UI", Roboto, "Hiragino Sans", "Noto Sans CJK JP", sans-serif;
+            max-width: 800px;
+            margin: 0 auto;
+            padding: 20px;
+            line-height: 1.6;
+            background-color: #fafafa;
+            color: #333;
+        }}
+        h1 {{
+            font-size: 1.8em;
+            margin-bottom: 10px;
+            color: #2c3e50;
+        }}
+        nav {{
+            margin-bottom: 20px;
+            padding-bottom: 10px;
+            border-bottom: 1px solid #ddd;
+        }}
+        nav a {{
+            color: #3498db;
+            text-decoration: none;
+            margin-right: 15px;
+        }}
+        nav a:hover {{ text-decoration: underline; }}
+        .inline-form {{
+            display: inline;
+        }}
+        button.link-button {{
+            background: none;
+            border: none;
+            padding: 0;
+            margin: 0 15px 0 0;
+            color: #3498db;
+            font-size: inherit;
+            cursor: pointer;
+        }}
+        button.link-button:hover {{ text-decoration: underline; }}
+        .date {{
+            color: #666;
+            font-size: 0.95em;
+            margin-bottom: 15px;
+        }}
+        textarea {{
+            width: 100%;
+            height: 300px;
+            font-size: 16px;
+            padding: 15px;
+            border: 1px solid #ddd;
+            border-radius: 8px;
+            resize: vertical;
+            font-family: inherit;
+            line-height: 1.6;
+        }}
+        textarea:focus {{
+            outline: none;
+            border-color: #3498db;
+            box-shadow: 0 0 0 3px rgba(52, 152, 219, 0.1);
+        }}
+        button {{
+            padding: 12px 24px;
+            font-size: 16px;
+            cursor: pointer;
+            background-color: #3498db;
+            color: white;
+            border: none;
+            border-radius: 6px;
+            margin-top: 15px;
+        }}
+        button:hover {{ background-color: #2980b9; }}
+        .hint {{
+            font-size: 0.85em;
+            color: #888;
+            margin-top: 10px;
+        }}
+        .entry-list {{
+            list-style: none;
+        }}
+        .entry-list li {{
+            padding: 15px;
+            margin-bottom: 10px;
+            background: white;
+            border-radius: 8px;
+            border: 1px solid #eee;
+        }}
+        .entry-list li:hover {{
+            border-color: #3498db;
+        }}
+        .entry-list a {{
+            text-decoration: none;
+            color: inherit;
+            display: block;
+        }}
+        .entry-date {{
+            font-weight: bold;
+            color: #2c3e50;
+            margin-bottom: 5px;
+        }}
+        .entry-preview {{
+            color: #666;
+            font-size: 0.9em;
+        }}
+        .content {{
+            background: white;
+            padding: 20px;
+            border-radius: 8px;
+            border: 1px solid #eee;
+            white-space: pre-wrap;
+            word-wrap: break-word;
+        }}
+        .empty {{
+            color: #888;
+            font-style: italic;
+            padding: 40px;
+            text-align: center;
+        }}
+        .export {{
+            color: #888;
+            font-size: 0.9em;
+            margin-top: 30px;
+        }}
+        .export a {{
+            margin-left: 8px;
+        }}
+        .entry-stats {{
+            display: grid;
+            grid-template-columns: max-content 1fr;
+            gap: 4px 15px;
+            margin-top: 20px;
+            color: #666;
+            font-size: 0.85em;
+        }}
+        .entry-stats dt {{
+            font-weight: bold;
+        }}
+        .stats-summary {{
+            list-style: none;
+            margin-bottom: 20px;
+        }}
+        .stats-table {{
+            width: 100%;
+            border-collapse: collapse;
+            margin-bottom: 30px;
+        }}
+        .stats-table th, .stats-table td {{
+            text-align: left;
+            padding: 4px 8px;
+            border-bottom: 1px solid #eee;
+            font-size: 0.9em;
+        }}
+        .stats-table .bar {{
+            width: 50%;
+        }}
+        .bar span {{
+            display: block;
+            height: 0.8em;
+            background-color: #3498db;
+            border-radius: 2px;
+        }}
+        h2 {{
+            font-size: 1.2em;
+            margin-bottom: 10px;
+            color: #2c3e50;
+        }}
+        .toast {{
+            position: fixed;
+            top: 20px;
+            right: 20px;
+            padding: 15px 25px;
+            background-color: #2ecc71;
+            color: white;
+            border-radius: 8px;
+            box-shadow: 0 4px 12px rgba(0,0,0,0.15);
+            font-size: 14px;
+            z-index: 1000;
+            animation: toast-slide-in 0.3s ease, toast-fade-out 0.3s ease 2.7s;
+            opacity: 0;
+            animation-fill-mode: forwards;
+        }}
+        .toast.error {{
+            background-color: #e74c3c;
+        }}
+        .hp {{
+            position: absolute;
+            left: -10000px;
+            width: 1px;
+            height: 1px;
+            overflow: hidden;
+        }}
+        p.error {{
+            color: #e74c3c;
+            margin-bottom: 15px;
+        }}
+        p.warning {{
+            color: #e67e22;
+            margin-top: 10px;
+        }}
+        .admin-actions form {{
+            display: inline-block;
+            margin-right: 10px;
+        }}
+        .admin-table {{
+            width: 100%;
+            border-collapse: collapse;
+            margin-bottom: 20px;
+        }}
+        .admin-table th, .admin-table td {{
+            text-align: left;
+            padding: 8px;
+            border-bottom: 1px solid #eee;
+        }}
+        .admin-table button {{
+            margin-top: 0;
+            padding: 6px 12px;
+            font-size: 14px;
+        }}
+        @keyframes toast-slide-in {{
+            from {{ transform: translateX(100%); opacity: 0; }}
+            to {{ transform: translateX(0); opacity: 1; }}
+        }}
+        @keyframes toast-fade-out {{
+            from {{ opacity: 1; }}
+            to {{ opacity: 0; }}
+        }}
+    </style>
+</head>
+<body>"#,
+        title = escape_html(title)
+    )
+}
+
+fn html_nav() -> &'static str {
+    r#"<nav>
+        <a href="/">今日の日記を書く</a>
+        <a href="/entries">過去の日記</a>
+        <a href="/stats">統計</a>
+        <a href="/a">これはなにか</a>
+        <a href="/feed">RSS</a>
+    </nav>"#
+}
+
+fn html_footer() -> &'static str {
+    "</body></html>"
+}
+
+/// プルーフ・オブ・ワークをブラウザで解くスクリプト
+///
+/// 「チャレンジ:ナンス」のSHA-256の先頭がdifficultyビット0になるナンスを探す。
+const POW_SOLVER_JS: &str = r#"
+    function solvePow(challenge, difficulty) {
+        var encoder = new TextEncoder();
+        function hasLeadingZeros(buffer) {
+            var bytes = new Uint8Array(buffer);
+            var bits = difficulty;
+            for (var i = 0; bits > 0; i++) {
+                if (bits >= 8) {
+                    if (bytes[i] !== 0) return false;
+                    bits -= 8;
+                } else {
+                    return (bytes[i] >> (8 - bits)) === 0;
+                }
+            }
+            return true;
+        }
+        function search(start) {
+            var digests = [];
+            for (var n = start; n < start + 1000; n++) {
+                digests.push(crypto.subtle.digest('SHA-256', encoder.encode(challenge + ':' + n)));
+            }
+            return Promise.all(digests).then(function(results) {
+                for (var i = 0; i < results.length; i++) {
+                    if (hasLeadingZeros(results[i])) return challenge + ':' + (start + i);
+                }
+                return search(start + 1000);
+            });
+        }
+        return search(0);
+    }"#;
+
+/// 確認方式ごとに、トークンを取得するverifierオブジェクトを定義するスクリプト
+///
+/// getToken()はトークンのPromiseを返し、まだ用意できていなければnullで解決する。
+fn verifier_script(widget: &VerifierWidget, today: &str) -> String {
+    match widget {
+        VerifierWidget::Turnstile { site_key } => format!(
+            r#"<script>
+    var verifier = (function() {{
+        var widgetId = null;
+        window.initVerifier = function() {{
+            widgetId = turnstile.render('#verifier-container', {{
+                sitekey: '{site_key}',
+                action: '{action}',
+                cdata: '{today}',
+                'error-callback': function() {{
+                    console.error('Turnstile error');
+                }}
+            }});
+        }};
+        return {{
+            getToken: function() {{
+                if (widgetId === null) return Promise.resolve(null);
+                return Promise.resolve(turnstile.getResponse(widgetId) || null);
+            }},
+            reset: function() {{
+                if (widgetId !== null) turnstile.reset(widgetId);
+            }}
+        }};
+    }})();
+    </script>
+    <script src="https://challenges.cloudflare.com/turnstile/v0/api.js?render=explicit&onload=initVerifier" async defer></script>"#,
+            site_key = escape_html(site_key),
+            action = verification::DIARY_ACTION,
+            today = escape_html(today),
+        ),
+        VerifierWidget::HCaptcha { site_key } => format!(
+            r#"<script>
+    var verifier = (function() {{
+        var widgetId = null;
+        window.initVerifier = function() {{
+            widgetId = hcaptcha.render('verifier-container', {{ sitekey: '{site_key}' }});
+        }};
+        return {{
+            getToken: function() {{
+                if (widgetId === null) return Promise.resolve(null);
+                return Promise.resolve(hcaptcha.getResponse(widgetId) || null);
+            }},
+            reset: function() {{
+                if (widgetId !== null) hcaptcha.reset(widgetId);
+            }}
+        }};
+    }})();
+    </script>
+    <script src="https://js.hcaptcha.com/1/api.js?render=explicit&onload=initVerifier" async defer></script>"#,
+            site_key = escape_html(site_key),
+        ),
+        VerifierWidget::ProofOfWork => format!(
+            r#"<script>
+    {solver}
+    var verifier = {{
+        getToken: function() {{
+            return fetch('/api/pow/challenge', {{ method: 'POST' }}).then(function(res) {{
+                if (!res.ok) throw new Error('challenge');
+                return res.json();
+            }}).then(function(data) {{
+                return solvePow(data.challenge, data.difficulty);
+            }});
+        }},
+        reset: function() {{}}
+    }};
+    </script>"#,
+            solver = POW_SOLVER_JS,
+        ),
+        VerifierWidget::NoOp => r#"<script>
+    var verifier = {
+        getToken: function() { return Promise.resolve(''); },
+        reset: function() {}
+    };
+    </script>"#
+            .to_string(),
+    }
+}
+
+pub fn render_home(
+    today: &str,
+    entry: Option<&DiaryEntry>,
+    widget: &VerifierWidget,
+    form_token: Option<&str>,
+    overwrite: &OverwritePolicy,
+) -> String {
+    let content = entry.map(|e| escape_html(&e.content)).unwrap_or_default();
+    let form_token_html = form_token
+        .map(|t| format!(r#"<input type="hidden" name="form_token" value="{}">"#, escape_html(t)))
+        .unwrap_or_default();
+
+    format!(
+        r#"{head}
+    {nav}
+    <h1>誰かが書く日記</h1>
+    <p class="date">{today}の日記</p>
+    <form id="diary-form">
+        <textarea name="content" placeholder="今日の日記を書いてください...">{content}</textarea>
+        <p class="warning" id="overwrite-warning" hidden></p>
+        <div class="hp" aria-hidden="true">
+            <label>ウェブサイト <input type="text" name="website" tabindex="-1" autocomplete="off"></label>
+        </div>
+        {form_token}
+        <br>
+        <div id="verifier-container"></div>
+        <button type="submit">保存する</button>
+    </form>
+    <p class="error" id="rate-limit-notice" hidden></p>
+    <p class="hint">0時（JST）になると編集できなくなります</p>
+    {verifier}
+    <script>
+    var overwriteMinLength = {overwrite_min_length};
+    var overwriteMaxRatio = {overwrite_max_ratio};
+    function showRateLimitNotice(seconds, reason) {{
+        var notice = document.getElementById('rate-limit-notice');
+        var at = new Date(Date.now() + seconds * 1000);
+        var time = at.toLocaleTimeString('ja-JP', {{ hour: '2-digit', minute: '2-digit' }});
+        var wait = seconds < 60 ? seconds + '秒' : Math.ceil(seconds / 60) + '分';
+        notice.textContent = reason + time + '頃（約' + wait + '後）から再び保存できます。';
+        notice.hidden = false;
+    }}
+    // 先頭と末尾の一致部分を除いて、元の日記のうち消える割合を見積もる（判定はサーバーで行う）
+    function deletedRatio(original, value) {{
+        var old = Array.from(original);
+        var cur = Array.from(value);
+        var prefix = 0;
+        while (prefix < old.length && prefix < cur.length && old[prefix] === cur[prefix]) {{
+            prefix++;
+        }}
+        var suffix = 0;
+        while (suffix < old.length - prefix && suffix < cur.length - prefix
+            && old[old.length - 1 - suffix] === cur[cur.length - 1 - suffix]) {{
+            suffix++;
+        }}
+        return old.length === 0 ? 0 : (old.length - prefix - suffix) / old.length;
+    }}
+    function updateOverwriteWarning() {{
+        var textarea = document.querySelector('#diary-form textarea');
+        var warning = document.getElementById('overwrite-warning');
+        var original = textarea.defaultValue.replace(/\r/g, '');
+        var ratio = deletedRatio(original, textarea.value.replace(/\r/g, ''));
+        if (Array.from(original).length >= overwriteMinLength && ratio > overwriteMaxRatio) {{
+            warning.textContent = '誰かが書いた日記の約' + Math.round(ratio * 100)
+                + '%を消そうとしています。保存すると元には戻せません。';
+            warning.hidden = false;
+        }} else {{
+            warning.hidden = true;
+        }}
+    }}
+    document.querySelector('#diary-form textarea')
+        .addEventListener('input', updateOverwriteWarning);
+    function save(form, confirmOverwrite) {{
+        var btn = form.querySelector('button');
+        btn.textContent = '確認中...';
+        return verifier.getToken().then(function(token) {{
+            if (token === null) {{
+                alert('認証処理中です。少々お待ちください。');
+                return;
+            }}
+            btn.textContent = '保存中...';
+            return fetch('/api/today', {{
+                method: 'POST',
+                headers: {{ 'Content-Type': 'application/json' }},
+                body: JSON.stringify({{
+                    content: form.content.value,
+                    verification_token: token,
+                    website: form.website.value,
+                    form_token: form.form_token ? form.form_token.value : null,
+                    confirm_overwrite: confirmOverwrite
+                }})
+            }}).then(function(res) {{
+                // トークンは1回限りなので結果にかかわらず取り直す
+                verifier.reset();
+                if (res.ok) {{
+                    document.getElementById('rate-limit-notice').hidden = true;
+                    // 保存した内容を基準に、次に消す量を見積もる
+                    form.content.defaultValue = form.content.value;
+                    updateOverwriteWarning();
+                    var toast = document.createElement('div');
+                    toast.className = 'toast';
+                    // 202は内容フィルターにより保留され、承認待ちになった場合
+                    toast.textContent = res.status === 202
+                        ? '確認後に公開されます'
+                        : '保存しました';
+                    document.body.appendChild(toast);
+                    setTimeout(function() {{ toast.remove(); }}, 3000);
+                }} else if (res.status === 429) {{
+                    var header = parseInt(res.headers.get('Retry-After'), 10);
+                    return res.json().catch(function() {{ return {{}}; }}).then(function(data) {{
+                        var seconds = data.retry_after || header;
+                        var reason = data.code === 'OVERWRITE_COOLDOWN'
+                            ? '直前に保存された日記の大半は、すぐには消せません。'
+                            : '投稿制限中です。';
+                        if (seconds > 0) {{
+                            showRateLimitNotice(seconds, reason);
+                        }} else {{
+                            alert('投稿制限中です。しばらくお待ちください。');
+                        }}
+                    }});
+                }} else {{
+                    return res.json().catch(function() {{ return {{}}; }}).then(function(data) {{
+                        if (/_EXPIRED$/.test(data.code || '')) {{
+                            alert('認証の有効期限が切れました。もう一度保存してください。');
+                        }} else if (data.code === 'CONTENT_REJECTED') {{
+                            alert('この内容は保存できません');
+                        }} else if (data.code === 'OVERWRITE_CONFIRMATION_REQUIRED') {{
+                            var percent = Math.round(data.deleted_ratio * 100);
+                            if (confirm('誰かが書いた日記の約' + percent
+                                + '%が消えます。本当に保存しますか？')) {{
+                                return save(form, true);
+                            }}
+                        }} else {{
+                            alert('保存に失敗しました');
+                        }}
+                    }});
+                }}
+            }});
+        }});
+    }}
+    document.getElementById('diary-form').addEventListener('submit', function(e) {{
+        e.preventDefault();
+        var form = this;
+        var btn = form.querySelector('button');
+        btn.disabled = true;
+        save(form, false).catch(function() {{
+            alert('保存に失敗しました');
+        }}).finally(function() {{
+            btn.disabled = false;
+            btn.textContent = '保存する';
+        }});
+    }});
+    </script>
+{footer}"#,
+        head = html_head("今日の日記"),
+        nav = html_nav(),
+        today = today,
+        content = content,
+        form_token = form_token_html,
+        overwrite_min_length = overwrite.min_length,
+        overwrite_max_ratio = overwrite.max_deleted_ratio,
+        verifier = verifier_script(widget, today),
+        footer = html_footer()
+    )
+}
+
+pub fn render_archive(entries: &[DiaryEntrySummary]) -> String {
+    let entries_html = if entries.is_empty() {
+        r#"<p class="empty">まだ過去の日記はありません</p>"#.to_string()
+    } else {
+        let items: Vec<String> = entries
+            .iter()
+            .map(|e| {
+                format!(
+                    r#"<li><a href="/entries/{date}">
+                        <div class="entry-date">{date}</div>
+                        <div class="entry-preview">{preview}</div>
+                    </a></li>"#,
+                    date = escape_html(&e.date),
+                    preview = escape_html(&e.preview)
+                )
+            })
+            .collect();
+        format!(r#"<ul class="entry-list">{}</ul>"#, items.join("\n"))
+    };
+
+    format!(
+        r#"{head}
+    {nav}
+    <h1>過去の日記</h1>
+    {entries}
+    <p class="export">まとめてダウンロード:
+        <a href="/export/entries.jsonl">JSON Lines</a>
+        <a href="/export/entries.csv">CSV</a>
+        <a href="/export/entries.tar">Markdown</a>
+    </p>
+{footer}"#,
+        head = html_head("過去の日記"),
+        nav = html_nav(),
+        entries = entries_html,
+        footer = html_footer()
+    )
+}
+
+/// ISO8601形式の日時をJSTで表示する（読めなければそのまま）
+fn format_jst(timestamp: &str) -> String {
+    match DateTime::parse_from_rfc3339(timestamp) {
+        Ok(time) => time.with_timezone(&Tokyo).format("%Y-%m-%d %H:%M").to_string(),
+        Err(_) => timestamp.to_string(),
+    }
+}
+
+/// 秒数を「何時間何分」で表示する
+fn format_duration(seconds: i64) -> String {
+    let (hours, minutes) = (seconds / 3600, seconds % 3600 / 60);
+    match (hours, minutes) {
+        (0, 0) => "1分未満".to_string(),
+        (0, m) => format!("{}分", m),
+        (h, 0) => format!("{}時間", h),
+        (h, m) => format!("{}時間{}分", h, m),
+    }
+}
+
+/// 割合を百分率で表示する
+fn percent(ratio: f64) -> String {
+    format!("{}%", (ratio * 1000.0).round() / 10.0)
+}
+
+/// 確定した日記の分析の欄
+fn entry_stats_panel(stats: &EntryStats) -> String {
+    let scripts = &stats.scripts;
+    let writers = match stats.writers {
+        Some(writers) => format!("{}人", writers),
+        None => "記録なし".to_string(),
+    };
+    let survived = match stats.survived_seconds {
+        Some(seconds) => format_duration(seconds),
+        None => "不明".to_string(),
+    };
+    format!(
+        r#"<dl class="entry-stats">
+        <dt>文字数</dt><dd>{characters}文字・{lines}行</dd>
+        <dt>文字の内訳</dt>
+        <dd>ひらがな {hiragana} / カタカナ {katakana} / 漢字 {kanji} / ラテン文字 {latin} / 絵文字 {emoji}</dd>
+        <dt>保存</dt><dd>{saves}回（書いた人: {writers}）</dd>
+        <dt>最初の保存</dt><dd>{first}</dd>
+        <dt>最後の保存</dt><dd>{last}</dd>
+        <dt>最後の文章が残った時間</dt><dd>{survived}</dd>
+    </dl>"#,
+        characters = stats.characters,
+        lines = stats.lines,
+        hiragana = percent(scripts.hiragana),
+        katakana = percent(scripts.katakana),
+        kanji = percent(scripts.kanji),
+        latin = percent(scripts.latin),
+        emoji = percent(scripts.emoji),
+        saves = stats.saves,
+        writers = writers,
+        first = escape_html(&format_jst(&stats.first_saved_at)),
+        last = escape_html(&format_jst(&stats.last_saved_at)),
+        survived = survived
+    )
+}
+
+pub fn render_entry(entry: &DiaryEntry, can_edit: bool, stats: Option<&EntryStats>) -> String {
+    let edit_link = if can_edit {
+        r#"<p><a href="/">編集する</a></p>"#
+    } else {
+        ""
+    };
+    let stats_panel = stats.map(entry_stats_panel).unwrap_or_default();
+
+    format!(
+        r#"{head}
+    {nav}
+    <h1>{date}の日記</h1>
+    <div class="content">{content}</div>
+    {edit_link}
+    {stats_panel}
+{footer}"#,
+        head = html_head(&format!("{}の日記", entry.date)),
+        nav = html_nav(),
+        date = escape_html(&entry.date),
+        content = escape_html(&entry.content),
+        edit_link = edit_link,
+        stats_panel = stats_panel,
+        footer = html_footer()
+    )
+}
+
+/// グラフの棒（最大値に対する割合の幅）
+fn bar(value: i64, max: i64) -> String {
+    let percent = if max > 0 { value * 100 / max } else { 0 };
+    format!(r#"<td class="bar"><span style="width: {}%"></span></td>"#, percent)
+}
+
+pub fn render_stats(stats: &SiteStats) -> String {
+    if stats.months.is_empty() {
+        return format!(
+            r#"{head}
+    {nav}
+    <h1>統計</h1>
+    <p class="empty">まだ確定した日記はありません</p>
+{footer}"#,
+            head = html_head("統計"),
+            nav = html_nav(),
+            footer = html_footer()
+        );
+    }
+
+    let max_entries = stats.max_month_entries();
+    let months: Vec<String> = stats
+        .months
+        .iter()
+        .rev()
+        .map(|m| {
+            format!(
+                "<tr><td>{month}</td><td>{entries}</td><td>{empty}</td><td>{average}</td>{bar}\
+                 </tr>",
+                month = escape_html(&m.month),
+                entries = m.entries,
+                empty = m.empty_days,
+                average = m.average_length,
+                bar = bar(m.entries, max_entries)
+            )
+        })
+        .collect();
+
+    let overwritten = if stats.most_overwritten.is_empty() {
+        r#"<p class="empty">上書きされた日はまだありません</p>"#.to_string()
+    } else {
+        let rows: Vec<String> = stats
+            .most_overwritten
+            .iter()
+            .map(|d| {
+                format!(
+                    r#"<tr><td><a href="/entries/{date}">{date}</a></td><td>{versions}回</td></tr>"#,
+                    date = escape_html(&d.date),
+                    versions = d.versions
+                )
+            })
+            .collect();
+        format!(r#"<table class="stats-table">{}</table>"#, rows.join("\n"))
+    };
+
+    let max_saves = stats.max_hour_saves();
+    let hours: Vec<String> = stats
+        .hours
+        .iter()
+        .enumerate()
+        .map(|(hour, &saves)| {
+            format!(
+                "<tr><td>{hour}時</td><td>{saves}</td>{bar}</tr>",
+                bar = bar(saves, max_saves)
+            )
+        })
+        .collect();
+
+    format!(
+        r#"{head}
+    {nav}
+    <h1>統計</h1>
+    <ul class="stats-summary">
+        <li>{first}から{total}日分の日記</li>
+        <li>平均{average}文字</li>
+        <li>誰も書かなかった日: {empty}日</li>
+    </ul>
+    <h2>月ごとの日記</h2>
+    <table class="stats-table">
+        <tr><th>月</th><th>日記</th><th>空白の日</th><th>平均文字数</th><th></th></tr>
+        {months}
+    </table>
+    <h2>よく上書きされた日</h2>
+    {overwritten}
+    <h2>保存された時刻</h2>
+    <table class="stats-table">
+        {hours}
+    </table>
+    <p class="hint">時刻は日本時間です。今日の書きかけの日記は月ごとの集計に含めていません。</p>
+{footer}"#,
+        head = html_head("統計"),
+        nav = html_nav(),
+        first = escape_html(stats.first_date.as_deref().unwrap_or_default()),
+        total = stats.total_entries,
+        average = stats.average_length,
+        empty = stats.empty_days,
+        months = months.join("\n"),
+        overwritten = overwritten,
+        hours = hours.join("\n"),
+        footer = html_footer()
+    )
+}
+
+pub fn render_not_found() -> String {
+    format!(
+        r#"{head}
+    {nav}
+    <h1>日記が見つかりません</h1>
+    <p class="empty">この日の日記は存在しません。</p>
+{footer}"#,
+        head = html_head("見つかりません"),
+        nav = html_nav(),
+        footer = html_footer()
+    )
+}
+
+pub fn render_about() -> String {
+    format!(
+        r#"{head}
+    {nav}
+    <h1>これはなにか</h1>
+    <div class="content">
+        <p>「自分が書かなければおそらく誰かが書く日記」</p>
+        <p>ここでは日記をつけることができます。しかしその日記は日付がかわるその瞬間までインターネットにアクセスできるすべての存在（それは人間とも限りません）が書くことができます。</p>
+        <p>どこかの誰か（重ねていいますがそれは人間とは限りません）が書き残したものを跡形もなく消し去って、今まさにテクストエリアにフォーカスを持っている存在のその瞬間を記録することができます。どんな美しい言葉でさえも今という瞬間に存在する言葉を超えることはできないのです。</p>
+        <p>たとえそれがどんなに汚ない言葉でも例外はありません。</p>
+        <p>日付を越えるという経験をした言葉は（スーパーユーザではない限り）2度と手をいれることのできない存在になります。</p>
+    </div>
+    <p style="text-align: right; margin-top: 20px;"><a href="/">トップ</a></p>
+{footer}"#,
+        head = html_head("これはなにか"),
+        nav = html_nav(),
+        footer = html_footer()
+    )
+}
+
+/// 管理画面のフォームに埋め込むCSRFトークン
+fn csrf_field(admin: &AdminIdentity) -> String {
+    format!(
+        r#"<input type="hidden" name="{name}" value="{token}">"#,
+        name = CSRF_FIELD_NAME,
+        token = escape_html(admin.csrf_token.as_deref().unwrap_or_default()),
+    )
+}
+
+fn admin_nav(admin: &AdminIdentity) -> String {
+    let moderation_link = if admin.has_role(AdminRole::Moderator) {
+        r#"<a href="/admin/moderation">モデレーション</a>
+        <a href="/admin/filters">内容フィルター</a>"#
+    } else {
+        ""
+    };
+
+    let admins_link = if admin.has_role(AdminRole::Superuser) {
+        r#"<a href="/admin/admins">管理者</a>
+        <a href="/admin/api-keys">APIキー</a>
+        <a href="/admin/audit">監査ログ</a>"#
+    } else {
+        ""
+    };
+
+    // ブートストラップ管理者はアカウントを持たないため二要素認証を設定できない
+    let totp_link = if admin.admin_id.is_some() {
+        r#"<a href="/admin/totp">二要素認証</a>"#
+    } else {
+        ""
+    };
+
+    let passkeys_link = if admin.admin_id.is_some() {
+        r#"<a href="/admin/passkeys">パスキー</a>"#
+    } else {
+        ""
+    };
+
+    format!(
+        r#"<nav>
+        <a href="/admin/versions">バージョン履歴</a>
+        {moderation_link}
+        {admins_link}
+        {totp_link}
+        {passkeys_link}
+        <a href="/">トップページ</a>
+        <span class="hint">{username}（{role}）</span>
+        <form method="post" action="/admin/logout" class="inline-form">
+            {csrf}
+            <button type="submit" class="link-button">ログアウト</button>
+        </form>
+    </nav>"#,
+        moderation_link = moderation_link,
+        admins_link = admins_link,
+        totp_link = totp_link,
+        passkeys_link = passkeys_link,
+        username = escape_html(&admin.username),
+        role = admin.role.label(),
+        csrf = csrf_field(admin),
+    )
+}
+
+pub fn render_admin_versions_index(admin: &AdminIdentity, today: &str) -> String {
+    format!(
+        r#"{head}
+    {nav}
+    <h1>バージョン履歴 - 管理者ページ</h1>
+    <form method="get" action="/admin/entries/{today}/versions">
+        <label for="date">日付を入力:</label>
+        <input type="date" id="date" name="date" value="{today}" required
+               onchange="this.form.action='/admin/entries/'+this.value+'/versions'">
+        <button type="submit">表示</button>
+    </form>
+{footer}"#,
+        head = html_head("バージョン履歴"),
+        nav = admin_nav(admin),
+        today = today,
+        footer = html_footer()
+    )
+}
+
+pub fn render_admin_versions_list(
+    admin: &AdminIdentity,
+    date: &str,
+    current_content: Option<&str>,
+    versions: &[VersionSummary],
+) -> String {
+    let current_html = match current_content {
+        Some(content) => format!(
+            r#"<h2>現在の内容</h2>
+            <div class="content">{}</div>"#,
+            escape_html(content)
+        ),
+        None => r#"<p class="empty">この日付の日記はありません</p>"#.to_string(),
+    };
+
+    // スーパーユーザーは日付を越えた日記も直接編集できる
+    let edit_html = if admin.has_role(AdminRole::Superuser) {
+        format!(
+            r#"<h2>直接編集</h2>
+    <form method="post" action="/admin/entries/{date}/edit">
+        {csrf}
+        <textarea name="content">{content}</textarea>
+        <button type="submit">この内容で保存</button>
+    </form>"#,
+            csrf = csrf_field(admin),
+            date = escape_html(date),
+            content = escape_html(current_content.unwrap_or_default()),
+        )
+    } else {
+        String::new()
+    };
+
+    let versions_html = if versions.is_empty() {
+        r#"<p class="empty">バージョン履歴はありません</p>"#.to_string()
+    } else {
+        let items: Vec<String> = versions
+            .iter()
+            .map(|v| {
+                format!(
+                    r#"<li><a href="/admin/entries/{date}/versions/{version}">
+                        <div class="entry-date">バージョン {version} ({created_at})</div>
+                        <div class="entry-preview">{preview}</div>
+                    </a></li>"#,
+                    date = escape_html(date),
+                    version = v.version_number,
+                    created_at = escape_html(&v.created_at),
+                    preview = escape_html(&v.preview),
+                )
+            })
+            .collect();
+        format!(r#"<ul class="entry-list">{}</ul>"#, items.join("\n"))
+    };
+
+    format!(
+        r#"{head}
+    {nav}
+    <h1>{date}のバージョン履歴</h1>
+    {current}
+    {edit}
+    <h2>過去のバージョン</h2>
+    {versions}
+    <p><a href="/admin/versions">別の日付を選択</a></p>
+{footer}"#,
+        head = html_head(&format!("{} バージョン履歴", date)),
+        nav = admin_nav(admin),
+        date = escape_html(date),
+        current = current_html,
+        edit = edit_html,
+        versions = versions_html,
+        footer = html_footer()
+    )
+}
+
+pub fn render_admin_version_detail(admin: &AdminIdentity, version: &DiaryVersion) -> String {
+    // モデレーター以上は差し戻しと墨消しができる
+    let actions_html = if admin.has_role(AdminRole::Moderator) {
+        format!(
+            r#"<div class="admin-actions">
+        <form method="post" action="/admin/entries/{date}/revert" onsubmit="return confirm('このバージョンに差し戻しますか？');">
+            {csrf}
+            <input type="hidden" name="version" value="{version_number}">
+            <button type="submit">このバージョンに差し戻す</button>
+        </form>
+        <form method="post" action="/admin/entries/{date}/versions/{version_number}/redact" onsubmit="return confirm('このバージョンを墨消ししますか？元に戻せません。');">
+            {csrf}
+            <button type="submit">墨消しする</button>
+        </form>
+    </div>"#,
+            csrf = csrf_field(admin),
+            date = escape_html(&version.entry_date),
+            version_number = version.version_number,
+        )
+    } else {
+        String::new()
+    };
+
+    format!(
+        r#"{head}
+    {nav}
+    <h1>{date}の日記 - バージョン {version_number}</h1>
+    <p class="date">保存日時: {created_at}</p>
+    <div class="content">{content}</div>
+    {actions}
+    <p><a href="/admin/entries/{date}/versions">バージョン一覧に戻る</a></p>
+{footer}"#,
+        head = html_head(&format!(
+            "{} バージョン{}",
+            version.entry_date, version.version_number
+        )),
+        nav = admin_nav(admin),
+        date = escape_html(&version.entry_date),
+        version_number = version.version_number,
+        created_at = escape_html(&version.created_at),
+        content = escape_html(&version.content),
+        actions = actions_html,
+        footer = html_footer()
+    )
+}
+
+fn role_options(selected: AdminRole) -> String {
+    AdminRole::ALL
+        .iter()
+        .map(|role| {
+            format!(
+                r#"<option value="{value}"{selected}>{label}</option>"#,
+                value = role.as_str(),
+                selected = if *role == selected { " selected" } else { "" },
+                label = role.label(),
+            )
+        })
+        .collect()
+}
+
+pub fn render_admin_admins(admin: &AdminIdentity, admins: &[Admin], error: Option<&str>) -> String {
+    let error_html = error
+        .map(|e| format!(r#"<p class="error">{}</p>"#, escape_html(e)))
+        .unwrap_or_default();
+
+    let rows: Vec<String> = admins
+        .iter()
+        .map(|a| {
+            format!(
+                r#"<tr>
+            <td>{username}</td>
+            <td>
+                <form method="post" action="/admin/admins/{id}/role">
+                    {csrf}
+                    <select name="role">{options}</select>
+                    <button type="submit">変更</button>
+                </form>
+            </td>
+            <td>
+                <form method="post" action="/admin/admins/{id}/email">
+                    {csrf}
+                    <input type="email" name="email" value="{email}" placeholder="未設定" maxlength="{max_email}">
+                    <button type="submit">保存</button>
+                </form>
+            </td>
+            <td>
+                <form method="post" action="/admin/admins/{id}/delete" onsubmit="return confirm('{username}を削除しますか？');">
+                    {csrf}
+                    <button type="submit">削除</button>
+                </form>
+            </td>
+        </tr>"#,
+                csrf = csrf_field(admin),
+                id = a.id,
+                username = escape_html(&a.username),
+                options = role_options(a.role),
+                email = escape_html(a.email.as_deref().unwrap_or("")),
+                max_email = MAX_EMAIL_LENGTH,
+            )
+        })
+        .collect();
+
+    let table_html = if rows.is_empty() {
+        r#"<p class="empty">登録された管理者はいません</p>"#.to_string()
+    } else {
+        format!(
+            r#"<table class="admin-table">
+        <tr><th>ユーザー名</th><th>権限</th><th>Cloudflare Access メール</th><th></th></tr>
+        {}
+    </table>"#,
+            rows.join("\n")
+        )
+    };
+
+    format!(
+        r#"{head}
+    {nav}
+    <h1>管理者アカウント</h1>
+    {error}
+    {table}
+    <h2>管理者を追加</h2>
+    <form method="post" action="/admin/admins">
+        {csrf}
+        <label for="username">ユーザー名:</label>
+        <input type="text" id="username" name="username" required autocomplete="off">
+        <label for="password">パスワード:</label>
+        <input type="password" id="password" name="password" required autocomplete="new-password">
+        <select name="role">{options}</select>
+        <button type="submit">追加</button>
+    </form>
+{footer}"#,
+        head = html_head("管理者アカウント"),
+        nav = admin_nav(admin),
+        error = error_html,
+        table = table_html,
+        options = role_options(AdminRole::Viewer),
+        csrf = csrf_field(admin),
+        footer = html_footer()
+    )
+}
+
+pub fn render_forbidden() -> String {
+    format!(
+        r#"{head}
+    <nav><a href="/admin/versions">管理者ページ</a></nav>
+    <h1>権限がありません</h1>
+    <p class="empty">この操作を行う権限がありません。</p>
+{footer}"#,
+        head = html_head("権限がありません"),
+        footer = html_footer()
+    )
+}
+
+pub fn render_bad_request(message: &str) -> String {
+    format!(
+        r#"{head}
+    <nav><a href="/admin/versions">管理者ページ</a></nav>
+    <h1>入力内容に誤りがあります</h1>
+    <p class="error">{message}</p>
+{footer}"#,
+        head = html_head("入力エラー"),
+        message = escape_html(message),
+        footer = html_footer()
+    )
+}
+
+/// WebAuthn API とやり取りするための base64url 変換ヘルパー（クライアント側）
+const WEBAUTHN_HELPERS_JS: &str = r#"<script>
+    function b64urlToBuffer(s) {
+        var b64 = s.replace(/-/g, '+').replace(/_/g, '/');
+        while (b64.length % 4) { b64 += '='; }
+        var bin = atob(b64);
+        var buf = new Uint8Array(bin.length);
+        for (var i = 0; i < bin.length; i++) { buf[i] = bin.charCodeAt(i); }
+        return buf.buffer;
+    }
+    function bufferToB64url(buf) {
+        var bytes = new Uint8Array(buf);
+        var bin = '';
+        for (var i = 0; i < bytes.length; i++) { bin += String.fromCharCode(bytes[i]); }
+        return btoa(bin).replace(/\+/g, '-').replace(/\//g, '_').replace(/=+$/, '');
+    }
+    function postJson(url, body, headers) {
+        headers = headers || {};
+        headers['Content-Type'] = 'application/json';
+        return fetch(url, {
+            method: 'POST',
+            headers: headers,
+            body: JSON.stringify(body || {})
+        }).then(function(res) {
+            return res.json().then(function(data) {
+                if (!res.ok) { throw new Error(data.error || 'request failed'); }
+                return data;
+            });
+        });
+    }
+    </script>"#;
+
+pub fn render_admin_login(error: Option<&str>, turnstile_site_key: Option<&str>) -> String {
+    let error_html = error
+        .map(|e| format!(r#"<p class="error">{}</p>"#, escape_html(e)))
+        .unwrap_or_default();
+
+    // 失敗が続いた場合のみTurnstileを表示
+    let turnstile_html = turnstile_site_key
+        .map(|key| {
+            format!(
+                r#"<div class="cf-turnstile" data-sitekey="{key}" data-action="{action}"></div>
+        <script src="https://challenges.cloudflare.com/turnstile/v0/api.js" async defer></script>"#,
+                key = escape_html(key),
+                action = verification::LOGIN_ACTION,
+            )
+        })
+        .unwrap_or_default();
+
+    format!(
+        r#"{head}
+    <nav><a href="/">トップページ</a></nav>
+    <h1>管理者ログイン</h1>
+    {error}
+    <form method="post" action="/admin/login">
+        <label for="username">ユーザー名:</label>
+        <input type="text" id="username" name="username" autocomplete="username">
+        <label for="password">パスワード:</label>
+        <input type="password" id="password" name="password" required autocomplete="current-password">
+        <p class="hint">管理者トークンでログインする場合はユーザー名を空欄にしてください</p>
+        {turnstile}
+        <button type="submit">ログイン</button>
+    </form>
+    <p><button type="button" id="passkey-login" hidden>パスキーでログイン</button></p>
+    <p class="error" id="passkey-error" hidden></p>
+    {helpers}
+    <script>
+    (function() {{
+        if (!window.PublicKeyCredential) {{ return; }}
+        var btn = document.getElementById('passkey-login');
+        var errorEl = document.getElementById('passkey-error');
+        btn.hidden = false;
+        btn.addEventListener('click', function() {{
+            var challenge;
+            btn.disabled = true;
+            errorEl.hidden = true;
+            postJson('/admin/login/passkey/options').then(function(options) {{
+                challenge = options.challenge;
+                options.challenge = b64urlToBuffer(options.challenge);
+                return navigator.credentials.get({{ publicKey: options }});
+            }}).then(function(cred) {{
+                return postJson('/admin/login/passkey', {{
+                    challenge: challenge,
+                    credential_id: bufferToB64url(cred.rawId),
+                    client_data_json: bufferToB64url(cred.response.clientDataJSON),
+                    authenticator_data: bufferToB64url(cred.response.authenticatorData),
+                    signature: bufferToB64url(cred.response.signature)
+                }});
+            }}).then(function(data) {{
+                location.href = data.redirect;
+            }}).catch(function() {{
+                errorEl.textContent = 'パスキーでのログインに失敗しました';
+                errorEl.hidden = false;
+            }}).finally(function() {{
+                btn.disabled = false;
+            }});
+        }});
+    }})();
+    </script>
+{footer}"#,
+        head = html_head("管理者ログイン"),
+        error = error_html,
+        turnstile = turnstile_html,
+        helpers = WEBAUTHN_HELPERS_JS,
+        footer = html_footer()
+    )
+}
+
+pub fn render_admin_login_totp(pending_token: &str, error: Option<&str>) -> String {
+    let error_html = error
+        .map(|e| format!(r#"<p class="error">{}</p>"#, escape_html(e)))
+        .unwrap_or_default();
+
+    format!(
+        r#"{head}
+    <nav><a href="/">トップページ</a></nav>
+    <h1>二要素認証</h1>
+    {error}
+    <form method="post" action="/admin/login/totp">
+        <input type="hidden" name="pending" value="{pending}">
+        <label for="code">認証アプリの6桁のコード、またはリカバリーコード:</label>
+        <input type="text" id="code" name="code" required autocomplete="one-time-code" inputmode="numeric" autofocus>
+        <button type="submit">確認</button>
+    </form>
+{footer}"#,
+        head = html_head("二要素認証"),
+        error = error_html,
+        pending = escape_html(pending_token),
+        footer = html_footer()
+    )
+}
+
+pub fn render_admin_totp_setup(
+    admin: &AdminIdentity,
+    secret: &str,
+    provisioning_uri: &str,
+    qr_svg: Option<&str>,
+    error: Option<&str>,
+) -> String {
+    let error_html = error
+        .map(|e| format!(r#"<p class="error">{}</p>"#, escape_html(e)))
+        .unwrap_or_default();
+
+    format!(
+        r#"{head}
+    {nav}
+    <h1>二要素認証の設定</h1>
+    {error}
+    <p>認証アプリで次のQRコードを読み取るか、シークレットを入力してください。</p>
+    <div>{qr}</div>
+    <p class="hint">シークレット: <code>{secret}</code></p>
+    <p class="hint">URI: <code>{uri}</code></p>
+    <form method="post" action="/admin/totp/enable">
+        {csrf}
+        <label for="code">表示された6桁のコード:</label>
+        <input type="text" id="code" name="code" required autocomplete="one-time-code" inputmode="numeric">
+        <button type="submit">有効にする</button>
+    </form>
+{footer}"#,
+        head = html_head("二要素認証の設定"),
+        nav = admin_nav(admin),
+        error = error_html,
+        qr = qr_svg.unwrap_or_default(),
+        secret = escape_html(secret),
+        uri = escape_html(provisioning_uri),
+        csrf = csrf_field(admin),
+        footer = html_footer()
+    )
+}
+
+pub fn render_admin_totp_status(admin: &AdminIdentity, remaining_codes: i32, error: Option<&str>) -> String {
+    let error_html = error
+        .map(|e| format!(r#"<p class="error">{}</p>"#, escape_html(e)))
+        .unwrap_or_default();
+
+    format!(
+        r#"{head}
+    {nav}
+    <h1>二要素認証</h1>
+    {error}
+    <p>二要素認証は有効です。未使用のリカバリーコード: {remaining}個</p>
+    <h2>リカバリーコードの再発行</h2>
+    <form method="post" action="/admin/totp/recovery-codes">
+        {csrf}
+        <input type="text" name="code" required placeholder="現在のコード" autocomplete="one-time-code">
+        <button type="submit">再発行</button>
+    </form>
+    <h2>二要素認証の無効化</h2>
+    <form method="post" action="/admin/totp/disable" onsubmit="return confirm('二要素認証を無効にしますか？');">
+        {csrf}
+        <input type="text" name="code" required placeholder="現在のコード" autocomplete="one-time-code">
+        <button type="submit">無効にする</button>
+    </form>
+{footer}"#,
+        head = html_head("二要素認証"),
+        nav = admin_nav(admin),
+        error = error_html,
+        remaining = remaining_codes,
+        csrf = csrf_field(admin),
+        footer = html_footer()
+    )
+}
+
+pub fn render_admin_recovery_codes(admin: &AdminIdentity, codes: &[String]) -> String {
+    let items: Vec<String> = codes
+        .iter()
+        .map(|c| format!("<li><code>{}</code></li>", escape_html(c)))
+        .collect();
+
+    format!(
+        r#"{head}
+    {nav}
+    <h1>リカバリーコード</h1>
+    <p>認証アプリを使えなくなったときに、各コードを1回だけ使えます。このページを離れると二度と表示されないので、安全な場所に保管してください。</p>
+    <ul>{items}</ul>
+    <p><a href="/admin/totp">二要素認証の設定に戻る</a></p>
+{footer}"#,
+        head = html_head("リカバリーコード"),
+        nav = admin_nav(admin),
+        items = items.join("\n"),
+        footer = html_footer()
+    )
+}
+
+pub fn render_admin_passkeys(admin: &AdminIdentity, credentials: &[AdminCredential]) -> String {
+    let rows: Vec<String> = credentials
+        .iter()
+        .map(|c| {
+            format!(
+                r#"<tr>
+            <td>{label}</td>
+            <td>{created_at}</td>
+            <td>{last_used_at}</td>
+            <td>
+                <form method="post" action="/admin/passkeys/{id}/delete" onsubmit="return confirm('このパスキーを削除しますか？')">
+                    {csrf}
+                    <button type="submit">削除</button>
+                </form>
+            </td>
+        </tr>"#,
+                csrf = csrf_field(admin),
+                label = escape_html(&c.label),
+                created_at = escape_html(&c.created_at),
+                last_used_at = c
+                    .last_used_at
+                    .as_deref()
+                    .map(escape_html)
+                    .unwrap_or_else(|| "未使用".to_string()),
+                id = c.id,
+            )
+        })
+        .collect();
+
+    let list_html = if rows.is_empty() {
+        r#"<p class="empty">登録されたパスキーはありません</p>"#.to_string()
+    } else {
+        format!(
+            r#"<table class="admin-table">
+        <tr><th>名前</th><th>登録日時</th><th>最終使用</th><th></th></tr>
+        {}
+    </table>"#,
+            rows.join("\n")
+        )
+    };
+
+    format!(
+        r#"{head}
+    {nav}
+    <h1>パスキー</h1>
+    <p>パスキーを登録すると、パスワードと二要素認証コードの代わりに端末の生体認証などでログインできます。</p>
+    {list}
+    <h2>パスキーを追加</h2>
+    <form id="passkey-form" data-csrf="{csrf_token}">
+        <label for="label">名前（例: 仕事用ノートPC）:</label>
+        <input type="text" id="label" name="label" required maxlength="{max_label}">
+        <button type="submit">登録する</button>
+    </form>
+    <p class="error" id="passkey-error" hidden></p>
+    {helpers}
+    <script>
+    document.getElementById('passkey-form').addEventListener('submit', function(e) {{
+        e.preventDefault();
+        var form = this;
+        var btn = form.querySelector('button');
+        var errorEl = document.getElementById('passkey-error');
+        var challenge;
+        var headers = {{ '{csrf_header}': form.dataset.csrf }};
+        btn.disabled = true;
+        errorEl.hidden = true;
+        postJson('/admin/passkeys/options', {{}}, headers).then(function(options) {{
+            challenge = options.challenge;
+            options.challenge = b64urlToBuffer(options.challenge);
+            options.user.id = b64urlToBuffer(options.user.id);
+            (options.excludeCredentials || []).forEach(function(c) {{
+                c.id = b64urlToBuffer(c.id);
+            }});
+            return navigator.credentials.create({{ publicKey: options }});
+        }}).then(function(cred) {{
+            return postJson('/admin/passkeys', {{
+                challenge: challenge,
+                client_data_json: bufferToB64url(cred.response.clientDataJSON),
+                attestation_object: bufferToB64url(cred.response.attestationObject),
+                label: form.label.value
+            }}, headers);
+        }}).then(function(data) {{
+            location.href = data.redirect;
+        }}).catch(function() {{
+            errorEl.textContent = 'パスキーの登録に失敗しました';
+            errorEl.hidden = false;
+        }}).finally(function() {{
+            btn.disabled = false;
+        }});
+    }});
+    </script>
+{footer}"#,
+        head = html_head("パスキー"),
+        nav = admin_nav(admin),
+        list = list_html,
+        max_label = MAX_PASSKEY_LABEL_LENGTH,
+        csrf_token = escape_html(admin.csrf_token.as_deref().unwrap_or_default()),
+        csrf_header = CSRF_HEADER_NAME,
+        helpers = WEBAUTHN_HELPERS_JS,
+        footer = html_footer()
+    )
+}
+
+fn audit_action_options(selected: Option<AuditAction>) -> String {
+    let mut options = vec![format!(
+        r#"<option value=""{}>すべての操作</option>"#,
+        if selected.is_none() { " selected" } else { "" }
+    )];
+    options.extend(AuditAction::ALL.iter().map(|action| {
+        format!(
+            r#"<option value="{value}"{selected}>{label}</option>"#,
+            value = action.as_str(),
+            selected = if selected == Some(*action) { " selected" } else { "" },
+            label = action.label(),
+        )
+    }));
+    options.concat()
+}
+
+fn audit_target(entry: &AuditLogEntry) -> String {
+    let mut parts = Vec::new();
+    if let Some(date) = &entry.target_date {
+        match entry.target_version {
+            Some(version) => parts.push(format!(
+                r#"<a href="/admin/entries/{date}/versions/{version}">{date} v{version}</a>"#,
+                date = escape_html(date),
+                version = version,
+            )),
+            None => parts.push(format!(
+                r#"<a href="/admin/entries/{date}/versions">{date}</a>"#,
+                date = escape_html(date),
+            )),
+        }
+    }
+    if let Some(target) = &entry.target {
+        parts.push(escape_html(target));
+    }
+    parts.join(" ")
+}
+
+fn short_hash(hash: Option<&str>) -> String {
+    hash.filter(|h| !h.is_empty())
+        .map(|h| escape_html(&h.chars().take(12).collect::<String>()))
+        .unwrap_or_else(|| "-".to_string())
+}
+
+pub fn render_admin_audit(
+    admin: &AdminIdentity,
+    entries: &[AuditLogEntry],
+    filter: &AuditLogFilter,
+    has_next: bool,
+) -> String {
+    let rows: Vec<String> = entries
+        .iter()
+        .map(|e| {
+            format!(
+                r#"<tr>
+            <td>{created_at}</td>
+            <td>{actor}</td>
+            <td>{action}</td>
+            <td>{target}</td>
+            <td class="hint">{before} → {after}</td>
+            <td class="hint">{ip_hash}</td>
+        </tr>"#,
+                created_at = escape_html(&e.created_at),
+                actor = escape_html(&e.actor),
+                action = e.action.label(),
+                target = audit_target(e),
+                before = short_hash(e.before_hash.as_deref()),
+                after = short_hash(e.after_hash.as_deref()),
+                ip_hash = short_hash(Some(&e.ip_hash)),
+            )
+        })
+        .collect();
+
+    let table_html = if rows.is_empty() {
+        r#"<p class="empty">該当する記録はありません</p>"#.to_string()
+    } else {
+        format!(
+            r#"<table class="admin-table">
+        <tr><th>日時</th><th>操作者</th><th>操作</th><th>対象</th><th>変更前 → 変更後</th><th>IP</th></tr>
+        {}
+    </table>"#,
+            rows.join("\n")
+        )
+    };
+
+    let mut pager = Vec::new();
+    if filter.page > 1 {
+        pager.push(format!(
+            r#"<a href="/admin/audit?{}">← 前のページ</a>"#,
+            escape_html(&filter.query_string(filter.page - 1))
+        ));
+    }
+    if has_next {
+        pager.push(format!(
+            r#"<a href="/admin/audit?{}">次のページ →</a>"#,
+            escape_html(&filter.query_string(filter.page + 1))
+        ));
+    }
+
+    format!(
+        r#"{head}
+    {nav}
+    <h1>監査ログ</h1>
+    <form method="get" action="/admin/audit">
+        <label for="actor">操作者:</label>
+        <input type="text" id="actor" name="actor" value="{actor}" autocomplete="off">
+        <select name="action">{options}</select>
+        <button type="submit">絞り込む</button>
+    </form>
+    {table}
+    <p class="nav">{pager}</p>
+{footer}"#,
+        head = html_head("監査ログ"),
+        nav = admin_nav(admin),
+        actor = escape_html(filter.actor.as_deref().unwrap_or("")),
+        options = audit_action_options(filter.action),
+        table = table_html,
+        pager = pager.join(" "),
+        footer = html_footer()
+    )
+}
+
+fn moderation_item_html(admin: &AdminIdentity, item: &ModerationItem) -> String {
+    let buttons: Vec<String> = [
+        (ModerationResolution::Approved, "承認する", None),
+        (
+            ModerationResolution::Reverted,
+            "保存前の内容に戻す",
+            Some("保存前の内容に戻しますか？"),
+        ),
+        (
+            ModerationResolution::Redacted,
+            "墨消しする",
+            Some("この内容を墨消ししますか？元に戻せません。"),
+        ),
+        (ModerationResolution::Discarded, "破棄する", Some("公開せずに破棄しますか？")),
+    ]
+    .into_iter()
+    .filter(|(resolution, _, _)| resolution.applies_to(item.action))
+    .map(|(resolution, label, confirm)| {
+        format!(
+            r#"<form method="post" action="/admin/moderation/{id}"{onsubmit}>
+            {csrf}
+            <input type="hidden" name="resolution" value="{value}">
+            <button type="submit">{label}</button>
+        </form>"#,
+            id = item.id,
+            onsubmit = confirm
+                .map(|c| format!(r#" onsubmit="return confirm('{}');""#, c))
+                .unwrap_or_default(),
+            csrf = csrf_field(admin),
+            value = resolution.as_str(),
+            label = label,
+        )
+    })
+    .collect();
+
+    let status = match item.action {
+        FilterAction::Hold => "保留中（未公開）",
+        _ => "公開済み",
+    };
+
+    format!(
+        r#"<section class="moderation-item">
+    <h2><a href="/admin/entries/{date}/versions">{date}</a> <span class="hint">{status} / {created_at}</span></h2>
+    <ul class="hint">{reasons}</ul>
+    <div class="content">{content}</div>
+    <div class="admin-actions">
+        {buttons}
+    </div>
+</section>"#,
+        date = escape_html(&item.entry_date),
+        status = status,
+        created_at = escape_html(&item.created_at),
+        reasons = item
+            .reasons
+            .lines()
+            .map(|r| format!("<li>{}</li>", escape_html(r)))
+            .collect::<String>(),
+        content = escape_html(&item.content),
+        buttons = buttons.join("\n        "),
+    )
+}
+
+pub fn render_admin_moderation(
+    admin: &AdminIdentity,
+    items: &[ModerationItem],
+    error: Option<&str>,
+) -> String {
+    let error_html = error
+        .map(|e| format!(r#"<p class="error">{}</p>"#, escape_html(e)))
+        .unwrap_or_default();
+
+    let items_html = if items.is_empty() {
+        r#"<p class="empty">確認待ちの保存はありません</p>"#.to_string()
+    } else {
+        items
+            .iter()
+            .map(|item| moderation_item_html(admin, item))
+            .collect::<Vec<_>>()
+            .join("\n")
+    };
+
+    format!(
+        r#"{head}
+    {nav}
+    <h1>モデレーション</h1>
+    <p>内容フィルターに該当した保存です。要確認のものはすでに公開され、保留中のものは承認されるまで公開されません。</p>
+    {error}
+    {items}
+{footer}"#,
+        head = html_head("モデレーション"),
+        nav = admin_nav(admin),
+        error = error_html,
+        items = items_html,
+        footer = html_footer()
+    )
+}
+
+fn filter_options<T: Copy + PartialEq>(
+    all: &[T],
+    selected: T,
+    value: impl Fn(&T) -> &'static str,
+    label: impl Fn(&T) -> &'static str,
+) -> String {
+    all.iter()
+        .map(|item| {
+            format!(
+                r#"<option value="{value}"{selected}>{label}</option>"#,
+                value = value(item),
+                selected = if *item == selected { " selected" } else { "" },
+                label = label(item),
+            )
+        })
+        .collect()
+}
+
+pub fn render_admin_filters(
+    admin: &AdminIdentity,
+    filters: &[ContentFilter],
+    links: &LinkPolicy,
+    error: Option<&str>,
+) -> String {
+    let error_html = error
+        .map(|e| format!(r#"<p class="error">{}</p>"#, escape_html(e)))
+        .unwrap_or_default();
+
+    let rows: Vec<String> = filters
+        .iter()
+        .map(|f| {
+            format!(
+                r#"<tr>
+            <td>{kind}</td>
+            <td><code>{pattern}</code></td>
+            <td>{action}</td>
+            <td>{created_by}<br><span class="hint">{created_at}</span></td>
+            <td>
+                <form method="post" action="/admin/filters/{id}/delete" onsubmit="return confirm('このフィルターを削除しますか？');">
+                    {csrf}
+                    <button type="submit">削除</button>
+                </form>
+            </td>
+        </tr>"#,
+                kind = f.kind.label(),
+                pattern = escape_html(&f.pattern),
+                action = f.action.label(),
+                created_by = escape_html(&f.created_by),
+                created_at = escape_html(&f.created_at),
+                id = f.id,
+                csrf = csrf_field(admin),
+            )
+        })
+        .collect();
+
+    let table_html = if rows.is_empty() {
+        r#"<p class="empty">登録された内容フィルターはありません</p>"#.to_string()
+    } else {
+        format!(
+            r#"<table class="admin-table">
+        <tr><th>種類</th><th>パターン</th><th>扱い</th><th>登録</th><th></th></tr>
+        {}
+    </table>"#,
+            rows.join("\n")
+        )
+    };
+
+    format!(
+        r#"{head}
+    {nav}
+    <h1>内容フィルター</h1>
+    <p>日記の保存時に本文と照合します。複数に該当した場合は最も重い扱いになります。</p>
+    <p class="hint">リンクは{max_links}件まで。超えた場合の扱い: {link_action}（FILTER_MAX_LINKS・FILTER_LINK_ACTION変数で変更できます）</p>
+    {error}
+    {table}
+    <h2>フィルターを追加</h2>
+    <form method="post" action="/admin/filters">
+        {csrf}
+        <select name="kind">{kind_options}</select>
+        <label for="pattern">パターン:</label>
+        <input type="text" id="pattern" name="pattern" required maxlength="{max_pattern}" autocomplete="off">
+        <select name="action">{action_options}</select>
+        <button type="submit">追加</button>
+    </form>
+{footer}"#,
+        head = html_head("内容フィルター"),
+        nav = admin_nav(admin),
+        max_links = links.max_links,
+        link_action = links.action.label(),
+        error = error_html,
+        table = table_html,
+        csrf = csrf_field(admin),
+        kind_options = filter_options(
+            &FilterKind::ALL,
+            FilterKind::Word,
+            FilterKind::as_str,
+            FilterKind::label
+        ),
+        max_pattern = MAX_FILTER_PATTERN_LENGTH,
+        action_options = filter_options(
+            &FilterAction::ALL,
+            FilterAction::Flag,
+            FilterAction::as_str,
+            FilterAction::label
+        ),
+        footer = html_footer()
+    )
+}
+
+pub fn render_admin_api_keys(
+    admin: &AdminIdentity,
+    keys: &[ApiKeyWithUsage],
+    created_key: Option<&str>,
+    error: Option<&str>,
+) -> String {
+    let error_html = error
+        .map(|e| format!(r#"<p class="error">{}</p>"#, escape_html(e)))
+        .unwrap_or_default();
+
+    // 平文のキーは発行直後のこの画面でしか表示しない
+    let created_html = created_key
+        .map(|k| {
+            format!(
+                r#"<p>新しいAPIキーを発行しました。このページを離れると二度と表示されないので、安全な場所に保管してください。</p>
+    <p><code>{}</code></p>"#,
+                escape_html(k)
+            )
+        })
+        .unwrap_or_default();
+
+    let rows: Vec<String> = keys
+        .iter()
+        .map(|k| {
+            let key = &k.key;
+            let action = match &key.revoked_at {
+                Some(revoked_at) => format!("失効済み（{}）", escape_html(revoked_at)),
+                None => format!(
+                    r#"<form method="post" action="/admin/api-keys/{id}/revoke" onsubmit="return confirm('{label}を失効させますか？');">
+                    {csrf}
+                    <button type="submit">失効</button>
+                </form>"#,
+                    id = key.id,
+                    label = escape_html(&key.label),
+                    csrf = csrf_field(admin),
+                ),
+            };
+            format!(
+                r#"<tr>
+            <td>{label}</td>
+            <td><code>{prefix}…</code></td>
+            <td>{used} / {quota}</td>
+            <td>{created_by}<br><span class="hint">{created_at}</span></td>
+            <td class="hint">{last_used_at}</td>
+            <td>{action}</td>
+        </tr>"#,
+                label = escape_html(&key.label),
+                prefix = escape_html(&key.key_prefix),
+                used = k.used_today,
+                quota = key.daily_quota,
+                created_by = escape_html(&key.created_by),
+                created_at = escape_html(&key.created_at),
+                last_used_at = escape_html(key.last_used_at.as_deref().unwrap_or("未使用")),
+                action = action,
+            )
+        })
+        .collect();
+
+    let table_html = if rows.is_empty() {
+        r#"<p class="empty">発行したAPIキーはありません</p>"#.to_string()
+    } else {
+        format!(
+            r#"<table class="admin-table">
+        <tr><th>名前</th><th>キー</th><th>今日の保存回数</th><th>発行</th><th>最終使用</th><th></th></tr>
+        {}
+    </table>"#,
+            rows.join("\n")
+        )
+    };
+
+    format!(
+        r#"{head}
+    {nav}
+    <h1>APIキー</h1>
+    <p>スクリプトやAIエージェントは <code>Authorization: Bearer &lt;キー&gt;</code> を付けて <code>POST /api/today</code> を呼ぶと、人間確認の代わりにキーで日記を書けます。</p>
+    {error}
+    {created}
+    {table}
+    <h2>APIキーを発行</h2>
+    <form method="post" action="/admin/api-keys">
+        {csrf}
+        <label for="label">名前:</label>
+        <input type="text" id="label" name="label" required maxlength="{max_label}" autocomplete="off">
+        <label for="daily_quota">1日の上限回数:</label>
+        <input type="number" id="daily_quota" name="daily_quota" required min="1" max="{max_quota}" value="{default_quota}">
+        <button type="submit">発行</button>
+    </form>
+{footer}"#,
+        head = html_head("APIキー"),
+        nav = admin_nav(admin),
+        error = error_html,
+        created = created_html,
+        table = table_html,
+        csrf = csrf_field(admin),
+        max_label = MAX_API_KEY_LABEL_LENGTH,
+        max_quota = MAX_API_KEY_DAILY_QUOTA,
+        default_quota = DEFAULT_API_KEY_DAILY_QUOTA,
+        footer = html_footer()
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::ApiKey;
+
+    #[test]
+    fn test_render_admin_login_without_challenge() {
+        let html = render_admin_login(None, None);
+        assert!(!html.contains("cf-turnstile"));
+    }
+
+    #[test]
+    fn test_render_admin_login_with_challenge() {
+        let html = render_admin_login(Some("エラー"), Some("site-key"));
+        assert!(html.contains(r#"class="cf-turnstile" data-sitekey="site-key""#));
+        assert!(html.contains(r#"data-action="admin_login""#));
+        assert!(html.contains("エラー"));
+    }
+
+    fn test_admin(role: AdminRole) -> AdminIdentity {
+        AdminIdentity {
+            admin_id: Some(1),
+            username: "alice".to_string(),
+            role,
+            csrf_token: Some("test-csrf-token".to_string()),
+        }
+    }
+
+    fn test_version() -> DiaryVersion {
+        DiaryVersion {
+            id: 1,
+            entry_date: "2025-01-15".to_string(),
+            content: "古い内容".to_string(),
+            version_number: 2,
+            created_at: "2025-01-15T10:00:00Z".to_string(),
+        }
+    }
+
+    #[test]
+    fn test_render_admin_version_detail_viewer_has_no_actions() {
+        let html = render_admin_version_detail(&test_admin(AdminRole::Viewer), &test_version());
+        assert!(!html.contains("/revert"));
+        assert!(!html.contains("/redact"));
+    }
+
+    #[test]
+    fn test_render_admin_version_detail_moderator_actions() {
+        let html = render_admin_version_detail(&test_admin(AdminRole::Moderator), &test_version());
+        assert!(html.contains(r#"action="/admin/entries/2025-01-15/revert""#));
+        assert!(html.contains(r#"action="/admin/entries/2025-01-15/versions/2/redact""#));
+    }
+
+    #[test]
+    fn test_render_admin_versions_list_edit_requires_superuser() {
+        let moderator = test_admin(AdminRole::Moderator);
+        let html = render_admin_versions_list(&moderator, "2025-01-15", Some("本文"), &[]);
+        assert!(!html.contains("/edit"));
+        let superuser = test_admin(AdminRole::Superuser);
+        let superuser = render_admin_versions_list(&superuser, "2025-01-15", Some("本文"), &[]);
+        assert!(superuser.contains(r#"action="/admin/entries/2025-01-15/edit""#));
+    }
+
+    #[test]
+    fn test_admin_nav_shows_admins_link_for_superuser() {
+        assert!(!admin_nav(&test_admin(AdminRole::Moderator)).contains("/admin/admins"));
+        assert!(admin_nav(&test_admin(AdminRole::Superuser)).contains("/admin/admins"));
+    }
+
+    #[test]
+    fn test_admin_forms_embed_csrf_token() {
+        let admin = test_admin(AdminRole::Superuser);
+        let field = r#"<input type="hidden" name="csrf_token" value="test-csrf-token">"#;
+
+        let detail = render_admin_version_detail(&admin, &test_version());
+        // ナビのログアウト、差し戻し、墨消しの3つのフォーム
+        assert_eq!(detail.matches(field).count(), 3);
+        assert!(detail.contains(r#"action="/admin/logout""#));
+
+        let list = render_admin_versions_list(&admin, "2025-01-15", Some("本文"), &[]);
+        assert_eq!(list.matches(field).count(), 2);
+    }
+
+    #[test]
+    fn test_render_admin_passkeys_escapes_label() {
+        let credentials = vec![AdminCredential {
+            id: 3,
+            admin_id: 1,
+            credential_id: "abc".to_string(),
+            public_key: "pk".to_string(),
+            sign_count: 0,
+            label: "<b>PC</b>".to_string(),
+            created_at: "2025-01-15T10:00:00Z".to_string(),
+            last_used_at: None,
+        }];
+        let html = render_admin_passkeys(&test_admin(AdminRole::Viewer), &credentials);
+        assert!(html.contains("&lt;b&gt;PC&lt;/b&gt;"));
+        assert!(html.contains("/admin/passkeys/3/delete"));
+        assert!(html.contains("未使用"));
+    }
+
+    #[test]
+    fn test_verifier_script() {
+        let today = "2025-01-15";
+        let turnstile = verifier_script(
+            &VerifierWidget::Turnstile {
+                site_key: "site-key".to_string(),
+            },
+            today,
+        );
+        assert!(turnstile.contains("challenges.cloudflare.com/turnstile"));
+        assert!(turnstile.contains("sitekey: 'site-key'"));
+        assert!(turnstile.contains("action: 'diary_save'"));
+        assert!(turnstile.contains("cdata: '2025-01-15'"));
+
+        let hcaptcha = verifier_script(
+            &VerifierWidget::HCaptcha {
+                site_key: "h-key".to_string(),
+            },
+            today,
+        );
+        assert!(hcaptcha.contains("js.hcaptcha.com"));
+        assert!(hcaptcha.contains("sitekey: 'h-key'"));
+
+        let pow = verifier_script(&VerifierWidget::ProofOfWork, today);
+        assert!(pow.contains("/api/pow/challenge"));
+        assert!(pow.contains("function solvePow"));
+        assert!(!pow.contains("challenges.cloudflare.com"));
+
+        let noop = verifier_script(&VerifierWidget::NoOp, today);
+        assert!(noop.contains("Promise.resolve('')"));
+        assert!(!noop.contains("<script src="));
+    }
+
+    #[test]
+    fn test_render_admin_audit() {
+        let entries = vec![AuditLogEntry {
+            id: 1,
+            actor: "<alice>".to_string(),
+            actor_id: Some(1),
+            action: AuditAction::Redact,
+            ip_hash: "0123456789abcdef0123".to_string(),
+            target_date: Some("2025-01-15".to_string()),
+            target_version: Some(2),
+            target: None,
+            before_hash: Some("aaaaaaaaaaaaaaaaaaaa".to_string()),
+            after_hash: Some("bbbbbbbbbbbbbbbbbbbb".to_string()),
+            created_at: "2025-01-15T10:00:00Z".to_string(),
+        }];
+        let filter = AuditLogFilter {
+            actor: Some("alice".to_string()),
+            action: Some(AuditAction::Redact),
+            page: 2,
+        };
+        let html = render_admin_audit(&test_admin(AdminRole::Superuser), &entries, &filter, true);
+        assert!(html.contains("&lt;alice&gt;"));
+        assert!(html.contains("/admin/entries/2025-01-15/versions/2"));
+        assert!(html.contains("aaaaaaaaaaaa → bbbbbbbbbbbb"));
+        assert!(html.contains(r#"<option value="redact" selected>"#));
+        assert!(html.contains("/admin/audit?actor=alice&amp;action=redact&amp;page=1"));
+        assert!(html.contains("/admin/audit?actor=alice&amp;action=redact&amp;page=3"));
+
+        let empty = render_admin_audit(
+            &test_admin(AdminRole::Superuser),
+            &[],
+            &AuditLogFilter::default(),
+            false,
+        );
+        assert!(empty.contains("該当する記録はありません"));
+        assert!(!empty.contains("前のページ"));
+    }
+
+    #[test]
+    fn test_render_admin_moderation() {
+        let item = |id: i64, action: FilterAction| ModerationItem {
+            id,
+            entry_date: "2025-01-15".to_string(),
+            content: "<spam>".to_string(),
+            previous_content: None,
+            action,
+            reasons: "NGワード「spam」（#1）\nリンクが5件（上限3件）".to_string(),
+            created_at: "2025-01-15T10:00:00Z".to_string(),
+            resolution: None,
+            resolved_by: None,
+            resolved_at: None,
+        };
+        let admin = test_admin(AdminRole::Moderator);
+        let html = render_admin_moderation(
+            &admin,
+            &[item(1, FilterAction::Flag), item(2, FilterAction::Hold)],
+            None,
+        );
+        assert!(html.contains("&lt;spam&gt;"));
+        assert!(html.contains("<li>リンクが5件（上限3件）</li>"));
+        assert_eq!(html.matches(r#"value="approved""#).count(), 2);
+        assert_eq!(html.matches(r#"value="redacted""#).count(), 2);
+        // 差し戻しは公開済み、破棄は保留中の項目にだけ出す
+        assert_eq!(html.matches(r#"value="reverted""#).count(), 1);
+        assert_eq!(html.matches(r#"value="discarded""#).count(), 1);
+        assert!(html.contains("保留中（未公開）"));
+
+        let empty = render_admin_moderation(&admin, &[], Some("処理済みです"));
+        assert!(empty.contains("確認待ちの保存はありません"));
+        assert!(empty.contains("処理済みです"));
+    }
+
+    #[test]
+    fn test_render_admin_filters() {
+        let filters = vec![ContentFilter {
+            id: 3,
+            kind: FilterKind::Regex,
+            pattern: r"<b>\d+".to_string(),
+            action: FilterAction::Reject,
+            created_by: "alice".to_string(),
+            created_at: "2025-01-15T10:00:00Z".to_string(),
+        }];
+        let links = LinkPolicy {
+            max_links: 2,
+            action: FilterAction::Flag,
+        };
+        let html = render_admin_filters(&test_admin(AdminRole::Moderator), &filters, &links, None);
+        assert!(html.contains(r"<code>&lt;b&gt;\d+</code>"));
+        assert!(html.contains("/admin/filters/3/delete"));
+        assert!(html.contains("リンクは2件まで。超えた場合の扱い: 保存して要確認"));
+        assert!(html.contains(r#"<option value="flag" selected>"#));
+    }
+
+    #[test]
+    fn test_render_admin_api_keys() {
+        let key = |id: i64, revoked_at: Option<&str>| ApiKey {
+            id,
+            label: format!("<bot{}>", id),
+            key_prefix: "dk_0123abcd".to_string(),
+            daily_quota: 50,
+            created_by: "alice".to_string(),
+            created_at: "2025-01-15T10:00:00Z".to_string(),
+            last_used_at: None,
+            revoked_at: revoked_at.map(|r| r.to_string()),
+        };
+        let keys = vec![
+            ApiKeyWithUsage {
+                key: key(2, None),
+                used_today: 3,
+            },
+            ApiKeyWithUsage {
+                key: key(1, Some("2025-01-16T00:00:00Z")),
+                used_today: 0,
+            },
+        ];
+        let admin = test_admin(AdminRole::Superuser);
+        let html = render_admin_api_keys(&admin, &keys, Some("dk_secret"), None);
+        assert!(html.contains("&lt;bot2&gt;"));
+        assert!(html.contains("3 / 50"));
+        assert!(html.contains("/admin/api-keys/2/revoke"));
+        assert!(!html.contains("/admin/api-keys/1/revoke"));
+        assert!(html.contains("失効済み（2025-01-16T00:00:00Z）"));
+        assert!(html.contains("<code>dk_secret</code>"));
+
+        let empty = render_admin_api_keys(&admin, &[], None, Some("エラー"));
+        assert!(empty.contains("発行したAPIキーはありません"));
+        assert!(empty.contains(r#"<p class="error">エラー</p>"#));
+        assert!(!empty.contains("dk_secret"));
+    }
+
+    #[test]
+    fn test_render_admin_admins_selects_current_role() {
+        let admins = vec![Admin {
+            id: 7,
+            username: "<bob>".to_string(),
+            password_hash: "hash".to_string(),
+            role: AdminRole::Moderator,
+            totp_secret: None,
+            totp_last_step: None,
+            email: None,
+            created_at: "2025-01-15T10:00:00Z".to_string(),
+            updated_at: "2025-01-15T10:00:00Z".to_string(),
+        }];
+        let html = render_admin_admins(&test_admin(AdminRole::Superuser), &admins, None);
+        assert!(html.contains(r#"<option value="moderator" selected>"#));
+        assert!(html.contains("&lt;bob&gt;"));
+        assert!(!html.contains("hash"));
+    }
+
+    #[test]
+    fn test_admin_nav_hides_totp_link_for_bootstrap() {
+        assert!(admin_nav(&test_admin(AdminRole::Viewer)).contains("/admin/totp"));
+        assert!(!admin_nav(&AdminIdentity::bootstrap()).contains("/admin/totp"));
+    }
+
+    #[test]
+    fn test_render_admin_login_totp_escapes_pending_token() {
+        let html = render_admin_login_totp("\"><script>", None);
+        assert!(html.contains(r#"action="/admin/login/totp""#));
+        assert!(!html.contains("<script>"));
+    }
+
+    #[test]
+    fn test_toast_css_exists() {
+        let head = html_head("テスト");
+        assert!(head.contains(".toast {"));
+        assert!(head.contains("toast-slide-in"));
+        assert!(head.contains("toast-fade-out"));
+    }
+}
@@ -0,0 +1,116 @@
+use chrono::{DateTime, NaiveDate};
+use chrono_tz::Asia::Tokyo;
+
+/// 現在時刻の取得元（UNIX秒）
+///
+/// 実行環境ごとに実装し、テストでは任意の時刻を返すものに差し替える。
+pub trait Clock {
+    fn now(&self) -> i64;
+}
+
+/// 常に同じ時刻を返す時計
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FixedClock(pub i64);
+
+impl Clock for FixedClock {
+    fn now(&self) -> i64 {
+        self.0
+    }
+}
+
+/// UNIX秒の時刻をJSTの日付（YYYY-MM-DD）にする
+pub fn date_jst(unix: i64) -> String {
+    DateTime::from_timestamp(unix, 0)
+        .unwrap_or(DateTime::UNIX_EPOCH)
+        .with_timezone(&Tokyo)
+        .format("%Y-%m-%d")
+        .to_string()
+}
+
+/// 時計が指す今日の日付をJSTで返す
+pub fn today_jst(clock: &impl Clock) -> String {
+    date_jst(clock.now())
+}
+
+/// ISO8601形式の日時をUNIX秒に変換する
+pub fn parse_iso8601_unix(value: &str) -> Option<i64> {
+    DateTime::parse_from_rfc3339(value).ok().map(|t| t.timestamp())
+}
+
+/// 日付文字列をパースする
+pub fn parse_date(date: &str) -> Option<NaiveDate> {
+    NaiveDate::parse_from_str(date, "%Y-%m-%d").ok()
+}
+
+/// 日付が有効な形式かどうかを検証する
+pub fn is_valid_date(date: &str) -> bool {
+    parse_date(date).is_some()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_date_valid() {
+        let result = parse_date("2025-01-15");
+        assert!(result.is_some());
+        assert_eq!(result.unwrap().to_string(), "2025-01-15");
+    }
+
+    #[test]
+    fn test_parse_date_valid_leap_year() {
+        // 閏年の2月29日
+        assert!(parse_date("2024-02-29").is_some());
+    }
+
+    #[test]
+    fn test_parse_date_invalid_format() {
+        assert!(parse_date("2025/01/15").is_none());
+        assert!(parse_date("15-01-2025").is_none());
+        assert!(parse_date("not-a-date").is_none());
+        assert!(parse_date("").is_none());
+    }
+
+    #[test]
+    fn test_parse_date_invalid_values() {
+        // 無効な月
+        assert!(parse_date("2025-13-01").is_none());
+        // 無効な日
+        assert!(parse_date("2025-02-30").is_none());
+        // ゼロの月
+        assert!(parse_date("2025-00-15").is_none());
+        // 閏年じゃない年の2月29日
+        assert!(parse_date("2025-02-29").is_none());
+    }
+
+    #[test]
+    fn test_is_valid_date_valid() {
+        assert!(is_valid_date("2025-01-15"));
+        assert!(is_valid_date("2024-02-29")); // 閏年
+        assert!(is_valid_date("2000-12-31"));
+    }
+
+    #[test]
+    fn test_is_valid_date_invalid() {
+        assert!(!is_valid_date("invalid"));
+        assert!(!is_valid_date("2025-02-29")); // 閏年じゃない
+        assert!(!is_valid_date(""));
+        assert!(!is_valid_date("2025-13-01"));
+    }
+
+    #[test]
+    fn test_parse_iso8601_unix() {
+        assert_eq!(parse_iso8601_unix("1970-01-01T00:01:00+00:00"), Some(60));
+        assert_eq!(parse_iso8601_unix("2025-01-15T09:00:00+09:00"), Some(1_736_899_200));
+        assert_eq!(parse_iso8601_unix("2025-01-15"), None);
+    }
+
+    #[test]
+    fn test_today_jst_uses_clock() {
+        // 2025-01-15 14:59:59 UTC は JST で 23:59:59
+        assert_eq!(today_jst(&FixedClock(1_736_953_199)), "2025-01-15");
+        assert_eq!(today_jst(&FixedClock(1_736_953_200)), "2025-01-16");
+        assert_eq!(date_jst(0), "1970-01-01");
+    }
+}
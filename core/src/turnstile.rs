@@ -0,0 +1,332 @@
+use serde::{Deserialize, Serialize};
+
+pub use crate::verification::{DIARY_ACTION, LOGIN_ACTION};
+use crate::verification::VerificationFailure;
+
+/// siteverifyのURL
+pub const SITEVERIFY_URL: &str = "https://challenges.cloudflare.com/turnstile/v0/siteverify";
+
+/// siteverifyに送るリクエスト
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct TurnstileRequest {
+    pub secret: String,
+    pub response: String,
+    pub remoteip: Option<String>,
+    /// 再試行しても二重に検証されないよう、リクエストごとに振るUUID
+    pub idempotency_key: String,
+}
+
+/// siteverifyの応答
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct TurnstileResponse {
+    pub success: bool,
+    #[serde(rename = "error-codes", default)]
+    pub error_codes: Vec<String>,
+    #[serde(default)]
+    pub hostname: Option<String>,
+    #[serde(default)]
+    pub action: Option<String>,
+    #[serde(default)]
+    pub cdata: Option<String>,
+}
+
+/// トークンの発行元として照合する値
+#[derive(Debug, Clone, PartialEq)]
+pub struct TurnstileExpectation<'a> {
+    /// ウィジェットを表示したホスト名（Noneなら照合しない）
+    pub hostname: Option<String>,
+    pub action: &'a str,
+    pub cdata: Option<&'a str>,
+}
+
+/// Turnstileの検証結果
+#[derive(Debug, Clone, PartialEq)]
+pub enum TurnstileOutcome {
+    Success,
+    /// 期限切れ、または使用済みのトークン（取り直せば通る）
+    TimeoutOrDuplicate,
+    /// トークンが空、または不正
+    InvalidToken,
+    /// シークレットキーが未設定・不正（サーバーの設定ミス）
+    InvalidSecret,
+    /// 別のホスト名で発行されたトークン
+    HostnameMismatch,
+    /// 別のactionで発行されたトークン
+    ActionMismatch,
+    /// 別のcdataで発行されたトークン
+    CdataMismatch,
+    /// 上記以外の理由で拒否された（error-codesをそのまま持つ）
+    Rejected(Vec<String>),
+    /// siteverifyに問い合わせできなかった
+    Unavailable,
+}
+
+impl TurnstileOutcome {
+    pub fn is_success(&self) -> bool {
+        *self == Self::Success
+    }
+
+    /// APIのエラーレスポンスに使うコード
+    pub fn error_code(&self) -> &'static str {
+        match self {
+            Self::Success => "OK",
+            Self::TimeoutOrDuplicate => "TURNSTILE_EXPIRED",
+            Self::InvalidToken => "TURNSTILE_INVALID_TOKEN",
+            Self::InvalidSecret => "TURNSTILE_MISCONFIGURED",
+            Self::HostnameMismatch => "TURNSTILE_HOSTNAME_MISMATCH",
+            Self::ActionMismatch => "TURNSTILE_ACTION_MISMATCH",
+            Self::CdataMismatch => "TURNSTILE_CDATA_MISMATCH",
+            Self::Rejected(_) => "TURNSTILE_FAILED",
+            Self::Unavailable => "TURNSTILE_UNAVAILABLE",
+        }
+    }
+
+    pub fn message(&self) -> &'static str {
+        match self {
+            Self::Success => "OK",
+            Self::TimeoutOrDuplicate => "Turnstile token expired or already used",
+            Self::InvalidToken => "Invalid Turnstile token",
+            Self::InvalidSecret | Self::Unavailable => "Turnstile verification unavailable",
+            Self::HostnameMismatch | Self::ActionMismatch | Self::CdataMismatch => {
+                "Turnstile token was issued for another context"
+            }
+            Self::Rejected(_) => "Turnstile verification failed",
+        }
+    }
+
+    /// クライアント側の問題なら400、サーバー側の問題なら5xx
+    pub fn status(&self) -> u16 {
+        match self {
+            Self::Success => 200,
+            Self::InvalidSecret => 500,
+            Self::Unavailable => 503,
+            _ => 400,
+        }
+    }
+    /// 日記の保存前の確認としての結果
+    pub fn verification(&self) -> Result<(), VerificationFailure> {
+        if self.is_success() {
+            return Ok(());
+        }
+        Err(VerificationFailure {
+            code: self.error_code(),
+            message: self.message(),
+            status: self.status(),
+        })
+    }
+}
+
+/// siteverifyの応答を検証結果に変換（純粋関数）
+pub fn outcome_from_response(
+    resp: &TurnstileResponse,
+    expected: &TurnstileExpectation<'_>,
+) -> TurnstileOutcome {
+    if !resp.success {
+        let has = |code: &str| resp.error_codes.iter().any(|c| c == code);
+        return if has("timeout-or-duplicate") {
+            TurnstileOutcome::TimeoutOrDuplicate
+        } else if has("missing-input-secret") || has("invalid-input-secret") {
+            TurnstileOutcome::InvalidSecret
+        } else if has("missing-input-response") || has("invalid-input-response") {
+            TurnstileOutcome::InvalidToken
+        } else if has("internal-error") {
+            TurnstileOutcome::Unavailable
+        } else {
+            TurnstileOutcome::Rejected(resp.error_codes.clone())
+        };
+    }
+
+    if let Some(hostname) = &expected.hostname {
+        let matches = resp
+            .hostname
+            .as_deref()
+            .is_some_and(|h| h.eq_ignore_ascii_case(hostname));
+        if !matches {
+            return TurnstileOutcome::HostnameMismatch;
+        }
+    }
+    if resp.action.as_deref() != Some(expected.action) {
+        return TurnstileOutcome::ActionMismatch;
+    }
+    if let Some(cdata) = expected.cdata {
+        if resp.cdata.as_deref() != Some(cdata) {
+            return TurnstileOutcome::CdataMismatch;
+        }
+    }
+    TurnstileOutcome::Success
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn expected() -> TurnstileExpectation<'static> {
+        TurnstileExpectation {
+            hostname: Some("darekagakaku.day".to_string()),
+            action: DIARY_ACTION,
+            cdata: Some("2025-01-15"),
+        }
+    }
+
+    fn success_response() -> TurnstileResponse {
+        TurnstileResponse {
+            success: true,
+            error_codes: vec![],
+            hostname: Some("darekagakaku.day".to_string()),
+            action: Some(DIARY_ACTION.to_string()),
+            cdata: Some("2025-01-15".to_string()),
+        }
+    }
+
+    fn failure_response(codes: &[&str]) -> TurnstileResponse {
+        TurnstileResponse {
+            success: false,
+            error_codes: codes.iter().map(|c| c.to_string()).collect(),
+            hostname: None,
+            action: None,
+            cdata: None,
+        }
+    }
+
+    #[test]
+    fn test_turnstile_request_serialization() {
+        let req = TurnstileRequest {
+            secret: "test-secret".to_string(),
+            response: "test-token".to_string(),
+            remoteip: Some("192.168.1.1".to_string()),
+            idempotency_key: "key".to_string(),
+        };
+        let json = serde_json::to_string(&req).unwrap();
+        assert!(json.contains("\"secret\":\"test-secret\""));
+        assert!(json.contains("\"response\":\"test-token\""));
+        assert!(json.contains("\"remoteip\":\"192.168.1.1\""));
+        assert!(json.contains("\"idempotency_key\":\"key\""));
+    }
+
+    #[test]
+    fn test_turnstile_request_serialization_without_ip() {
+        let req = TurnstileRequest {
+            secret: "test-secret".to_string(),
+            response: "test-token".to_string(),
+            remoteip: None,
+            idempotency_key: "key".to_string(),
+        };
+        let json = serde_json::to_string(&req).unwrap();
+        assert!(json.contains("\"remoteip\":null"));
+    }
+
+    #[test]
+    fn test_turnstile_response_deserialization_success() {
+        let json = r#"{"success": true}"#;
+        let resp: TurnstileResponse = serde_json::from_str(json).unwrap();
+        assert!(resp.success);
+        assert!(resp.error_codes.is_empty());
+    }
+
+    #[test]
+    fn test_turnstile_response_deserialization_failure() {
+        let json = r#"{"success": false, "error-codes": ["timeout-or-duplicate"]}"#;
+        let resp: TurnstileResponse = serde_json::from_str(json).unwrap();
+        assert!(!resp.success);
+        assert_eq!(resp.error_codes, vec!["timeout-or-duplicate"]);
+    }
+
+    #[test]
+    fn test_turnstile_response_deserialization_with_extra_fields() {
+        // Turnstile APIは追加フィールドを返すことがある
+        let json = r#"{"success": true, "challenge_ts": "2025-01-15T00:00:00Z", "hostname": "example.com", "action": "diary_save", "cdata": "x", "metadata": {}}"#;
+        let resp: TurnstileResponse = serde_json::from_str(json).unwrap();
+        assert!(resp.success);
+        assert_eq!(resp.hostname.as_deref(), Some("example.com"));
+        assert_eq!(resp.action.as_deref(), Some("diary_save"));
+        assert_eq!(resp.cdata.as_deref(), Some("x"));
+    }
+
+    #[test]
+    fn test_outcome_success() {
+        assert_eq!(
+            outcome_from_response(&success_response(), &expected()),
+            TurnstileOutcome::Success
+        );
+
+        // ホスト名は大文字小文字を区別しない
+        let resp = TurnstileResponse {
+            hostname: Some("DarekaGakaku.day".to_string()),
+            ..success_response()
+        };
+        assert!(outcome_from_response(&resp, &expected()).is_success());
+
+        // ホスト名を照合しない設定
+        let resp = TurnstileResponse {
+            hostname: None,
+            ..success_response()
+        };
+        let no_host = TurnstileExpectation {
+            hostname: None,
+            ..expected()
+        };
+        assert!(outcome_from_response(&resp, &no_host).is_success());
+    }
+
+    #[test]
+    fn test_outcome_error_codes() {
+        let cases = [
+            (vec!["timeout-or-duplicate"], TurnstileOutcome::TimeoutOrDuplicate),
+            (vec!["invalid-input-secret"], TurnstileOutcome::InvalidSecret),
+            (vec!["missing-input-secret"], TurnstileOutcome::InvalidSecret),
+            (vec!["invalid-input-response"], TurnstileOutcome::InvalidToken),
+            (vec!["internal-error"], TurnstileOutcome::Unavailable),
+            (
+                vec!["bad-request"],
+                TurnstileOutcome::Rejected(vec!["bad-request".to_string()]),
+            ),
+        ];
+        for (codes, outcome) in cases {
+            assert_eq!(
+                outcome_from_response(&failure_response(&codes), &expected()),
+                outcome
+            );
+        }
+    }
+
+    #[test]
+    fn test_outcome_context_mismatch() {
+        let resp = TurnstileResponse {
+            hostname: Some("evil.example".to_string()),
+            ..success_response()
+        };
+        assert_eq!(
+            outcome_from_response(&resp, &expected()),
+            TurnstileOutcome::HostnameMismatch
+        );
+
+        let resp = TurnstileResponse {
+            action: Some(LOGIN_ACTION.to_string()),
+            ..success_response()
+        };
+        assert_eq!(
+            outcome_from_response(&resp, &expected()),
+            TurnstileOutcome::ActionMismatch
+        );
+
+        let resp = TurnstileResponse {
+            cdata: Some("2025-01-14".to_string()),
+            ..success_response()
+        };
+        assert_eq!(
+            outcome_from_response(&resp, &expected()),
+            TurnstileOutcome::CdataMismatch
+        );
+    }
+
+    #[test]
+    fn test_outcome_status_and_code() {
+        assert_eq!(TurnstileOutcome::TimeoutOrDuplicate.status(), 400);
+        assert_eq!(TurnstileOutcome::InvalidSecret.status(), 500);
+        assert_eq!(TurnstileOutcome::Unavailable.status(), 503);
+        assert_eq!(
+            TurnstileOutcome::TimeoutOrDuplicate.error_code(),
+            "TURNSTILE_EXPIRED"
+        );
+    }
+}
@@ -0,0 +1,34 @@
+pub use crate::time::is_valid_date;
+
+/// 日記の本文の最大文字数
+pub const MAX_CONTENT_LENGTH: usize = 10000;
+
+/// 保存する形に本文を整える（純粋関数）
+///
+/// CRLF を LF に正規化する（Windows環境対応）。
+pub fn normalize_content(content: &str) -> String {
+    content.replace('\r', "")
+}
+
+/// 本文が最大文字数を超えているか（純粋関数）
+pub fn is_content_too_long(content: &str) -> bool {
+    content.chars().count() > MAX_CONTENT_LENGTH
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_normalize_content() {
+        assert_eq!(normalize_content("一行目\r\n二行目\r\n"), "一行目\n二行目\n");
+        assert_eq!(normalize_content("そのまま"), "そのまま");
+    }
+
+    #[test]
+    fn test_is_content_too_long() {
+        // 文字数はバイト数ではなく文字で数える
+        assert!(!is_content_too_long(&"あ".repeat(MAX_CONTENT_LENGTH)));
+        assert!(is_content_too_long(&"a".repeat(MAX_CONTENT_LENGTH + 1)));
+    }
+}
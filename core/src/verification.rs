@@ -0,0 +1,97 @@
+use crate::config::FromVars;
+
+/// 日記の保存でTurnstileのウィジェットに指定するaction
+pub const DIARY_ACTION: &str = "diary_save";
+/// 管理者ログインでTurnstileのウィジェットに指定するaction
+pub const LOGIN_ACTION: &str = "admin_login";
+
+/// トップページに埋め込む確認ウィジェット
+#[derive(Debug, Clone, PartialEq)]
+pub enum VerifierWidget {
+    Turnstile { site_key: String },
+    HCaptcha { site_key: String },
+    ProofOfWork,
+    NoOp,
+}
+
+/// 人間確認（またはそれに代わる確認）に失敗した理由
+#[derive(Debug, Clone, PartialEq)]
+pub struct VerificationFailure {
+    /// APIのエラーレスポンスに使うコード
+    pub code: &'static str,
+    pub message: &'static str,
+    pub status: u16,
+}
+
+/// 日記の保存前に行う確認の方式（VERIFIER変数で選ぶ）
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum VerifierKind {
+    Turnstile,
+    HCaptcha,
+    ProofOfWork,
+    /// 確認しない（ローカル開発用）
+    NoOp,
+}
+
+impl VerifierKind {
+    pub fn parse(s: &str) -> Option<Self> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "turnstile" => Some(Self::Turnstile),
+            "hcaptcha" => Some(Self::HCaptcha),
+            "pow" => Some(Self::ProofOfWork),
+            "none" => Some(Self::NoOp),
+            _ => None,
+        }
+    }
+}
+
+impl FromVars for VerifierKind {
+    /// 未設定ならTurnstile、解釈できない値も安全側に倒してTurnstileにする
+    fn from_vars(get: impl Fn(&str) -> Option<String>) -> Self {
+        match get("VERIFIER") {
+            None => Self::Turnstile,
+            Some(value) => Self::parse(&value).unwrap_or_else(|| {
+                log::warn!("Unknown VERIFIER {:?}, falling back to turnstile", value);
+                Self::Turnstile
+            }),
+        }
+    }
+}
+
+/// 日記の保存前の確認
+pub trait Verifier {
+    /// 確認に使う外部サービスや保存先の障害
+    type Error;
+
+    /// クライアントから送られたトークンを検証する
+    ///
+    /// 確認に失敗した場合は内側のErrを返す。外側のErrは保存先などの障害。
+    async fn verify(
+        &self,
+        token: &str,
+        ip: Option<&str>,
+    ) -> Result<Result<(), VerificationFailure>, Self::Error>;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_verifier_kind_parse() {
+        assert_eq!(VerifierKind::parse("turnstile"), Some(VerifierKind::Turnstile));
+        assert_eq!(VerifierKind::parse(" hCaptcha "), Some(VerifierKind::HCaptcha));
+        assert_eq!(VerifierKind::parse("pow"), Some(VerifierKind::ProofOfWork));
+        assert_eq!(VerifierKind::parse("none"), Some(VerifierKind::NoOp));
+        assert_eq!(VerifierKind::parse("recaptcha"), None);
+        assert_eq!(VerifierKind::parse(""), None);
+    }
+
+    #[test]
+    fn test_verifier_kind_from_vars() {
+        let kind = |value: Option<&str>| VerifierKind::from_vars(|_| value.map(String::from));
+        assert_eq!(kind(None), VerifierKind::Turnstile);
+        assert_eq!(kind(Some("none")), VerifierKind::NoOp);
+        assert_eq!(kind(Some("recaptcha")), VerifierKind::Turnstile);
+    }
+}
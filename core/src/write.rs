@@ -0,0 +1,581 @@
+//! 今日の日記の保存（POST /api/today）
+//!
+//! APIキーの認証、レート制限、人間確認、スパム判定、上書きの歯止め、内容フィルターを
+//! 保存先のトレイト越しに行い、WorkersとネイティブのサーバーのどちらからもPOSTを処理する。
+
+use std::fmt::Debug;
+
+use crate::api::{self, PostTodayRequest};
+use crate::api_keys::{bearer_key, hash_key};
+use crate::config::FromVars;
+use crate::content_filter::{compile, evaluate, LinkPolicy};
+use crate::crypto::content_hash;
+use crate::entry_stats;
+use crate::models::FilterAction;
+use crate::overwrite::{self, OverwriteCheck, OverwritePolicy};
+use crate::rate_limit::{RateLimitPolicy, RateLimitStore, RateLimiter};
+use crate::reply::Reply;
+use crate::spam::{SpamCheck, SpamPolicy, SpamScore};
+use crate::store::{ApiKeyStore, DiaryStore, ModerationStore};
+use crate::time::{parse_iso8601_unix, today_jst, Clock};
+use crate::validation::{is_content_too_long, normalize_content};
+use crate::verification::Verifier;
+
+/// 保存に関わる設定
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct WritePolicy {
+    pub rate_limit: RateLimitPolicy,
+    pub overwrite: OverwritePolicy,
+    pub spam: SpamPolicy,
+    pub links: LinkPolicy,
+    /// フォームトークンの署名の鍵（未設定ならトークンは確かめない）
+    pub form_token_key: Option<String>,
+    /// 書き手のハッシュの鍵（未設定なら書き手を記録しない）
+    pub writer_key: Option<String>,
+}
+
+impl FromVars for WritePolicy {
+    /// 鍵は秘密値なので、呼び出し側で `form_token_key` と `writer_key` に設定する
+    fn from_vars(get: impl Fn(&str) -> Option<String>) -> Self {
+        Self {
+            rate_limit: RateLimitPolicy::from_vars(&get),
+            overwrite: OverwritePolicy::from_vars(&get),
+            spam: SpamPolicy::from_vars(&get),
+            links: LinkPolicy::from_vars(&get),
+            form_token_key: None,
+            writer_key: None,
+        }
+    }
+}
+
+/// 保存のリクエストのうち、実行環境から取り出して渡すもの
+pub struct SaveRequest<'a> {
+    /// PostTodayRequestのJSON
+    pub body: &'a [u8],
+    /// Authorizationヘッダー（APIキーの認証に使う）
+    pub authorization: Option<&'a str>,
+    /// レート制限とスパム判定に使うクライアントのキー（APIキーを使う場合は使わない）
+    pub client: &'a str,
+    /// 人間確認に渡す接続元のIPアドレス
+    pub ip: Option<&'a str>,
+}
+
+/// 保存先などの障害（ログに残して500にする）
+struct Failure(String);
+
+fn failure(e: impl Debug) -> Failure {
+    Failure(format!("{:?}", e))
+}
+
+impl From<serde_json::Error> for Failure {
+    fn from(e: serde_json::Error) -> Self {
+        failure(e)
+    }
+}
+
+/// 今日の日記を保存する
+pub struct DiaryWriter<'a, S, L, V, C> {
+    pub store: &'a S,
+    /// レート制限とスパム判定の状態の保存先
+    pub limits: &'a L,
+    pub verifier: &'a V,
+    pub clock: C,
+    pub policy: &'a WritePolicy,
+}
+
+impl<S, L, V, C> DiaryWriter<'_, S, L, V, C>
+where
+    S: DiaryStore + ModerationStore + ApiKeyStore,
+    L: RateLimitStore,
+    L::Error: Debug,
+    V: Verifier,
+    V::Error: Debug,
+    C: Clock + Copy,
+{
+    /// POST /api/today - 今日の日記を作成/更新
+    pub async fn save_today(&self, request: &SaveRequest<'_>) -> serde_json::Result<Reply> {
+        match self.run(request).await {
+            Ok(reply) => Ok(reply),
+            Err(Failure(e)) => {
+                log::error!("Failed to save entry: {}", e);
+                api::internal_error_reply()
+            }
+        }
+    }
+
+    async fn run(&self, request: &SaveRequest<'_>) -> Result<Reply, Failure> {
+        let store = self.store;
+        let policy = self.policy;
+
+        // APIキーを使うリクエストは人間確認の代わりにキーで認証する
+        let api_key = match bearer_key(request.authorization) {
+            Some(key) => match store
+                .get_api_key_by_hash(&hash_key(key))
+                .await
+                .map_err(failure)?
+            {
+                Some(api_key) if !api_key.is_revoked() => Some(api_key),
+                _ => return Ok(api::invalid_api_key_reply()?),
+            },
+            None => None,
+        };
+        let client = match &api_key {
+            Some(api_key) => format!("apikey:{}", api_key.id),
+            None => request.client.to_string(),
+        };
+        let limiter = RateLimiter::new(self.limits, self.clock, policy.rate_limit.clone());
+
+        let decision = limiter.check(&client).await.map_err(failure)?;
+        if let Some(reply) = api::rate_limited_reply(&decision, limiter.policy())? {
+            return Ok(reply);
+        }
+
+        let body: PostTodayRequest = match serde_json::from_slice(request.body) {
+            Ok(body) => body,
+            Err(_) => return Ok(api::invalid_json_reply()?),
+        };
+
+        if api_key.is_none() {
+            let token = body.verification_token.as_deref().unwrap_or_default();
+            let verification = self.verifier.verify(token, request.ip).await;
+            if let Err(failure) = verification.map_err(failure)? {
+                return Ok(api::verification_failed_reply(&failure)?);
+            }
+        }
+
+        let content = normalize_content(&body.content);
+        if is_content_too_long(&content) {
+            return Ok(api::content_too_long_reply()?);
+        }
+
+        let now = self.clock.now();
+        let today = today_jst(&self.clock);
+        let existing = store.get_entry(&today).await.map_err(failure)?;
+        let last_saved_at = existing
+            .as_ref()
+            .and_then(|e| parse_iso8601_unix(&e.updated_at));
+        let current = existing.map(|e| e.content);
+
+        // APIキーの書き込みはクォータで抑えるため、疑わしさは人のフォームからの保存だけで測る
+        let spam = match api_key {
+            Some(_) => SpamScore::default(),
+            None => {
+                let check = SpamCheck {
+                    content: &content,
+                    unchanged: current.as_deref() == Some(content.as_str()),
+                    client: &client,
+                    honeypot: body.website.as_deref(),
+                    form_token: body.form_token.as_deref(),
+                    now,
+                };
+                let key = policy.form_token_key.as_deref();
+                check.score(self.limits, key, &policy.spam).await
+            }
+        };
+        if !spam.signals.is_empty() {
+            log::warn!(
+                "Suspicious save: score={} signals={}",
+                spam.score(),
+                spam.describe()
+            );
+        }
+
+        // 他の人の文章を大きく消す保存は、確認と前回の保存からの間隔を求める
+        let mut cost = spam.rate_limit_cost();
+        let check = overwrite::check(
+            current.as_deref(),
+            &content,
+            body.confirm_overwrite,
+            last_saved_at,
+            now,
+            &policy.overwrite,
+        );
+        if let Some(reply) = api::overwrite_reply(&check)? {
+            return Ok(reply);
+        }
+        if let OverwriteCheck::Confirmed { deleted_ratio } = check {
+            log::info!("Confirmed overwrite: deleted_ratio={:.2}", deleted_ratio);
+            cost += policy.overwrite.extra_cost;
+        }
+
+        // 検証を通ったリクエストだけが消費し、疑わしいものほど多く消費する
+        let decision = limiter.acquire(&client, cost).await.map_err(failure)?;
+        if let Some(reply) = api::rate_limited_reply(&decision, limiter.policy())? {
+            return Ok(reply);
+        }
+        if spam.should_reject(&policy.spam) {
+            return Ok(api::spam_rejected_reply()?);
+        }
+
+        let filters = store.list_content_filters().await.map_err(failure)?;
+        let verdict = evaluate(&content, &compile(&filters), &policy.links);
+        match verdict.action {
+            Some(FilterAction::Reject) => return Ok(api::content_rejected_reply()?),
+            Some(FilterAction::Hold) => {
+                // 保留中の内容は公開せず、承認されたときに保存する
+                // 承認までに書き換えられていないか確かめられるよう、保留時の内容も控える
+                store
+                    .insert_moderation_item(
+                        &today,
+                        &content,
+                        current.as_deref(),
+                        FilterAction::Hold,
+                        &verdict.reasons_text(),
+                    )
+                    .await
+                    .map_err(failure)?;
+                return Ok(api::held_reply(today)?);
+            }
+            Some(FilterAction::Flag) | None => {}
+        }
+
+        // 拒否や保留になった保存ではクォータを使わない
+        if let Some(api_key) = &api_key {
+            let consumed = store
+                .consume_api_key_quota(api_key.id, &today, api_key.daily_quota)
+                .await
+                .map_err(failure)?;
+            if !consumed {
+                return Ok(api::quota_exceeded_reply(api_key.daily_quota)?);
+            }
+        }
+
+        store
+            .upsert_entry(&today, &content)
+            .await
+            .map_err(failure)?;
+
+        // 要確認の版を差し戻せるよう、上書きされる直前の内容を控えておく
+        if verdict.action == Some(FilterAction::Flag) {
+            let result = store
+                .insert_moderation_item(
+                    &today,
+                    &content,
+                    current.as_deref(),
+                    FilterAction::Flag,
+                    &verdict.reasons_text(),
+                )
+                .await;
+            if let Err(e) = result {
+                log::error!("Failed to queue flagged entry: {:?}", e);
+            }
+        }
+        if let Some(key) = &policy.writer_key {
+            let writer = entry_stats::writer_hash(key, &today, &client);
+            if let Err(e) = store.record_save(&today, &writer).await {
+                log::error!("Failed to record writer: {:?}", e);
+            }
+        }
+        if let Some(api_key) = &api_key {
+            // 記録の失敗で保存自体は失敗扱いにしない
+            let hash = content_hash(&content);
+            if let Err(e) = store.record_api_key_write(api_key.id, &today, &hash).await {
+                log::error!("Failed to record API key write: {:?}", e);
+            }
+        }
+        Ok(api::saved_reply(
+            today,
+            content,
+            &decision,
+            limiter.policy(),
+        )?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::convert::Infallible;
+
+    use crate::models::FilterKind;
+    use crate::store::{block_on, MemoryStore};
+    use crate::time::FixedClock;
+    use crate::verification::VerificationFailure;
+
+    /// 2025-01-15 10:00:00 JST
+    const NOW: i64 = 1_736_902_800;
+    const TODAY: &str = "2025-01-15";
+
+    /// "ok" だけを正しいトークンとして受け付ける確認
+    struct TokenVerifier;
+
+    impl Verifier for TokenVerifier {
+        type Error = Infallible;
+
+        async fn verify(
+            &self,
+            token: &str,
+            _ip: Option<&str>,
+        ) -> Result<Result<(), VerificationFailure>, Infallible> {
+            Ok((token == "ok").then_some(()).ok_or(VerificationFailure {
+                code: "VERIFICATION_FAILED",
+                message: "Verification failed",
+                status: 403,
+            }))
+        }
+    }
+
+    fn policy() -> WritePolicy {
+        WritePolicy {
+            rate_limit: RateLimitPolicy {
+                min_interval_seconds: 0,
+                ..RateLimitPolicy::default()
+            },
+            writer_key: Some("writer-key".to_string()),
+            ..WritePolicy::default()
+        }
+    }
+
+    /// 時刻を指定して保存する（保存先の時刻も合わせて進める）
+    fn save(
+        store: &MemoryStore,
+        policy: &WritePolicy,
+        now: i64,
+        client: &str,
+        authorization: Option<&str>,
+        body: serde_json::Value,
+    ) -> Reply {
+        let saved_at = chrono::DateTime::from_timestamp(now, 0)
+            .unwrap()
+            .to_rfc3339();
+        store.set_now(&saved_at);
+        let writer = DiaryWriter {
+            store,
+            limits: store,
+            verifier: &TokenVerifier,
+            clock: FixedClock(now),
+            policy,
+        };
+        let body = body.to_string();
+        let request = SaveRequest {
+            body: body.as_bytes(),
+            authorization,
+            client,
+            ip: Some("192.0.2.1"),
+        };
+        block_on(writer.save_today(&request)).unwrap()
+    }
+
+    fn content(store: &MemoryStore) -> Option<String> {
+        block_on(store.get_entry(TODAY)).unwrap().map(|e| e.content)
+    }
+
+    #[test]
+    fn test_save_and_overwrite() {
+        let store = MemoryStore::default();
+        let policy = policy();
+        let body =
+            |content: &str| serde_json::json!({"content": content, "verification_token": "ok"});
+
+        let reply = save(&store, &policy, NOW, "a", None, body("今日は晴れ\r\n"));
+        assert_eq!(reply.status, 201);
+        assert_eq!(reply.field("date"), TODAY);
+        assert_eq!(reply.field("content"), "今日は晴れ\n");
+        assert!(reply.header("RateLimit-Remaining").is_some());
+
+        let reply = save(&store, &policy, NOW + 60, "b", None, body("今日は雨"));
+        assert_eq!(reply.status, 201);
+        assert_eq!(content(&store).as_deref(), Some("今日は雨"));
+        let versions = block_on(store.list_versions(TODAY)).unwrap();
+        assert_eq!(versions.len(), 1);
+        assert_eq!(versions[0].content, "今日は晴れ\n");
+
+        let activity = block_on(store.save_activity(TODAY)).unwrap();
+        assert_eq!(activity.writers, 2);
+    }
+
+    #[test]
+    fn test_save_rejects_bad_input() {
+        let store = MemoryStore::default();
+        let policy = policy();
+
+        let reply = save(
+            &store,
+            &policy,
+            NOW,
+            "a",
+            None,
+            serde_json::json!({"text": "x"}),
+        );
+        assert_eq!(reply.status, 400);
+        assert_eq!(reply.field("error"), "Invalid JSON");
+
+        let body = serde_json::json!({"content": "x", "verification_token": "ng"});
+        let reply = save(&store, &policy, NOW, "a", None, body);
+        assert_eq!(reply.status, 403);
+        assert_eq!(reply.field("code"), "VERIFICATION_FAILED");
+
+        let long = "あ".repeat(crate::validation::MAX_CONTENT_LENGTH + 1);
+        let body = serde_json::json!({"content": long, "verification_token": "ok"});
+        let reply = save(&store, &policy, NOW, "a", None, body);
+        assert_eq!(reply.status, 400);
+        assert!(content(&store).is_none());
+    }
+
+    #[test]
+    fn test_overwrite_conflict_flow() {
+        let store = MemoryStore::default();
+        let policy = policy();
+        let long = "長い日記です。".repeat(50);
+        let body = |content: &str, confirm: bool| {
+            serde_json::json!({
+                "content": content,
+                "verification_token": "ok",
+                "confirm_overwrite": confirm,
+            })
+        };
+        let reply = save(&store, &policy, NOW, "a", None, body(&long, false));
+        assert_eq!(reply.status, 201);
+
+        // 保存直後の日記を大きく消す保存は、確認があっても待たせる
+        let reply = save(&store, &policy, NOW + 60, "b", None, body("消した", true));
+        assert_eq!(reply.status, 429);
+        assert_eq!(reply.field("code"), "OVERWRITE_COOLDOWN");
+        assert_eq!(reply.header("Retry-After"), Some("240"));
+
+        let later = NOW + policy.overwrite.cooldown_seconds;
+        let reply = save(&store, &policy, later, "b", None, body("消した", false));
+        assert_eq!(reply.status, 409);
+        assert_eq!(reply.field("code"), "OVERWRITE_CONFIRMATION_REQUIRED");
+        assert_eq!(content(&store).as_deref(), Some(long.as_str()));
+
+        let reply = save(&store, &policy, later, "b", None, body("消した", true));
+        assert_eq!(reply.status, 201);
+        assert_eq!(content(&store).as_deref(), Some("消した"));
+        let versions = block_on(store.list_versions(TODAY)).unwrap();
+        assert_eq!(versions[0].content, long);
+    }
+
+    #[test]
+    fn test_save_is_rate_limited_per_client() {
+        let store = MemoryStore::default();
+        let policy = WritePolicy::default();
+        let body = serde_json::json!({"content": "一回目", "verification_token": "ok"});
+
+        assert_eq!(
+            save(&store, &policy, NOW, "a", None, body.clone()).status,
+            201
+        );
+        let reply = save(&store, &policy, NOW + 1, "a", None, body.clone());
+        assert_eq!(reply.status, 429);
+        assert!(reply.header("Retry-After").is_some());
+        // 別のクライアントは制限されない
+        assert_eq!(save(&store, &policy, NOW + 1, "b", None, body).status, 201);
+    }
+
+    #[test]
+    fn test_spam_is_rejected() {
+        let store = MemoryStore::default();
+        let policy = policy();
+        let body = serde_json::json!({
+            "content": "買って",
+            "verification_token": "ok",
+            "website": "http://spam.example",
+        });
+
+        let reply = save(&store, &policy, NOW, "a", None, body);
+        assert_eq!(reply.status, 400);
+        assert_eq!(reply.field("code"), "SPAM_DETECTED");
+        assert!(content(&store).is_none());
+    }
+
+    #[test]
+    fn test_api_key_skips_verification_and_uses_quota() {
+        let store = MemoryStore::default();
+        let policy = policy();
+        let api_key = store.add_api_key("dk_0123456789", 1);
+        let auth = Some("Bearer dk_0123456789");
+
+        // 人間確認のトークンがなくてもキーで保存できる
+        let reply = save(
+            &store,
+            &policy,
+            NOW,
+            "a",
+            auth,
+            serde_json::json!({"content": "自動"}),
+        );
+        assert_eq!(reply.status, 201);
+        let writes = store.api_key_writes();
+        assert_eq!(writes.len(), 1);
+        assert_eq!(writes[0].0, api_key.id);
+        assert_eq!(writes[0].2, content_hash("自動"));
+
+        let body = serde_json::json!({"content": "二回目"});
+        let reply = save(&store, &policy, NOW + 60, "a", auth, body.clone());
+        assert_eq!(reply.status, 429);
+        assert_eq!(reply.field("code"), "API_KEY_QUOTA_EXCEEDED");
+
+        // 失効したキーや知らないキーは人間確認にフォールバックしない
+        store.revoke_api_key(api_key.id);
+        let reply = save(&store, &policy, NOW + 120, "a", auth, body.clone());
+        assert_eq!(reply.status, 401);
+        let reply = save(
+            &store,
+            &policy,
+            NOW + 120,
+            "a",
+            Some("Bearer dk_unknown"),
+            body,
+        );
+        assert_eq!(reply.status, 401);
+        assert_eq!(reply.field("code"), "INVALID_API_KEY");
+    }
+
+    #[test]
+    fn test_filtered_api_key_save_keeps_quota() {
+        let store = MemoryStore::default();
+        let policy = policy();
+        store.add_api_key("dk_0123456789", 1);
+        store.add_content_filter(FilterKind::Word, "禁句", FilterAction::Reject);
+        store.add_content_filter(FilterKind::Word, "保留", FilterAction::Hold);
+        let auth = Some("Bearer dk_0123456789");
+
+        let body = serde_json::json!({"content": "禁句を含む"});
+        assert_eq!(save(&store, &policy, NOW, "a", auth, body).status, 422);
+        let body = serde_json::json!({"content": "保留される"});
+        assert_eq!(save(&store, &policy, NOW + 60, "a", auth, body).status, 202);
+
+        // 拒否も保留もクォータを使っていないので、まだ保存できる
+        let body = serde_json::json!({"content": "自動"});
+        assert_eq!(save(&store, &policy, NOW + 120, "a", auth, body).status, 201);
+        let body = serde_json::json!({"content": "二回目"});
+        let reply = save(&store, &policy, NOW + 180, "a", auth, body);
+        assert_eq!(reply.field("code"), "API_KEY_QUOTA_EXCEEDED");
+    }
+
+    #[test]
+    fn test_content_filters() {
+        let store = MemoryStore::default();
+        let policy = policy();
+        store.add_content_filter(FilterKind::Word, "禁句", FilterAction::Reject);
+        store.add_content_filter(FilterKind::Word, "保留", FilterAction::Hold);
+        store.add_content_filter(FilterKind::Word, "注意", FilterAction::Flag);
+        let body =
+            |content: &str| serde_json::json!({"content": content, "verification_token": "ok"});
+
+        let reply = save(&store, &policy, NOW, "a", None, body("最初の日記"));
+        assert_eq!(reply.status, 201);
+
+        let reply = save(&store, &policy, NOW + 60, "a", None, body("禁句を含む"));
+        assert_eq!(reply.status, 422);
+        assert_eq!(reply.field("code"), "CONTENT_REJECTED");
+
+        let reply = save(&store, &policy, NOW + 120, "a", None, body("保留される"));
+        assert_eq!(reply.status, 202);
+        assert_eq!(reply.field("code"), "CONTENT_HELD");
+        assert_eq!(content(&store).as_deref(), Some("最初の日記"));
+
+        let reply = save(&store, &policy, NOW + 180, "a", None, body("注意が必要"));
+        assert_eq!(reply.status, 201);
+        assert_eq!(content(&store).as_deref(), Some("注意が必要"));
+
+        let items = store.moderation_items();
+        assert_eq!(items.len(), 2);
+        assert_eq!(items[0].action, FilterAction::Hold);
+        assert_eq!(items[0].content, "保留される");
+        assert_eq!(items[0].previous_content.as_deref(), Some("最初の日記"));
+        assert_eq!(items[1].action, FilterAction::Flag);
+        assert_eq!(items[1].previous_content.as_deref(), Some("最初の日記"));
+    }
+}
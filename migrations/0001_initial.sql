@@ -0,0 +1,150 @@
+-- 誰かが書く日記 - 最初のスキーマ
+--
+-- マイグレーションの仕組みを入れる前はschema.sqlをそのまま流していたため、
+-- すべてIF NOT EXISTSにしてある。既存のデータベースに当てても何も変わらない。
+CREATE TABLE IF NOT EXISTS diary_entries (
+    date TEXT PRIMARY KEY,           -- YYYY-MM-DD (JST)
+    content TEXT NOT NULL,           -- 日記本文 (最大10000文字)
+    created_at TEXT NOT NULL,        -- ISO8601タイムスタンプ
+    updated_at TEXT NOT NULL         -- ISO8601タイムスタンプ
+) STRICT;
+
+CREATE INDEX IF NOT EXISTS idx_entries_date_desc
+ON diary_entries(date DESC);
+
+-- バージョン履歴テーブル
+CREATE TABLE IF NOT EXISTS diary_versions (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    entry_date TEXT NOT NULL,           -- diary_entries.dateへの参照
+    content TEXT NOT NULL,              -- 保存時点の内容
+    version_number INTEGER NOT NULL,    -- バージョン番号（1から始まる連番）
+    created_at TEXT NOT NULL,           -- このバージョンが作成された日時
+    FOREIGN KEY (entry_date) REFERENCES diary_entries(date)
+) STRICT;
+
+CREATE INDEX IF NOT EXISTS idx_versions_entry_date
+ON diary_versions(entry_date, version_number DESC);
+
+-- 管理者アカウント
+CREATE TABLE IF NOT EXISTS admins (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    username TEXT NOT NULL UNIQUE,      -- ログイン名
+    password_hash TEXT NOT NULL,        -- pbkdf2-sha256$反復回数$ソルト$ハッシュ
+    role TEXT NOT NULL,                 -- viewer / moderator / superuser
+    totp_secret TEXT,                   -- TOTPシークレット（Base32、未設定ならNULL）
+    totp_last_step INTEGER,             -- 最後に使われたTOTPの時間ステップ（再利用防止）
+    email TEXT UNIQUE,                  -- Cloudflare Accessで使うメールアドレス（小文字、未設定ならNULL）
+    created_at TEXT NOT NULL,
+    updated_at TEXT NOT NULL
+) STRICT;
+
+-- 二要素認証のリカバリーコード
+CREATE TABLE IF NOT EXISTS admin_recovery_codes (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    admin_id INTEGER NOT NULL,          -- admins.idへの参照
+    code_hash TEXT NOT NULL,            -- SHA-256ハッシュ
+    used_at TEXT,                       -- 使用日時（未使用ならNULL）
+    FOREIGN KEY (admin_id) REFERENCES admins(id) ON DELETE CASCADE
+) STRICT;
+
+CREATE INDEX IF NOT EXISTS idx_recovery_codes_admin
+ON admin_recovery_codes(admin_id);
+
+-- パスキー（WebAuthnクレデンシャル）
+CREATE TABLE IF NOT EXISTS admin_credentials (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    admin_id INTEGER NOT NULL,          -- admins.idへの参照
+    credential_id TEXT NOT NULL UNIQUE, -- クレデンシャルID（Base64URL）
+    public_key TEXT NOT NULL,           -- COSE公開鍵（Base64URL）
+    sign_count INTEGER NOT NULL,        -- 認証器の署名カウンタ
+    label TEXT NOT NULL,                -- 管理者が付けた名前
+    created_at TEXT NOT NULL,
+    last_used_at TEXT,
+    FOREIGN KEY (admin_id) REFERENCES admins(id) ON DELETE CASCADE
+) STRICT;
+
+CREATE INDEX IF NOT EXISTS idx_credentials_admin
+ON admin_credentials(admin_id);
+
+-- 管理者の操作の監査ログ
+CREATE TABLE IF NOT EXISTS admin_audit_log (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    actor TEXT NOT NULL,                -- 操作した管理者のユーザー名（ログイン失敗では入力された名前）
+    actor_id INTEGER,                   -- admins.id（ブートストラップ管理者などはNULL、削除後も残す）
+    action TEXT NOT NULL,               -- login / revert / redact などの操作種別
+    ip_hash TEXT NOT NULL,              -- 接続元IPのハッシュ
+    target_date TEXT,                   -- 対象の日記の日付
+    target_version INTEGER,             -- 対象のバージョン番号
+    target TEXT,                        -- その他の対象（管理者名など）
+    before_hash TEXT,                   -- 変更前の内容のSHA-256
+    after_hash TEXT,                    -- 変更後の内容のSHA-256
+    created_at TEXT NOT NULL
+) STRICT;
+
+CREATE INDEX IF NOT EXISTS idx_audit_log_actor
+ON admin_audit_log(actor, id);
+
+CREATE INDEX IF NOT EXISTS idx_audit_log_action
+ON admin_audit_log(action, id);
+
+-- スクリプトやAIエージェント向けのAPIキー
+CREATE TABLE IF NOT EXISTS api_keys (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    label TEXT NOT NULL,                -- 用途や利用者を表す名前
+    key_prefix TEXT NOT NULL,           -- 画面で見分けるためのキーの先頭部分
+    key_hash TEXT NOT NULL UNIQUE,      -- キー全体のSHA-256（平文は保存しない）
+    daily_quota INTEGER NOT NULL,       -- 1日（JST）に保存できる回数
+    created_by TEXT NOT NULL,           -- 発行した管理者のユーザー名
+    created_at TEXT NOT NULL,
+    last_used_at TEXT,
+    revoked_at TEXT                     -- 失効日時（有効ならNULL）
+) STRICT;
+
+-- APIキーごとの日別の保存回数
+CREATE TABLE IF NOT EXISTS api_key_usage (
+    key_id INTEGER NOT NULL,            -- api_keys.idへの参照
+    date TEXT NOT NULL,                 -- YYYY-MM-DD (JST)
+    count INTEGER NOT NULL,
+    PRIMARY KEY (key_id, date),
+    FOREIGN KEY (key_id) REFERENCES api_keys(id)
+) STRICT;
+
+-- APIキーによる保存の記録（どのキーが何を書いたか）
+CREATE TABLE IF NOT EXISTS api_key_writes (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    key_id INTEGER NOT NULL,            -- api_keys.idへの参照
+    entry_date TEXT NOT NULL,           -- diary_entries.dateへの参照
+    content_hash TEXT NOT NULL,         -- 保存した内容のSHA-256
+    created_at TEXT NOT NULL,
+    FOREIGN KEY (key_id) REFERENCES api_keys(id)
+) STRICT;
+
+CREATE INDEX IF NOT EXISTS idx_api_key_writes_key
+ON api_key_writes(key_id, id);
+
+-- 日記の保存時に照合する内容フィルター（NGワード・正規表現）
+CREATE TABLE IF NOT EXISTS content_filters (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    kind TEXT NOT NULL,                 -- word / regex
+    pattern TEXT NOT NULL,              -- NGワードまたは正規表現
+    action TEXT NOT NULL,               -- flag / hold / reject
+    created_by TEXT NOT NULL,           -- 登録した管理者のユーザー名
+    created_at TEXT NOT NULL
+) STRICT;
+
+-- 内容フィルターに該当した保存のモデレーションキュー
+CREATE TABLE IF NOT EXISTS moderation_queue (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    entry_date TEXT NOT NULL,           -- 対象の日記の日付
+    content TEXT NOT NULL,              -- 保存された（保留中の）内容
+    previous_content TEXT,              -- 要確認で保存した時点の直前の内容
+    action TEXT NOT NULL,               -- flag（保存済み） / hold（保留中）
+    reasons TEXT NOT NULL,              -- 該当したフィルターの説明（改行区切り）
+    created_at TEXT NOT NULL,
+    resolution TEXT,                    -- approved / reverted / redacted / discarded（未処理ならNULL）
+    resolved_by TEXT,                   -- 処理した管理者のユーザー名
+    resolved_at TEXT
+) STRICT;
+
+CREATE INDEX IF NOT EXISTS idx_moderation_queue_pending
+ON moderation_queue(resolution, id);
@@ -0,0 +1,97 @@
+-- サイト全体の統計の集計表
+--
+-- /statsのたびに全履歴を数えないよう、日記と版の書き込みに合わせてトリガーで更新する。
+-- 保存1回は「日記の作成」か「内容が変わって版が1つ増えたこと」として数える。
+-- 日記と版は削除しないため、削除には追従しない。
+
+-- 月ごとの日記の数と本文の文字数の合計
+CREATE TABLE stats_months (
+    month TEXT PRIMARY KEY,             -- YYYY-MM (JST)
+    entries INTEGER NOT NULL,
+    total_length INTEGER NOT NULL       -- 最新の本文の文字数の合計
+) STRICT;
+
+-- 日ごとの上書きの回数（diary_versionsの行数）
+CREATE TABLE stats_days (
+    date TEXT PRIMARY KEY,              -- YYYY-MM-DD (JST)
+    versions INTEGER NOT NULL
+) STRICT;
+
+CREATE INDEX idx_stats_days_versions
+ON stats_days(versions DESC, date);
+
+-- 保存の時刻（JSTの時）ごとの回数
+CREATE TABLE stats_hours (
+    hour INTEGER PRIMARY KEY,           -- 0〜23
+    saves INTEGER NOT NULL
+) STRICT;
+
+-- 既存のデータから集計する
+INSERT INTO stats_months (month, entries, total_length)
+SELECT substr(date, 1, 7), COUNT(*), SUM(length(content))
+FROM diary_entries
+GROUP BY substr(date, 1, 7);
+
+INSERT INTO stats_days (date, versions)
+SELECT entry_date, COUNT(*)
+FROM diary_versions
+GROUP BY entry_date;
+
+INSERT INTO stats_hours (hour, saves)
+SELECT hour, COUNT(*)
+FROM (
+    SELECT CAST(strftime('%H', created_at, '+9 hours') AS INTEGER) AS hour FROM diary_entries
+    UNION ALL
+    SELECT CAST(strftime('%H', created_at, '+9 hours') AS INTEGER) FROM diary_versions
+)
+WHERE hour IS NOT NULL
+GROUP BY hour;
+
+-- 日記の作成: 月の件数と文字数、作成時刻の保存回数
+CREATE TRIGGER stats_entry_insert AFTER INSERT ON diary_entries
+BEGIN
+    INSERT INTO stats_months (month, entries, total_length)
+    VALUES (substr(NEW.date, 1, 7), 1, length(NEW.content))
+    ON CONFLICT(month) DO UPDATE SET
+      entries = entries + 1,
+      total_length = total_length + excluded.total_length;
+
+    INSERT INTO stats_hours (hour, saves)
+    SELECT hour, 1
+    FROM (SELECT CAST(strftime('%H', NEW.created_at, '+9 hours') AS INTEGER) AS hour)
+    WHERE hour IS NOT NULL
+    ON CONFLICT(hour) DO UPDATE SET saves = saves + 1;
+END;
+
+-- 日記の更新: 文字数の差分（インポートで作成日時が変わった場合は時刻も付け替える）
+CREATE TRIGGER stats_entry_update AFTER UPDATE ON diary_entries
+BEGIN
+    UPDATE stats_months
+    SET total_length = total_length + length(NEW.content) - length(OLD.content)
+    WHERE month = substr(NEW.date, 1, 7);
+
+    UPDATE stats_hours
+    SET saves = saves - 1
+    WHERE NEW.created_at <> OLD.created_at
+      AND hour = CAST(strftime('%H', OLD.created_at, '+9 hours') AS INTEGER);
+
+    INSERT INTO stats_hours (hour, saves)
+    SELECT hour, 1
+    FROM (SELECT CAST(strftime('%H', NEW.created_at, '+9 hours') AS INTEGER) AS hour)
+    WHERE hour IS NOT NULL AND NEW.created_at <> OLD.created_at
+    ON CONFLICT(hour) DO UPDATE SET saves = saves + 1;
+END;
+
+-- 版の追加: その日の上書きの回数と、上書きした時刻の保存回数
+CREATE TRIGGER stats_version_insert AFTER INSERT ON diary_versions
+BEGIN
+    INSERT INTO stats_days (date, versions)
+    VALUES (NEW.entry_date, 1)
+    ON CONFLICT(date) DO UPDATE SET versions = versions + 1;
+
+    INSERT INTO stats_hours (hour, saves)
+    SELECT hour, 1
+    FROM (SELECT CAST(strftime('%H', NEW.created_at, '+9 hours') AS INTEGER) AS hour)
+    WHERE hour IS NOT NULL
+    ON CONFLICT(hour) DO UPDATE SET saves = saves + 1;
+END;
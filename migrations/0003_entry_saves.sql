@@ -0,0 +1,15 @@
+-- 日記ごとの書き手の記録
+--
+-- /api/entries/:date/statsで何人が書いたかを数えるため、保存のたびに書き手のハッシュを残す。
+-- ハッシュは日付を混ぜて作るため、別の日の書き手とは結び付けられない。
+-- このマイグレーションより前の保存は記録されていない。
+CREATE TABLE entry_saves (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    entry_date TEXT NOT NULL,           -- diary_entries.dateへの参照
+    writer TEXT NOT NULL,               -- 日付ごとに変わる書き手のハッシュ
+    created_at TEXT NOT NULL,
+    FOREIGN KEY (entry_date) REFERENCES diary_entries(date)
+) STRICT;
+
+CREATE INDEX idx_entry_saves_date
+ON entry_saves(entry_date, writer);
@@ -0,0 +1,14 @@
+-- プルーフ・オブ・ワークのチャレンジ
+--
+-- KVは削除の反映に時間がかかり、同じ解答を何度も使えてしまうため、D1に置いて
+-- DELETE ... RETURNINGで1回だけ取り出す。発行したクライアントのキーと結び付け、
+-- 別のクライアントからは使えないようにする。期限切れの行は発行のたびに消す。
+CREATE TABLE pow_challenges (
+    challenge TEXT PRIMARY KEY,
+    client TEXT NOT NULL,               -- 発行先のクライアントのキー（ハッシュ）
+    difficulty INTEGER NOT NULL,        -- 発行時の難易度
+    expires_at INTEGER NOT NULL         -- 失効時刻（Unix秒）
+) STRICT;
+
+CREATE INDEX idx_pow_challenges_expires_at
+ON pow_challenges(expires_at);
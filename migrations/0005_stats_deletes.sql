@@ -0,0 +1,35 @@
+-- 日記と版の削除に統計の集計表を追従させる
+--
+-- スナップショットからの置き換えのリストアで日記と版を消すため、
+-- 0002_stats.sqlのトリガーと逆の増減を行う。件数が0になった行は消す。
+
+-- 日記の削除: 月の件数と文字数、作成時刻の保存回数
+CREATE TRIGGER stats_entry_delete AFTER DELETE ON diary_entries
+BEGIN
+    UPDATE stats_months
+    SET entries = entries - 1,
+        total_length = total_length - length(OLD.content)
+    WHERE month = substr(OLD.date, 1, 7);
+
+    DELETE FROM stats_months
+    WHERE month = substr(OLD.date, 1, 7) AND entries <= 0;
+
+    UPDATE stats_hours
+    SET saves = saves - 1
+    WHERE hour = CAST(strftime('%H', OLD.created_at, '+9 hours') AS INTEGER);
+END;
+
+-- 版の削除: その日の上書きの回数と、上書きした時刻の保存回数
+CREATE TRIGGER stats_version_delete AFTER DELETE ON diary_versions
+BEGIN
+    UPDATE stats_days
+    SET versions = versions - 1
+    WHERE date = OLD.entry_date;
+
+    DELETE FROM stats_days
+    WHERE date = OLD.entry_date AND versions <= 0;
+
+    UPDATE stats_hours
+    SET saves = saves - 1
+    WHERE hour = CAST(strftime('%H', OLD.created_at, '+9 hours') AS INTEGER);
+END;
@@ -0,0 +1,7 @@
+-- 確定した日記の書き手の人数
+--
+-- entry_savesの書き手のハッシュは保持期間を過ぎたら消し、日ごとの人数だけをここに残す。
+CREATE TABLE entry_writer_counts (
+    entry_date TEXT PRIMARY KEY,        -- diary_entries.dateへの参照
+    writers INTEGER NOT NULL
+) STRICT;
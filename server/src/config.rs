@@ -0,0 +1,87 @@
+use std::net::SocketAddr;
+
+use darekagakaku_core::config::{parse_var, FromVars};
+
+/// サーバーの起動設定
+#[derive(Debug, Clone, PartialEq)]
+pub struct ServerConfig {
+    /// 待ち受けるアドレス（BIND_ADDR）
+    pub bind_addr: SocketAddr,
+    /// SQLiteのファイル（DATABASE_PATH）
+    pub database_path: String,
+    /// リバースプロキシが接続元のIPを入れるヘッダー（CLIENT_IP_HEADER、未設定なら接続元）
+    pub client_ip_header: Option<String>,
+    /// RSSのリンクに使うURL（BASE_URL、未設定ならHostヘッダーから作る）
+    pub base_url: Option<String>,
+    /// 起動時に未適用のマイグレーションを当てるか（AUTO_MIGRATE、既定はtrue）
+    pub auto_migrate: bool,
+    /// 管理者用APIのBearerトークン（ADMIN_TOKEN、未設定なら管理者用APIは使えない）
+    pub admin_token: Option<String>,
+    /// 書き手のハッシュの鍵（WRITER_HASH_KEY、未設定なら書き手の人数を記録しない）
+    pub writer_hash_key: Option<String>,
+}
+
+impl Default for ServerConfig {
+    fn default() -> Self {
+        Self {
+            bind_addr: SocketAddr::from(([127, 0, 0, 1], 8787)),
+            database_path: "darekagakaku.sqlite3".to_string(),
+            client_ip_header: None,
+            base_url: None,
+            auto_migrate: true,
+            admin_token: None,
+            writer_hash_key: None,
+        }
+    }
+}
+
+impl FromVars for ServerConfig {
+    fn from_vars(get: impl Fn(&str) -> Option<String>) -> Self {
+        let default = Self::default();
+        let non_empty = |name| get(name).map(|v| v.trim().to_string()).filter(|v| !v.is_empty());
+        Self {
+            bind_addr: parse_var(get("BIND_ADDR")).unwrap_or(default.bind_addr),
+            database_path: non_empty("DATABASE_PATH").unwrap_or(default.database_path),
+            client_ip_header: non_empty("CLIENT_IP_HEADER"),
+            base_url: non_empty("BASE_URL").map(|v| v.trim_end_matches('/').to_string()),
+            auto_migrate: parse_var(get("AUTO_MIGRATE")).unwrap_or(default.auto_migrate),
+            admin_token: non_empty("ADMIN_TOKEN"),
+            writer_hash_key: non_empty("WRITER_HASH_KEY"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use super::*;
+
+    #[test]
+    fn test_config_from_vars() {
+        let vars: HashMap<&str, &str> = [
+            ("BIND_ADDR", "0.0.0.0:8080"),
+            ("DATABASE_PATH", "/var/lib/darekagakaku/diary.db"),
+            ("CLIENT_IP_HEADER", "X-Real-IP"),
+            ("BASE_URL", "https://diary.example/"),
+            ("AUTO_MIGRATE", "false"),
+            ("ADMIN_TOKEN", " secret "),
+            ("WRITER_HASH_KEY", "writer-secret"),
+        ]
+        .into_iter()
+        .collect();
+        let config = ServerConfig::from_vars(|name| vars.get(name).map(|v| v.to_string()));
+        assert_eq!(config.bind_addr, SocketAddr::from(([0, 0, 0, 0], 8080)));
+        assert_eq!(config.database_path, "/var/lib/darekagakaku/diary.db");
+        assert_eq!(config.client_ip_header.as_deref(), Some("X-Real-IP"));
+        assert_eq!(config.base_url.as_deref(), Some("https://diary.example"));
+        assert!(!config.auto_migrate);
+        assert_eq!(config.admin_token.as_deref(), Some("secret"));
+        assert_eq!(config.writer_hash_key.as_deref(), Some("writer-secret"));
+
+        let config = ServerConfig::from_vars(|name| {
+            (name == "BIND_ADDR").then(|| "localhost".to_string())
+        });
+        assert_eq!(config, ServerConfig::default());
+    }
+}
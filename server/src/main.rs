@@ -0,0 +1,167 @@
+//! 誰かが書く日記をCloudflareを使わずに動かすためのサーバー
+//!
+//! Workers版と同じ共通部分（ハンドラーの本体とテンプレート）を使い、
+//! D1の代わりにSQLite、KVの代わりにプロセス内のメモリでレート制限を行う。
+//! 設定は環境変数で渡す（wrangler.tomlの[vars]と同じ名前を使える）。
+//! スキーマはmigrations/のファイルを起動時に当てる（AUTO_MIGRATE=falseで無効）。
+//! 管理者用APIはADMIN_TOKENを設定したときだけ、Bearerトークンで使える。
+//! 日記ごとの書き手の人数はWRITER_HASH_KEYを設定したときだけ記録する。
+//!
+//! 対応するのは公開ページ・JSON API・書き出し・すべての確認方式と、管理者用APIのうち
+//! 版の閲覧・モデレーション・全件の書き出しと取り込み。管理画面（ログイン・TOTP・
+//! パスキー・管理者とフィルターとAPIキーの管理）、監査ログ、R2のバックアップと
+//! リストアはWorkers版だけの機能で、これらのパスには501を返す。
+//! フィルターとAPIキーは、必要ならSQLiteのテーブルに直接登録する。
+
+use std::process::ExitCode;
+use std::sync::Arc;
+use std::time::Duration;
+
+use darekagakaku_core::config::FromVars;
+use darekagakaku_core::entry_stats;
+use darekagakaku_core::migrations::SchemaStatus;
+use darekagakaku_core::write::WritePolicy;
+use log::{Level, LevelFilter, Log, Metadata, Record};
+
+mod config;
+mod rate_limit;
+mod routes;
+mod store;
+mod time;
+mod verifier;
+
+use config::ServerConfig;
+use rate_limit::MemoryStateStore;
+use routes::AppState;
+use store::SqliteStore;
+use time::{today_jst, SystemClock};
+use verifier::ServerVerifier;
+
+/// 標準エラー出力へのログ
+struct StderrLogger;
+
+impl Log for StderrLogger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        metadata.level() <= Level::Info
+    }
+
+    fn log(&self, record: &Record) {
+        if self.enabled(record.metadata()) {
+            eprintln!("[{}] {}", record.level(), record.args());
+        }
+    }
+
+    fn flush(&self) {}
+}
+
+static LOGGER: StderrLogger = StderrLogger;
+
+/// 書き手の記録を整理する間隔
+const COMPACT_INTERVAL: Duration = Duration::from_secs(24 * 60 * 60);
+
+fn env_var(name: &str) -> Option<String> {
+    std::env::var(name).ok()
+}
+
+#[tokio::main]
+async fn main() -> ExitCode {
+    if log::set_logger(&LOGGER).is_ok() {
+        log::set_max_level(LevelFilter::Info);
+    }
+
+    let config = ServerConfig::from_vars(env_var);
+    let verifier = match ServerVerifier::from_vars(env_var) {
+        Ok(verifier) => verifier,
+        Err(e) => {
+            log::error!("{}", e);
+            return ExitCode::FAILURE;
+        }
+    };
+    let store = match SqliteStore::open(&config.database_path) {
+        Ok(store) => store,
+        Err(e) => {
+            log::error!("Failed to open {}: {}", config.database_path, e);
+            return ExitCode::FAILURE;
+        }
+    };
+    if config.auto_migrate {
+        match store.migrate() {
+            Ok(applied) => {
+                for name in applied {
+                    log::info!("Applied migration {}", name);
+                }
+            }
+            Err(e) => {
+                log::error!("Failed to apply migrations: {}", e);
+                return ExitCode::FAILURE;
+            }
+        }
+    }
+    // スキーマが古いままなら読み出しだけ受け付ける
+    let writable = match store.schema_status() {
+        Ok(status) => {
+            if !status.is_writable() {
+                log::error!("Schema is out of date ({:?}); writes are disabled", status);
+            } else if status != SchemaStatus::Current {
+                log::warn!("Schema is newer than this build: {:?}", status);
+            }
+            status.is_writable()
+        }
+        Err(e) => {
+            log::error!("Failed to read schema_migrations: {}", e);
+            return ExitCode::FAILURE;
+        }
+    };
+    let listener = match tokio::net::TcpListener::bind(config.bind_addr).await {
+        Ok(listener) => listener,
+        Err(e) => {
+            log::error!("Failed to bind {}: {}", config.bind_addr, e);
+            return ExitCode::FAILURE;
+        }
+    };
+
+    if config.writer_hash_key.is_none() {
+        log::warn!("WRITER_HASH_KEY is not set; writer counts are not recorded");
+    }
+
+    log::info!("Listening on http://{}", config.bind_addr);
+    let state = Arc::new(AppState {
+        store,
+        writable,
+        limits: MemoryStateStore::new(SystemClock),
+        widget: verifier.widget(),
+        verifier,
+        // フォームトークンはWorkers版の疑わしさの判定でだけ使う
+        write: WritePolicy {
+            writer_key: config.writer_hash_key.clone(),
+            ..WritePolicy::from_vars(env_var)
+        },
+        config,
+    });
+    // 保持期間を過ぎた書き手のハッシュを毎日人数にまとめる（スキーマが古ければ書き込まない）
+    if state.writable {
+        let state = state.clone();
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(COMPACT_INTERVAL);
+            loop {
+                interval.tick().await;
+                if let Err(e) = entry_stats::compact_saves(&state.store, &today_jst()).await {
+                    log::error!("Failed to compact entry saves: {}", e);
+                }
+            }
+        });
+    }
+    let app = routes::router(state)
+        .into_make_service_with_connect_info::<std::net::SocketAddr>();
+    let shutdown = async {
+        let _ = tokio::signal::ctrl_c().await;
+    };
+    if let Err(e) = axum::serve(listener, app)
+        .with_graceful_shutdown(shutdown)
+        .await
+    {
+        log::error!("Server error: {}", e);
+        return ExitCode::FAILURE;
+    }
+    ExitCode::SUCCESS
+}
@@ -0,0 +1,102 @@
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+use darekagakaku_core::rate_limit::RateLimitStore;
+use darekagakaku_core::time::Clock;
+
+/// 期限切れの値を掃除し始める件数
+const PURGE_THRESHOLD: usize = 10_000;
+
+/// プロセス内のメモリに置くレート制限の状態
+///
+/// KVの代わりに期限付きの値を持つ。再起動すると制限はすべて解除される。
+pub struct MemoryStateStore<C> {
+    values: Mutex<HashMap<String, (String, i64)>>,
+    clock: C,
+}
+
+impl<C: Clock> MemoryStateStore<C> {
+    pub fn new(clock: C) -> Self {
+        Self {
+            values: Mutex::default(),
+            clock,
+        }
+    }
+}
+
+impl<C: Clock> RateLimitStore for MemoryStateStore<C> {
+    type Error = serde_json::Error;
+
+    async fn get_state(&self, key: &str) -> serde_json::Result<Option<String>> {
+        let now = self.clock.now();
+        let values = self.values.lock().unwrap_or_else(|e| e.into_inner());
+        Ok(values
+            .get(key)
+            .filter(|(_, expires_at)| *expires_at > now)
+            .map(|(value, _)| value.clone()))
+    }
+
+    async fn put_state(
+        &self,
+        key: &str,
+        value: String,
+        ttl_seconds: u64,
+    ) -> serde_json::Result<()> {
+        let now = self.clock.now();
+        let mut values = self.values.lock().unwrap_or_else(|e| e.into_inner());
+        if values.len() >= PURGE_THRESHOLD {
+            values.retain(|_, (_, expires_at)| *expires_at > now);
+        }
+        let expires_at = now.saturating_add(ttl_seconds as i64);
+        values.insert(key.to_string(), (value, expires_at));
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicI64, Ordering};
+
+    use darekagakaku_core::rate_limit::{RateLimitDecision, RateLimitPolicy, RateLimiter};
+    use darekagakaku_core::store::block_on;
+
+    use super::*;
+
+    struct FakeClock(AtomicI64);
+
+    impl Clock for &FakeClock {
+        fn now(&self) -> i64 {
+            self.0.load(Ordering::Relaxed)
+        }
+    }
+
+    #[test]
+    fn test_values_expire() {
+        let clock = FakeClock(AtomicI64::new(1_000));
+        let store = MemoryStateStore::new(&clock);
+        block_on(store.put_state("a", "1".to_string(), 60)).unwrap();
+        assert_eq!(block_on(store.get_state("a")).unwrap().as_deref(), Some("1"));
+
+        clock.0.store(1_060, Ordering::Relaxed);
+        assert_eq!(block_on(store.get_state("a")).unwrap(), None);
+    }
+
+    #[test]
+    fn test_limiter_runs_on_memory() {
+        let clock = FakeClock(AtomicI64::new(1_000));
+        let store = MemoryStateStore::new(&clock);
+        let policy = RateLimitPolicy {
+            min_interval_seconds: 5,
+            ..RateLimitPolicy::default()
+        };
+        let limiter = RateLimiter::new(&store, &clock, policy);
+
+        let allowed = |client| {
+            let decision = block_on(limiter.acquire(client, 1)).unwrap();
+            matches!(decision, RateLimitDecision::Allowed { .. })
+        };
+        assert!(allowed("a"));
+        assert!(!allowed("a"));
+        assert!(allowed("b"));
+    }
+}
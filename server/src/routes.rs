@@ -0,0 +1,863 @@
+use std::error::Error;
+use std::net::SocketAddr;
+use std::sync::Arc;
+
+use axum::body::{Body, Bytes};
+use axum::extract::{ConnectInfo, Path, Query, State};
+use axum::http::header::{AUTHORIZATION, CONTENT_TYPE, HOST};
+use axum::http::{HeaderMap, HeaderName, HeaderValue, StatusCode};
+use axum::response::{Html, IntoResponse, Json, Response};
+use axum::routing::{any, get, post};
+use axum::{Extension, Router};
+use darekagakaku_core::api;
+use darekagakaku_core::crypto::constant_time_eq;
+use darekagakaku_core::entry_stats;
+use darekagakaku_core::export::{Export, ExportFormat};
+use darekagakaku_core::identity::AdminIdentity;
+use darekagakaku_core::import::{self, ImportOptions};
+use darekagakaku_core::models::{ErrorResponse, ModerationResolution};
+use darekagakaku_core::moderation;
+use darekagakaku_core::pages::{
+    archive_reply, entry_page_reply, feed_xml, stats_page_reply, HomePage,
+};
+use darekagakaku_core::pow;
+use darekagakaku_core::rate_limit::{client_network, RateLimiter};
+use darekagakaku_core::reply::{Reply, ReplyBody};
+use darekagakaku_core::stats;
+use darekagakaku_core::store::ModerationStore;
+use darekagakaku_core::templates;
+use darekagakaku_core::time::Clock;
+use darekagakaku_core::verification::VerifierWidget;
+use darekagakaku_core::write::{DiaryWriter, SaveRequest, WritePolicy};
+use futures_util::stream;
+use serde::Deserialize;
+
+use crate::config::ServerConfig;
+use crate::rate_limit::MemoryStateStore;
+use crate::store::SqliteStore;
+use crate::time::{now_iso8601, today_jst, SystemClock};
+use crate::verifier::{new_challenge, ServerVerifier};
+
+/// ハンドラーの中で起きた障害（境界で500にする）
+type Failure = Box<dyn Error + Send + Sync>;
+
+/// リクエストをまたいで共有する状態
+pub struct AppState {
+    pub store: SqliteStore,
+    /// 起動時の確認でスキーマが最新だったか（falseなら保存を受け付けない）
+    pub writable: bool,
+    pub limits: MemoryStateStore<SystemClock>,
+    pub verifier: ServerVerifier,
+    pub widget: VerifierWidget,
+    pub write: WritePolicy,
+    pub config: ServerConfig,
+}
+
+/// Workers版のRouterと同じパスで応答する
+///
+/// 公開ページ・JSON API・書き出し・プルーフ・オブ・ワークのチャレンジはWorkers版と同じ。
+/// 管理者用APIはADMIN_TOKENのBearer認証で、版の閲覧・モデレーション・全件の書き出しと
+/// 取り込みだけを提供する。管理画面（ログイン・TOTP・パスキー・管理者の管理）と、
+/// 監査ログ・R2のバックアップとリストアのAPIはWorkers版にしかないため、501を返す。
+pub fn router(state: Arc<AppState>) -> Router {
+    Router::new()
+        // HTMLページ
+        .route("/", get(home))
+        .route("/a", get(about))
+        .route("/feed", get(feed))
+        .route("/entries", get(entries_list))
+        .route("/entries/{date}", get(entry_page))
+        .route("/stats", get(stats_page))
+        // エクスポート
+        .route("/export/entries.jsonl", get(|s| export(s, ExportFormat::Jsonl)))
+        .route("/export/entries.csv", get(|s| export(s, ExportFormat::Csv)))
+        .route("/export/entries.tar", get(|s| export(s, ExportFormat::Markdown)))
+        // JSON API
+        .route("/api/today", get(get_today).post(post_today))
+        .route("/api/pow/challenge", post(pow_challenge))
+        .route("/api/entries", get(get_entries))
+        .route("/api/entries/{date}", get(get_entry_by_date))
+        .route("/api/entries/{date}/stats", get(get_entry_stats))
+        .route("/api/stats", get(get_stats))
+        // 管理者用API
+        .route("/api/admin/entries/{date}/versions", get(admin_list_versions))
+        .route("/api/admin/entries/{date}/versions/{version}", get(admin_get_version))
+        .route("/api/admin/moderation", get(admin_list_moderation))
+        .route("/api/admin/moderation/{id}", post(admin_resolve_moderation))
+        .route("/api/admin/export", get(admin_export))
+        .route("/api/admin/import", post(admin_import))
+        // 管理画面と、Workers版にしかない管理者用API（監査ログとR2のバックアップ）
+        .route("/admin/{*rest}", any(admin_unavailable))
+        .route("/api/admin/{*rest}", any(admin_unavailable))
+        .with_state(state)
+}
+
+/// 共通部分の応答をaxumのResponseに変換する
+fn respond(reply: Reply) -> Response {
+    let mut response = match reply.body {
+        ReplyBody::Json(value) => Json(value).into_response(),
+        ReplyBody::Html(html) => Html(html).into_response(),
+    };
+    *response.status_mut() =
+        StatusCode::from_u16(reply.status).unwrap_or(StatusCode::INTERNAL_SERVER_ERROR);
+    for (name, value) in reply.headers {
+        if let (Ok(name), Ok(value)) = (
+            HeaderName::from_bytes(name.as_bytes()),
+            HeaderValue::from_str(&value),
+        ) {
+            response.headers_mut().insert(name, value);
+        }
+    }
+    response
+}
+
+/// 障害はログに残して500にする
+fn respond_result(result: Result<Reply, Failure>) -> Response {
+    match result {
+        Ok(reply) => respond(reply),
+        Err(e) => {
+            log::error!("Request failed: {:?}", e);
+            let body = Json(ErrorResponse::internal_error());
+            (StatusCode::INTERNAL_SERVER_ERROR, body).into_response()
+        }
+    }
+}
+
+/// GET /a - Aboutページ（これはなにか）
+async fn about() -> Html<String> {
+    Html(templates::render_about())
+}
+
+/// GET / - ホームページ（今日の日記フォーム）
+async fn home(State(state): State<Arc<AppState>>) -> Response {
+    let page = HomePage {
+        today: &today_jst(),
+        widget: &state.widget,
+        // フォームトークンはWorkers版の疑わしさの判定でだけ使う
+        form_token: None,
+        overwrite: &state.write.overwrite,
+    };
+    respond(page.reply(&state.store).await)
+}
+
+/// GET /entries - 過去の日記一覧
+async fn entries_list(State(state): State<Arc<AppState>>) -> Response {
+    respond(archive_reply(&state.store, &today_jst()).await)
+}
+
+/// GET /entries/:date - 特定日の日記を表示
+async fn entry_page(State(state): State<Arc<AppState>>, Path(date): Path<String>) -> Response {
+    respond(entry_page_reply(&state.store, Some(&date), &today_jst()).await)
+}
+
+/// GET /stats - サイト全体の統計
+async fn stats_page(State(state): State<Arc<AppState>>) -> Response {
+    respond(stats_page_reply(&state.store, &today_jst()).await)
+}
+
+/// GET /feed - RSSフィード
+async fn feed(State(state): State<Arc<AppState>>, headers: HeaderMap) -> Response {
+    let base_url = match &state.config.base_url {
+        Some(base_url) => base_url.clone(),
+        None => {
+            let host = headers.get(HOST).and_then(|h| h.to_str().ok());
+            format!("http://{}", host.unwrap_or("localhost"))
+        }
+    };
+    let rss = feed_xml(&state.store, &today_jst(), &base_url).await;
+    ([(CONTENT_TYPE, "application/rss+xml; charset=utf-8")], rss).into_response()
+}
+
+/// GET /export/entries.* - 確定した日記を書き出す
+///
+/// Workers版と同じく、ページごとに読みながら応答を流す。
+async fn export(State(state): State<Arc<AppState>>, format: ExportFormat) -> Response {
+    stream_export(state, Export::finalized(format, &today_jst()))
+}
+
+/// 書き出しをページごとに読みながら流す
+fn stream_export(state: Arc<AppState>, export: Export) -> Response {
+    let format = export.format();
+    let chunks = stream::try_unfold((state, export), |(state, mut export)| async move {
+        let chunk = export.next_chunk(&state.store).await?;
+        Ok::<_, rusqlite::Error>(chunk.map(|chunk| (chunk, (state, export))))
+    });
+    let mut response = Body::from_stream(chunks).into_response();
+    for (name, value) in format.headers() {
+        if let Ok(value) = HeaderValue::from_str(&value) {
+            response.headers_mut().insert(name, value);
+        }
+    }
+    response
+}
+
+/// GET /api/today - 今日の日記を取得
+async fn get_today(State(state): State<Arc<AppState>>) -> Response {
+    respond_result(api::today_reply(&state.store, &today_jst()).await.map_err(Into::into))
+}
+
+/// GET /api/entries - 過去の日記一覧を取得
+async fn get_entries(State(state): State<Arc<AppState>>) -> Response {
+    respond_result(api::entries_reply(&state.store, &today_jst()).await.map_err(Into::into))
+}
+
+/// GET /api/entries/:date - 特定日の日記を取得
+async fn get_entry_by_date(
+    State(state): State<Arc<AppState>>,
+    Path(date): Path<String>,
+) -> Response {
+    let result = api::entry_reply(&state.store, Some(&date), &today_jst()).await;
+    respond_result(result.map_err(Into::into))
+}
+
+/// GET /api/entries/:date/stats - 確定した日記の分析を取得
+async fn get_entry_stats(
+    State(state): State<Arc<AppState>>,
+    Path(date): Path<String>,
+) -> Response {
+    let result = entry_stats::entry_stats_reply(&state.store, Some(&date), &today_jst()).await;
+    respond_result(result.map_err(Into::into))
+}
+
+/// GET /api/stats - サイト全体の統計を取得
+async fn get_stats(State(state): State<Arc<AppState>>) -> Response {
+    respond_result(stats::stats_reply(&state.store, &today_jst()).await.map_err(Into::into))
+}
+
+/// POST /api/pow/challenge - プルーフ・オブ・ワークのチャレンジ発行
+///
+/// チャレンジはSQLiteに保存する。確認方式がプルーフ・オブ・ワークでなければ404。
+async fn pow_challenge(
+    State(state): State<Arc<AppState>>,
+    connect: Option<Extension<ConnectInfo<SocketAddr>>>,
+    headers: HeaderMap,
+) -> Response {
+    let Some(policy) = state.verifier.pow_policy() else {
+        return (StatusCode::NOT_FOUND, Json(ErrorResponse::not_found())).into_response();
+    };
+    if !state.writable {
+        return respond_result(api::schema_outdated_reply().map_err(Into::into));
+    }
+    let connect = connect.map(|Extension(ConnectInfo(addr))| addr);
+    let ip = client_ip(&state.config, &headers, connect);
+    let client = client_key(&state, ip.as_deref());
+    let limiter = RateLimiter::new(&state.limits, SystemClock, state.write.rate_limit.clone());
+    let now = SystemClock.now();
+    let reply = pow::issue_reply(&state.store, &limiter, &client, new_challenge(), policy, now);
+    respond_result(reply.await.map_err(Into::into))
+}
+
+/// Workers版にしかない管理画面と管理者用API（対応しないことを明示して501を返す）
+async fn admin_unavailable() -> Response {
+    let body = ErrorResponse::new(
+        "Admin pages are not available on the native server",
+        "NOT_IMPLEMENTED",
+    );
+    (StatusCode::NOT_IMPLEMENTED, Json(body)).into_response()
+}
+
+/// 管理者用APIのトークンを確かめ、認められなければ返す応答を作る
+///
+/// ADMIN_TOKENが未設定なら管理者用APIは使えない。
+/// 認められたリクエストはWorkers版のADMIN_TOKENと同じブートストラップ管理者として扱う。
+fn require_admin(state: &AppState, headers: &HeaderMap) -> Result<AdminIdentity, AdminRejection> {
+    let token = headers
+        .get(AUTHORIZATION)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.strip_prefix("Bearer "))
+        .map(str::trim);
+    let Some(token) = token else {
+        return Err(AdminRejection::Unauthorized);
+    };
+    match &state.config.admin_token {
+        Some(expected) if constant_time_eq(token, expected) => Ok(AdminIdentity::bootstrap()),
+        _ => Err(AdminRejection::Forbidden),
+    }
+}
+
+/// 管理者用APIを使えない理由
+enum AdminRejection {
+    /// トークンがない
+    Unauthorized,
+    /// トークンが違う、またはADMIN_TOKENが未設定
+    Forbidden,
+}
+
+impl IntoResponse for AdminRejection {
+    fn into_response(self) -> Response {
+        let (status, message, code) = match self {
+            Self::Unauthorized => (StatusCode::UNAUTHORIZED, "Unauthorized", "UNAUTHORIZED"),
+            Self::Forbidden => (StatusCode::FORBIDDEN, "Forbidden", "FORBIDDEN"),
+        };
+        (status, Json(ErrorResponse::new(message, code))).into_response()
+    }
+}
+
+/// GET /api/admin/entries/:date/versions - バージョン一覧取得（管理者用）
+async fn admin_list_versions(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+    Path(date): Path<String>,
+) -> Response {
+    if let Err(rejection) = require_admin(&state, &headers) {
+        return rejection.into_response();
+    }
+    respond_result(api::versions_reply(&state.store, Some(&date)).await.map_err(Into::into))
+}
+
+/// GET /api/admin/entries/:date/versions/:version - 特定バージョン取得（管理者用）
+async fn admin_get_version(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+    Path((date, version)): Path<(String, String)>,
+) -> Response {
+    if let Err(rejection) = require_admin(&state, &headers) {
+        return rejection.into_response();
+    }
+    let result = api::version_reply(&state.store, Some(&date), Some(&version)).await;
+    respond_result(result.map_err(Into::into))
+}
+
+/// GET /api/admin/moderation - 未処理の項目（管理者用）
+async fn admin_list_moderation(State(state): State<Arc<AppState>>, headers: HeaderMap) -> Response {
+    if let Err(rejection) = require_admin(&state, &headers) {
+        return rejection.into_response();
+    }
+    respond_result(moderation::pending_reply(&state.store).await.map_err(Into::into))
+}
+
+/// GET /api/admin/export - 今日の日記と版を含めてすべて書き出す（管理者用）
+async fn admin_export(State(state): State<Arc<AppState>>, headers: HeaderMap) -> Response {
+    let admin = match require_admin(&state, &headers) {
+        Ok(admin) => admin,
+        Err(rejection) => return rejection.into_response(),
+    };
+    log::info!("{} exported all entries", admin.username);
+    stream_export(state, Export::full())
+}
+
+/// POST /api/admin/import - JSON Linesの日記と版を取り込む（管理者用）
+///
+/// `dry_run` と `on_conflict` はWorkers版と同じクエリ文字列で指定する。
+async fn admin_import(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+    Query(query): Query<Vec<(String, String)>>,
+    body: String,
+) -> Response {
+    let admin = match require_admin(&state, &headers) {
+        Ok(admin) => admin,
+        Err(rejection) => return rejection.into_response(),
+    };
+    if !state.writable {
+        return respond_result(api::schema_outdated_reply().map_err(Into::into));
+    }
+    let pairs = query.iter().map(|(k, v)| (k.as_str(), v.as_str()));
+    let options = match ImportOptions::from_query(pairs) {
+        Ok(options) => options,
+        Err(message) => {
+            let body = Json(ErrorResponse::bad_request(message));
+            return (StatusCode::BAD_REQUEST, body).into_response();
+        }
+    };
+    respond_result(import_entries(&state, &admin, &body, options).await)
+}
+
+async fn import_entries(
+    state: &AppState,
+    admin: &AdminIdentity,
+    body: &str,
+    options: ImportOptions,
+) -> Result<Reply, Failure> {
+    let report = import::run(&state.store, body, options, &today_jst(), &now_iso8601()).await?;
+    if report.applied {
+        log::info!(
+            "{} imported entries (on_conflict={})",
+            admin.username,
+            options.on_conflict.as_str()
+        );
+    }
+    Ok(report.reply()?)
+}
+
+#[derive(Debug, Deserialize)]
+struct ResolveRequest {
+    resolution: String,
+}
+
+/// POST /api/admin/moderation/:id - 項目を処理する（管理者用）
+async fn admin_resolve_moderation(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+    Path(id): Path<i64>,
+    body: Bytes,
+) -> Response {
+    let admin = match require_admin(&state, &headers) {
+        Ok(admin) => admin,
+        Err(rejection) => return rejection.into_response(),
+    };
+    respond_result(resolve_moderation(&state, &admin, id, &body).await)
+}
+
+async fn resolve_moderation(
+    state: &AppState,
+    admin: &AdminIdentity,
+    id: i64,
+    body: &[u8],
+) -> Result<Reply, Failure> {
+    let body: ResolveRequest = match serde_json::from_slice(body) {
+        Ok(body) => body,
+        Err(_) => return Ok(api::invalid_json_reply()?),
+    };
+    let resolution = ModerationResolution::parse(&body.resolution);
+    let resolved =
+        moderation::resolve(&state.store, id, resolution, &admin.username, &today_jst()).await?;
+    match resolved {
+        Ok(resolved) => {
+            log::info!(
+                "{} resolved moderation item {} ({}) as {:?}",
+                admin.username,
+                id,
+                resolved.item.entry_date,
+                resolved.action
+            );
+            let item = state.store.get_moderation_item(id).await?;
+            Ok(Reply::json(200, &item)?)
+        }
+        Err(rejection) => Ok(rejection.reply()?),
+    }
+}
+
+/// 接続元のIPアドレス（設定されていればリバースプロキシのヘッダーを信頼する）
+fn client_ip(
+    config: &ServerConfig,
+    headers: &HeaderMap,
+    connect: Option<SocketAddr>,
+) -> Option<String> {
+    match &config.client_ip_header {
+        Some(name) => headers
+            .get(name.as_str())
+            .and_then(|v| v.to_str().ok())
+            .and_then(|v| v.split(',').next())
+            .map(|v| v.trim().to_string())
+            .filter(|v| !v.is_empty()),
+        None => connect.map(|addr| addr.ip().to_string()),
+    }
+}
+
+/// レート制限とチャレンジの発行先に使うクライアントのキー
+///
+/// 状態はメモリにしか置かないため、IPをハッシュ化せずにネットワーク単位でまとめる。
+fn client_key(state: &AppState, ip: Option<&str>) -> String {
+    ip.and_then(|ip| client_network(ip, state.write.rate_limit.ipv6_prefix_length))
+        .unwrap_or_else(|| "unknown".to_string())
+}
+
+/// POST /api/today - 今日の日記を作成/更新
+async fn post_today(
+    State(state): State<Arc<AppState>>,
+    connect: Option<Extension<ConnectInfo<SocketAddr>>>,
+    headers: HeaderMap,
+    body: Bytes,
+) -> Response {
+    let connect = connect.map(|Extension(ConnectInfo(addr))| addr);
+    let ip = client_ip(&state.config, &headers, connect);
+    let authorization = headers.get(AUTHORIZATION).and_then(|v| v.to_str().ok());
+    respond_result(save_today(&state, ip.as_deref(), authorization, &body).await)
+}
+
+async fn save_today(
+    state: &AppState,
+    ip: Option<&str>,
+    authorization: Option<&str>,
+    body: &[u8],
+) -> Result<Reply, Failure> {
+    if !state.writable {
+        return Ok(api::schema_outdated_reply()?);
+    }
+
+    let client = client_key(state, ip);
+    let verifier = state.verifier.for_request(&state.store, &client);
+    let writer = DiaryWriter {
+        store: &state.store,
+        limits: &state.limits,
+        verifier: &verifier,
+        clock: SystemClock,
+        policy: &state.write,
+    };
+    let request = SaveRequest {
+        body,
+        authorization,
+        client: &client,
+        ip,
+    };
+    Ok(writer.save_today(&request).await?)
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::body::to_bytes;
+    use axum::http::Request;
+    use tower::ServiceExt;
+
+    use darekagakaku_core::models::FilterAction;
+    use darekagakaku_core::rate_limit::RateLimitPolicy;
+    use darekagakaku_core::store::DiaryStore;
+
+    use super::*;
+
+    const ADMIN_TOKEN: &str = "admin-secret";
+
+    fn app() -> (Router, Arc<AppState>) {
+        app_with_schema(true)
+    }
+
+    fn app_with_schema(writable: bool) -> (Router, Arc<AppState>) {
+        app_with(writable, ServerVerifier::NoOp)
+    }
+
+    fn app_with(writable: bool, verifier: ServerVerifier) -> (Router, Arc<AppState>) {
+        let state = Arc::new(AppState {
+            store: SqliteStore::open_in_memory().unwrap(),
+            writable,
+            limits: MemoryStateStore::new(SystemClock),
+            widget: verifier.widget(),
+            verifier,
+            write: WritePolicy {
+                rate_limit: RateLimitPolicy {
+                    min_interval_seconds: 0,
+                    ..RateLimitPolicy::default()
+                },
+                writer_key: Some("writer-key".to_string()),
+                ..WritePolicy::default()
+            },
+            config: ServerConfig {
+                client_ip_header: Some("X-Real-IP".to_string()),
+                admin_token: Some(ADMIN_TOKEN.to_string()),
+                ..ServerConfig::default()
+            },
+        });
+        (router(state.clone()), state)
+    }
+
+    async fn send(app: &Router, request: Request<Body>) -> (StatusCode, HeaderMap, String) {
+        let response = app.clone().oneshot(request).await.unwrap();
+        let status = response.status();
+        let headers = response.headers().clone();
+        let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        (status, headers, String::from_utf8(body.to_vec()).unwrap())
+    }
+
+    fn get(uri: &str) -> Request<Body> {
+        Request::get(uri).body(Body::empty()).unwrap()
+    }
+
+    fn post_json(uri: &str, ip: &str, body: &str) -> Request<Body> {
+        Request::post(uri)
+            .header("Content-Type", "application/json")
+            .header("X-Real-IP", ip)
+            .body(Body::from(body.to_string()))
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_save_and_read_today() {
+        let (app, state) = app();
+
+        let (status, _, body) = send(&app, get("/api/today")).await;
+        assert_eq!(status, StatusCode::OK);
+        assert!(body.contains(r#""content":null"#));
+
+        let request = post_json("/api/today", "192.0.2.1", r#"{"content":"今日は晴れ"}"#);
+        let (status, headers, body) = send(&app, request).await;
+        assert_eq!(status, StatusCode::CREATED);
+        assert!(body.contains("今日は晴れ"));
+        assert_eq!(headers["ratelimit-limit"], "10");
+
+        let (_, _, body) = send(&app, get("/api/today")).await;
+        assert!(body.contains("今日は晴れ"));
+        // 書き手を記録するが、書きかけの今日の分析は返さない
+        let activity = state.store.save_activity(&today_jst()).await.unwrap();
+        assert_eq!(activity.writers, 1);
+        let uri = format!("/api/entries/{}/stats", today_jst());
+        let (status, _, _) = send(&app, get(&uri)).await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+        let (status, _, body) = send(&app, get("/")).await;
+        assert_eq!(status, StatusCode::OK);
+        assert!(body.contains("今日は晴れ</textarea>"));
+    }
+
+    #[tokio::test]
+    async fn test_save_rejects_bad_input() {
+        let (app, _) = app();
+
+        let (status, _, body) = send(&app, post_json("/api/today", "192.0.2.1", "{")).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert!(body.contains("Invalid JSON"));
+
+        let long = format!(r#"{{"content":"{}"}}"#, "あ".repeat(10_001));
+        let (status, _, body) = send(&app, post_json("/api/today", "192.0.2.1", &long)).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert!(body.contains("Content too long"));
+    }
+
+    #[tokio::test]
+    async fn test_outdated_schema_refuses_writes() {
+        let (app, _) = app_with_schema(false);
+        let body = r#"{"content":"書けない"}"#;
+        let (status, _, body) = send(&app, post_json("/api/today", "192.0.2.1", body)).await;
+        assert_eq!(status, StatusCode::SERVICE_UNAVAILABLE);
+        assert!(body.contains("SCHEMA_OUTDATED"));
+
+        // 読み出しは続けられる
+        let (status, _, _) = send(&app, get("/api/today")).await;
+        assert_eq!(status, StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn test_pow_challenge() {
+        // プルーフ・オブ・ワークでなければチャレンジを発行しない
+        let (app, _) = app();
+        let (status, _, _) = send(&app, post_json("/api/pow/challenge", "192.0.2.1", "")).await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+
+        // 適当な解答がたまたま通らないよう、最も難しくする
+        let verifier = ServerVerifier::from_vars(|name| match name {
+            "VERIFIER" => Some("pow".to_string()),
+            "POW_DIFFICULTY" => Some("32".to_string()),
+            _ => None,
+        });
+        let (app, _) = app_with(true, verifier.unwrap());
+        let request = post_json("/api/pow/challenge", "192.0.2.1", "");
+        let (status, _, body) = send(&app, request).await;
+        assert_eq!(status, StatusCode::OK);
+        assert!(body.contains(r#""difficulty":32"#));
+        let challenge: serde_json::Value = serde_json::from_str(&body).unwrap();
+        let challenge = challenge["challenge"].as_str().unwrap();
+
+        // 解けていない解答や、別のクライアントが発行したチャレンジでは保存できない
+        let body = format!(
+            r#"{{"content":"解いていない","verification_token":"{}:0"}}"#,
+            challenge
+        );
+        let request = post_json("/api/today", "198.51.100.1", &body);
+        let (status, _, response) = send(&app, request).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert!(response.contains("POW_EXPIRED"));
+        let (status, _, response) = send(&app, post_json("/api/today", "192.0.2.1", &body)).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert!(response.contains("POW_INVALID"));
+    }
+
+    #[tokio::test]
+    async fn test_save_is_rate_limited_per_client() {
+        let (app, _) = app();
+        for i in 0..10 {
+            let body = format!(r#"{{"content":"{}回目"}}"#, i);
+            let (status, _, _) = send(&app, post_json("/api/today", "192.0.2.1", &body)).await;
+            assert_eq!(status, StatusCode::CREATED);
+        }
+
+        let body = r#"{"content":"11回目"}"#;
+        let (status, headers, body) = send(&app, post_json("/api/today", "192.0.2.1", body)).await;
+        assert_eq!(status, StatusCode::TOO_MANY_REQUESTS);
+        assert!(body.contains("RATE_LIMITED"));
+        assert!(headers.contains_key("retry-after"));
+
+        // 別のクライアントは書ける
+        let body = r#"{"content":"別の人"}"#;
+        let (status, _, _) = send(&app, post_json("/api/today", "198.51.100.1", body)).await;
+        assert_eq!(status, StatusCode::CREATED);
+    }
+
+    #[tokio::test]
+    async fn test_public_pages() {
+        let (app, state) = app();
+        state.store.upsert_entry("2025-01-14", "雪が降った").await.unwrap();
+
+        let (status, _, body) = send(&app, get("/api/entries/2025-01-14")).await;
+        assert_eq!(status, StatusCode::OK);
+        assert!(body.contains("雪が降った"));
+        let (status, _, _) = send(&app, get("/api/entries/2025-02-30")).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+
+        let (status, _, body) = send(&app, get("/entries/2025-01-14")).await;
+        assert_eq!(status, StatusCode::OK);
+        assert!(body.contains("雪が降った"));
+        let (status, _, _) = send(&app, get("/entries/yesterday")).await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+
+        let (_, _, body) = send(&app, get("/api/entries")).await;
+        assert!(body.contains("2025-01-14"));
+        let (_, headers, body) = send(&app, get("/feed")).await;
+        assert_eq!(headers[CONTENT_TYPE], "application/rss+xml; charset=utf-8");
+        assert!(body.contains("<link>http://localhost/entries/2025-01-14</link>"));
+
+        let (_, headers, body) = send(&app, get("/export/entries.csv")).await;
+        assert_eq!(headers[CONTENT_TYPE], "text/csv; charset=utf-8");
+        assert!(body.contains("\"雪が降った\""));
+        let (_, headers, body) = send(&app, get("/export/entries.jsonl")).await;
+        assert!(headers["content-disposition"].to_str().unwrap().contains(".jsonl"));
+        assert_eq!(body.lines().count(), 1);
+
+        let (_, _, body) = send(&app, get("/api/entries/2025-01-14/stats")).await;
+        assert!(body.contains(r#""characters":5"#));
+
+        let (_, _, body) = send(&app, get("/api/stats")).await;
+        assert!(body.contains(r#""total_entries":1"#));
+        let (status, _, body) = send(&app, get("/stats")).await;
+        assert_eq!(status, StatusCode::OK);
+        assert!(body.contains("2025-01-14から1日分の日記"));
+
+        // 管理画面はWorkers版だけにある
+        let (status, _, _) = send(&app, get("/admin/versions")).await;
+        assert_eq!(status, StatusCode::NOT_IMPLEMENTED);
+    }
+
+    fn admin_request(method: &str, uri: &str, token: &str, body: &str) -> Request<Body> {
+        Request::builder()
+            .method(method)
+            .uri(uri)
+            .header("Authorization", format!("Bearer {}", token))
+            .body(Body::from(body.to_string()))
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_admin_versions_require_token() {
+        let (app, state) = app();
+        state.store.upsert_entry("2025-01-14", "一回目").await.unwrap();
+        state.store.upsert_entry("2025-01-14", "二回目").await.unwrap();
+
+        let uri = "/api/admin/entries/2025-01-14/versions";
+        let (status, _, _) = send(&app, get(uri)).await;
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+        let (status, _, _) = send(&app, admin_request("GET", uri, "wrong", "")).await;
+        assert_eq!(status, StatusCode::FORBIDDEN);
+
+        let (status, _, body) = send(&app, admin_request("GET", uri, ADMIN_TOKEN, "")).await;
+        assert_eq!(status, StatusCode::OK);
+        assert!(body.contains("一回目"));
+        let uri = "/api/admin/entries/2025-01-14/versions/1";
+        let (status, _, body) = send(&app, admin_request("GET", uri, ADMIN_TOKEN, "")).await;
+        assert_eq!(status, StatusCode::OK);
+        assert!(body.contains("一回目"));
+        let uri = "/api/admin/entries/2025-01-14/versions/9";
+        let (status, _, _) = send(&app, admin_request("GET", uri, ADMIN_TOKEN, "")).await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn test_admin_export_and_import() {
+        let (app, state) = app();
+        state.store.upsert_entry("2025-01-14", "一回目").await.unwrap();
+        state.store.upsert_entry("2025-01-14", "二回目").await.unwrap();
+
+        let (status, _, _) = send(&app, get("/api/admin/export")).await;
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+        let request = admin_request("GET", "/api/admin/export", ADMIN_TOKEN, "");
+        let (status, _, exported) = send(&app, request).await;
+        assert_eq!(status, StatusCode::OK);
+        assert!(exported.contains("一回目"));
+
+        // 別のサーバーに取り込むと、版も含めて同じ内容になる
+        let (other, other_state) = app_with_schema(true);
+        let request = admin_request("POST", "/api/admin/import", "wrong", &exported);
+        let (status, _, _) = send(&other, request).await;
+        assert_eq!(status, StatusCode::FORBIDDEN);
+        let uri = "/api/admin/import?on_conflict=unknown";
+        let (status, _, _) = send(&other, admin_request("POST", uri, ADMIN_TOKEN, &exported)).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        let uri = "/api/admin/import?dry_run=1";
+        let (status, _, _) = send(&other, admin_request("POST", uri, ADMIN_TOKEN, &exported)).await;
+        assert_eq!(status, StatusCode::OK);
+        assert!(other_state.store.get_entry("2025-01-14").await.unwrap().is_none());
+        let uri = "/api/admin/import";
+        let (status, _, _) = send(&other, admin_request("POST", uri, ADMIN_TOKEN, &exported)).await;
+        assert_eq!(status, StatusCode::OK);
+        let entry = other_state.store.get_entry("2025-01-14").await.unwrap().unwrap();
+        assert_eq!(entry.content, "二回目");
+        let versions = other_state.store.list_versions("2025-01-14").await.unwrap();
+        assert_eq!(versions.len(), 1);
+        assert_eq!(versions[0].content, "一回目");
+
+        // バックアップはWorkers版だけにある
+        let request = admin_request("GET", "/api/admin/backups", ADMIN_TOKEN, "");
+        let (status, _, _) = send(&app, request).await;
+        assert_eq!(status, StatusCode::NOT_IMPLEMENTED);
+    }
+
+    #[tokio::test]
+    async fn test_held_save_is_approved_from_admin_api() {
+        let (app, state) = app();
+        state
+            .store
+            .execute_batch(
+                "INSERT INTO content_filters (kind, pattern, action, created_by, created_at)
+                 VALUES ('word', '保留語', 'hold', 'admin', '2025-01-15T00:00:00+00:00')",
+            )
+            .unwrap();
+
+        let body = r#"{"content":"保留語を含む"}"#;
+        let (status, _, _) = send(&app, post_json("/api/today", "192.0.2.1", body)).await;
+        assert_eq!(status, StatusCode::ACCEPTED);
+        assert!(state.store.get_entry(&today_jst()).await.unwrap().is_none());
+
+        let request = admin_request("GET", "/api/admin/moderation", ADMIN_TOKEN, "");
+        let (status, _, body) = send(&app, request).await;
+        assert_eq!(status, StatusCode::OK);
+        assert!(body.contains("保留語を含む"));
+        let pending = state.store.list_pending_moderation(10).await.unwrap();
+        assert_eq!(pending[0].action, FilterAction::Hold);
+
+        let uri = format!("/api/admin/moderation/{}", pending[0].id);
+        let request = admin_request("POST", &uri, ADMIN_TOKEN, r#"{"resolution":"reverted"}"#);
+        let (status, _, _) = send(&app, request).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        let request = admin_request("POST", &uri, ADMIN_TOKEN, r#"{"resolution":"approved"}"#);
+        let (status, _, body) = send(&app, request).await;
+        assert_eq!(status, StatusCode::OK);
+        assert!(body.contains(r#""resolution":"approved""#));
+        assert!(body.contains(r#""resolved_by":"bootstrap""#));
+        let entry = state.store.get_entry(&today_jst()).await.unwrap().unwrap();
+        assert_eq!(entry.content, "保留語を含む");
+
+        let request = admin_request("POST", &uri, ADMIN_TOKEN, r#"{"resolution":"approved"}"#);
+        let (status, _, _) = send(&app, request).await;
+        assert_eq!(status, StatusCode::CONFLICT);
+    }
+
+    #[tokio::test]
+    async fn test_save_with_api_key() {
+        let (app, state) = app();
+        let key = "dk_test_key_for_server";
+        state
+            .store
+            .execute_batch(&format!(
+                "INSERT INTO api_keys (label, key_prefix, key_hash, daily_quota, created_by, created_at)
+                 VALUES ('bot', 'dk_test_key', '{}', 1, 'admin', '2025-01-15T00:00:00+00:00')",
+                darekagakaku_core::api_keys::hash_key(key)
+            ))
+            .unwrap();
+
+        let save = |content: &str| {
+            Request::post("/api/today")
+                .header("Content-Type", "application/json")
+                .header("Authorization", format!("Bearer {}", key))
+                .body(Body::from(format!(r#"{{"content":"{}"}}"#, content)))
+                .unwrap()
+        };
+        let (status, _, _) = send(&app, save("キーで書いた")).await;
+        assert_eq!(status, StatusCode::CREATED);
+        // 1日の上限を超えた
+        let (status, _, _) = send(&app, save("もう一度")).await;
+        assert_eq!(status, StatusCode::TOO_MANY_REQUESTS);
+
+        let request = Request::post("/api/today")
+            .header("Authorization", "Bearer dk_unknown")
+            .body(Body::from(r#"{"content":"x"}"#))
+            .unwrap();
+        let (status, _, _) = send(&app, request).await;
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+    }
+}
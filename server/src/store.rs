@@ -0,0 +1,987 @@
+use std::path::Path;
+use std::sync::{Arc, Mutex, MutexGuard};
+
+use darekagakaku_core::entry_stats::SaveActivity;
+use darekagakaku_core::migrations::{self, SchemaStatus, CREATE_MIGRATIONS_TABLE};
+use darekagakaku_core::models::{
+    ApiKey, ContentFilter, DiaryEntry, DiaryVersion, FilterAction, FilterKind, ModerationItem,
+    ModerationResolution,
+};
+use darekagakaku_core::stats::{DayOverwrites, MonthTotals, StatsTotals};
+use darekagakaku_core::store::{
+    ApiKeyStore, ClearRange, DiaryStore, ImportWrite, ModerationStore, PowStore,
+};
+use rusqlite::types::{FromSql, FromSqlError, FromSqlResult, ValueRef};
+use rusqlite::{params, Connection, OptionalExtension, Row};
+
+use crate::time::now_iso8601;
+
+/// SQLite上の日記の保存先
+///
+/// 接続は1本だけ持ち、リクエストごとに順番に使う。
+/// rusqliteの呼び出しは同期なので、ランタイムのワーカーを止めないようブロッキング用のスレッドで行う。
+pub struct SqliteStore {
+    conn: Arc<Mutex<Connection>>,
+}
+
+impl SqliteStore {
+    /// ファイルを開く（マイグレーションは当てない）
+    pub fn open(path: impl AsRef<Path>) -> rusqlite::Result<Self> {
+        Self::init(Connection::open(path)?)
+    }
+
+    /// メモリ上のデータベースを作り、マイグレーションをすべて当てる
+    #[cfg(test)]
+    pub fn open_in_memory() -> rusqlite::Result<Self> {
+        let store = Self::init(Connection::open_in_memory()?)?;
+        store.migrate()?;
+        Ok(store)
+    }
+
+    /// テスト用にSQLを直接実行する（管理画面のないデータの用意に使う）
+    #[cfg(test)]
+    pub fn execute_batch(&self, sql: &str) -> rusqlite::Result<()> {
+        self.conn().execute_batch(sql)
+    }
+
+    fn init(conn: Connection) -> rusqlite::Result<Self> {
+        conn.pragma_update(None, "foreign_keys", "ON")?;
+        conn.execute_batch(CREATE_MIGRATIONS_TABLE)?;
+        Ok(Self {
+            conn: Arc::new(Mutex::new(conn)),
+        })
+    }
+
+    /// 適用済みのマイグレーションの名前
+    pub fn applied_migrations(&self) -> rusqlite::Result<Vec<String>> {
+        let conn = self.conn();
+        let mut stmt = conn.prepare("SELECT name FROM schema_migrations ORDER BY name")?;
+        let names = stmt.query_map([], |row| row.get(0))?;
+        names.collect()
+    }
+
+    /// スキーマがこのビルドと合っているか
+    pub fn schema_status(&self) -> rusqlite::Result<SchemaStatus> {
+        Ok(migrations::status(&self.applied_migrations()?))
+    }
+
+    /// 未適用のマイグレーションを順に当て、当てたものの名前を返す
+    ///
+    /// 1つずつトランザクションにまとめ、途中で失敗してもそれより前の分は記録が残る。
+    pub fn migrate(&self) -> rusqlite::Result<Vec<&'static str>> {
+        let pending = migrations::pending(&self.applied_migrations()?);
+        let mut conn = self.conn();
+        let mut applied = Vec::new();
+        for migration in pending {
+            let tx = conn.transaction()?;
+            tx.execute_batch(migration.sql)?;
+            tx.execute(
+                "INSERT INTO schema_migrations (name) VALUES (?1)",
+                params![migration.name],
+            )?;
+            tx.commit()?;
+            applied.push(migration.name);
+        }
+        Ok(applied)
+    }
+
+    fn conn(&self) -> MutexGuard<'_, Connection> {
+        lock(&self.conn)
+    }
+
+    /// 接続を使う処理をブロッキング用のスレッドで行う
+    ///
+    /// tokioのランタイムの外（起動前の処理やテスト）では、その場で行う。
+    async fn with_conn<T, F>(&self, f: F) -> rusqlite::Result<T>
+    where
+        T: Send + 'static,
+        F: FnOnce(&mut Connection) -> rusqlite::Result<T> + Send + 'static,
+    {
+        let conn = self.conn.clone();
+        let run = move || f(&mut lock(&conn));
+        match tokio::runtime::Handle::try_current() {
+            Ok(handle) => match handle.spawn_blocking(run).await {
+                Ok(result) => result,
+                Err(e) => std::panic::resume_unwind(e.into_panic()),
+            },
+            Err(_) => run(),
+        }
+    }
+}
+
+fn lock(conn: &Mutex<Connection>) -> MutexGuard<'_, Connection> {
+    // 途中でpanicしたリクエストがあっても、SQLite側のトランザクションは巻き戻っている
+    conn.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+fn entry_from_row(row: &Row) -> rusqlite::Result<DiaryEntry> {
+    Ok(DiaryEntry {
+        date: row.get(0)?,
+        content: row.get(1)?,
+        created_at: row.get(2)?,
+        updated_at: row.get(3)?,
+    })
+}
+
+fn version_from_row(row: &Row) -> rusqlite::Result<DiaryVersion> {
+    Ok(DiaryVersion {
+        id: row.get(0)?,
+        entry_date: row.get(1)?,
+        content: row.get(2)?,
+        version_number: row.get(3)?,
+        created_at: row.get(4)?,
+    })
+}
+
+/// 列の文字列を列挙型として読む
+fn parse_column<T>(value: ValueRef<'_>, parse: impl Fn(&str) -> Option<T>) -> FromSqlResult<T> {
+    let text = value.as_str()?;
+    parse(text).ok_or_else(|| FromSqlError::Other(format!("unknown value {:?}", text).into()))
+}
+
+/// 列挙型の列を読むための包み
+struct Column<T>(T);
+
+impl FromSql for Column<FilterKind> {
+    fn column_result(value: ValueRef<'_>) -> FromSqlResult<Self> {
+        parse_column(value, FilterKind::parse).map(Column)
+    }
+}
+
+impl FromSql for Column<FilterAction> {
+    fn column_result(value: ValueRef<'_>) -> FromSqlResult<Self> {
+        parse_column(value, FilterAction::parse).map(Column)
+    }
+}
+
+impl FromSql for Column<ModerationResolution> {
+    fn column_result(value: ValueRef<'_>) -> FromSqlResult<Self> {
+        parse_column(value, ModerationResolution::parse).map(Column)
+    }
+}
+
+const MODERATION_COLUMNS: &str = "id, entry_date, content, previous_content, action, reasons, \
+     created_at, resolution, resolved_by, resolved_at";
+
+fn moderation_from_row(row: &Row) -> rusqlite::Result<ModerationItem> {
+    Ok(ModerationItem {
+        id: row.get(0)?,
+        entry_date: row.get(1)?,
+        content: row.get(2)?,
+        previous_content: row.get(3)?,
+        action: row.get::<_, Column<FilterAction>>(4)?.0,
+        reasons: row.get(5)?,
+        created_at: row.get(6)?,
+        resolution: row.get::<_, Option<Column<ModerationResolution>>>(7)?.map(|c| c.0),
+        resolved_by: row.get(8)?,
+        resolved_at: row.get(9)?,
+    })
+}
+
+impl ModerationStore for SqliteStore {
+    type Error = rusqlite::Error;
+
+    async fn list_content_filters(&self) -> rusqlite::Result<Vec<ContentFilter>> {
+        self.with_conn(|conn| {
+            let mut stmt = conn.prepare(
+                "SELECT id, kind, pattern, action, created_by, created_at
+                 FROM content_filters ORDER BY id",
+            )?;
+            let filters = stmt.query_map([], |row| {
+                Ok(ContentFilter {
+                    id: row.get(0)?,
+                    kind: row.get::<_, Column<FilterKind>>(1)?.0,
+                    pattern: row.get(2)?,
+                    action: row.get::<_, Column<FilterAction>>(3)?.0,
+                    created_by: row.get(4)?,
+                    created_at: row.get(5)?,
+                })
+            })?;
+            filters.collect()
+        })
+        .await
+    }
+
+    async fn insert_moderation_item(
+        &self,
+        date: &str,
+        content: &str,
+        previous_content: Option<&str>,
+        action: FilterAction,
+        reasons: &str,
+    ) -> rusqlite::Result<()> {
+        let (date, content, reasons) = (date.to_string(), content.to_string(), reasons.to_string());
+        let previous_content = previous_content.map(str::to_string);
+        self.with_conn(move |conn| {
+            conn.execute(
+                "INSERT INTO moderation_queue
+                   (entry_date, content, previous_content, action, reasons, created_at)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+                params![date, content, previous_content, action.as_str(), reasons, now_iso8601()],
+            )?;
+            Ok(())
+        })
+        .await
+    }
+
+    async fn list_pending_moderation(&self, limit: i32) -> rusqlite::Result<Vec<ModerationItem>> {
+        self.with_conn(move |conn| {
+            let mut stmt = conn.prepare(&format!(
+                "SELECT {} FROM moderation_queue WHERE resolution IS NULL ORDER BY id LIMIT ?1",
+                MODERATION_COLUMNS
+            ))?;
+            let items = stmt.query_map(params![limit], moderation_from_row)?;
+            items.collect()
+        })
+        .await
+    }
+
+    async fn get_moderation_item(&self, id: i64) -> rusqlite::Result<Option<ModerationItem>> {
+        self.with_conn(move |conn| {
+            conn.query_row(
+                &format!("SELECT {} FROM moderation_queue WHERE id = ?1", MODERATION_COLUMNS),
+                params![id],
+                moderation_from_row,
+            )
+            .optional()
+        })
+        .await
+    }
+
+    async fn resolve_moderation_item(
+        &self,
+        id: i64,
+        resolution: ModerationResolution,
+        resolved_by: &str,
+    ) -> rusqlite::Result<bool> {
+        let resolved_by = resolved_by.to_string();
+        self.with_conn(move |conn| {
+            let changes = conn.execute(
+                "UPDATE moderation_queue SET resolution = ?2, resolved_by = ?3, resolved_at = ?4
+                 WHERE id = ?1 AND resolution IS NULL",
+                params![id, resolution.as_str(), resolved_by, now_iso8601()],
+            )?;
+            Ok(changes > 0)
+        })
+        .await
+    }
+
+    async fn redact_moderation_content(&self, id: i64, replacement: &str) -> rusqlite::Result<()> {
+        let replacement = replacement.to_string();
+        self.with_conn(move |conn| {
+            conn.execute(
+                "UPDATE moderation_queue SET content = ?2 WHERE id = ?1",
+                params![id, replacement],
+            )?;
+            Ok(())
+        })
+        .await
+    }
+
+    async fn redact_versions_with_content(
+        &self,
+        date: &str,
+        content: &str,
+        replacement: &str,
+    ) -> rusqlite::Result<()> {
+        let (date, content) = (date.to_string(), content.to_string());
+        let replacement = replacement.to_string();
+        self.with_conn(move |conn| {
+            conn.execute(
+                "UPDATE diary_versions SET content = ?3 WHERE entry_date = ?1 AND content = ?2",
+                params![date, content, replacement],
+            )?;
+            Ok(())
+        })
+        .await
+    }
+}
+
+impl ApiKeyStore for SqliteStore {
+    type Error = rusqlite::Error;
+
+    async fn get_api_key_by_hash(&self, key_hash: &str) -> rusqlite::Result<Option<ApiKey>> {
+        let key_hash = key_hash.to_string();
+        self.with_conn(move |conn| {
+            conn.query_row(
+                "SELECT id, label, key_prefix, daily_quota, created_by, created_at,
+                        last_used_at, revoked_at
+                 FROM api_keys WHERE key_hash = ?1",
+                params![key_hash],
+                |row| {
+                    Ok(ApiKey {
+                        id: row.get(0)?,
+                        label: row.get(1)?,
+                        key_prefix: row.get(2)?,
+                        daily_quota: row.get(3)?,
+                        created_by: row.get(4)?,
+                        created_at: row.get(5)?,
+                        last_used_at: row.get(6)?,
+                        revoked_at: row.get(7)?,
+                    })
+                },
+            )
+            .optional()
+        })
+        .await
+    }
+
+    async fn consume_api_key_quota(
+        &self,
+        key_id: i64,
+        date: &str,
+        daily_quota: i64,
+    ) -> rusqlite::Result<bool> {
+        let date = date.to_string();
+        self.with_conn(move |conn| {
+            let changes = conn.execute(
+                "INSERT INTO api_key_usage (key_id, date, count)
+                 SELECT ?1, ?2, 1 WHERE ?3 > 0
+                 ON CONFLICT(key_id, date) DO UPDATE SET count = count + 1
+                 WHERE count < ?3",
+                params![key_id, date, daily_quota],
+            )?;
+            Ok(changes > 0)
+        })
+        .await
+    }
+
+    async fn record_api_key_write(
+        &self,
+        key_id: i64,
+        date: &str,
+        content_hash: &str,
+    ) -> rusqlite::Result<()> {
+        let (date, content_hash) = (date.to_string(), content_hash.to_string());
+        self.with_conn(move |conn| {
+            let now = now_iso8601();
+            let tx = conn.transaction()?;
+            tx.execute(
+                "INSERT INTO api_key_writes (key_id, entry_date, content_hash, created_at)
+                 VALUES (?1, ?2, ?3, ?4)",
+                params![key_id, date, content_hash, now],
+            )?;
+            tx.execute(
+                "UPDATE api_keys SET last_used_at = ?2 WHERE id = ?1",
+                params![key_id, now],
+            )?;
+            tx.commit()
+        })
+        .await
+    }
+}
+
+impl PowStore for SqliteStore {
+    type Error = rusqlite::Error;
+
+    async fn insert_pow_challenge(
+        &self,
+        challenge: &str,
+        client: &str,
+        difficulty: u32,
+        expires_at: i64,
+        now: i64,
+    ) -> rusqlite::Result<()> {
+        let challenge = challenge.to_string();
+        let client = client.to_string();
+        self.with_conn(move |conn| {
+            let tx = conn.transaction()?;
+            tx.execute(
+                "DELETE FROM pow_challenges WHERE expires_at <= ?1",
+                params![now],
+            )?;
+            tx.execute(
+                "INSERT INTO pow_challenges (challenge, client, difficulty, expires_at)
+                 VALUES (?1, ?2, ?3, ?4)",
+                params![challenge, client, difficulty, expires_at],
+            )?;
+            tx.commit()
+        })
+        .await
+    }
+
+    async fn take_pow_challenge(
+        &self,
+        challenge: &str,
+        client: &str,
+        now: i64,
+    ) -> rusqlite::Result<Option<u32>> {
+        let challenge = challenge.to_string();
+        let client = client.to_string();
+        self.with_conn(move |conn| {
+            conn.query_row(
+                "DELETE FROM pow_challenges
+                 WHERE challenge = ?1 AND client = ?2 AND expires_at > ?3
+                 RETURNING difficulty",
+                params![challenge, client, now],
+                |row| row.get(0),
+            )
+            .optional()
+        })
+        .await
+    }
+}
+
+impl DiaryStore for SqliteStore {
+    type Error = rusqlite::Error;
+
+    async fn get_entry(&self, date: &str) -> rusqlite::Result<Option<DiaryEntry>> {
+        let date = date.to_string();
+        self.with_conn(move |conn| {
+            conn.query_row(
+                "SELECT date, content, created_at, updated_at FROM diary_entries WHERE date = ?1",
+                params![date],
+                entry_from_row,
+            )
+            .optional()
+        })
+        .await
+    }
+
+    async fn upsert_entry(&self, date: &str, content: &str) -> rusqlite::Result<()> {
+        let (date, content) = (date.to_string(), content.to_string());
+        self.with_conn(move |conn| {
+            let now = now_iso8601();
+            // D1と違い1つのトランザクションにまとめ、版の番号が重ならないようにする
+            let tx = conn.transaction()?;
+
+            let previous: Option<String> = tx
+                .query_row(
+                    "SELECT content FROM diary_entries WHERE date = ?1",
+                    params![date],
+                    |row| row.get(0),
+                )
+                .optional()?;
+            if let Some(previous) = previous.filter(|p| *p != content) {
+                tx.execute(
+                    "INSERT INTO diary_versions (entry_date, content, version_number, created_at)
+                     SELECT ?1, ?2, COALESCE(MAX(version_number), 0) + 1, ?3
+                     FROM diary_versions WHERE entry_date = ?1",
+                    params![date, previous, now],
+                )?;
+            }
+
+            tx.execute(
+                "INSERT INTO diary_entries (date, content, created_at, updated_at)
+                 VALUES (?1, ?2, ?3, ?3)
+                 ON CONFLICT(date) DO UPDATE SET
+                   content = excluded.content,
+                   updated_at = excluded.updated_at",
+                params![date, content, now],
+            )?;
+            tx.commit()
+        })
+        .await
+    }
+
+    async fn list_versions(&self, date: &str) -> rusqlite::Result<Vec<DiaryVersion>> {
+        let date = date.to_string();
+        self.with_conn(move |conn| {
+            let mut stmt = conn.prepare(
+                "SELECT id, entry_date, content, version_number, created_at
+                 FROM diary_versions
+                 WHERE entry_date = ?1
+                 ORDER BY version_number DESC",
+            )?;
+            let versions = stmt.query_map(params![date], version_from_row)?;
+            versions.collect()
+        })
+        .await
+    }
+
+    async fn get_version(
+        &self,
+        date: &str,
+        version: i32,
+    ) -> rusqlite::Result<Option<DiaryVersion>> {
+        let date = date.to_string();
+        self.with_conn(move |conn| {
+            conn.query_row(
+                "SELECT id, entry_date, content, version_number, created_at
+                 FROM diary_versions
+                 WHERE entry_date = ?1 AND version_number = ?2",
+                params![date, version],
+                version_from_row,
+            )
+            .optional()
+        })
+        .await
+    }
+
+    async fn redact_version(
+        &self,
+        date: &str,
+        version: i32,
+        replacement: &str,
+    ) -> rusqlite::Result<()> {
+        let (date, replacement) = (date.to_string(), replacement.to_string());
+        self.with_conn(move |conn| {
+            conn.execute(
+                "UPDATE diary_versions SET content = ?3
+                 WHERE entry_date = ?1 AND version_number = ?2",
+                params![date, version, replacement],
+            )?;
+            Ok(())
+        })
+        .await
+    }
+
+    async fn list_entries_before(
+        &self,
+        date: &str,
+        limit: i32,
+    ) -> rusqlite::Result<Vec<DiaryEntry>> {
+        let date = date.to_string();
+        self.with_conn(move |conn| {
+            let mut stmt = conn.prepare(
+                "SELECT date, content, created_at, updated_at
+                 FROM diary_entries
+                 WHERE date < ?1
+                 ORDER BY date DESC
+                 LIMIT ?2",
+            )?;
+            let entries = stmt.query_map(params![date, limit], entry_from_row)?;
+            entries.collect()
+        })
+        .await
+    }
+
+    async fn list_entries_after(
+        &self,
+        date: &str,
+        limit: i32,
+    ) -> rusqlite::Result<Vec<DiaryEntry>> {
+        let date = date.to_string();
+        self.with_conn(move |conn| {
+            let mut stmt = conn.prepare(
+                "SELECT date, content, created_at, updated_at
+                 FROM diary_entries
+                 WHERE date > ?1
+                 ORDER BY date ASC
+                 LIMIT ?2",
+            )?;
+            let entries = stmt.query_map(params![date, limit], entry_from_row)?;
+            entries.collect()
+        })
+        .await
+    }
+
+    async fn list_versions_after(
+        &self,
+        id: i64,
+        limit: i32,
+    ) -> rusqlite::Result<Vec<DiaryVersion>> {
+        self.with_conn(move |conn| {
+            let mut stmt = conn.prepare(
+                "SELECT id, entry_date, content, version_number, created_at
+                 FROM diary_versions
+                 WHERE id > ?1
+                 ORDER BY id ASC
+                 LIMIT ?2",
+            )?;
+            let versions = stmt.query_map(params![id, limit], version_from_row)?;
+            versions.collect()
+        })
+        .await
+    }
+
+    async fn write_import(&self, writes: &[ImportWrite]) -> rusqlite::Result<()> {
+        let writes = writes.to_vec();
+        self.with_conn(move |conn| {
+            let tx = conn.transaction()?;
+            for write in &writes {
+                match write {
+                    ImportWrite::Clear(range) => {
+                        let (condition, date) = match range {
+                            ClearRange::Date(date) => ("= ?1", date),
+                            ClearRange::Before(date) => ("< ?1", date),
+                        };
+                        // 日記を参照している行から消す
+                        for table in ["entry_saves", "entry_writer_counts", "diary_versions"] {
+                            tx.execute(
+                                &format!("DELETE FROM {} WHERE entry_date {}", table, condition),
+                                params![date],
+                            )?;
+                        }
+                        tx.execute(
+                            &format!("DELETE FROM diary_entries WHERE date {}", condition),
+                            params![date],
+                        )?
+                    }
+                    ImportWrite::Entry(entry) => tx.execute(
+                        "INSERT INTO diary_entries (date, content, created_at, updated_at)
+                         VALUES (?1, ?2, ?3, ?4)
+                         ON CONFLICT(date) DO UPDATE SET
+                           content = excluded.content,
+                           created_at = excluded.created_at,
+                           updated_at = excluded.updated_at",
+                        params![entry.date, entry.content, entry.created_at, entry.updated_at],
+                    )?,
+                    ImportWrite::Version {
+                        date,
+                        content,
+                        created_at,
+                    } => tx.execute(
+                        "INSERT INTO diary_versions
+                           (entry_date, content, version_number, created_at)
+                         SELECT ?1, ?2, COALESCE(MAX(version_number), 0) + 1, ?3
+                         FROM diary_versions WHERE entry_date = ?1",
+                        params![date, content, created_at],
+                    )?,
+                };
+            }
+            tx.commit()
+        })
+        .await
+    }
+
+    async fn stats_totals(&self, limit: i32) -> rusqlite::Result<StatsTotals> {
+        self.with_conn(move |conn| {
+            let mut totals = StatsTotals {
+                first_date: conn.query_row("SELECT MIN(date) FROM diary_entries", [], |row| {
+                    row.get(0)
+                })?,
+                ..StatsTotals::default()
+            };
+
+            let mut stmt = conn
+                .prepare("SELECT month, entries, total_length FROM stats_months ORDER BY month")?;
+            let months = stmt.query_map([], |row| {
+                Ok(MonthTotals {
+                    month: row.get(0)?,
+                    entries: row.get(1)?,
+                    total_length: row.get(2)?,
+                })
+            })?;
+            totals.months = months.collect::<rusqlite::Result<_>>()?;
+
+            let mut stmt = conn.prepare(
+                "SELECT date, versions FROM stats_days ORDER BY versions DESC, date LIMIT ?1",
+            )?;
+            let days = stmt.query_map(params![limit], |row| {
+                Ok(DayOverwrites {
+                    date: row.get(0)?,
+                    versions: row.get(1)?,
+                })
+            })?;
+            totals.most_overwritten = days.collect::<rusqlite::Result<_>>()?;
+
+            let mut stmt = conn.prepare("SELECT hour, saves FROM stats_hours")?;
+            let mut rows = stmt.query([])?;
+            while let Some(row) = rows.next()? {
+                let hour: usize = row.get(0)?;
+                if let Some(saves) = totals.hours.get_mut(hour) {
+                    *saves = row.get(1)?;
+                }
+            }
+            Ok(totals)
+        })
+        .await
+    }
+
+    async fn record_save(&self, date: &str, writer: &str) -> rusqlite::Result<()> {
+        let (date, writer) = (date.to_string(), writer.to_string());
+        self.with_conn(move |conn| {
+            conn.execute(
+                "INSERT INTO entry_saves (entry_date, writer, created_at) VALUES (?1, ?2, ?3)",
+                params![date, writer, now_iso8601()],
+            )?;
+            Ok(())
+        })
+        .await
+    }
+
+    async fn save_activity(&self, date: &str) -> rusqlite::Result<SaveActivity> {
+        let date = date.to_string();
+        self.with_conn(move |conn| {
+            conn.query_row(
+                "SELECT
+                   (SELECT COUNT(*) FROM diary_versions WHERE entry_date = ?1),
+                   (SELECT COUNT(DISTINCT writer) FROM entry_saves WHERE entry_date = ?1)
+                     + COALESCE((SELECT writers FROM entry_writer_counts WHERE entry_date = ?1), 0),
+                   (SELECT created_at FROM diary_versions WHERE entry_date = ?1
+                    ORDER BY version_number DESC LIMIT 1)",
+                params![date],
+                |row| {
+                    Ok(SaveActivity {
+                        versions: row.get(0)?,
+                        writers: row.get(1)?,
+                        last_changed_at: row.get(2)?,
+                    })
+                },
+            )
+        })
+        .await
+    }
+
+    async fn compact_saves(&self, before: &str) -> rusqlite::Result<()> {
+        let before = before.to_string();
+        self.with_conn(move |conn| {
+            let tx = conn.transaction()?;
+            tx.execute(
+                "INSERT INTO entry_writer_counts (entry_date, writers)
+                 SELECT entry_date, COUNT(DISTINCT writer) FROM entry_saves
+                 WHERE entry_date < ?1 GROUP BY entry_date
+                 ON CONFLICT(entry_date) DO UPDATE SET writers = writers + excluded.writers",
+                params![before],
+            )?;
+            tx.execute("DELETE FROM entry_saves WHERE entry_date < ?1", params![before])?;
+            tx.commit()
+        })
+        .await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use darekagakaku_core::store::{block_on, MemoryStore};
+
+    use super::*;
+
+    #[test]
+    fn test_migrate_records_and_skips_applied() {
+        let store = SqliteStore::init(Connection::open_in_memory().unwrap()).unwrap();
+        assert!(!store.schema_status().unwrap().is_writable());
+
+        let applied = store.migrate().unwrap();
+        assert_eq!(applied.len(), migrations::MIGRATIONS.len());
+        assert_eq!(store.schema_status().unwrap(), SchemaStatus::Current);
+        // 2回目は何も当てない
+        assert!(store.migrate().unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_pre_migration_database_applies_all() {
+        // 導入前のデータベースには日記と版のテーブルしかない
+        let conn = Connection::open_in_memory().unwrap();
+        conn.execute_batch(
+            "CREATE TABLE diary_entries (
+                 date TEXT PRIMARY KEY, content TEXT NOT NULL,
+                 created_at TEXT NOT NULL, updated_at TEXT NOT NULL);
+             INSERT INTO diary_entries VALUES ('2025-01-15', '残す', 't', 't');",
+        )
+        .unwrap();
+        let store = SqliteStore::init(conn).unwrap();
+        assert!(store.applied_migrations().unwrap().is_empty());
+
+        // 0001も当て、足りない管理用のテーブルを作る
+        let applied = store.migrate().unwrap();
+        assert_eq!(applied.len(), migrations::MIGRATIONS.len());
+        assert_eq!(store.schema_status().unwrap(), SchemaStatus::Current);
+        let entry = block_on(store.get_entry("2025-01-15")).unwrap().unwrap();
+        assert_eq!(entry.content, "残す");
+        assert!(block_on(store.list_pending_moderation(10)).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_sqlite_store_keeps_versions() {
+        let store = SqliteStore::open_in_memory().unwrap();
+        block_on(store.upsert_entry("2025-01-15", "一回目")).unwrap();
+        block_on(store.upsert_entry("2025-01-15", "二回目")).unwrap();
+        // 同じ内容の保存では版を増やさない
+        block_on(store.upsert_entry("2025-01-15", "二回目")).unwrap();
+        block_on(store.upsert_entry("2025-01-15", "三回目")).unwrap();
+
+        let entry = block_on(store.get_entry("2025-01-15")).unwrap().unwrap();
+        assert_eq!(entry.content, "三回目");
+
+        let versions = block_on(store.list_versions("2025-01-15")).unwrap();
+        let contents: Vec<_> = versions.iter().map(|v| v.content.as_str()).collect();
+        assert_eq!(contents, ["二回目", "一回目"]);
+        assert_eq!(versions[0].version_number, 2);
+
+        block_on(store.redact_version("2025-01-15", 1, "[削除]")).unwrap();
+        let version = block_on(store.get_version("2025-01-15", 1)).unwrap().unwrap();
+        assert_eq!(version.content, "[削除]");
+        assert!(block_on(store.get_version("2025-01-15", 3)).unwrap().is_none());
+    }
+
+    #[test]
+    fn test_write_import_is_all_or_nothing() {
+        let store = SqliteStore::open_in_memory().unwrap();
+        let entry = DiaryEntry {
+            date: "2025-01-10".to_string(),
+            content: "取り込み".to_string(),
+            created_at: "2025-01-10T00:00:00+09:00".to_string(),
+            updated_at: "2025-01-10T00:00:00+09:00".to_string(),
+        };
+        // 日記のない日付の版は外部キーで失敗し、先に書いた日記も取り消される
+        let writes = [
+            ImportWrite::Entry(entry),
+            ImportWrite::Version {
+                date: "2025-01-11".to_string(),
+                content: "迷子".to_string(),
+                created_at: "2025-01-11T00:00:00+09:00".to_string(),
+            },
+        ];
+        assert!(block_on(store.write_import(&writes)).is_err());
+        assert!(block_on(store.get_entry("2025-01-10")).unwrap().is_none());
+    }
+
+    #[test]
+    fn test_compact_saves_keeps_writer_counts() {
+        let store = SqliteStore::open_in_memory().unwrap();
+        for (date, writer) in [("2025-01-01", "a"), ("2025-01-01", "b"), ("2025-01-10", "a")] {
+            block_on(store.upsert_entry(date, writer)).unwrap();
+            block_on(store.record_save(date, writer)).unwrap();
+        }
+
+        block_on(store.compact_saves("2025-01-05")).unwrap();
+        // 同じ日にまとめた後の書き手は人数に足す
+        block_on(store.record_save("2025-01-01", "c")).unwrap();
+        block_on(store.compact_saves("2025-01-05")).unwrap();
+        assert_eq!(block_on(store.save_activity("2025-01-01")).unwrap().writers, 3);
+        assert_eq!(block_on(store.save_activity("2025-01-10")).unwrap().writers, 1);
+        let hashes: i64 = store
+            .conn()
+            .query_row("SELECT COUNT(*) FROM entry_saves", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(hashes, 1);
+    }
+
+    #[test]
+    fn test_sqlite_store_lists_entries_before_date() {
+        let store = SqliteStore::open_in_memory().unwrap();
+        for date in ["2025-01-13", "2025-01-14", "2025-01-15"] {
+            block_on(store.upsert_entry(date, date)).unwrap();
+        }
+
+        let dates = |before, limit| -> Vec<String> {
+            block_on(store.list_entries_before(before, limit))
+                .unwrap()
+                .into_iter()
+                .map(|e| e.date)
+                .collect()
+        };
+        assert_eq!(dates("2025-01-15", 100), ["2025-01-14", "2025-01-13"]);
+        assert_eq!(dates("2025-01-15", 1), ["2025-01-14"]);
+        assert!(dates("2025-01-13", 100).is_empty());
+    }
+
+    /// 同じ書き込みをメモリ上のストアとSQLiteの両方に行う
+    fn write_both(sqlite: &SqliteStore, memory: &MemoryStore) {
+        let writes: [(&str, &str); 4] = [
+            ("2025-01-13", "あいう"),
+            ("2025-01-13", "あいうえお"),
+            ("2025-01-14", "雪"),
+            ("2025-02-01", "二月"),
+        ];
+        for (date, content) in writes {
+            block_on(sqlite.upsert_entry(date, content)).unwrap();
+            block_on(memory.upsert_entry(date, content)).unwrap();
+        }
+        let imported = DiaryEntry {
+            date: "2025-01-14".to_string(),
+            content: "取り込んだ雪".to_string(),
+            created_at: "2025-01-14T03:00:00+09:00".to_string(),
+            updated_at: "2025-01-14T23:00:00+09:00".to_string(),
+        };
+        let writes = [
+            ImportWrite::Entry(imported),
+            ImportWrite::Version {
+                date: "2025-01-14".to_string(),
+                content: "雪".to_string(),
+                created_at: "2025-01-14T12:00:00+09:00".to_string(),
+            },
+        ];
+        block_on(sqlite.write_import(&writes)).unwrap();
+        block_on(memory.write_import(&writes)).unwrap();
+    }
+
+    #[test]
+    fn test_stats_triggers_match_full_count() {
+        let sqlite = SqliteStore::open_in_memory().unwrap();
+        let memory = MemoryStore::default();
+        memory.set_now(&now_iso8601());
+        write_both(&sqlite, &memory);
+
+        let totals = block_on(sqlite.stats_totals(10)).unwrap();
+        assert_eq!(totals, block_on(memory.stats_totals(10)).unwrap());
+        assert_eq!(totals.first_date.as_deref(), Some("2025-01-13"));
+        assert_eq!(totals.months[0].total_length, 5 + 6);
+        assert_eq!(totals.hours.iter().sum::<i64>(), 5);
+
+        // 置き換えのリストアで消した分も集計から外れる
+        let ranges = [
+            ClearRange::Date("2025-01-14".to_string()),
+            ClearRange::Before("2025-02-01".to_string()),
+        ];
+        for range in ranges {
+            let writes = [ImportWrite::Clear(range)];
+            block_on(sqlite.write_import(&writes)).unwrap();
+            block_on(memory.write_import(&writes)).unwrap();
+            let totals = block_on(sqlite.stats_totals(10)).unwrap();
+            assert_eq!(totals, block_on(memory.stats_totals(10)).unwrap());
+        }
+        let totals = block_on(sqlite.stats_totals(10)).unwrap();
+        assert_eq!(totals.first_date.as_deref(), Some("2025-02-01"));
+        assert_eq!(totals.hours.iter().sum::<i64>(), 1);
+    }
+
+    #[test]
+    fn test_stats_migration_counts_existing_data() {
+        let sqlite = SqliteStore::init(Connection::open_in_memory().unwrap()).unwrap();
+        sqlite.conn().execute_batch(migrations::MIGRATIONS[0].sql).unwrap();
+        let memory = MemoryStore::default();
+        memory.set_now(&now_iso8601());
+        write_both(&sqlite, &memory);
+
+        // 集計表のない時期に書かれたデータを、マイグレーションで数える
+        sqlite.migrate().unwrap();
+        let totals = block_on(sqlite.stats_totals(10)).unwrap();
+        assert_eq!(totals, block_on(memory.stats_totals(10)).unwrap());
+    }
+
+    #[test]
+    fn test_api_key_quota_and_writes() {
+        let store = SqliteStore::open_in_memory().unwrap();
+        store
+            .conn()
+            .execute(
+                "INSERT INTO api_keys (label, key_prefix, key_hash, daily_quota, created_by, created_at)
+                 VALUES ('bot', 'dk_abcdefgh', 'hash', 2, 'admin', '2025-01-15T00:00:00+00:00')",
+                [],
+            )
+            .unwrap();
+        let api_key = block_on(store.get_api_key_by_hash("hash")).unwrap().unwrap();
+        assert_eq!(api_key.daily_quota, 2);
+        assert!(block_on(store.get_api_key_by_hash("other")).unwrap().is_none());
+
+        let consume = |date| block_on(store.consume_api_key_quota(api_key.id, date, 2)).unwrap();
+        assert!(consume("2025-01-15"));
+        assert!(consume("2025-01-15"));
+        assert!(!consume("2025-01-15"));
+        // 日付が変われば数え直す
+        assert!(consume("2025-01-16"));
+        assert!(!block_on(store.consume_api_key_quota(api_key.id, "2025-01-17", 0)).unwrap());
+
+        block_on(store.record_api_key_write(api_key.id, "2025-01-15", "abc")).unwrap();
+        let api_key = block_on(store.get_api_key_by_hash("hash")).unwrap().unwrap();
+        assert!(api_key.last_used_at.is_some());
+    }
+
+    #[test]
+    fn test_moderation_queue() {
+        let store = SqliteStore::open_in_memory().unwrap();
+        block_on(store.upsert_entry("2025-01-15", "問題")).unwrap();
+        let insert = store.insert_moderation_item("2025-01-15", "問題", None, FilterAction::Flag, "NG");
+        block_on(insert).unwrap();
+
+        let pending = block_on(store.list_pending_moderation(10)).unwrap();
+        assert_eq!(pending.len(), 1);
+        assert_eq!(pending[0].action, FilterAction::Flag);
+        assert!(pending[0].resolution.is_none());
+
+        let resolve = || store.resolve_moderation_item(pending[0].id, ModerationResolution::Redacted, "admin");
+        assert!(block_on(resolve()).unwrap());
+        // 処理済みのものは二度処理しない
+        assert!(!block_on(resolve()).unwrap());
+        block_on(store.redact_versions_with_content("2025-01-15", "問題", "[削除]")).unwrap();
+        block_on(store.redact_moderation_content(pending[0].id, "[削除]")).unwrap();
+
+        let item = block_on(store.get_moderation_item(pending[0].id)).unwrap().unwrap();
+        assert_eq!(item.resolution, Some(ModerationResolution::Redacted));
+        assert_eq!(item.content, "[削除]");
+        assert!(block_on(store.list_pending_moderation(10)).unwrap().is_empty());
+    }
+}
@@ -0,0 +1,31 @@
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use chrono::DateTime;
+use darekagakaku_core::time::Clock;
+
+/// プロセスの実行環境の時計
+#[derive(Debug, Clone, Copy)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> i64 {
+        SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |d| d.as_secs() as i64)
+    }
+}
+
+/// 現在の日付をJSTでYYYY-MM-DD形式の文字列として返す
+pub fn today_jst() -> String {
+    darekagakaku_core::time::today_jst(&SystemClock)
+}
+
+/// 現在時刻をISO8601形式で返す（Workers版と同じくUTCで記録する）
+pub fn now_iso8601() -> String {
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default();
+    DateTime::from_timestamp(now.as_secs() as i64, now.subsec_nanos())
+        .unwrap_or(DateTime::UNIX_EPOCH)
+        .to_rfc3339()
+}
@@ -0,0 +1,358 @@
+use std::time::Duration;
+
+use darekagakaku_core::config::FromVars;
+use darekagakaku_core::crypto::to_hex;
+use darekagakaku_core::hcaptcha::{self, HCaptchaResponse};
+use darekagakaku_core::pow::{self, PowPolicy};
+use darekagakaku_core::time::{today_jst, Clock};
+use darekagakaku_core::turnstile::{
+    outcome_from_response, TurnstileExpectation, TurnstileOutcome, TurnstileRequest,
+    TurnstileResponse, DIARY_ACTION, SITEVERIFY_URL,
+};
+use darekagakaku_core::verification::{
+    VerificationFailure, Verifier, VerifierKind, VerifierWidget,
+};
+
+use crate::store::SqliteStore;
+use crate::time::SystemClock;
+
+/// siteverifyの応答を待つ上限
+const TIMEOUT: Duration = Duration::from_secs(5);
+/// 通信エラー時は同じ冪等キーで1回だけ再試行する
+const MAX_ATTEMPTS: u32 = 2;
+
+/// 設定で選ばれた確認方式（Workers版と同じVERIFIER変数で選ぶ）
+pub enum ServerVerifier {
+    Turnstile {
+        client: reqwest::Client,
+        secret: String,
+        site_key: String,
+        hostname: Option<String>,
+    },
+    HCaptcha {
+        client: reqwest::Client,
+        verify_url: String,
+        secret: String,
+        site_key: Option<String>,
+        hostname: Option<String>,
+    },
+    /// チャレンジはSQLiteに保存する
+    ProofOfWork(PowPolicy),
+    NoOp,
+}
+
+fn http_client() -> Result<reqwest::Client, String> {
+    reqwest::Client::builder()
+        .timeout(TIMEOUT)
+        .build()
+        .map_err(|e| format!("failed to build HTTP client: {}", e))
+}
+
+impl ServerVerifier {
+    /// 環境変数から確認方式を選ぶ（鍵の不足はエラー）
+    pub fn from_vars(get: impl Fn(&str) -> Option<String>) -> Result<Self, String> {
+        match VerifierKind::from_vars(&get) {
+            VerifierKind::Turnstile => {
+                let secret = get("TURNSTILE_SECRET_KEY")
+                    .ok_or("TURNSTILE_SECRET_KEY is required (or set VERIFIER=none)")?;
+                Ok(Self::Turnstile {
+                    client: http_client()?,
+                    secret,
+                    site_key: get("TURNSTILE_SITE_KEY").unwrap_or_default(),
+                    hostname: get("CANONICAL_HOST"),
+                })
+            }
+            VerifierKind::HCaptcha => {
+                let secret = get("HCAPTCHA_SECRET_KEY")
+                    .ok_or("HCAPTCHA_SECRET_KEY is required with VERIFIER=hcaptcha")?;
+                Ok(Self::HCaptcha {
+                    client: http_client()?,
+                    verify_url: get("HCAPTCHA_VERIFY_URL")
+                        .unwrap_or_else(|| hcaptcha::DEFAULT_VERIFY_URL.to_string()),
+                    secret,
+                    site_key: get("HCAPTCHA_SITE_KEY"),
+                    hostname: get("CANONICAL_HOST"),
+                })
+            }
+            VerifierKind::ProofOfWork => Ok(Self::ProofOfWork(PowPolicy::from_vars(&get))),
+            VerifierKind::NoOp => {
+                log::warn!("VERIFIER=none: diary writes are not verified");
+                Ok(Self::NoOp)
+            }
+        }
+    }
+
+    /// トップページに埋め込むウィジェット
+    pub fn widget(&self) -> VerifierWidget {
+        match self {
+            Self::Turnstile { site_key, .. } => VerifierWidget::Turnstile {
+                site_key: site_key.clone(),
+            },
+            Self::HCaptcha { site_key, .. } => VerifierWidget::HCaptcha {
+                site_key: site_key.clone().unwrap_or_default(),
+            },
+            Self::ProofOfWork(_) => VerifierWidget::ProofOfWork,
+            Self::NoOp => VerifierWidget::NoOp,
+        }
+    }
+
+    /// プルーフ・オブ・ワークのチャレンジの設定（ほかの方式ならNone）
+    pub fn pow_policy(&self) -> Option<&PowPolicy> {
+        match self {
+            Self::ProofOfWork(policy) => Some(policy),
+            _ => None,
+        }
+    }
+
+    /// リクエストごとの確認を作る
+    ///
+    /// `client` はレート制限と同じクライアントのキーで、チャレンジの発行先と照らし合わせる。
+    pub fn for_request<'a>(
+        &'a self,
+        store: &'a SqliteStore,
+        client: &'a str,
+    ) -> RequestVerifier<'a> {
+        RequestVerifier {
+            verifier: self,
+            store,
+            client,
+        }
+    }
+}
+
+/// プルーフ・オブ・ワークのチャレンジ（推測できないよう乱数で作る）
+pub fn new_challenge() -> String {
+    let mut bytes = [0u8; pow::CHALLENGE_BYTES];
+    getrandom::getrandom(&mut bytes).expect("OS random source is available");
+    to_hex(&bytes)
+}
+
+/// 再試行しても二重に検証されないよう、リクエストごとにUUID v4を振る
+fn new_idempotency_key() -> String {
+    let mut bytes = [0u8; 16];
+    getrandom::getrandom(&mut bytes).expect("OS random source is available");
+    bytes[6] = (bytes[6] & 0x0f) | 0x40;
+    bytes[8] = (bytes[8] & 0x3f) | 0x80;
+    let hex: String = bytes.iter().map(|b| format!("{:02x}", b)).collect();
+    format!(
+        "{}-{}-{}-{}-{}",
+        &hex[0..8],
+        &hex[8..12],
+        &hex[12..16],
+        &hex[16..20],
+        &hex[20..32]
+    )
+}
+
+async fn verify_turnstile(
+    client: &reqwest::Client,
+    secret: &str,
+    token: &str,
+    ip: Option<&str>,
+    expected: &TurnstileExpectation<'_>,
+) -> TurnstileOutcome {
+    if token.is_empty() {
+        return TurnstileOutcome::InvalidToken;
+    }
+
+    let body = TurnstileRequest {
+        secret: secret.to_string(),
+        response: token.to_string(),
+        remoteip: ip.map(|s| s.to_string()),
+        idempotency_key: new_idempotency_key(),
+    };
+
+    for attempt in 1..=MAX_ATTEMPTS {
+        let result = async {
+            let resp = client.post(SITEVERIFY_URL).json(&body).send().await?;
+            resp.json::<TurnstileResponse>().await
+        }
+        .await;
+        match result {
+            Ok(resp) => {
+                let outcome = outcome_from_response(&resp, expected);
+                if outcome == TurnstileOutcome::Unavailable && attempt < MAX_ATTEMPTS {
+                    continue;
+                }
+                if !outcome.is_success() {
+                    log::warn!(
+                        "Turnstile rejected: {:?} error_codes={:?}",
+                        outcome,
+                        resp.error_codes
+                    );
+                }
+                return outcome;
+            }
+            Err(e) => {
+                log::error!("Turnstile verification error (attempt {}): {:?}", attempt, e);
+            }
+        }
+    }
+    TurnstileOutcome::Unavailable
+}
+
+async fn verify_hcaptcha(
+    client: &reqwest::Client,
+    verify_url: &str,
+    secret: &str,
+    site_key: Option<&str>,
+    token: &str,
+    ip: Option<&str>,
+) -> Result<HCaptchaResponse, reqwest::Error> {
+    client
+        .post(verify_url)
+        .header(
+            reqwest::header::CONTENT_TYPE,
+            "application/x-www-form-urlencoded",
+        )
+        .body(hcaptcha::request_body(secret, token, ip, site_key))
+        .send()
+        .await?
+        .json()
+        .await
+}
+
+/// リクエストごとの確認
+pub struct RequestVerifier<'a> {
+    verifier: &'a ServerVerifier,
+    store: &'a SqliteStore,
+    client: &'a str,
+}
+
+impl Verifier for RequestVerifier<'_> {
+    type Error = rusqlite::Error;
+
+    async fn verify(
+        &self,
+        token: &str,
+        ip: Option<&str>,
+    ) -> Result<Result<(), VerificationFailure>, rusqlite::Error> {
+        match self.verifier {
+            ServerVerifier::Turnstile {
+                client,
+                secret,
+                hostname,
+                ..
+            } => {
+                // ウィジェットには表示した日付をcdataとして埋め込んでいる
+                let today = today_jst(&SystemClock);
+                let expected = TurnstileExpectation {
+                    hostname: hostname.clone(),
+                    action: DIARY_ACTION,
+                    cdata: Some(&today),
+                };
+                let outcome = verify_turnstile(client, secret, token, ip, &expected).await;
+                Ok(outcome.verification())
+            }
+            ServerVerifier::HCaptcha {
+                client,
+                verify_url,
+                secret,
+                site_key,
+                hostname,
+            } => {
+                if token.is_empty() {
+                    return Ok(Err(hcaptcha::INVALID_TOKEN));
+                }
+                let site_key = site_key.as_deref();
+                match verify_hcaptcha(client, verify_url, secret, site_key, token, ip).await {
+                    Ok(resp) => {
+                        let result = hcaptcha::check_response(&resp, hostname.as_deref());
+                        if let Err(failure) = &result {
+                            log::warn!(
+                                "hCaptcha rejected: {} error_codes={:?}",
+                                failure.code,
+                                resp.error_codes
+                            );
+                        }
+                        Ok(result)
+                    }
+                    Err(e) => {
+                        log::error!("hCaptcha verification error: {:?}", e);
+                        Ok(Err(hcaptcha::UNAVAILABLE))
+                    }
+                }
+            }
+            ServerVerifier::ProofOfWork(_) => {
+                let now = SystemClock.now();
+                pow::verify_solution(self.store, self.client, token, now).await
+            }
+            ServerVerifier::NoOp => Ok(Ok(())),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use darekagakaku_core::store::block_on;
+
+    use super::*;
+
+    fn verifier(vars: &[(&str, &str)]) -> Result<ServerVerifier, String> {
+        let vars: HashMap<&str, &str> = vars.iter().copied().collect();
+        ServerVerifier::from_vars(|name| vars.get(name).map(|v| v.to_string()))
+    }
+
+    #[test]
+    fn test_verifier_from_vars() {
+        let turnstile = verifier(&[
+            ("TURNSTILE_SECRET_KEY", "secret"),
+            ("TURNSTILE_SITE_KEY", "site"),
+        ])
+        .unwrap();
+        assert_eq!(
+            turnstile.widget(),
+            VerifierWidget::Turnstile {
+                site_key: "site".to_string()
+            }
+        );
+
+        // Turnstileは既定なので、鍵がなければ起動させない
+        assert!(verifier(&[]).is_err());
+        assert!(verifier(&[("VERIFIER", "hcaptcha")]).is_err());
+
+        let hcaptcha = verifier(&[
+            ("VERIFIER", "hcaptcha"),
+            ("HCAPTCHA_SECRET_KEY", "secret"),
+            ("HCAPTCHA_SITE_KEY", "site"),
+        ])
+        .unwrap();
+        assert_eq!(
+            hcaptcha.widget(),
+            VerifierWidget::HCaptcha {
+                site_key: "site".to_string()
+            }
+        );
+
+        let pow = verifier(&[("VERIFIER", "pow"), ("POW_DIFFICULTY", "20")]).unwrap();
+        assert_eq!(pow.widget(), VerifierWidget::ProofOfWork);
+        assert_eq!(pow.pow_policy().map(|p| p.difficulty), Some(20));
+
+        let none = verifier(&[("VERIFIER", "none")]).unwrap();
+        assert_eq!(none.widget(), VerifierWidget::NoOp);
+        let store = SqliteStore::open_in_memory().unwrap();
+        let request = none.for_request(&store, "a");
+        assert_eq!(block_on(request.verify("", None)).unwrap(), Ok(()));
+    }
+
+    #[test]
+    fn test_pow_requires_issued_challenge() {
+        let pow = verifier(&[("VERIFIER", "pow")]).unwrap();
+        let store = SqliteStore::open_in_memory().unwrap();
+        let token = format!("{}:1", "0".repeat(32));
+        let request = pow.for_request(&store, "a");
+        let failure = block_on(request.verify(&token, None)).unwrap().unwrap_err();
+        assert_eq!(failure.code, "POW_EXPIRED");
+    }
+
+    #[test]
+    fn test_new_idempotency_key_is_uuid_v4() {
+        let key = new_idempotency_key();
+        assert_eq!(key.len(), 36);
+        assert_eq!(&key[14..15], "4");
+        assert!(matches!(&key[19..20], "8" | "9" | "a" | "b"));
+        assert_ne!(key, new_idempotency_key());
+    }
+}
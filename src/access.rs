@@ -0,0 +1,492 @@
+use rsa::pkcs1v15::{Signature, VerifyingKey};
+use rsa::signature::Verifier;
+use rsa::{BigUint, RsaPublicKey};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use worker::{Env, Fetch, Request, Result};
+
+use crate::auth::AdminIdentity;
+use crate::crypto::to_hex;
+use crate::db;
+use crate::time::now_unix;
+use crate::webauthn::b64url_decode;
+
+/// Cloudflare Accessが検証済みリクエストに付与するヘッダー
+pub const ACCESS_JWT_HEADER: &str = "Cf-Access-Jwt-Assertion";
+/// 公開鍵セットをKVにキャッシュする期間
+const JWKS_CACHE_TTL_SECONDS: u64 = 3600;
+/// 未知のkidを受け取ったときに再取得するまでの最短間隔（鍵ローテーション対応）
+const JWKS_REFRESH_INTERVAL_SECONDS: i64 = 300;
+/// exp/nbfの判定で許容する時計のずれ
+const CLOCK_SKEW_SECONDS: i64 = 60;
+const JWKS_CACHE_KEY: &str = "access_jwks";
+
+/// JWT検証の失敗理由
+#[derive(Debug, Clone, PartialEq)]
+pub enum AccessError {
+    Malformed,
+    UnsupportedAlgorithm(String),
+    UnknownKey,
+    InvalidSignature,
+    Expired,
+    NotYetValid,
+    InvalidAudience,
+    InvalidIssuer,
+    MissingEmail,
+}
+
+/// Cloudflare Access の設定（wranglerの環境変数から読み込む）
+#[derive(Debug, Clone)]
+pub struct AccessConfig {
+    /// チームドメイン（例: `example.cloudflareaccess.com`）
+    pub team_domain: String,
+    /// AccessアプリケーションのAUDタグ
+    pub audience: String,
+}
+
+impl AccessConfig {
+    /// `ACCESS_TEAM_DOMAIN` と `ACCESS_AUD` が両方設定されている場合のみ有効
+    pub fn from_env(env: &Env) -> Option<Self> {
+        let team_domain = env.var("ACCESS_TEAM_DOMAIN").ok()?.to_string();
+        let audience = env.var("ACCESS_AUD").ok()?.to_string();
+        Self::new(&team_domain, &audience)
+    }
+
+    pub fn new(team_domain: &str, audience: &str) -> Option<Self> {
+        let team_domain = team_domain
+            .trim()
+            .trim_start_matches("https://")
+            .trim_end_matches('/')
+            .to_string();
+        let audience = audience.trim().to_string();
+        if team_domain.is_empty() || audience.is_empty() {
+            return None;
+        }
+        Some(Self {
+            team_domain,
+            audience,
+        })
+    }
+
+    pub fn issuer(&self) -> String {
+        format!("https://{}", self.team_domain)
+    }
+
+    pub fn certs_url(&self) -> String {
+        format!("https://{}/cdn-cgi/access/certs", self.team_domain)
+    }
+}
+
+/// JWKSの鍵（RSA以外の鍵は読み飛ばす）
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Jwk {
+    pub kid: String,
+    pub kty: String,
+    #[serde(default)]
+    pub alg: Option<String>,
+    #[serde(default)]
+    pub n: Option<String>,
+    #[serde(default)]
+    pub e: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct JwkSet {
+    keys: Vec<Jwk>,
+}
+
+/// JWKSのJSONから鍵一覧を取り出す（純粋関数）
+pub fn parse_jwks(json: &str) -> Option<Vec<Jwk>> {
+    serde_json::from_str::<JwkSet>(json).ok().map(|set| set.keys)
+}
+
+#[derive(Debug, Deserialize)]
+struct JwtHeader {
+    alg: String,
+    #[serde(default)]
+    kid: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+enum Audience {
+    One(String),
+    Many(Vec<String>),
+}
+
+impl Audience {
+    fn contains(&self, audience: &str) -> bool {
+        match self {
+            Audience::One(aud) => aud == audience,
+            Audience::Many(auds) => auds.iter().any(|a| a == audience),
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct RawClaims {
+    aud: Audience,
+    iss: String,
+    exp: i64,
+    #[serde(default)]
+    nbf: Option<i64>,
+    #[serde(default)]
+    email: Option<String>,
+}
+
+/// 検証済みのJWTから取り出した情報
+#[derive(Debug, Clone, PartialEq)]
+pub struct AccessClaims {
+    pub email: String,
+}
+
+fn decode_json<T: for<'de> Deserialize<'de>>(segment: &str) -> std::result::Result<T, AccessError> {
+    let bytes = b64url_decode(segment).ok_or(AccessError::Malformed)?;
+    serde_json::from_slice(&bytes).map_err(|_| AccessError::Malformed)
+}
+
+fn rsa_key(jwk: &Jwk) -> Option<RsaPublicKey> {
+    if jwk.kty != "RSA" {
+        return None;
+    }
+    let n = b64url_decode(jwk.n.as_deref()?)?;
+    let e = b64url_decode(jwk.e.as_deref()?)?;
+    RsaPublicKey::new(BigUint::from_bytes_be(&n), BigUint::from_bytes_be(&e)).ok()
+}
+
+/// Cloudflare AccessのJWTを検証する（純粋関数）
+///
+/// RS256の署名、有効期限、audience、issuerを確認し、メールアドレスを返す。
+pub fn verify_access_jwt(
+    token: &str,
+    keys: &[Jwk],
+    config: &AccessConfig,
+    now: i64,
+) -> std::result::Result<AccessClaims, AccessError> {
+    let mut segments = token.trim().split('.');
+    let (Some(header_b64), Some(claims_b64), Some(signature_b64), None) = (
+        segments.next(),
+        segments.next(),
+        segments.next(),
+        segments.next(),
+    ) else {
+        return Err(AccessError::Malformed);
+    };
+
+    let header: JwtHeader = decode_json(header_b64)?;
+    if header.alg != "RS256" {
+        return Err(AccessError::UnsupportedAlgorithm(header.alg));
+    }
+
+    let key = keys
+        .iter()
+        .filter(|k| header.kid.as_deref().is_none_or(|kid| k.kid == kid))
+        .filter(|k| k.alg.as_deref().is_none_or(|alg| alg == "RS256"))
+        .find_map(rsa_key)
+        .ok_or(AccessError::UnknownKey)?;
+
+    let signature_bytes = b64url_decode(signature_b64).ok_or(AccessError::Malformed)?;
+    let signature =
+        Signature::try_from(signature_bytes.as_slice()).map_err(|_| AccessError::InvalidSignature)?;
+    let signing_input = format!("{}.{}", header_b64, claims_b64);
+    VerifyingKey::<Sha256>::new(key)
+        .verify(signing_input.as_bytes(), &signature)
+        .map_err(|_| AccessError::InvalidSignature)?;
+
+    // 署名を確認してからクレームを解釈する
+    let claims: RawClaims = decode_json(claims_b64)?;
+    if claims.exp + CLOCK_SKEW_SECONDS < now {
+        return Err(AccessError::Expired);
+    }
+    if claims.nbf.is_some_and(|nbf| nbf - CLOCK_SKEW_SECONDS > now) {
+        return Err(AccessError::NotYetValid);
+    }
+    if !claims.aud.contains(&config.audience) {
+        return Err(AccessError::InvalidAudience);
+    }
+    if claims.iss.trim_end_matches('/') != config.issuer() {
+        return Err(AccessError::InvalidIssuer);
+    }
+
+    let email = claims
+        .email
+        .map(|e| e.trim().to_ascii_lowercase())
+        .filter(|e| !e.is_empty())
+        .ok_or(AccessError::MissingEmail)?;
+    Ok(AccessClaims { email })
+}
+
+/// Access経由のリクエストで使うCSRFトークン（純粋関数）
+///
+/// JWTはAccessがリクエストごとに付与し、他サイトからは読めないため、
+/// そのハッシュをセッションごとのトークンとして使う。
+pub fn csrf_token_for(jwt: &str) -> String {
+    to_hex(&Sha256::digest(format!("csrf:{}", jwt.trim()).as_bytes()))
+}
+
+/// KVにキャッシュする公開鍵セット
+#[derive(Debug, Serialize, Deserialize)]
+struct CachedJwks {
+    fetched_at: i64,
+    keys: Vec<Jwk>,
+}
+
+async fn fetch_jwks(env: &Env, config: &AccessConfig, now: i64) -> Result<CachedJwks> {
+    let mut resp = Fetch::Url(config.certs_url().parse()?).send().await?;
+    let body = resp.text().await?;
+    let keys = parse_jwks(&body)
+        .ok_or_else(|| worker::Error::RustError("invalid JWKS response".to_string()))?;
+
+    let cached = CachedJwks {
+        fetched_at: now,
+        keys,
+    };
+    let kv = env.kv("RATE_LIMIT")?;
+    kv.put(JWKS_CACHE_KEY, serde_json::to_string(&cached)?)?
+        .expiration_ttl(JWKS_CACHE_TTL_SECONDS)
+        .execute()
+        .await?;
+    Ok(cached)
+}
+
+async fn load_jwks(env: &Env, config: &AccessConfig, now: i64) -> Result<CachedJwks> {
+    let kv = env.kv("RATE_LIMIT")?;
+    match kv.get(JWKS_CACHE_KEY).json::<CachedJwks>().await? {
+        Some(cached) => Ok(cached),
+        None => fetch_jwks(env, config, now).await,
+    }
+}
+
+/// `Cf-Access-Jwt-Assertion` ヘッダーから管理者を特定する
+///
+/// Accessが未設定、ヘッダーがない、検証に失敗した、メールアドレスに対応する
+/// 管理者がいない場合はNoneを返し、他の認証方式にフォールバックさせる。
+pub async fn identity_from_request(req: &Request, env: &Env) -> Result<Option<AdminIdentity>> {
+    let Some(config) = AccessConfig::from_env(env) else {
+        return Ok(None);
+    };
+    let Some(token) = req.headers().get(ACCESS_JWT_HEADER)? else {
+        return Ok(None);
+    };
+
+    // 鍵の取得に失敗してもエラーにはせず、Bearerトークンやクッキーによる認証に任せる
+    let now = now_unix();
+    let jwks = match load_jwks(env, &config, now).await {
+        Ok(jwks) => jwks,
+        Err(e) => {
+            worker::console_error!("Failed to load Cloudflare Access JWKS: {:?}", e);
+            return Ok(None);
+        }
+    };
+    let mut result = verify_access_jwt(&token, &jwks.keys, &config, now);
+
+    // 鍵がローテーションされた可能性があるため、一定間隔をあけて再取得する
+    if result == Err(AccessError::UnknownKey)
+        && now - jwks.fetched_at >= JWKS_REFRESH_INTERVAL_SECONDS
+    {
+        match fetch_jwks(env, &config, now).await {
+            Ok(jwks) => result = verify_access_jwt(&token, &jwks.keys, &config, now),
+            Err(e) => worker::console_error!("Failed to refresh Cloudflare Access JWKS: {:?}", e),
+        }
+    }
+
+    let claims = match result {
+        Ok(claims) => claims,
+        Err(e) => {
+            worker::console_warn!("Cloudflare Access JWT rejected: {:?}", e);
+            return Ok(None);
+        }
+    };
+
+    let db = env.d1("DB")?;
+    Ok(db::get_admin_by_email(&db, &claims.email)
+        .await?
+        .map(|admin| AdminIdentity {
+            admin_id: Some(admin.id),
+            username: admin.username,
+            role: admin.role,
+            csrf_token: Some(csrf_token_for(&token)),
+        }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+    use base64::Engine;
+    use rsa::pkcs1v15::SigningKey;
+    use rsa::pkcs8::DecodePrivateKey;
+    use rsa::signature::{SignatureEncoding, Signer};
+    use rsa::RsaPrivateKey;
+
+    const JWKS_FIXTURE: &str = include_str!("../tests/fixtures/access_jwks.json");
+    const PRIVATE_KEY_FIXTURE: &str = include_str!("../tests/fixtures/access_private_key.pem");
+    const NOW: i64 = 1_700_000_000;
+
+    fn config() -> AccessConfig {
+        AccessConfig::new("example.cloudflareaccess.com", "test-aud").unwrap()
+    }
+
+    fn keys() -> Vec<Jwk> {
+        parse_jwks(JWKS_FIXTURE).unwrap()
+    }
+
+    fn sign(header: serde_json::Value, claims: serde_json::Value) -> String {
+        let private_key = RsaPrivateKey::from_pkcs8_pem(PRIVATE_KEY_FIXTURE).unwrap();
+        let signing_input = format!(
+            "{}.{}",
+            URL_SAFE_NO_PAD.encode(header.to_string()),
+            URL_SAFE_NO_PAD.encode(claims.to_string())
+        );
+        let signature = SigningKey::<Sha256>::new(private_key).sign(signing_input.as_bytes());
+        format!("{}.{}", signing_input, URL_SAFE_NO_PAD.encode(signature.to_bytes()))
+    }
+
+    fn header() -> serde_json::Value {
+        serde_json::json!({ "alg": "RS256", "kid": "fixture-key-1", "typ": "JWT" })
+    }
+
+    fn claims() -> serde_json::Value {
+        serde_json::json!({
+            "aud": ["test-aud"],
+            "iss": "https://example.cloudflareaccess.com",
+            "email": "Alice@Example.com",
+            "sub": "user-1",
+            "iat": NOW - 10,
+            "nbf": NOW - 10,
+            "exp": NOW + 600,
+        })
+    }
+
+    #[test]
+    fn test_config_normalizes_team_domain() {
+        let config = AccessConfig::new("https://example.cloudflareaccess.com/", "aud").unwrap();
+        assert_eq!(config.issuer(), "https://example.cloudflareaccess.com");
+        assert_eq!(
+            config.certs_url(),
+            "https://example.cloudflareaccess.com/cdn-cgi/access/certs"
+        );
+        assert!(AccessConfig::new("example.cloudflareaccess.com", " ").is_none());
+    }
+
+    #[test]
+    fn test_parse_jwks_fixture() {
+        let keys = keys();
+        assert_eq!(keys.len(), 2);
+        assert!(rsa_key(&keys[0]).is_some());
+        // EC鍵は検証に使わない
+        assert!(rsa_key(&keys[1]).is_none());
+    }
+
+    #[test]
+    fn test_verify_valid_token() {
+        let token = sign(header(), claims());
+        let claims = verify_access_jwt(&token, &keys(), &config(), NOW).unwrap();
+        assert_eq!(claims.email, "alice@example.com");
+    }
+
+    #[test]
+    fn test_verify_accepts_string_audience() {
+        let mut claims = claims();
+        claims["aud"] = serde_json::json!("test-aud");
+        let token = sign(header(), claims);
+        assert!(verify_access_jwt(&token, &keys(), &config(), NOW).is_ok());
+    }
+
+    #[test]
+    fn test_verify_rejects_tampered_claims() {
+        let token = sign(header(), claims());
+        let parts: Vec<&str> = token.split('.').collect();
+        let mut forged = claims();
+        forged["email"] = serde_json::json!("mallory@example.com");
+        let forged_token = format!(
+            "{}.{}.{}",
+            parts[0],
+            URL_SAFE_NO_PAD.encode(forged.to_string()),
+            parts[2]
+        );
+        assert_eq!(
+            verify_access_jwt(&forged_token, &keys(), &config(), NOW),
+            Err(AccessError::InvalidSignature)
+        );
+    }
+
+    #[test]
+    fn test_verify_rejects_wrong_audience_and_issuer() {
+        let mut wrong_aud = claims();
+        wrong_aud["aud"] = serde_json::json!(["other-app"]);
+        assert_eq!(
+            verify_access_jwt(&sign(header(), wrong_aud), &keys(), &config(), NOW),
+            Err(AccessError::InvalidAudience)
+        );
+
+        let mut wrong_iss = claims();
+        wrong_iss["iss"] = serde_json::json!("https://evil.cloudflareaccess.com");
+        assert_eq!(
+            verify_access_jwt(&sign(header(), wrong_iss), &keys(), &config(), NOW),
+            Err(AccessError::InvalidIssuer)
+        );
+    }
+
+    #[test]
+    fn test_verify_checks_time_window() {
+        let token = sign(header(), claims());
+        assert_eq!(
+            verify_access_jwt(&token, &keys(), &config(), NOW + 600 + CLOCK_SKEW_SECONDS + 1),
+            Err(AccessError::Expired)
+        );
+        assert_eq!(
+            verify_access_jwt(&token, &keys(), &config(), NOW - 10 - CLOCK_SKEW_SECONDS - 1),
+            Err(AccessError::NotYetValid)
+        );
+    }
+
+    #[test]
+    fn test_verify_rejects_unknown_kid_and_algorithm() {
+        let mut other_kid = header();
+        other_kid["kid"] = serde_json::json!("rotated-key");
+        assert_eq!(
+            verify_access_jwt(&sign(other_kid, claims()), &keys(), &config(), NOW),
+            Err(AccessError::UnknownKey)
+        );
+
+        let mut none_alg = header();
+        none_alg["alg"] = serde_json::json!("none");
+        assert_eq!(
+            verify_access_jwt(&sign(none_alg, claims()), &keys(), &config(), NOW),
+            Err(AccessError::UnsupportedAlgorithm("none".to_string()))
+        );
+    }
+
+    #[test]
+    fn test_verify_requires_email() {
+        let mut service_token = claims();
+        service_token.as_object_mut().unwrap().remove("email");
+        assert_eq!(
+            verify_access_jwt(&sign(header(), service_token), &keys(), &config(), NOW),
+            Err(AccessError::MissingEmail)
+        );
+    }
+
+    #[test]
+    fn test_csrf_token_for_is_bound_to_jwt() {
+        let a = csrf_token_for(&sign(header(), claims()));
+        let mut other = claims();
+        other["exp"] = serde_json::json!(NOW + 1200);
+        let b = csrf_token_for(&sign(header(), other));
+        assert_eq!(a.len(), 64);
+        assert_ne!(a, b);
+    }
+
+    #[test]
+    fn test_verify_rejects_malformed() {
+        assert_eq!(
+            verify_access_jwt("not-a-jwt", &keys(), &config(), NOW),
+            Err(AccessError::Malformed)
+        );
+        assert_eq!(
+            verify_access_jwt("a.b.c.d", &keys(), &config(), NOW),
+            Err(AccessError::Malformed)
+        );
+    }
+}
@@ -0,0 +1,25 @@
+pub use darekagakaku_core::api_keys::*;
+
+use crate::crypto::{random_bytes, to_hex};
+
+/// キーの乱数部分のバイト数（16進で40文字）
+const KEY_RANDOM_BYTES: usize = 20;
+
+/// 新しいAPIキーを生成（平文は発行時に一度だけ表示する）
+pub fn generate_key() -> String {
+    format!("{}{}", KEY_PREFIX, to_hex(&random_bytes(KEY_RANDOM_BYTES)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_generate_key() {
+        let key = generate_key();
+        assert!(key.starts_with(KEY_PREFIX));
+        assert_eq!(key.len(), KEY_PREFIX.len() + KEY_RANDOM_BYTES * 2);
+        assert_ne!(key, generate_key());
+        assert!(key.starts_with(&display_prefix(&key)));
+    }
+}
@@ -0,0 +1,116 @@
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+use worker::d1::D1Database;
+use worker::{Env, Request, Result};
+
+use crate::auth::AdminIdentity;
+use crate::crypto::to_hex;
+use crate::db;
+use crate::models::{AuditAction, AuditEvent, AuditLogEntry, AuditLogFilter};
+use crate::rate_limit;
+
+pub use darekagakaku_core::crypto::content_hash;
+
+/// 監査ログに残すIPハッシュの長さ（16進文字数）
+const IP_HASH_LENGTH: usize = 32;
+/// 監査ログの1ページあたりの件数
+pub const PAGE_SIZE: u32 = 50;
+
+/// IPアドレスを監査ログ用にハッシュ化（純粋関数）
+///
+/// 鍵付きのHMACにして、IPv4の全数探索による逆算を防ぐ。
+pub fn hash_ip(ip: &str, key: &str) -> String {
+    let mut mac =
+        Hmac::<Sha256>::new_from_slice(key.as_bytes()).expect("HMAC accepts any key length");
+    mac.update(ip.as_bytes());
+    let mut hex = to_hex(&mac.finalize().into_bytes());
+    hex.truncate(IP_HASH_LENGTH);
+    hex
+}
+
+/// IPハッシュ用の鍵（IP_HASH_KEY）を取得（未設定や空ならNone）
+pub fn ip_hash_key(env: &Env) -> Option<String> {
+    env.secret("IP_HASH_KEY")
+        .ok()
+        .map(|k| k.to_string())
+        .filter(|k| !k.is_empty())
+}
+
+/// 監査ログに残すIPハッシュ
+///
+/// 鍵がなければ逆算できるハッシュを残さないよう、IPは記録しない（空文字列）。
+fn request_ip_hash(env: &Env, req: &Request) -> String {
+    match ip_hash_key(env) {
+        Some(key) => hash_ip(&rate_limit::get_client_ip(req), &key),
+        None => {
+            worker::console_warn!("IP_HASH_KEY is not set; audit log will not record client IPs");
+            String::new()
+        }
+    }
+}
+
+async fn write(env: &Env, req: &Request, actor: &str, actor_id: Option<i64>, event: &AuditEvent) {
+    let ip_hash = request_ip_hash(env, req);
+    let result = match env.d1("DB") {
+        Ok(db) => db::insert_audit_log(&db, actor, actor_id, &ip_hash, event).await,
+        Err(e) => Err(e),
+    };
+    // 監査ログの書き込み失敗で管理操作自体は止めない
+    if let Err(e) = result {
+        worker::console_error!("Failed to write audit log ({}): {:?}", event.action.as_str(), e);
+    }
+}
+
+/// 管理者の操作を監査ログに記録
+pub async fn record(env: &Env, req: &Request, actor: &AdminIdentity, event: AuditEvent) {
+    write(env, req, &actor.username, actor.admin_id, &event).await;
+}
+
+/// ログインの失敗を監査ログに記録（入力されたユーザー名を操作者とする）
+pub async fn record_failed_login(env: &Env, req: &Request, username: &str) {
+    let event = AuditEvent::new(AuditAction::LoginFailed);
+    write(env, req, username, None, &event).await;
+}
+
+/// リクエストのクエリ文字列から監査ログの絞り込み条件を作る
+pub fn filter_from_request(req: &Request) -> Result<AuditLogFilter> {
+    let url = req.url()?;
+    let pairs: Vec<(String, String)> = url.query_pairs().into_owned().collect();
+    Ok(AuditLogFilter::from_query(
+        pairs.iter().map(|(k, v)| (k.as_str(), v.as_str())),
+    ))
+}
+
+/// 監査ログの1ページ分と、次のページがあるかを取得
+pub async fn load_page(
+    db: &D1Database,
+    filter: &AuditLogFilter,
+) -> Result<(Vec<AuditLogEntry>, bool)> {
+    let offset = (filter.page.saturating_sub(1)).saturating_mul(PAGE_SIZE);
+    // 1件多く取得して次のページの有無を判定する
+    let mut entries = db::list_audit_log(
+        db,
+        filter,
+        (PAGE_SIZE + 1) as i32,
+        i32::try_from(offset).unwrap_or(i32::MAX),
+    )
+    .await?;
+    let has_next = entries.len() > PAGE_SIZE as usize;
+    entries.truncate(PAGE_SIZE as usize);
+    Ok((entries, has_next))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_hash_ip_is_keyed_and_truncated() {
+        let keyed = hash_ip("192.0.2.1", "secret");
+        assert_eq!(keyed.len(), IP_HASH_LENGTH);
+        assert_ne!(keyed, content_hash("192.0.2.1")[..IP_HASH_LENGTH]);
+        assert_eq!(keyed, hash_ip("192.0.2.1", "secret"));
+        assert_ne!(keyed, hash_ip("192.0.2.1", "other"));
+        assert_ne!(keyed, hash_ip("192.0.2.2", "secret"));
+    }
+}
@@ -1,14 +1,36 @@
-use worker::{Env, Request, Response, Result};
+use serde::{Deserialize, Serialize};
+use worker::{Env, FormData, FormEntry, Headers, Request, Response, Result, Url};
 
-use crate::models::ErrorResponse;
+use crate::access;
+use crate::crypto::random_token;
+use crate::db;
+use crate::login_guard;
+use crate::models::{AdminRole, ErrorResponse};
+use crate::rate_limit;
+use crate::templates;
+use crate::time::now_unix;
+
+pub use darekagakaku_core::crypto::constant_time_eq;
+pub use darekagakaku_core::identity::{
+    AdminIdentity, BOOTSTRAP_USERNAME, CSRF_FIELD_NAME, CSRF_HEADER_NAME,
+};
 
 const ADMIN_COOKIE_NAME: &str = "admin_token";
+const SESSION_TTL_SECONDS: u64 = 86400;
+/// パスワード確認後、二要素認証の入力を待つ期間
+const PENDING_LOGIN_TTL_SECONDS: u64 = 300;
+/// 二要素認証待ちのログインで許容するコード誤りの回数
+const MAX_PENDING_LOGIN_FAILURES: u32 = 5;
+/// 二要素認証の登録途中のシークレットを保持する期間
+const TOTP_ENROLLMENT_TTL_SECONDS: u64 = 600;
+/// パスキーのチャレンジの有効期間
+const WEBAUTHN_CHALLENGE_TTL_SECONDS: u64 = 300;
 
 /// Bearerトークンをチェック（純粋関数）
 fn check_bearer_token(auth_header: Option<&str>, expected: &str) -> bool {
     auth_header
         .and_then(|h| h.strip_prefix("Bearer "))
-        .map(|t| t == expected)
+        .map(|t| constant_time_eq(t, expected))
         .unwrap_or(false)
 }
 
@@ -22,41 +44,366 @@ fn extract_cookie_token<'a>(cookie_header: Option<&'a str>, cookie_name: &str) -
         .map(|(_, value)| value)
 }
 
-/// Cookieからトークンをチェック（純粋関数）
-fn check_cookie_token(cookie_header: Option<&str>, expected: &str) -> bool {
-    extract_cookie_token(cookie_header, ADMIN_COOKIE_NAME)
-        .map(|t| t == expected)
-        .unwrap_or(false)
+fn session_key(session_id: &str) -> String {
+    format!("admin_session:{}", session_id)
+}
+
+/// リクエストのCookieからセッションIDを取得
+pub fn session_id_from_request(req: &Request) -> Result<Option<String>> {
+    let cookie_header = req.headers().get("Cookie")?;
+    Ok(extract_cookie_token(cookie_header.as_deref(), ADMIN_COOKIE_NAME)
+        .filter(|t| !t.is_empty())
+        .map(|t| t.to_string()))
+}
+
+/// ログインセッションを作成し、セッションIDを返す
+///
+/// セッションごとに新しいCSRFトークンを発行して保存する。
+pub async fn create_session(env: &Env, identity: &AdminIdentity) -> Result<String> {
+    let kv = env.kv("RATE_LIMIT")?;
+    let session_id = random_token();
+    let session = AdminIdentity {
+        csrf_token: Some(random_token()),
+        ..identity.clone()
+    };
+    kv.put(&session_key(&session_id), serde_json::to_string(&session)?)?
+        .expiration_ttl(SESSION_TTL_SECONDS)
+        .execute()
+        .await?;
+    Ok(session_id)
+}
+
+/// ログインセッションを破棄
+pub async fn delete_session(env: &Env, session_id: &str) -> Result<()> {
+    let kv = env.kv("RATE_LIMIT")?;
+    kv.delete(&session_key(session_id)).await?;
+    Ok(())
+}
+
+fn pending_login_key(token: &str) -> String {
+    format!("login_pending:{}", token)
+}
+
+/// 二要素認証待ちのログイン（KVに保存する）
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PendingLogin {
+    pub admin_id: i64,
+    /// このトークンでコードを間違えた回数
+    pub failures: u32,
+    /// 失効時刻（Unix秒）。失敗を記録しても延長しない
+    expires_at: u64,
+}
+
+async fn put_pending_login(env: &Env, token: &str, pending: &PendingLogin) -> Result<()> {
+    let kv = env.kv("RATE_LIMIT")?;
+    kv.put(&pending_login_key(token), serde_json::to_string(pending)?)?
+        .expiration(pending.expires_at)
+        .execute()
+        .await?;
+    Ok(())
+}
+
+/// パスワード確認済みで二要素認証待ちのログインを作成し、そのトークンを返す
+pub async fn create_pending_login(env: &Env, admin_id: i64) -> Result<String> {
+    let token = random_token();
+    let pending = PendingLogin {
+        admin_id,
+        failures: 0,
+        expires_at: now_unix() as u64 + PENDING_LOGIN_TTL_SECONDS,
+    };
+    put_pending_login(env, &token, &pending).await?;
+    Ok(token)
+}
+
+/// 二要素認証待ちのログインを取得
+pub async fn get_pending_login(env: &Env, token: &str) -> Result<Option<PendingLogin>> {
+    let kv = env.kv("RATE_LIMIT")?;
+    Ok(kv.get(&pending_login_key(token)).json::<PendingLogin>().await?)
+}
+
+/// 二要素認証のコード誤りを記録し、上限に達したトークンは破棄する
+///
+/// 破棄した場合はfalseを返す。
+pub async fn record_pending_login_failure(
+    env: &Env,
+    token: &str,
+    pending: &PendingLogin,
+) -> Result<bool> {
+    let failures = pending.failures.saturating_add(1);
+    if failures >= MAX_PENDING_LOGIN_FAILURES {
+        delete_pending_login(env, token).await?;
+        return Ok(false);
+    }
+    let pending = PendingLogin { failures, ..pending.clone() };
+    put_pending_login(env, token, &pending).await?;
+    Ok(true)
+}
+
+/// 二要素認証待ちのログインを破棄
+pub async fn delete_pending_login(env: &Env, token: &str) -> Result<()> {
+    let kv = env.kv("RATE_LIMIT")?;
+    kv.delete(&pending_login_key(token)).await?;
+    Ok(())
+}
+
+fn totp_enrollment_key(admin_id: i64) -> String {
+    format!("totp_enroll:{}", admin_id)
+}
+
+/// 二要素認証の登録途中のシークレットを保存
+pub async fn store_totp_enrollment(env: &Env, admin_id: i64, secret: &str) -> Result<()> {
+    let kv = env.kv("RATE_LIMIT")?;
+    kv.put(&totp_enrollment_key(admin_id), secret)?
+        .expiration_ttl(TOTP_ENROLLMENT_TTL_SECONDS)
+        .execute()
+        .await?;
+    Ok(())
+}
+
+/// 二要素認証の登録途中のシークレットを取得
+pub async fn get_totp_enrollment(env: &Env, admin_id: i64) -> Result<Option<String>> {
+    let kv = env.kv("RATE_LIMIT")?;
+    Ok(kv.get(&totp_enrollment_key(admin_id)).text().await?)
+}
+
+/// 二要素認証の登録途中のシークレットを破棄
+pub async fn delete_totp_enrollment(env: &Env, admin_id: i64) -> Result<()> {
+    let kv = env.kv("RATE_LIMIT")?;
+    kv.delete(&totp_enrollment_key(admin_id)).await?;
+    Ok(())
+}
+
+/// 発行済みのWebAuthnチャレンジ
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "purpose", rename_all = "lowercase")]
+pub enum WebAuthnChallenge {
+    /// ログイン中の管理者によるパスキー登録
+    Register { admin_id: i64 },
+    /// パスキーによるログイン
+    Login,
 }
 
-/// Bearer tokenまたはCookieから管理者認証を検証
-pub fn verify_admin_token(req: &Request, env: &Env) -> Result<bool> {
-    let expected_token = match env.secret("ADMIN_TOKEN") {
-        Ok(secret) => secret.to_string(),
-        Err(_) => return Ok(false),
+fn webauthn_challenge_key(challenge: &str) -> String {
+    format!("webauthn_challenge:{}", challenge)
+}
+
+/// WebAuthnチャレンジを保存
+pub async fn store_webauthn_challenge(
+    env: &Env,
+    challenge: &str,
+    purpose: &WebAuthnChallenge,
+) -> Result<()> {
+    let kv = env.kv("RATE_LIMIT")?;
+    kv.put(
+        &webauthn_challenge_key(challenge),
+        serde_json::to_string(purpose)?,
+    )?
+    .expiration_ttl(WEBAUTHN_CHALLENGE_TTL_SECONDS)
+    .execute()
+    .await?;
+    Ok(())
+}
+
+/// WebAuthnチャレンジを取り出す（再利用できないよう削除する）
+pub async fn take_webauthn_challenge(
+    env: &Env,
+    challenge: &str,
+) -> Result<Option<WebAuthnChallenge>> {
+    let kv = env.kv("RATE_LIMIT")?;
+    let key = webauthn_challenge_key(challenge);
+    let purpose = kv.get(&key).json::<WebAuthnChallenge>().await?;
+    if purpose.is_some() {
+        kv.delete(&key).await?;
+    }
+    Ok(purpose)
+}
+
+/// セッションIDから管理者を取得
+///
+/// アカウントの削除や権限変更を即座に反映するため、DBの管理者情報で上書きする。
+async fn identity_from_session(env: &Env, session_id: &str) -> Result<Option<AdminIdentity>> {
+    let kv = env.kv("RATE_LIMIT")?;
+    let Some(identity) = kv.get(&session_key(session_id)).json::<AdminIdentity>().await? else {
+        return Ok(None);
+    };
+    // CSRFトークンを持たない古いセッションは無効として再ログインさせる
+    if identity.csrf_token.is_none() {
+        return Ok(None);
+    }
+
+    let Some(admin_id) = identity.admin_id else {
+        return Ok(Some(identity));
     };
 
+    let db = env.d1("DB")?;
+    Ok(db::get_admin(&db, admin_id).await?.map(|admin| AdminIdentity {
+        admin_id: Some(admin.id),
+        username: admin.username,
+        role: admin.role,
+        csrf_token: identity.csrf_token,
+    }))
+}
+
+/// Bearer token、Cloudflare AccessのJWT、セッションCookieの順に管理者認証を検証
+pub async fn verify_admin_token(req: &Request, env: &Env) -> Result<Option<AdminIdentity>> {
     // まずAuthorizationヘッダーをチェック（API用）
-    let auth_header = req.headers().get("Authorization")?;
-    if check_bearer_token(auth_header.as_deref(), &expected_token) {
-        return Ok(true);
+    if let Ok(secret) = env.secret("ADMIN_TOKEN") {
+        let auth_header = req.headers().get("Authorization")?;
+        if check_bearer_token(auth_header.as_deref(), &secret.to_string()) {
+            return Ok(Some(AdminIdentity::bootstrap()));
+        }
     }
 
-    // 次にCookieをチェック（HTML画面用）
-    let cookie_header = req.headers().get("Cookie")?;
-    if check_cookie_token(cookie_header.as_deref(), &expected_token) {
-        return Ok(true);
+    // Cloudflare Access配下ではAccessが付与したJWTをチェック
+    if let Some(identity) = access::identity_from_request(req, env).await? {
+        return Ok(Some(identity));
+    }
+
+    // 最後にセッションCookieをチェック（HTML画面用）
+    match session_id_from_request(req)? {
+        Some(session_id) => identity_from_session(env, &session_id).await,
+        None => Ok(None),
+    }
+}
+
+/// 管理者APIの認証を行い、拒否する場合はそのレスポンスを返す
+///
+/// ロックアウト中のクライアントは429で拒否し、Bearerトークンの失敗は試行回数として記録する。
+/// 全体の失敗による遅延は認証できなかったリクエストにだけかけ、ログイン済みの管理者は待たせない。
+pub async fn require_admin_api(
+    req: &Request,
+    env: &Env,
+    required: AdminRole,
+) -> Result<std::result::Result<AdminIdentity, Response>> {
+    let kv = env.kv("RATE_LIMIT")?;
+    let client = rate_limit::login_client_key(req, env).await?;
+    let now = now_unix();
+
+    let status = login_guard::check(&kv, &client, now).await?;
+    if let Some(retry_after) = status.retry_after {
+        return too_many_attempts_response(retry_after).map(Err);
+    }
+
+    match verify_admin_token(req, env).await? {
+        Some(admin) if admin.has_role(required) => Ok(Ok(admin)),
+        Some(_) => forbidden_response().map(Err),
+        None => {
+            login_guard::throttle(&status).await;
+            if req.headers().get("Authorization")?.is_some() {
+                login_guard::record_failure(&kv, &client, now).await?;
+            }
+            unauthorized_response().map(Err)
+        }
+    }
+}
+
+/// 管理画面の認証を行い、拒否する場合はそのレスポンスを返す
+///
+/// 未ログインならログインページへリダイレクトし、権限不足なら403を返す。
+pub async fn require_admin_page(
+    req: &Request,
+    env: &Env,
+    required: AdminRole,
+) -> Result<std::result::Result<AdminIdentity, Response>> {
+    match verify_admin_token(req, env).await? {
+        Some(admin) if admin.has_role(required) => Ok(Ok(admin)),
+        Some(_) => {
+            let html = templates::render_forbidden();
+            Response::from_html(html).map(|r| Err(r.with_status(403)))
+        }
+        None => {
+            let headers = Headers::new();
+            headers.set("Location", "/admin/login")?;
+            Ok(Err(Response::empty()?.with_status(302).with_headers(headers)))
+        }
+    }
+}
+
+/// 送信されたCSRFトークンが正しいか（純粋関数）
+pub fn verify_csrf_token(identity: &AdminIdentity, submitted: Option<&str>) -> bool {
+    match identity.csrf_token.as_deref() {
+        None => true,
+        Some(expected) => submitted
+            .is_some_and(|token| !expected.is_empty() && constant_time_eq(token, expected)),
+    }
+}
+
+/// Origin/Refererヘッダーが自サイトを指しているか（純粋関数）
+///
+/// どちらも送られない場合はCSRFトークンの検証に任せて許可する。
+pub fn is_same_origin(origin: Option<&str>, referer: Option<&str>, expected: &str) -> bool {
+    match (origin, referer) {
+        (Some(origin), _) => origin == expected,
+        (None, Some(referer)) => Url::parse(referer)
+            .map(|url| url.origin().ascii_serialization() == expected)
+            .unwrap_or(false),
+        (None, None) => true,
     }
+}
+
+/// リクエストの送信元が自サイトか確認
+pub fn check_same_origin(req: &Request) -> Result<bool> {
+    let expected = req.url()?.origin().ascii_serialization();
+    let headers = req.headers();
+    Ok(is_same_origin(
+        headers.get("Origin")?.as_deref(),
+        headers.get("Referer")?.as_deref(),
+        &expected,
+    ))
+}
+
+/// CSRF検証に失敗したときのHTMLレスポンスを生成
+pub fn csrf_rejected_page() -> Result<Response> {
+    let html = templates::render_bad_request(
+        "フォームの有効期限が切れたか、別のサイトから送信されました。ページを再読み込みしてやり直してください",
+    );
+    Response::from_html(html).map(|r| r.with_status(403))
+}
+
+/// 管理画面のフォーム送信を認証し、送信元とCSRFトークンを検証してフォームを返す
+pub async fn require_admin_form(
+    req: &mut Request,
+    env: &Env,
+    required: AdminRole,
+) -> Result<std::result::Result<(AdminIdentity, FormData), Response>> {
+    let admin = match require_admin_page(req, env, required).await? {
+        Ok(admin) => admin,
+        Err(response) => return Ok(Err(response)),
+    };
+    if !check_same_origin(req)? {
+        return csrf_rejected_page().map(Err);
+    }
+
+    let form_data = req.form_data().await?;
+    let submitted = match form_data.get(CSRF_FIELD_NAME) {
+        Some(FormEntry::Field(value)) => Some(value),
+        _ => None,
+    };
+    if !verify_csrf_token(&admin, submitted.as_deref()) {
+        return csrf_rejected_page().map(Err);
+    }
+    Ok(Ok((admin, form_data)))
+}
 
-    Ok(false)
+/// fetchによる管理画面のリクエストで送信元とCSRFヘッダーを検証する
+pub fn require_csrf_header(
+    req: &Request,
+    admin: &AdminIdentity,
+) -> Result<std::result::Result<(), Response>> {
+    let submitted = req.headers().get(CSRF_HEADER_NAME)?;
+    if check_same_origin(req)? && verify_csrf_token(admin, submitted.as_deref()) {
+        return Ok(Ok(()));
+    }
+    Response::from_json(&ErrorResponse::new("CSRF verification failed", "CSRF_FAILED"))
+        .map(|r| Err(r.with_status(403)))
 }
 
 /// 認証Cookie設定用のSet-Cookieヘッダー値を生成
 pub fn create_auth_cookie(token: &str, secure: bool) -> String {
     let secure_flag = if secure { "; Secure" } else { "" };
     format!(
-        "{}={}; HttpOnly; SameSite=Strict; Path=/admin; Max-Age=86400{}",
-        ADMIN_COOKIE_NAME, token, secure_flag
+        "{}={}; HttpOnly; SameSite=Strict; Path=/admin; Max-Age={}{}",
+        ADMIN_COOKIE_NAME, token, SESSION_TTL_SECONDS, secure_flag
     )
 }
 
@@ -74,6 +421,25 @@ pub fn unauthorized_response() -> Result<Response> {
         .map(|r| r.with_status(401))
 }
 
+/// 権限不足時のJSONレスポンスを生成
+pub fn forbidden_response() -> Result<Response> {
+    Response::from_json(&ErrorResponse::new("Forbidden", "FORBIDDEN"))
+        .map(|r| r.with_status(403))
+}
+
+/// ロックアウト中のJSONレスポンスを生成
+fn too_many_attempts_response(retry_after: i64) -> Result<Response> {
+    let mut response = Response::from_json(&ErrorResponse::new(
+        "Too many failed attempts",
+        "TOO_MANY_ATTEMPTS",
+    ))?
+    .with_status(429);
+    response
+        .headers_mut()
+        .set("Retry-After", &retry_after.to_string())?;
+    Ok(response)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -138,23 +504,70 @@ mod tests {
     }
 
     #[test]
-    fn test_check_cookie_token_valid() {
-        assert!(check_cookie_token(Some("admin_token=secret123"), "secret123"));
+    fn test_admin_identity_has_role() {
+        let admin = AdminIdentity {
+            admin_id: Some(1),
+            username: "mod".to_string(),
+            role: AdminRole::Moderator,
+            csrf_token: None,
+        };
+        assert!(admin.has_role(AdminRole::Viewer));
+        assert!(admin.has_role(AdminRole::Moderator));
+        assert!(!admin.has_role(AdminRole::Superuser));
     }
 
     #[test]
-    fn test_check_cookie_token_invalid() {
-        assert!(!check_cookie_token(Some("admin_token=wrong"), "secret123"));
+    fn test_bootstrap_identity_is_superuser() {
+        let admin = AdminIdentity::bootstrap();
+        assert_eq!(admin.admin_id, None);
+        assert!(admin.has_role(AdminRole::Superuser));
+    }
+
+    #[test]
+    fn test_verify_csrf_token() {
+        let session = AdminIdentity {
+            csrf_token: Some("expected-token".to_string()),
+            ..AdminIdentity::bootstrap()
+        };
+        assert!(verify_csrf_token(&session, Some("expected-token")));
+        assert!(!verify_csrf_token(&session, Some("other-token")));
+        assert!(!verify_csrf_token(&session, None));
+
+        let empty = AdminIdentity {
+            csrf_token: Some(String::new()),
+            ..AdminIdentity::bootstrap()
+        };
+        assert!(!verify_csrf_token(&empty, Some("")));
+
+        // Bearerトークンによる認証はブラウザが自動送信しないため検証不要
+        assert!(verify_csrf_token(&AdminIdentity::bootstrap(), None));
     }
 
     #[test]
-    fn test_check_cookie_token_no_cookie() {
-        assert!(!check_cookie_token(None, "secret123"));
+    fn test_is_same_origin() {
+        let expected = "https://darekagakaku.day";
+        assert!(is_same_origin(Some("https://darekagakaku.day"), None, expected));
+        assert!(!is_same_origin(Some("https://evil.example"), None, expected));
+        assert!(!is_same_origin(Some("null"), None, expected));
+        assert!(is_same_origin(None, Some("https://darekagakaku.day/admin/admins"), expected));
+        assert!(!is_same_origin(None, Some("https://darekagakaku.day.evil.example/"), expected));
+        assert!(!is_same_origin(None, Some("not a url"), expected));
+        // Originがあればそちらを優先する
+        assert!(!is_same_origin(
+            Some("https://evil.example"),
+            Some("https://darekagakaku.day/"),
+            expected
+        ));
+        assert!(is_same_origin(None, None, expected));
     }
 
     #[test]
-    fn test_check_cookie_token_wrong_name() {
-        assert!(!check_cookie_token(Some("other=secret123"), "secret123"));
+    fn test_webauthn_challenge_serialization() {
+        let register = WebAuthnChallenge::Register { admin_id: 3 };
+        let json = serde_json::to_string(&register).unwrap();
+        assert_eq!(json, r#"{"purpose":"register","admin_id":3}"#);
+        let login: WebAuthnChallenge = serde_json::from_str(r#"{"purpose":"login"}"#).unwrap();
+        assert_eq!(login, WebAuthnChallenge::Login);
     }
 
     #[test]
@@ -164,6 +577,7 @@ mod tests {
         assert!(cookie.contains("HttpOnly"));
         assert!(cookie.contains("SameSite=Strict"));
         assert!(cookie.contains("Path=/admin"));
+        assert!(cookie.contains("Max-Age=86400"));
         assert!(cookie.contains("Secure"));
     }
 
@@ -0,0 +1,153 @@
+use std::ops::Deref;
+
+use worker::{Bucket, Env, Request, Response, Result, RouteContext};
+
+use crate::api;
+use crate::audit;
+use crate::auth;
+use crate::config::FromEnv;
+use crate::models::{AdminRole, AuditAction, AuditEvent};
+use crate::reply::IntoResponse;
+use crate::store::D1Store;
+use crate::time::{now_iso8601, today_jst};
+
+pub use darekagakaku_core::backup::{
+    RestoreRequest, RetentionPolicy, SnapshotInfo, SnapshotStore, SNAPSHOT_PREFIX,
+};
+use darekagakaku_core::backup::{self, snapshot_date};
+
+/// R2上のスナップショットの置き場所
+pub struct R2Snapshots(Bucket);
+
+impl R2Snapshots {
+    /// BACKUPSバインディングから作る
+    pub fn from_env(env: &Env) -> Result<Self> {
+        Ok(Self(env.bucket("BACKUPS")?))
+    }
+}
+
+impl Deref for R2Snapshots {
+    type Target = Bucket;
+
+    fn deref(&self) -> &Bucket {
+        &self.0
+    }
+}
+
+impl SnapshotStore for R2Snapshots {
+    type Error = worker::Error;
+
+    async fn list_snapshots(&self) -> Result<Vec<SnapshotInfo>> {
+        let mut snapshots = Vec::new();
+        let mut cursor = None;
+        loop {
+            let mut list = self.list().prefix(SNAPSHOT_PREFIX);
+            if let Some(cursor) = cursor {
+                list = list.cursor(cursor);
+            }
+            let objects = list.execute().await?;
+            for object in objects.objects() {
+                let key = object.key();
+                if let Some(date) = snapshot_date(&key) {
+                    snapshots.push(SnapshotInfo {
+                        date: date.to_string(),
+                        size: object.size(),
+                        key,
+                    });
+                }
+            }
+            if !objects.truncated() {
+                break;
+            }
+            cursor = objects.cursor();
+        }
+        Ok(snapshots)
+    }
+
+    async fn get_snapshot(&self, key: &str) -> Result<Option<Vec<u8>>> {
+        let Some(object) = self.get(key).execute().await? else {
+            return Ok(None);
+        };
+        match object.body() {
+            Some(body) => Ok(Some(body.bytes().await?)),
+            None => Ok(None),
+        }
+    }
+
+    async fn put_snapshot(&self, key: &str, data: Vec<u8>) -> Result<()> {
+        self.put(key, data).execute().await?;
+        Ok(())
+    }
+
+    async fn delete_snapshot(&self, key: &str) -> Result<()> {
+        self.delete(key).await
+    }
+}
+
+/// Cron Triggerから毎晩呼ばれ、今日のスナップショットを書いて古いものを消す
+pub async fn run_nightly(env: &Env) -> Result<()> {
+    let db = D1Store::from_env(env)?;
+    let snapshots = R2Snapshots::from_env(env)?;
+    let policy = RetentionPolicy::from_env(env);
+    let report = backup::run_backup(&db, &snapshots, &today_jst(), &policy).await?;
+    log::info!(
+        "Wrote {} ({} bytes), deleted {} expired snapshots",
+        report.key,
+        report.size,
+        report.deleted.len()
+    );
+    Ok(())
+}
+
+/// GET /api/admin/backups - スナップショットの一覧（新しい順、管理者用）
+pub async fn admin_list_backups(req: Request, ctx: RouteContext<()>) -> Result<Response> {
+    if let Err(response) = auth::require_admin_api(&req, &ctx.env, AdminRole::Superuser).await? {
+        return Ok(response);
+    }
+
+    let snapshots = R2Snapshots::from_env(&ctx.env)?;
+    let mut list = snapshots.list_snapshots().await?;
+    list.sort_by(|a, b| b.date.cmp(&a.date));
+    Response::from_json(&list)
+}
+
+/// POST /api/admin/restore - スナップショットからデータベースまたは1日分を戻す（管理者用）
+///
+/// 本文はJSONで`snapshot`（日付）を必須とし、`date`を指定するとその日だけを戻す。
+/// `replace`を指定すると、今の日記と版を消してからスナップショットの内容に置き換える。
+/// 応答の`next_offset`があれば、それを`offset`に指定して続きを戻す。
+pub async fn admin_restore(mut req: Request, ctx: RouteContext<()>) -> Result<Response> {
+    let admin = match auth::require_admin_api(&req, &ctx.env, AdminRole::Superuser).await? {
+        Ok(admin) => admin,
+        Err(response) => return Ok(response),
+    };
+
+    let request: RestoreRequest = match req.json().await {
+        Ok(request) => request,
+        Err(_) => return api::invalid_json_reply()?.into_response(),
+    };
+
+    let db = D1Store::from_env(&ctx.env)?;
+    let snapshots = R2Snapshots::from_env(&ctx.env)?;
+    let report = match backup::restore(&db, &snapshots, &request, &today_jst(), &now_iso8601()).await? {
+        Ok(report) => report,
+        Err(failure) => return failure.reply()?.into_response(),
+    };
+
+    if report.import.applied {
+        let mut target = match &report.date {
+            Some(date) => format!("snapshot={} date={}", report.snapshot, date),
+            None => format!("snapshot={} offset={}", report.snapshot, report.offset),
+        };
+        if report.replace {
+            target.push_str(" replace");
+        }
+        let event = AuditEvent {
+            target: Some(target),
+            ..AuditEvent::new(AuditAction::Restore)
+        };
+        audit::record(&ctx.env, &req, &admin, event).await;
+    }
+
+    report.reply()?.into_response()
+}
@@ -0,0 +1,14 @@
+use worker::Env;
+
+pub use darekagakaku_core::config::FromVars;
+
+/// Workersの変数から設定を組み立てる
+pub trait FromEnv {
+    fn from_env(env: &Env) -> Self;
+}
+
+impl<T: FromVars> FromEnv for T {
+    fn from_env(env: &Env) -> Self {
+        T::from_vars(|name| env.var(name).ok().map(|v| v.to_string()))
+    }
+}
@@ -0,0 +1,26 @@
+pub use darekagakaku_core::crypto::to_hex;
+
+/// 暗号論的に安全な乱数バイト列を生成
+pub fn random_bytes(len: usize) -> Vec<u8> {
+    let mut buf = vec![0u8; len];
+    getrandom::getrandom(&mut buf).expect("failed to obtain random bytes");
+    buf
+}
+
+/// セッションIDなどに使うランダムなトークン（16進64文字）
+pub fn random_token() -> String {
+    to_hex(&random_bytes(32))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_random_token_is_unique() {
+        let a = random_token();
+        let b = random_token();
+        assert_eq!(a.len(), 64);
+        assert_ne!(a, b);
+    }
+}
@@ -1,8 +1,25 @@
 use worker::d1::{D1Database, D1Type};
+use worker::wasm_bindgen::JsValue;
 use worker::Result;
 
-use crate::models::{DiaryEntry, DiaryVersion};
-use crate::time::{now_iso8601, today_jst};
+use crate::models::{
+    Admin, AdminCredential, AdminRole, ApiKey, ApiKeyWithUsage, AuditEvent, AuditLogEntry,
+    AuditLogFilter, ContentFilter, DiaryEntry, DiaryVersion, FilterAction, FilterKind,
+    ModerationItem, ModerationResolution,
+};
+use crate::entry_stats::SaveActivity;
+use crate::stats::{DayOverwrites, MonthTotals, StatsTotals};
+use crate::store::{ClearRange, ImportWrite};
+use crate::time::now_iso8601;
+
+/// i64の値をD1の整数パラメータに変換
+///
+/// D1Type::Integerはi32のため、範囲外の値は切り詰めて別の行に当たらないようエラーにする。
+fn integer_param(value: i64) -> Result<D1Type<'static>> {
+    i32::try_from(value)
+        .map(D1Type::Integer)
+        .map_err(|_| worker::Error::RustError(format!("integer parameter out of range: {}", value)))
+}
 
 /// 指定日の日記エントリを取得
 pub async fn get_entry(db: &D1Database, date: &str) -> Result<Option<DiaryEntry>> {
@@ -11,18 +28,19 @@ pub async fn get_entry(db: &D1Database, date: &str) -> Result<Option<DiaryEntry>
     stmt.first::<DiaryEntry>(None).await
 }
 
-/// 今日の日記エントリを作成または更新（変更がある場合はバージョンを保存）
-pub async fn upsert_today_entry(db: &D1Database, content: &str) -> Result<()> {
-    let today = today_jst();
+/// 指定日の日記エントリを作成または更新（変更がある場合はバージョンを保存）
+///
+/// 管理者による差し戻し・編集は過去の日付にも行えるため、日付を指定できる。
+pub async fn upsert_entry(db: &D1Database, date: &str, content: &str) -> Result<()> {
     let now = now_iso8601();
 
     // 既存エントリを取得
-    let existing = get_entry(db, &today).await?;
+    let existing = get_entry(db, date).await?;
 
     // 既存エントリがあり、内容が異なる場合のみバージョンを保存
     if let Some(entry) = existing {
         if entry.content != content {
-            save_version(db, &today, &entry.content).await?;
+            save_version(db, date, &entry.content).await?;
         }
     }
 
@@ -35,7 +53,7 @@ pub async fn upsert_today_entry(db: &D1Database, content: &str) -> Result<()> {
     );
 
     let stmt = stmt.bind_refs(&[
-        D1Type::Text(&today),
+        D1Type::Text(date),
         D1Type::Text(content),
         D1Type::Text(&now),
     ])?;
@@ -46,7 +64,16 @@ pub async fn upsert_today_entry(db: &D1Database, content: &str) -> Result<()> {
 
 /// 日記のバージョンを履歴に保存
 async fn save_version(db: &D1Database, date: &str, content: &str) -> Result<()> {
-    let now = now_iso8601();
+    insert_version(db, date, content, &now_iso8601()).await
+}
+
+/// 作成日時を指定して次の番号のバージョンを追加
+pub async fn insert_version(
+    db: &D1Database,
+    date: &str,
+    content: &str,
+    created_at: &str,
+) -> Result<()> {
     let next_version = get_next_version_number(db, date).await?;
 
     let stmt = db.prepare(
@@ -58,13 +85,78 @@ async fn save_version(db: &D1Database, date: &str, content: &str) -> Result<()>
         D1Type::Text(date),
         D1Type::Text(content),
         D1Type::Integer(next_version),
-        D1Type::Text(&now),
+        D1Type::Text(created_at),
     ])?;
 
     stmt.run().await?;
     Ok(())
 }
 
+/// インポートの書き込みを1回のバッチで行う（D1のバッチはすべて成功するか、すべて取り消される）
+pub async fn write_import(db: &D1Database, writes: &[ImportWrite]) -> Result<()> {
+    if writes.is_empty() {
+        return Ok(());
+    }
+    let mut statements = Vec::with_capacity(writes.len());
+    for write in writes {
+        let statement = match write {
+            ImportWrite::Clear(range) => {
+                let (condition, date) = match range {
+                    ClearRange::Date(date) => ("= ?1", date),
+                    ClearRange::Before(date) => ("< ?1", date),
+                };
+                // 日記を参照している行から消す
+                for sql in [
+                    "DELETE FROM entry_saves WHERE entry_date ",
+                    "DELETE FROM entry_writer_counts WHERE entry_date ",
+                    "DELETE FROM diary_versions WHERE entry_date ",
+                ] {
+                    let delete = db
+                        .prepare(format!("{}{}", sql, condition))
+                        .bind_refs(&D1Type::Text(date))?;
+                    statements.push(delete);
+                }
+                db.prepare(format!("DELETE FROM diary_entries WHERE date {}", condition))
+                    .bind_refs(&D1Type::Text(date))?
+            }
+            ImportWrite::Entry(entry) => db
+                .prepare(
+                    "INSERT INTO diary_entries (date, content, created_at, updated_at)
+                     VALUES (?1, ?2, ?3, ?4)
+                     ON CONFLICT(date) DO UPDATE SET
+                       content = excluded.content,
+                       created_at = excluded.created_at,
+                       updated_at = excluded.updated_at",
+                )
+                .bind_refs(&[
+                    D1Type::Text(&entry.date),
+                    D1Type::Text(&entry.content),
+                    D1Type::Text(&entry.created_at),
+                    D1Type::Text(&entry.updated_at),
+                ])?,
+            // 番号は同じバッチの前の文で足した版も数える
+            ImportWrite::Version {
+                date,
+                content,
+                created_at,
+            } => db
+                .prepare(
+                    "INSERT INTO diary_versions (entry_date, content, version_number, created_at)
+                     SELECT ?1, ?2, COALESCE(MAX(version_number), 0) + 1, ?3
+                     FROM diary_versions WHERE entry_date = ?1",
+                )
+                .bind_refs(&[
+                    D1Type::Text(date),
+                    D1Type::Text(content),
+                    D1Type::Text(created_at),
+                ])?,
+        };
+        statements.push(statement);
+    }
+    db.batch(statements).await?;
+    Ok(())
+}
+
 /// 次のバージョン番号を取得
 async fn get_next_version_number(db: &D1Database, date: &str) -> Result<i32> {
     let stmt = db.prepare(
@@ -111,10 +203,27 @@ pub async fn get_version(db: &D1Database, date: &str, version: i32) -> Result<Op
     stmt.first::<DiaryVersion>(None).await
 }
 
-/// 過去の日記エントリ一覧を取得（今日を除く、新しい順）
-pub async fn list_past_entries(db: &D1Database, limit: i32) -> Result<Vec<DiaryEntry>> {
-    let today = today_jst();
+/// 特定バージョンの内容を墨消し（置き換え）する
+pub async fn redact_version(db: &D1Database, date: &str, version: i32, replacement: &str) -> Result<()> {
+    let stmt = db.prepare(
+        "UPDATE diary_versions SET content = ?3
+         WHERE entry_date = ?1 AND version_number = ?2"
+    );
+    let stmt = stmt.bind_refs(&[
+        D1Type::Text(date),
+        D1Type::Integer(version),
+        D1Type::Text(replacement),
+    ])?;
+    stmt.run().await?;
+    Ok(())
+}
 
+/// 過去の日記エントリ一覧を取得（`today` を含まず、新しい順）
+pub async fn list_past_entries(
+    db: &D1Database,
+    today: &str,
+    limit: i32,
+) -> Result<Vec<DiaryEntry>> {
     let stmt = db.prepare(
         "SELECT date, content, created_at, updated_at
          FROM diary_entries
@@ -124,7 +233,7 @@ pub async fn list_past_entries(db: &D1Database, limit: i32) -> Result<Vec<DiaryE
     );
 
     let stmt = stmt.bind_refs(&[
-        D1Type::Text(&today),
+        D1Type::Text(today),
         D1Type::Integer(limit),
     ])?;
 
@@ -132,3 +241,799 @@ pub async fn list_past_entries(db: &D1Database, limit: i32) -> Result<Vec<DiaryE
     result.results::<DiaryEntry>()
 }
 
+/// 指定日より後の日記エントリ一覧を取得（古い順）
+pub async fn list_entries_after(
+    db: &D1Database,
+    date: &str,
+    limit: i32,
+) -> Result<Vec<DiaryEntry>> {
+    let stmt = db.prepare(
+        "SELECT date, content, created_at, updated_at
+         FROM diary_entries
+         WHERE date > ?1
+         ORDER BY date ASC
+         LIMIT ?2"
+    );
+    let stmt = stmt.bind_refs(&[D1Type::Text(date), D1Type::Integer(limit)])?;
+    let result = stmt.all().await?;
+    result.results::<DiaryEntry>()
+}
+
+/// 指定したIDより後のバージョンを取得（IDの順）
+pub async fn list_versions_after(
+    db: &D1Database,
+    id: i64,
+    limit: i32,
+) -> Result<Vec<DiaryVersion>> {
+    let stmt = db.prepare(
+        "SELECT id, entry_date, content, version_number, created_at
+         FROM diary_versions
+         WHERE id > ?1
+         ORDER BY id ASC
+         LIMIT ?2"
+    );
+    let stmt = stmt.bind_refs(&[integer_param(id)?, D1Type::Integer(limit)])?;
+    let result = stmt.all().await?;
+    result.results::<DiaryVersion>()
+}
+
+/// 統計の集計表を読む（上書きの多い日はlimit件まで）
+pub async fn stats_totals(db: &D1Database, limit: i32) -> Result<StatsTotals> {
+    #[derive(serde::Deserialize)]
+    struct FirstDate {
+        first_date: Option<String>,
+    }
+
+    #[derive(serde::Deserialize)]
+    struct HourSaves {
+        hour: usize,
+        saves: i64,
+    }
+
+    let stmt = db.prepare("SELECT MIN(date) as first_date FROM diary_entries");
+    let first_date = stmt.first::<FirstDate>(None).await?.and_then(|r| r.first_date);
+
+    let stmt = db.prepare("SELECT month, entries, total_length FROM stats_months ORDER BY month");
+    let months = stmt.all().await?.results::<MonthTotals>()?;
+
+    let stmt = db.prepare(
+        "SELECT date, versions FROM stats_days
+         ORDER BY versions DESC, date
+         LIMIT ?1"
+    );
+    let stmt = stmt.bind_refs(&D1Type::Integer(limit))?;
+    let most_overwritten = stmt.all().await?.results::<DayOverwrites>()?;
+
+    let mut hours = [0; 24];
+    let stmt = db.prepare("SELECT hour, saves FROM stats_hours");
+    for row in stmt.all().await?.results::<HourSaves>()? {
+        if let Some(saves) = hours.get_mut(row.hour) {
+            *saves = row.saves;
+        }
+    }
+
+    Ok(StatsTotals {
+        months,
+        first_date,
+        most_overwritten,
+        hours,
+    })
+}
+
+/// 保存した書き手のハッシュを記録
+pub async fn record_save(db: &D1Database, date: &str, writer: &str) -> Result<()> {
+    let now = now_iso8601();
+    let stmt = db.prepare(
+        "INSERT INTO entry_saves (entry_date, writer, created_at) VALUES (?1, ?2, ?3)"
+    );
+    let stmt = stmt.bind_refs(&[
+        D1Type::Text(date),
+        D1Type::Text(writer),
+        D1Type::Text(&now),
+    ])?;
+    stmt.run().await?;
+    Ok(())
+}
+
+/// 指定日の版の数・書き手の数・最後に書き換えられた日時を取得
+pub async fn save_activity(db: &D1Database, date: &str) -> Result<SaveActivity> {
+    #[derive(serde::Deserialize)]
+    struct Activity {
+        versions: i64,
+        writers: i64,
+        last_changed_at: Option<String>,
+    }
+
+    let stmt = db.prepare(
+        "SELECT
+           (SELECT COUNT(*) FROM diary_versions WHERE entry_date = ?1) as versions,
+           (SELECT COUNT(DISTINCT writer) FROM entry_saves WHERE entry_date = ?1)
+             + COALESCE((SELECT writers FROM entry_writer_counts WHERE entry_date = ?1), 0)
+             as writers,
+           (SELECT created_at FROM diary_versions WHERE entry_date = ?1
+            ORDER BY version_number DESC LIMIT 1) as last_changed_at"
+    );
+    let stmt = stmt.bind_refs(&D1Type::Text(date))?;
+    let activity = stmt.first::<Activity>(None).await?;
+    Ok(activity
+        .map(|a| SaveActivity {
+            versions: a.versions,
+            writers: a.writers,
+            last_changed_at: a.last_changed_at,
+        })
+        .unwrap_or_default())
+}
+
+/// 指定日より前の書き手のハッシュを日ごとの人数にまとめて消す（1回のバッチで行う）
+pub async fn compact_saves(db: &D1Database, before: &str) -> Result<()> {
+    let count = db
+        .prepare(
+            "INSERT INTO entry_writer_counts (entry_date, writers)
+             SELECT entry_date, COUNT(DISTINCT writer) FROM entry_saves
+             WHERE entry_date < ?1 GROUP BY entry_date
+             ON CONFLICT(entry_date) DO UPDATE SET writers = writers + excluded.writers",
+        )
+        .bind_refs(&D1Type::Text(before))?;
+    let delete = db
+        .prepare("DELETE FROM entry_saves WHERE entry_date < ?1")
+        .bind_refs(&D1Type::Text(before))?;
+    db.batch(vec![count, delete]).await?;
+    Ok(())
+}
+
+const ADMIN_COLUMNS: &str =
+    "id, username, password_hash, role, totp_secret, totp_last_step, email, created_at, updated_at";
+
+/// 管理者一覧を取得（ユーザー名順）
+pub async fn list_admins(db: &D1Database) -> Result<Vec<Admin>> {
+    let stmt = db.prepare(format!("SELECT {} FROM admins ORDER BY username", ADMIN_COLUMNS));
+    let result = stmt.all().await?;
+    result.results::<Admin>()
+}
+
+/// IDで管理者を取得
+pub async fn get_admin(db: &D1Database, id: i64) -> Result<Option<Admin>> {
+    let stmt = db.prepare(format!("SELECT {} FROM admins WHERE id = ?1", ADMIN_COLUMNS));
+    let stmt = stmt.bind_refs(&integer_param(id)?)?;
+    stmt.first::<Admin>(None).await
+}
+
+/// ユーザー名で管理者を取得
+pub async fn get_admin_by_username(db: &D1Database, username: &str) -> Result<Option<Admin>> {
+    let stmt = db.prepare(format!("SELECT {} FROM admins WHERE username = ?1", ADMIN_COLUMNS));
+    let stmt = stmt.bind_refs(&D1Type::Text(username))?;
+    stmt.first::<Admin>(None).await
+}
+
+/// メールアドレスから管理者を取得
+pub async fn get_admin_by_email(db: &D1Database, email: &str) -> Result<Option<Admin>> {
+    let stmt = db.prepare(format!("SELECT {} FROM admins WHERE email = ?1", ADMIN_COLUMNS));
+    let stmt = stmt.bind_refs(&D1Type::Text(email))?;
+    stmt.first::<Admin>(None).await
+}
+
+/// 管理者を作成
+pub async fn create_admin(
+    db: &D1Database,
+    username: &str,
+    password_hash: &str,
+    role: AdminRole,
+) -> Result<()> {
+    let now = now_iso8601();
+    let stmt = db.prepare(
+        "INSERT INTO admins (username, password_hash, role, created_at, updated_at)
+         VALUES (?1, ?2, ?3, ?4, ?4)"
+    );
+    let stmt = stmt.bind_refs(&[
+        D1Type::Text(username),
+        D1Type::Text(password_hash),
+        D1Type::Text(role.as_str()),
+        D1Type::Text(&now),
+    ])?;
+    stmt.run().await?;
+    Ok(())
+}
+
+/// 管理者の権限を変更
+pub async fn update_admin_role(db: &D1Database, id: i64, role: AdminRole) -> Result<()> {
+    let now = now_iso8601();
+    let stmt = db.prepare("UPDATE admins SET role = ?2, updated_at = ?3 WHERE id = ?1");
+    let stmt = stmt.bind_refs(&[
+        integer_param(id)?,
+        D1Type::Text(role.as_str()),
+        D1Type::Text(&now),
+    ])?;
+    stmt.run().await?;
+    Ok(())
+}
+
+/// 管理者のメールアドレスを変更（Noneで解除）
+pub async fn update_admin_email(db: &D1Database, id: i64, email: Option<&str>) -> Result<()> {
+    let now = now_iso8601();
+    let stmt = db.prepare("UPDATE admins SET email = ?2, updated_at = ?3 WHERE id = ?1");
+    let stmt = stmt.bind_refs(&[
+        integer_param(id)?,
+        email.map(D1Type::Text).unwrap_or(D1Type::Null),
+        D1Type::Text(&now),
+    ])?;
+    stmt.run().await?;
+    Ok(())
+}
+
+/// 管理者を削除
+pub async fn delete_admin(db: &D1Database, id: i64) -> Result<()> {
+    let stmt = db.prepare("DELETE FROM admins WHERE id = ?1");
+    let stmt = stmt.bind_refs(&integer_param(id)?)?;
+    stmt.run().await?;
+    Ok(())
+}
+
+/// 二要素認証のシークレットを設定（Noneで無効化）
+pub async fn set_admin_totp_secret(db: &D1Database, id: i64, secret: Option<&str>) -> Result<()> {
+    let now = now_iso8601();
+    let stmt = db.prepare(
+        "UPDATE admins SET totp_secret = ?2, totp_last_step = NULL, updated_at = ?3 WHERE id = ?1"
+    );
+    let stmt = stmt.bind_refs(&[
+        integer_param(id)?,
+        secret.map(D1Type::Text).unwrap_or(D1Type::Null),
+        D1Type::Text(&now),
+    ])?;
+    stmt.run().await?;
+    Ok(())
+}
+
+/// 使われたTOTPの時間ステップを記録する（記録できた場合はtrue）
+///
+/// 記録済みのステップ以前なら更新しないため、同じコードの同時送信は1件しか通らない。
+pub async fn claim_admin_totp_step(db: &D1Database, id: i64, step: i64) -> Result<bool> {
+    let stmt = db.prepare(
+        "UPDATE admins SET totp_last_step = ?2
+         WHERE id = ?1 AND (totp_last_step IS NULL OR totp_last_step < ?2)"
+    );
+    let stmt = stmt.bind_refs(&[integer_param(id)?, integer_param(step)?])?;
+    let result = stmt.run().await?;
+    let changes = result.meta()?.and_then(|m| m.changes).unwrap_or(0);
+    Ok(changes > 0)
+}
+
+/// リカバリーコードを入れ替える（既存のコードはすべて無効になる）
+pub async fn replace_recovery_codes(db: &D1Database, admin_id: i64, code_hashes: &[String]) -> Result<()> {
+    let delete = db
+        .prepare("DELETE FROM admin_recovery_codes WHERE admin_id = ?1")
+        .bind_refs(&integer_param(admin_id)?)?;
+
+    let mut statements = vec![delete];
+    for hash in code_hashes {
+        let insert = db
+            .prepare("INSERT INTO admin_recovery_codes (admin_id, code_hash) VALUES (?1, ?2)")
+            .bind_refs(&[integer_param(admin_id)?, D1Type::Text(hash)])?;
+        statements.push(insert);
+    }
+
+    db.batch(statements).await?;
+    Ok(())
+}
+
+/// 未使用のリカバリーコードを消費する（消費できた場合はtrue）
+pub async fn consume_recovery_code(db: &D1Database, admin_id: i64, code_hash: &str) -> Result<bool> {
+    let now = now_iso8601();
+    let stmt = db.prepare(
+        "UPDATE admin_recovery_codes SET used_at = ?3
+         WHERE admin_id = ?1 AND code_hash = ?2 AND used_at IS NULL"
+    );
+    let stmt = stmt.bind_refs(&[
+        integer_param(admin_id)?,
+        D1Type::Text(code_hash),
+        D1Type::Text(&now),
+    ])?;
+    let result = stmt.run().await?;
+    let changes = result.meta()?.and_then(|m| m.changes).unwrap_or(0);
+    Ok(changes > 0)
+}
+
+/// 未使用のリカバリーコードの数を取得
+pub async fn count_unused_recovery_codes(db: &D1Database, admin_id: i64) -> Result<i32> {
+    let stmt = db.prepare(
+        "SELECT COUNT(*) as remaining FROM admin_recovery_codes
+         WHERE admin_id = ?1 AND used_at IS NULL"
+    );
+    let stmt = stmt.bind_refs(&integer_param(admin_id)?)?;
+
+    #[derive(serde::Deserialize)]
+    struct Remaining {
+        remaining: i32,
+    }
+
+    Ok(stmt.first::<Remaining>(None).await?.map(|r| r.remaining).unwrap_or(0))
+}
+
+const CREDENTIAL_COLUMNS: &str =
+    "id, admin_id, credential_id, public_key, sign_count, label, created_at, last_used_at";
+
+/// 管理者のパスキー一覧を取得
+pub async fn list_admin_credentials(
+    db: &D1Database,
+    admin_id: i64,
+) -> Result<Vec<AdminCredential>> {
+    let stmt = db.prepare(format!(
+        "SELECT {} FROM admin_credentials WHERE admin_id = ?1 ORDER BY created_at",
+        CREDENTIAL_COLUMNS
+    ));
+    let stmt = stmt.bind_refs(&integer_param(admin_id)?)?;
+    let result = stmt.all().await?;
+    result.results::<AdminCredential>()
+}
+
+/// クレデンシャルIDでパスキーを取得
+pub async fn get_admin_credential(
+    db: &D1Database,
+    credential_id: &str,
+) -> Result<Option<AdminCredential>> {
+    let stmt = db.prepare(format!(
+        "SELECT {} FROM admin_credentials WHERE credential_id = ?1",
+        CREDENTIAL_COLUMNS
+    ));
+    let stmt = stmt.bind_refs(&D1Type::Text(credential_id))?;
+    stmt.first::<AdminCredential>(None).await
+}
+
+/// パスキーを登録
+pub async fn create_admin_credential(
+    db: &D1Database,
+    admin_id: i64,
+    credential_id: &str,
+    public_key: &str,
+    sign_count: u32,
+    label: &str,
+) -> Result<()> {
+    let now = now_iso8601();
+    let stmt = db.prepare(
+        "INSERT INTO admin_credentials
+           (admin_id, credential_id, public_key, sign_count, label, created_at)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+    );
+    // 署名カウンタはi32に収まらない可能性があるため数値として直接バインドする
+    let stmt = stmt.bind(&[
+        JsValue::from(admin_id as f64),
+        JsValue::from(credential_id),
+        JsValue::from(public_key),
+        JsValue::from(sign_count),
+        JsValue::from(label),
+        JsValue::from(now.as_str()),
+    ])?;
+    stmt.run().await?;
+    Ok(())
+}
+
+/// 認証に成功したパスキーの署名カウンタと最終使用日時を更新
+pub async fn update_admin_credential_usage(
+    db: &D1Database,
+    id: i64,
+    sign_count: u32,
+) -> Result<()> {
+    let now = now_iso8601();
+    let stmt =
+        db.prepare("UPDATE admin_credentials SET sign_count = ?2, last_used_at = ?3 WHERE id = ?1");
+    let stmt = stmt.bind(&[
+        JsValue::from(id as f64),
+        JsValue::from(sign_count),
+        JsValue::from(now.as_str()),
+    ])?;
+    stmt.run().await?;
+    Ok(())
+}
+
+/// 管理者自身のパスキーを削除
+pub async fn delete_admin_credential(db: &D1Database, admin_id: i64, id: i64) -> Result<()> {
+    let stmt = db.prepare("DELETE FROM admin_credentials WHERE id = ?1 AND admin_id = ?2");
+    let stmt = stmt.bind_refs(&[integer_param(id)?, integer_param(admin_id)?])?;
+    stmt.run().await?;
+    Ok(())
+}
+
+/// 監査ログを追記
+pub async fn insert_audit_log(
+    db: &D1Database,
+    actor: &str,
+    actor_id: Option<i64>,
+    ip_hash: &str,
+    event: &AuditEvent,
+) -> Result<()> {
+    let now = now_iso8601();
+    let stmt = db.prepare(
+        "INSERT INTO admin_audit_log
+           (actor, actor_id, action, ip_hash, target_date, target_version, target,
+            before_hash, after_hash, created_at)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10)",
+    );
+    let stmt = stmt.bind_refs(&[
+        D1Type::Text(actor),
+        actor_id.map(integer_param).transpose()?.unwrap_or(D1Type::Null),
+        D1Type::Text(event.action.as_str()),
+        D1Type::Text(ip_hash),
+        event.target_date.as_deref().map(D1Type::Text).unwrap_or(D1Type::Null),
+        event.target_version.map(D1Type::Integer).unwrap_or(D1Type::Null),
+        event.target.as_deref().map(D1Type::Text).unwrap_or(D1Type::Null),
+        event.before_hash.as_deref().map(D1Type::Text).unwrap_or(D1Type::Null),
+        event.after_hash.as_deref().map(D1Type::Text).unwrap_or(D1Type::Null),
+        D1Type::Text(&now),
+    ])?;
+    stmt.run().await?;
+    Ok(())
+}
+
+/// 監査ログを新しい順に取得（操作者・操作種別で絞り込み可能）
+pub async fn list_audit_log(
+    db: &D1Database,
+    filter: &AuditLogFilter,
+    limit: i32,
+    offset: i32,
+) -> Result<Vec<AuditLogEntry>> {
+    let stmt = db.prepare(
+        "SELECT id, actor, actor_id, action, ip_hash, target_date, target_version, target,
+                before_hash, after_hash, created_at
+         FROM admin_audit_log
+         WHERE (?1 IS NULL OR actor = ?1) AND (?2 IS NULL OR action = ?2)
+         ORDER BY id DESC
+         LIMIT ?3 OFFSET ?4",
+    );
+    let stmt = stmt.bind_refs(&[
+        filter.actor.as_deref().map(D1Type::Text).unwrap_or(D1Type::Null),
+        filter.action.map(|a| D1Type::Text(a.as_str())).unwrap_or(D1Type::Null),
+        D1Type::Integer(limit),
+        D1Type::Integer(offset),
+    ])?;
+    let result = stmt.all().await?;
+    result.results::<AuditLogEntry>()
+}
+
+const API_KEY_COLUMNS: &str =
+    "id, label, key_prefix, daily_quota, created_by, created_at, last_used_at, revoked_at";
+
+/// APIキーの一覧と指定日の使用回数を取得（新しい順）
+pub async fn list_api_keys(db: &D1Database, date: &str) -> Result<Vec<ApiKeyWithUsage>> {
+    let stmt = db.prepare(format!("SELECT {} FROM api_keys ORDER BY id DESC", API_KEY_COLUMNS));
+    let keys = stmt.all().await?.results::<ApiKey>()?;
+
+    #[derive(serde::Deserialize)]
+    struct Usage {
+        key_id: i64,
+        count: i64,
+    }
+
+    let stmt = db.prepare("SELECT key_id, count FROM api_key_usage WHERE date = ?1");
+    let stmt = stmt.bind_refs(&D1Type::Text(date))?;
+    let usage = stmt.all().await?.results::<Usage>()?;
+
+    Ok(keys
+        .into_iter()
+        .map(|key| {
+            let used_today = usage
+                .iter()
+                .find(|u| u.key_id == key.id)
+                .map(|u| u.count)
+                .unwrap_or(0);
+            ApiKeyWithUsage { key, used_today }
+        })
+        .collect())
+}
+
+/// キーのハッシュからAPIキーを取得（失効済みも返す）
+pub async fn get_api_key_by_hash(db: &D1Database, key_hash: &str) -> Result<Option<ApiKey>> {
+    let stmt = db.prepare(format!("SELECT {} FROM api_keys WHERE key_hash = ?1", API_KEY_COLUMNS));
+    let stmt = stmt.bind_refs(&D1Type::Text(key_hash))?;
+    stmt.first::<ApiKey>(None).await
+}
+
+/// APIキーを登録
+pub async fn create_api_key(
+    db: &D1Database,
+    label: &str,
+    key_prefix: &str,
+    key_hash: &str,
+    daily_quota: i64,
+    created_by: &str,
+) -> Result<()> {
+    let now = now_iso8601();
+    let stmt = db.prepare(
+        "INSERT INTO api_keys (label, key_prefix, key_hash, daily_quota, created_by, created_at)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+    );
+    let stmt = stmt.bind_refs(&[
+        D1Type::Text(label),
+        D1Type::Text(key_prefix),
+        D1Type::Text(key_hash),
+        integer_param(daily_quota)?,
+        D1Type::Text(created_by),
+        D1Type::Text(&now),
+    ])?;
+    stmt.run().await?;
+    Ok(())
+}
+
+/// APIキーを失効させる（失効済みなら何もしない。失効させた場合はtrue）
+pub async fn revoke_api_key(db: &D1Database, id: i64) -> Result<bool> {
+    let now = now_iso8601();
+    let stmt =
+        db.prepare("UPDATE api_keys SET revoked_at = ?2 WHERE id = ?1 AND revoked_at IS NULL");
+    let stmt = stmt.bind_refs(&[integer_param(id)?, D1Type::Text(&now)])?;
+    let result = stmt.run().await?;
+    let changes = result.meta()?.and_then(|m| m.changes).unwrap_or(0);
+    Ok(changes > 0)
+}
+
+/// APIキーの指定日の使用回数を1つ増やす（上限に達していれば増やさずfalse）
+///
+/// 判定と加算を1文で行い、同時に届いたリクエストでも上限を超えないようにする。
+pub async fn consume_api_key_quota(
+    db: &D1Database,
+    key_id: i64,
+    date: &str,
+    daily_quota: i64,
+) -> Result<bool> {
+    let stmt = db.prepare(
+        "INSERT INTO api_key_usage (key_id, date, count)
+         SELECT ?1, ?2, 1 WHERE ?3 > 0
+         ON CONFLICT(key_id, date) DO UPDATE SET count = count + 1
+         WHERE count < ?3",
+    );
+    let stmt = stmt.bind_refs(&[
+        integer_param(key_id)?,
+        D1Type::Text(date),
+        integer_param(daily_quota)?,
+    ])?;
+    let result = stmt.run().await?;
+    let changes = result.meta()?.and_then(|m| m.changes).unwrap_or(0);
+    Ok(changes > 0)
+}
+
+/// APIキーによる保存を記録し、最終使用日時を更新
+pub async fn record_api_key_write(
+    db: &D1Database,
+    key_id: i64,
+    entry_date: &str,
+    content_hash: &str,
+) -> Result<()> {
+    let now = now_iso8601();
+    let insert = db
+        .prepare(
+            "INSERT INTO api_key_writes (key_id, entry_date, content_hash, created_at)
+             VALUES (?1, ?2, ?3, ?4)",
+        )
+        .bind_refs(&[
+            integer_param(key_id)?,
+            D1Type::Text(entry_date),
+            D1Type::Text(content_hash),
+            D1Type::Text(&now),
+        ])?;
+    let touch = db
+        .prepare("UPDATE api_keys SET last_used_at = ?2 WHERE id = ?1")
+        .bind_refs(&[integer_param(key_id)?, D1Type::Text(&now)])?;
+    db.batch(vec![insert, touch]).await?;
+    Ok(())
+}
+
+/// 内容フィルターの一覧を取得（登録順）
+pub async fn list_content_filters(db: &D1Database) -> Result<Vec<ContentFilter>> {
+    let stmt = db.prepare(
+        "SELECT id, kind, pattern, action, created_by, created_at FROM content_filters ORDER BY id",
+    );
+    let result = stmt.all().await?;
+    result.results::<ContentFilter>()
+}
+
+/// 内容フィルターを登録
+pub async fn create_content_filter(
+    db: &D1Database,
+    kind: FilterKind,
+    pattern: &str,
+    action: FilterAction,
+    created_by: &str,
+) -> Result<()> {
+    let now = now_iso8601();
+    let stmt = db.prepare(
+        "INSERT INTO content_filters (kind, pattern, action, created_by, created_at)
+         VALUES (?1, ?2, ?3, ?4, ?5)",
+    );
+    let stmt = stmt.bind_refs(&[
+        D1Type::Text(kind.as_str()),
+        D1Type::Text(pattern),
+        D1Type::Text(action.as_str()),
+        D1Type::Text(created_by),
+        D1Type::Text(&now),
+    ])?;
+    stmt.run().await?;
+    Ok(())
+}
+
+/// 内容フィルターを削除し、削除したフィルターを返す
+pub async fn delete_content_filter(db: &D1Database, id: i64) -> Result<Option<ContentFilter>> {
+    let stmt = db.prepare(
+        "DELETE FROM content_filters WHERE id = ?1
+         RETURNING id, kind, pattern, action, created_by, created_at",
+    );
+    let stmt = stmt.bind_refs(&integer_param(id)?)?;
+    stmt.first::<ContentFilter>(None).await
+}
+
+const MODERATION_COLUMNS: &str = "id, entry_date, content, previous_content, action, reasons, \
+     created_at, resolution, resolved_by, resolved_at";
+
+/// モデレーションキューに追加
+pub async fn insert_moderation_item(
+    db: &D1Database,
+    entry_date: &str,
+    content: &str,
+    previous_content: Option<&str>,
+    action: FilterAction,
+    reasons: &str,
+) -> Result<()> {
+    let now = now_iso8601();
+    let stmt = db.prepare(
+        "INSERT INTO moderation_queue
+           (entry_date, content, previous_content, action, reasons, created_at)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+    );
+    let stmt = stmt.bind_refs(&[
+        D1Type::Text(entry_date),
+        D1Type::Text(content),
+        previous_content.map(D1Type::Text).unwrap_or(D1Type::Null),
+        D1Type::Text(action.as_str()),
+        D1Type::Text(reasons),
+        D1Type::Text(&now),
+    ])?;
+    stmt.run().await?;
+    Ok(())
+}
+
+/// 未処理のモデレーションキューを古い順に取得
+pub async fn list_pending_moderation(db: &D1Database, limit: i32) -> Result<Vec<ModerationItem>> {
+    let stmt = db.prepare(format!(
+        "SELECT {} FROM moderation_queue WHERE resolution IS NULL ORDER BY id LIMIT ?1",
+        MODERATION_COLUMNS
+    ));
+    let stmt = stmt.bind_refs(&D1Type::Integer(limit))?;
+    let result = stmt.all().await?;
+    result.results::<ModerationItem>()
+}
+
+/// IDでモデレーションキューの項目を取得
+pub async fn get_moderation_item(db: &D1Database, id: i64) -> Result<Option<ModerationItem>> {
+    let stmt = db.prepare(format!(
+        "SELECT {} FROM moderation_queue WHERE id = ?1",
+        MODERATION_COLUMNS
+    ));
+    let stmt = stmt.bind_refs(&integer_param(id)?)?;
+    stmt.first::<ModerationItem>(None).await
+}
+
+/// 未処理の項目を処理済みにする（他の管理者が先に処理していればfalse）
+pub async fn resolve_moderation_item(
+    db: &D1Database,
+    id: i64,
+    resolution: ModerationResolution,
+    resolved_by: &str,
+) -> Result<bool> {
+    let now = now_iso8601();
+    let stmt = db.prepare(
+        "UPDATE moderation_queue SET resolution = ?2, resolved_by = ?3, resolved_at = ?4
+         WHERE id = ?1 AND resolution IS NULL",
+    );
+    let stmt = stmt.bind_refs(&[
+        integer_param(id)?,
+        D1Type::Text(resolution.as_str()),
+        D1Type::Text(resolved_by),
+        D1Type::Text(&now),
+    ])?;
+    let result = stmt.run().await?;
+    let changes = result.meta()?.and_then(|m| m.changes).unwrap_or(0);
+    Ok(changes > 0)
+}
+
+/// モデレーションキューに残る内容を墨消しする
+pub async fn redact_moderation_content(db: &D1Database, id: i64, replacement: &str) -> Result<()> {
+    let stmt = db.prepare("UPDATE moderation_queue SET content = ?2 WHERE id = ?1");
+    let stmt = stmt.bind_refs(&[integer_param(id)?, D1Type::Text(replacement)])?;
+    stmt.run().await?;
+    Ok(())
+}
+
+/// 指定日のバージョン履歴から、指定した内容と一致する版をすべて墨消しする
+pub async fn redact_versions_with_content(
+    db: &D1Database,
+    date: &str,
+    content: &str,
+    replacement: &str,
+) -> Result<()> {
+    let stmt =
+        db.prepare("UPDATE diary_versions SET content = ?3 WHERE entry_date = ?1 AND content = ?2");
+    let stmt = stmt.bind_refs(&[
+        D1Type::Text(date),
+        D1Type::Text(content),
+        D1Type::Text(replacement),
+    ])?;
+    stmt.run().await?;
+    Ok(())
+}
+
+/// 適用済みのマイグレーションの名前（wranglerが記録したもの）
+pub async fn list_applied_migrations(db: &D1Database) -> Result<Vec<String>> {
+    let stmt = db.prepare("SELECT name FROM schema_migrations ORDER BY name");
+
+    #[derive(serde::Deserialize)]
+    struct Applied {
+        name: String,
+    }
+
+    let applied = stmt.all().await?.results::<Applied>()?;
+    Ok(applied.into_iter().map(|a| a.name).collect())
+}
+
+/// プルーフ・オブ・ワークのチャレンジを保存し、期限切れのものを消す
+pub async fn insert_pow_challenge(
+    db: &D1Database,
+    challenge: &str,
+    client: &str,
+    difficulty: u32,
+    expires_at: i64,
+    now: i64,
+) -> Result<()> {
+    let cleanup = db
+        .prepare("DELETE FROM pow_challenges WHERE expires_at <= ?1")
+        .bind_refs(&integer_param(now)?)?;
+    let insert = db
+        .prepare(
+            "INSERT INTO pow_challenges (challenge, client, difficulty, expires_at)
+             VALUES (?1, ?2, ?3, ?4)"
+        )
+        .bind_refs(&[
+            D1Type::Text(challenge),
+            D1Type::Text(client),
+            integer_param(i64::from(difficulty))?,
+            integer_param(expires_at)?,
+        ])?;
+    db.batch(vec![cleanup, insert]).await?;
+    Ok(())
+}
+
+/// 有効なチャレンジを取り出して消し、発行時の難易度を返す
+///
+/// 1文で削除と読み出しを行うため、同じチャレンジは1回しか取り出せない。
+pub async fn take_pow_challenge(
+    db: &D1Database,
+    challenge: &str,
+    client: &str,
+    now: i64,
+) -> Result<Option<u32>> {
+    let stmt = db.prepare(
+        "DELETE FROM pow_challenges
+         WHERE challenge = ?1 AND client = ?2 AND expires_at > ?3
+         RETURNING difficulty"
+    );
+    let stmt = stmt.bind_refs(&[
+        D1Type::Text(challenge),
+        D1Type::Text(client),
+        integer_param(now)?,
+    ])?;
+
+    #[derive(serde::Deserialize)]
+    struct Taken {
+        difficulty: u32,
+    }
+
+    Ok(stmt.first::<Taken>(None).await?.map(|t| t.difficulty))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_integer_param_rejects_out_of_range() {
+        assert!(matches!(integer_param(42), Ok(D1Type::Integer(42))));
+        assert!(matches!(integer_param(-1), Ok(D1Type::Integer(-1))));
+        assert!(integer_param(i64::from(i32::MAX) + 1).is_err());
+        assert!(integer_param(1 << 32).is_err());
+    }
+}
@@ -0,0 +1,58 @@
+use futures_util::stream;
+use worker::{Error, Request, Response, Result, RouteContext};
+
+use crate::audit;
+use crate::auth;
+use crate::models::{AdminRole, AuditAction, AuditEvent};
+use crate::store::D1Store;
+use crate::time::today_jst;
+
+pub use darekagakaku_core::export::{Export, ExportFormat};
+
+/// ページごとにD1から読みながら応答を流す
+fn stream_response(db: D1Store, export: Export) -> Result<Response> {
+    let format = export.format();
+    let chunks = stream::try_unfold((db, export), |(db, mut export)| async move {
+        let chunk = export.next_chunk(&db).await?;
+        Ok::<_, Error>(chunk.map(|chunk| (chunk, (db, export))))
+    });
+    let mut response = Response::from_stream(chunks)?;
+    for (name, value) in format.headers() {
+        response.headers_mut().set(name, &value)?;
+    }
+    Ok(response)
+}
+
+/// 確定した日記（昨日まで）を指定の形式で書き出す
+async fn finalized(ctx: &RouteContext<()>, format: ExportFormat) -> Result<Response> {
+    let db = D1Store::from_env(&ctx.env)?;
+    stream_response(db, Export::finalized(format, &today_jst()))
+}
+
+/// GET /export/entries.jsonl - 確定した日記のJSON Lines
+pub async fn entries_jsonl(_req: Request, ctx: RouteContext<()>) -> Result<Response> {
+    finalized(&ctx, ExportFormat::Jsonl).await
+}
+
+/// GET /export/entries.csv - 確定した日記のCSV
+pub async fn entries_csv(_req: Request, ctx: RouteContext<()>) -> Result<Response> {
+    finalized(&ctx, ExportFormat::Csv).await
+}
+
+/// GET /export/entries.tar - 確定した日記を1日1ファイルのMarkdownにまとめたもの
+pub async fn entries_markdown(_req: Request, ctx: RouteContext<()>) -> Result<Response> {
+    finalized(&ctx, ExportFormat::Markdown).await
+}
+
+/// GET /api/admin/export - 今日の日記と版の履歴を含めたすべてのデータ（管理者用）
+pub async fn admin_export(req: Request, ctx: RouteContext<()>) -> Result<Response> {
+    let admin = match auth::require_admin_api(&req, &ctx.env, AdminRole::Superuser).await? {
+        Ok(admin) => admin,
+        Err(response) => return Ok(response),
+    };
+
+    audit::record(&ctx.env, &req, &admin, AuditEvent::new(AuditAction::Export)).await;
+
+    let db = D1Store::from_env(&ctx.env)?;
+    stream_response(db, Export::full())
+}
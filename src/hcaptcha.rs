@@ -0,0 +1,66 @@
+use std::time::Duration;
+
+use worker::*;
+
+pub use darekagakaku_core::hcaptcha::*;
+
+use crate::verifier::VerificationFailure;
+
+/// siteverifyの応答を待つ上限
+const TIMEOUT: Duration = Duration::from_secs(5);
+
+async fn siteverify(verify_url: &str, body: String) -> Result<HCaptchaResponse> {
+    let headers = Headers::new();
+    headers.set("Content-Type", "application/x-www-form-urlencoded")?;
+
+    let req = Request::new_with_init(
+        verify_url,
+        RequestInit::new()
+            .with_method(Method::Post)
+            .with_headers(headers)
+            .with_body(Some(body.into())),
+    )?;
+
+    // 応答が遅い場合は中断する
+    let controller = AbortController::default();
+    let signal = controller.signal();
+    wasm_bindgen_futures::spawn_local(async move {
+        Delay::from(TIMEOUT).await;
+        controller.abort();
+    });
+
+    let mut resp = Fetch::Request(req).send_with_signal(&signal).await?;
+    resp.json().await
+}
+
+/// hCaptcha（または互換サービス）のトークンを検証
+pub async fn verify_hcaptcha(
+    verify_url: &str,
+    secret: &str,
+    site_key: Option<&str>,
+    token: &str,
+    ip: Option<&str>,
+    hostname: Option<&str>,
+) -> std::result::Result<(), VerificationFailure> {
+    if token.is_empty() {
+        return Err(INVALID_TOKEN);
+    }
+
+    match siteverify(verify_url, request_body(secret, token, ip, site_key)).await {
+        Ok(resp) => {
+            let result = check_response(&resp, hostname);
+            if let Err(failure) = &result {
+                worker::console_warn!(
+                    "hCaptcha rejected: {} error_codes={:?}",
+                    failure.code,
+                    resp.error_codes
+                );
+            }
+            result
+        }
+        Err(e) => {
+            worker::console_error!("hCaptcha verification error: {:?}", e);
+            Err(UNAVAILABLE)
+        }
+    }
+}
@@ -1,17 +1,41 @@
 use worker::*;
 
+use darekagakaku_core::{
+    api, content_filter, entry_stats, import, migrations, models, overwrite, spam, stats,
+    templates, validation, write,
+};
+
+mod access;
+mod api_keys;
+mod audit;
 mod auth;
+mod backup;
+mod config;
+mod crypto;
 mod db;
+mod export;
 mod handlers;
-mod models;
+mod hcaptcha;
+mod logging;
+mod login_guard;
 mod pages;
+mod passkeys;
+mod password;
+mod pow;
 mod rate_limit;
-mod templates;
+mod reply;
+mod schema;
+mod store;
 mod time;
+mod totp;
 mod turnstile;
+mod verifier;
+mod webauthn;
 
 #[event(fetch, respond_with_errors)]
 async fn fetch(req: Request, env: Env, _ctx: Context) -> Result<Response> {
+    logging::init();
+
     // カノニカルホストへのリダイレクト
     if let Ok(canonical) = env.var("CANONICAL_HOST") {
         let canonical_host = canonical.to_string();
@@ -26,6 +50,11 @@ async fn fetch(req: Request, env: Env, _ctx: Context) -> Result<Response> {
         }
     }
 
+    // スキーマが古い間は書き込みを受け付けない
+    if let Some(response) = schema::guard_writes(&req, &env).await? {
+        return Ok(response);
+    }
+
     Router::new()
         // HTMLページ
         .get_async("/", pages::home)
@@ -33,21 +62,63 @@ async fn fetch(req: Request, env: Env, _ctx: Context) -> Result<Response> {
         .get_async("/feed", pages::feed)
         .get_async("/entries", pages::entries_list)
         .get_async("/entries/:date", pages::entry_page)
+        .get_async("/stats", pages::stats_page)
+        // エクスポート
+        .get_async("/export/entries.jsonl", export::entries_jsonl)
+        .get_async("/export/entries.csv", export::entries_csv)
+        .get_async("/export/entries.tar", export::entries_markdown)
         // JSON API
         .get_async("/api/today", handlers::get_today)
         .post_async("/api/today", handlers::post_today)
+        .post_async("/api/pow/challenge", pow::issue_challenge)
         .get_async("/api/entries", handlers::get_entries)
         .get_async("/api/entries/:date", handlers::get_entry_by_date)
+        .get_async("/api/entries/:date/stats", handlers::get_entry_stats)
+        .get_async("/api/stats", handlers::get_stats)
         // 管理者用HTML画面
         .get_async("/admin/login", pages::admin_login_page)
         .post_async("/admin/login", pages::admin_login_submit)
-        .get_async("/admin/logout", pages::admin_logout)
+        .post_async("/admin/login/totp", pages::admin_login_totp_submit)
+        .post_async("/admin/login/passkey/options", passkeys::login_options)
+        .post_async("/admin/login/passkey", passkeys::login)
+        .post_async("/admin/logout", pages::admin_logout)
         .get_async("/admin/versions", pages::admin_versions_index)
         .get_async("/admin/entries/:date/versions", pages::admin_versions_list)
         .get_async(
             "/admin/entries/:date/versions/:version",
             pages::admin_version_detail,
         )
+        .post_async("/admin/entries/:date/revert", pages::admin_revert)
+        .post_async(
+            "/admin/entries/:date/versions/:version/redact",
+            pages::admin_redact_version,
+        )
+        .post_async("/admin/entries/:date/edit", pages::admin_edit_entry)
+        .get_async("/admin/admins", pages::admin_admins_page)
+        .post_async("/admin/admins", pages::admin_create_admin)
+        .post_async("/admin/admins/:id/role", pages::admin_update_admin_role)
+        .post_async("/admin/admins/:id/email", pages::admin_update_admin_email)
+        .post_async("/admin/admins/:id/delete", pages::admin_delete_admin)
+        .get_async("/admin/moderation", pages::admin_moderation_page)
+        .post_async("/admin/moderation/:id", pages::admin_resolve_moderation)
+        .get_async("/admin/filters", pages::admin_filters_page)
+        .post_async("/admin/filters", pages::admin_create_filter)
+        .post_async("/admin/filters/:id/delete", pages::admin_delete_filter)
+        .get_async("/admin/api-keys", pages::admin_api_keys_page)
+        .post_async("/admin/api-keys", pages::admin_create_api_key)
+        .post_async("/admin/api-keys/:id/revoke", pages::admin_revoke_api_key)
+        .get_async("/admin/audit", pages::admin_audit_page)
+        .get_async("/admin/totp", pages::admin_totp_page)
+        .post_async("/admin/totp/enable", pages::admin_totp_enable)
+        .post_async(
+            "/admin/totp/recovery-codes",
+            pages::admin_totp_regenerate_codes,
+        )
+        .post_async("/admin/totp/disable", pages::admin_totp_disable)
+        .get_async("/admin/passkeys", passkeys::passkeys_page)
+        .post_async("/admin/passkeys", passkeys::register)
+        .post_async("/admin/passkeys/options", passkeys::registration_options)
+        .post_async("/admin/passkeys/:id/delete", passkeys::delete)
         // 管理者用API
         .get_async(
             "/api/admin/entries/:date/versions",
@@ -57,6 +128,28 @@ async fn fetch(req: Request, env: Env, _ctx: Context) -> Result<Response> {
             "/api/admin/entries/:date/versions/:version",
             handlers::admin_get_version,
         )
+        .get_async("/api/admin/audit", handlers::admin_list_audit_log)
+        .get_async("/api/admin/export", export::admin_export)
+        .post_async("/api/admin/import", handlers::admin_import)
+        .get_async("/api/admin/backups", backup::admin_list_backups)
+        .post_async("/api/admin/restore", backup::admin_restore)
         .run(req, env)
         .await
 }
+
+/// 毎晩のバックアップと書き手の記録の整理（wrangler.tomlのtriggers.crons）
+#[event(scheduled)]
+async fn scheduled(_event: ScheduledEvent, env: Env, _ctx: ScheduleContext) {
+    logging::init();
+
+    if let Err(e) = backup::run_nightly(&env).await {
+        log::error!("Nightly backup failed: {:?}", e);
+    }
+    let compacted = match store::D1Store::from_env(&env) {
+        Ok(db) => entry_stats::compact_saves(&db, &time::today_jst()).await,
+        Err(e) => Err(e),
+    };
+    if let Err(e) = compacted {
+        log::error!("Failed to compact entry saves: {:?}", e);
+    }
+}
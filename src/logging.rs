@@ -0,0 +1,32 @@
+use log::{Level, LevelFilter, Log, Metadata, Record};
+
+/// 共通部分が `log` で出したログをWorkersのコンソールに流す
+struct ConsoleLogger;
+
+impl Log for ConsoleLogger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        metadata.level() <= Level::Info
+    }
+
+    fn log(&self, record: &Record) {
+        if !self.enabled(record.metadata()) {
+            return;
+        }
+        match record.level() {
+            Level::Error => worker::console_error!("{}", record.args()),
+            Level::Warn => worker::console_warn!("{}", record.args()),
+            _ => worker::console_log!("{}", record.args()),
+        }
+    }
+
+    fn flush(&self) {}
+}
+
+static LOGGER: ConsoleLogger = ConsoleLogger;
+
+/// ロガーを登録する（isolateが再利用された場合の2回目以降は何もしない）
+pub fn init() {
+    if log::set_logger(&LOGGER).is_ok() {
+        log::set_max_level(LevelFilter::Info);
+    }
+}
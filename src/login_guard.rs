@@ -0,0 +1,213 @@
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+use worker::kv::KvStore;
+use worker::{Delay, Result};
+
+/// ロックアウトなしで許容するクライアントごとの失敗回数
+const IP_FREE_FAILURES: u32 = 5;
+/// 遅延なしで許容するサイト全体の失敗回数
+const GLOBAL_FREE_FAILURES: u32 = 50;
+/// この回数以上失敗したクライアントにはTurnstileを要求する
+const CHALLENGE_AFTER_FAILURES: u32 = 3;
+const BASE_LOCKOUT_SECONDS: i64 = 30;
+const MAX_LOCKOUT_SECONDS: i64 = 3600;
+const BASE_THROTTLE_MILLIS: u64 = 250;
+const MAX_THROTTLE_MILLIS: u64 = 5000;
+/// 失敗カウンタを保持する期間
+const FAILURE_WINDOW_SECONDS: i64 = 86400;
+/// サイト全体の失敗を数える区間の長さ（区間が変われば数え直す）
+const GLOBAL_WINDOW_SECONDS: i64 = 3600;
+
+/// KVに保存する失敗記録
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+struct FailureRecord {
+    failures: u32,
+    locked_until: i64,
+}
+
+/// ログイン試行前の判定結果
+#[derive(Debug, Clone, PartialEq)]
+pub struct LoginGuardStatus {
+    /// ロック解除までの秒数（ロック中でなければNone）
+    pub retry_after: Option<i64>,
+    /// サイト全体で失敗が続いているときに照合前に待つミリ秒数
+    ///
+    /// 全体の失敗では誰もロックアウトせず、試行の速度だけを落とす。
+    pub throttle_millis: u64,
+    /// Turnstileによるチャレンジが必要か
+    pub challenge_required: bool,
+}
+
+/// 失敗回数からロックアウト秒数を計算（純粋関数）
+///
+/// 許容回数を超えた分だけ待ち時間を倍々に伸ばす。
+fn lockout_seconds(failures: u32, free_failures: u32) -> i64 {
+    if failures < free_failures {
+        return 0;
+    }
+    let exponent = (failures - free_failures).min(16);
+    (BASE_LOCKOUT_SECONDS << exponent).min(MAX_LOCKOUT_SECONDS)
+}
+
+/// サイト全体の失敗回数から照合前の待ち時間を計算（純粋関数）
+fn throttle_millis(failures: u32) -> u64 {
+    if failures < GLOBAL_FREE_FAILURES {
+        return 0;
+    }
+    let exponent = ((failures - GLOBAL_FREE_FAILURES) / 10).min(16);
+    (BASE_THROTTLE_MILLIS << exponent).min(MAX_THROTTLE_MILLIS)
+}
+
+/// 失敗を1回加算した記録を返す（純粋関数）
+fn next_record(record: &FailureRecord, free_failures: u32, now: i64) -> FailureRecord {
+    let failures = record.failures.saturating_add(1);
+    let lockout = lockout_seconds(failures, free_failures);
+    FailureRecord {
+        failures,
+        locked_until: if lockout > 0 { now + lockout } else { record.locked_until },
+    }
+}
+
+/// ロック解除までの残り秒数（純粋関数）
+fn remaining_lockout(record: &FailureRecord, now: i64) -> Option<i64> {
+    (record.locked_until > now).then(|| record.locked_until - now)
+}
+
+/// サイト全体の失敗記録のキー（純粋関数）
+///
+/// 区間ごとに別のキーにして、失敗が続いても期限が延びず、区間が終われば遅延もなくなるようにする。
+fn global_record_key(now: i64) -> String {
+    format!("login_fail:global:{}", now.div_euclid(GLOBAL_WINDOW_SECONDS))
+}
+
+/// クライアントごとの失敗記録のキー
+///
+/// `client` は `rate_limit::login_client_key` で求めたハッシュで、生のIPは使わない。
+fn client_record_key(client: &str) -> String {
+    format!("login_fail:{}", client)
+}
+
+async fn get_record(kv: &KvStore, key: &str) -> Result<FailureRecord> {
+    Ok(kv.get(key).json::<FailureRecord>().await?.unwrap_or_default())
+}
+
+async fn put_record(kv: &KvStore, key: &str, record: &FailureRecord, now: i64) -> Result<()> {
+    let ttl = (record.locked_until - now).max(FAILURE_WINDOW_SECONDS);
+    kv.put(key, serde_json::to_string(record)?)?
+        .expiration_ttl(ttl as u64)
+        .execute()
+        .await?;
+    Ok(())
+}
+
+/// 指定クライアントからのログイン試行を受け付けてよいか判定
+pub async fn check(kv: &KvStore, client: &str, now: i64) -> Result<LoginGuardStatus> {
+    let client_record = get_record(kv, &client_record_key(client)).await?;
+    let global_record = get_record(kv, &global_record_key(now)).await?;
+
+    Ok(LoginGuardStatus {
+        retry_after: remaining_lockout(&client_record, now),
+        throttle_millis: throttle_millis(global_record.failures),
+        challenge_required: client_record.failures >= CHALLENGE_AFTER_FAILURES,
+    })
+}
+
+/// サイト全体で失敗が続いている間、待機して試行を遅らせる
+pub async fn throttle(status: &LoginGuardStatus) {
+    if status.throttle_millis > 0 {
+        Delay::from(Duration::from_millis(status.throttle_millis)).await;
+    }
+}
+
+/// ログイン失敗を記録し、監査用にログへ出力
+pub async fn record_failure(kv: &KvStore, client: &str, now: i64) -> Result<()> {
+    let key = client_record_key(client);
+    let client_record = next_record(&get_record(kv, &key).await?, IP_FREE_FAILURES, now);
+    put_record(kv, &key, &client_record, now).await?;
+
+    // サイト全体の記録はロックアウトに使わないため回数だけを数え、区間の終わりで消えるようにする
+    let global_key = global_record_key(now);
+    let mut global_record = get_record(kv, &global_key).await?;
+    global_record.failures = global_record.failures.saturating_add(1);
+    kv.put(&global_key, serde_json::to_string(&global_record)?)?
+        .expiration_ttl(GLOBAL_WINDOW_SECONDS as u64)
+        .execute()
+        .await?;
+
+    worker::console_warn!(
+        "Admin login failed: client={} failures={} global_failures={} locked_until={}",
+        client,
+        client_record.failures,
+        global_record.failures,
+        client_record.locked_until
+    );
+    Ok(())
+}
+
+/// ログイン成功時にクライアントごとの失敗記録を消去
+pub async fn record_success(kv: &KvStore, client: &str) -> Result<()> {
+    kv.delete(&client_record_key(client)).await?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_lockout_seconds_under_limit() {
+        assert_eq!(lockout_seconds(0, 5), 0);
+        assert_eq!(lockout_seconds(4, 5), 0);
+    }
+
+    #[test]
+    fn test_lockout_seconds_exponential() {
+        assert_eq!(lockout_seconds(5, 5), 30);
+        assert_eq!(lockout_seconds(6, 5), 60);
+        assert_eq!(lockout_seconds(7, 5), 120);
+    }
+
+    #[test]
+    fn test_lockout_seconds_capped() {
+        assert_eq!(lockout_seconds(20, 5), MAX_LOCKOUT_SECONDS);
+        assert_eq!(lockout_seconds(u32::MAX, 5), MAX_LOCKOUT_SECONDS);
+    }
+
+    #[test]
+    fn test_throttle_millis() {
+        assert_eq!(throttle_millis(0), 0);
+        assert_eq!(throttle_millis(GLOBAL_FREE_FAILURES - 1), 0);
+        assert_eq!(throttle_millis(GLOBAL_FREE_FAILURES), BASE_THROTTLE_MILLIS);
+        assert_eq!(throttle_millis(GLOBAL_FREE_FAILURES + 10), BASE_THROTTLE_MILLIS * 2);
+        assert_eq!(throttle_millis(u32::MAX), MAX_THROTTLE_MILLIS);
+    }
+
+    #[test]
+    fn test_global_record_key_changes_by_window() {
+        assert_eq!(global_record_key(0), global_record_key(GLOBAL_WINDOW_SECONDS - 1));
+        assert_ne!(global_record_key(0), global_record_key(GLOBAL_WINDOW_SECONDS));
+    }
+
+    #[test]
+    fn test_next_record_locks_after_free_failures() {
+        let record = FailureRecord { failures: 4, locked_until: 0 };
+        let next = next_record(&record, 5, 1000);
+        assert_eq!(next.failures, 5);
+        assert_eq!(next.locked_until, 1030);
+    }
+
+    #[test]
+    fn test_next_record_keeps_unlocked_under_limit() {
+        let next = next_record(&FailureRecord::default(), 5, 1000);
+        assert_eq!(next.failures, 1);
+        assert_eq!(next.locked_until, 0);
+    }
+
+    #[test]
+    fn test_remaining_lockout() {
+        let record = FailureRecord { failures: 5, locked_until: 1030 };
+        assert_eq!(remaining_lockout(&record, 1000), Some(30));
+        assert_eq!(remaining_lockout(&record, 1030), None);
+        assert_eq!(remaining_lockout(&record, 2000), None);
+    }
+}
@@ -1,11 +1,38 @@
 use worker::d1::D1Database;
-use worker::{Headers, Request, Response, Result, RouteContext};
+use worker::{FormData, Headers, Request, Response, Result, RouteContext};
 
-use crate::auth;
+use darekagakaku_core::moderation::{self, ResolveRejection, MODERATION_PAGE_SIZE};
+use darekagakaku_core::pages::{
+    archive_reply, entry_page_reply, feed_xml, stats_page_reply, HomePage,
+};
+
+use crate::api_keys;
+use crate::audit;
+use crate::auth::{self, AdminIdentity};
+use crate::config::FromEnv;
+use crate::content_filter::{self, LinkPolicy};
 use crate::db;
-use crate::models::{DiaryEntrySummary, VersionSummary};
+use crate::login_guard;
+use crate::models::{
+    is_valid_email, is_valid_username, Admin, AdminRole, AuditAction, AuditEvent, FilterAction,
+    FilterKind, ModerationResolution, VersionSummary, DEFAULT_API_KEY_DAILY_QUOTA,
+    MAX_API_KEY_DAILY_QUOTA, MAX_API_KEY_LABEL_LENGTH, MIN_PASSWORD_LENGTH, REDACTED_CONTENT,
+};
+use crate::overwrite::OverwritePolicy;
+use crate::password;
+use crate::rate_limit::{self, KvStateStore};
+use crate::reply::IntoResponse;
+use crate::spam;
+use crate::store::{D1Store, DiaryStore};
 use crate::templates;
-use crate::time::{is_today, is_valid_date, today_jst};
+use crate::time::{now_unix, today_jst};
+use crate::totp;
+use crate::turnstile::{self, TurnstileOutcome};
+use crate::validation::{is_content_too_long, is_valid_date, normalize_content, MAX_CONTENT_LENGTH};
+use crate::verifier;
+
+/// 認証アプリに表示される発行者名
+const TOTP_ISSUER: &str = "誰かが書く日記";
 
 /// GET /a - Aboutページ（これはなにか）
 pub async fn about(_req: Request, _ctx: RouteContext<()>) -> Result<Response> {
@@ -15,102 +42,51 @@ pub async fn about(_req: Request, _ctx: RouteContext<()>) -> Result<Response> {
 
 /// GET / - ホームページ（今日の日記フォーム）
 pub async fn home(_req: Request, ctx: RouteContext<()>) -> Result<Response> {
-    let db: D1Database = ctx.env.d1("DB")?;
-    let today = today_jst();
-
-    let entry = match db::get_entry(&db, &today).await {
-        Ok(entry) => entry,
-        Err(e) => {
-            worker::console_error!("Failed to get today's entry: {:?}", e);
-            None
-        }
-    };
-
-    let turnstile_site_key = ctx
+    let db = D1Store::from_env(&ctx.env)?;
+    let widget = verifier::widget_from_env(&ctx.env);
+    // 開いてから保存するまでの時間を測るため、表示した時刻を署名して埋め込む
+    let form_token = ctx
         .env
-        .var("TURNSTILE_SITE_KEY")
-        .map(|v| v.to_string())
-        .unwrap_or_default();
-
-    let html = templates::render_home(entry.as_ref(), &turnstile_site_key);
-    Response::from_html(html)
+        .secret("FORM_TOKEN_KEY")
+        .ok()
+        .map(|key| spam::issue_form_token(&key.to_string(), now_unix()));
+    let page = HomePage {
+        today: &today_jst(),
+        widget: &widget,
+        form_token: form_token.as_deref(),
+        overwrite: &OverwritePolicy::from_env(&ctx.env),
+    };
+    page.reply(&db).await.into_response()
 }
 
 /// GET /entries - 過去の日記一覧
 pub async fn entries_list(_req: Request, ctx: RouteContext<()>) -> Result<Response> {
-    let db: D1Database = ctx.env.d1("DB")?;
-
-    let entries = match db::list_past_entries(&db, 100).await {
-        Ok(entries) => entries,
-        Err(e) => {
-            worker::console_error!("Failed to list entries: {:?}", e);
-            vec![]
-        }
-    };
-
-    let summaries: Vec<DiaryEntrySummary> = entries
-        .iter()
-        .map(DiaryEntrySummary::from_entry)
-        .collect();
-
-    let html = templates::render_archive(&summaries);
-    Response::from_html(html)
+    let db = D1Store::from_env(&ctx.env)?;
+    archive_reply(&db, &today_jst()).await.into_response()
 }
 
 /// GET /entries/:date - 特定日の日記を表示
 pub async fn entry_page(_req: Request, ctx: RouteContext<()>) -> Result<Response> {
-    let db: D1Database = ctx.env.d1("DB")?;
-
-    let date = match ctx.param("date") {
-        Some(d) => d,
-        None => {
-            let html = templates::render_not_found();
-            return Response::from_html(html).map(|r| r.with_status(404));
-        }
-    };
-
-    // 日付の形式を検証
-    if !is_valid_date(date) {
-        let html = templates::render_not_found();
-        return Response::from_html(html).map(|r| r.with_status(404));
-    }
+    let db = D1Store::from_env(&ctx.env)?;
+    let date = ctx.param("date").map(String::as_str);
+    entry_page_reply(&db, date, &today_jst()).await.into_response()
+}
 
-    match db::get_entry(&db, date).await {
-        Ok(Some(entry)) => {
-            let can_edit = is_today(date);
-            let html = templates::render_entry(&entry, can_edit);
-            Response::from_html(html)
-        }
-        Ok(None) => {
-            let html = templates::render_not_found();
-            Response::from_html(html).map(|r| r.with_status(404))
-        }
-        Err(e) => {
-            worker::console_error!("Failed to get entry: {:?}", e);
-            let html = templates::render_not_found();
-            Response::from_html(html).map(|r| r.with_status(500))
-        }
-    }
+/// GET /stats - サイト全体の統計
+pub async fn stats_page(_req: Request, ctx: RouteContext<()>) -> Result<Response> {
+    let db = D1Store::from_env(&ctx.env)?;
+    stats_page_reply(&db, &today_jst()).await.into_response()
 }
 
 /// GET /feed - RSSフィード
 pub async fn feed(req: Request, ctx: RouteContext<()>) -> Result<Response> {
-    let db: D1Database = ctx.env.d1("DB")?;
-
-    // 今日の日記は編集中なので、過去の確定した日記のみをRSSに含める
-    let entries = match db::list_past_entries(&db, 20).await {
-        Ok(entries) => entries,
-        Err(e) => {
-            worker::console_error!("Failed to list entries for RSS: {:?}", e);
-            vec![]
-        }
-    };
+    let db = D1Store::from_env(&ctx.env)?;
 
     // ベースURLをリクエストから取得
     let url = req.url()?;
     let base_url = format!("{}://{}", url.scheme(), url.host_str().unwrap_or("localhost"));
 
-    let rss = templates::render_rss(&entries, &base_url);
+    let rss = feed_xml(&db, &today_jst(), &base_url).await;
 
     let headers = Headers::new();
     headers.set("Content-Type", "application/rss+xml; charset=utf-8")?;
@@ -119,41 +95,202 @@ pub async fn feed(req: Request, ctx: RouteContext<()>) -> Result<Response> {
 }
 
 /// GET /admin/login - 管理者ログインページ
-pub async fn admin_login_page(_req: Request, _ctx: RouteContext<()>) -> Result<Response> {
-    let html = templates::render_admin_login(None);
+pub async fn admin_login_page(req: Request, ctx: RouteContext<()>) -> Result<Response> {
+    let kv = KvStateStore::from_env(&ctx.env)?;
+    let client = rate_limit::login_client_key(&req, &ctx.env).await?;
+    let status = login_guard::check(&kv, &client, now_unix()).await?;
+
+    let site_key = login_challenge_site_key(&ctx, status.challenge_required);
+    let html = templates::render_admin_login(None, site_key.as_deref());
     Response::from_html(html)
 }
 
 /// POST /admin/login - 管理者ログイン処理
 pub async fn admin_login_submit(mut req: Request, ctx: RouteContext<()>) -> Result<Response> {
-    let expected_token = match ctx.env.secret("ADMIN_TOKEN") {
-        Ok(secret) => secret.to_string(),
-        Err(_) => {
-            let html = templates::render_admin_login(Some("認証が設定されていません"));
-            return Response::from_html(html).map(|r| r.with_status(500));
+    // ログインCSRF対策（セッションがないため送信元のみ確認）
+    if !auth::check_same_origin(&req)? {
+        return auth::csrf_rejected_page();
+    }
+
+    let kv = KvStateStore::from_env(&ctx.env)?;
+    let client = rate_limit::login_client_key(&req, &ctx.env).await?;
+    let now = now_unix();
+
+    let status = login_guard::check(&kv, &client, now).await?;
+    if let Some(retry_after) = status.retry_after {
+        let message = format!(
+            "ログインの失敗が続いたため制限中です。{}秒後に再試行してください",
+            retry_after
+        );
+        let html = templates::render_admin_login(Some(&message), None);
+        return Response::from_html(html).map(|r| r.with_status(429));
+    }
+    login_guard::throttle(&status).await;
+
+    // フォームデータを取得
+    let form_data = req.form_data().await?;
+    let username = form_field(&form_data, "username");
+    let submitted_password = form_field(&form_data, "password");
+
+    // 失敗が続いている場合はTurnstileを要求（未設定の環境では省略）
+    if let Some(site_key) = login_challenge_site_key(&ctx, status.challenge_required) {
+        let turnstile_token = form_field(&form_data, "cf-turnstile-response");
+        if turnstile_token.is_empty() {
+            let html = templates::render_admin_login(
+                Some("認証を完了してください"),
+                Some(&site_key),
+            );
+            return Response::from_html(html).map(|r| r.with_status(400));
+        }
+
+        let secret = ctx.env.secret("TURNSTILE_SECRET_KEY")?.to_string();
+        let expected = turnstile::expectation_from_env(&ctx.env, turnstile::LOGIN_ACTION, None);
+        let ip = rate_limit::get_client_ip(&req);
+        let outcome =
+            turnstile::verify_turnstile(&secret, &turnstile_token, Some(&ip), &expected).await;
+        if !outcome.is_success() {
+            let message = match outcome {
+                TurnstileOutcome::TimeoutOrDuplicate => "認証の有効期限が切れました。もう一度お試しください",
+                _ => "認証に失敗しました",
+            };
+            let html = templates::render_admin_login(Some(message), Some(&site_key));
+            return Response::from_html(html).map(|r| r.with_status(outcome.status()));
+        }
+    }
+
+    let (identity, totp_required) = match authenticate(&ctx, &username, &submitted_password).await? {
+        Some(result) => result,
+        None => {
+            login_guard::record_failure(&kv, &client, now).await?;
+            let actor = if username.is_empty() {
+                auth::BOOTSTRAP_USERNAME
+            } else {
+                &username
+            };
+            audit::record_failed_login(&ctx.env, &req, actor).await;
+            let status = login_guard::check(&kv, &client, now).await?;
+            let site_key = login_challenge_site_key(&ctx, status.challenge_required);
+            let html = templates::render_admin_login(
+                Some("ユーザー名またはパスワードが正しくありません"),
+                site_key.as_deref(),
+            );
+            return Response::from_html(html).map(|r| r.with_status(401));
         }
     };
 
-    // フォームデータを取得
+    // 二要素認証が有効な場合はコード入力へ進む
+    if let (true, Some(admin_id)) = (totp_required, identity.admin_id) {
+        let pending_token = auth::create_pending_login(&ctx.env, admin_id).await?;
+        let html = templates::render_admin_login_totp(&pending_token, None);
+        return Response::from_html(html);
+    }
+
+    if let Err(e) = login_guard::record_success(&kv, &client).await {
+        worker::console_error!("Failed to reset login failures: {:?}", e);
+    }
+
+    start_session(&req, &ctx, &identity).await
+}
+
+/// POST /admin/login/totp - 管理者ログイン（二要素認証コードの確認）
+pub async fn admin_login_totp_submit(mut req: Request, ctx: RouteContext<()>) -> Result<Response> {
+    // ログインCSRF対策（セッションがないため送信元のみ確認）
+    if !auth::check_same_origin(&req)? {
+        return auth::csrf_rejected_page();
+    }
+
+    let kv = KvStateStore::from_env(&ctx.env)?;
+    let client = rate_limit::login_client_key(&req, &ctx.env).await?;
+    let now = now_unix();
+
+    let status = login_guard::check(&kv, &client, now).await?;
+    if let Some(retry_after) = status.retry_after {
+        let message = format!(
+            "ログインの失敗が続いたため制限中です。{}秒後に再試行してください",
+            retry_after
+        );
+        let html = templates::render_admin_login(Some(&message), None);
+        return Response::from_html(html).map(|r| r.with_status(429));
+    }
+    login_guard::throttle(&status).await;
+
     let form_data = req.form_data().await?;
-    let submitted_token = form_data
-        .get("token")
-        .and_then(|v| match v {
-            worker::FormEntry::Field(s) => Some(s),
-            _ => None,
-        })
-        .unwrap_or_default();
+    let pending_token = form_field(&form_data, "pending");
+    let code = form_field(&form_data, "code");
+
+    let db = D1Store::from_env(&ctx.env)?;
+    let pending = auth::get_pending_login(&ctx.env, &pending_token).await?;
+    let admin = match &pending {
+        Some(pending) => db::get_admin(&db, pending.admin_id).await?,
+        None => None,
+    };
+    let (pending, admin) = match (pending, admin) {
+        (Some(pending), Some(admin)) => (pending, admin),
+        _ => {
+            let html = templates::render_admin_login(
+                Some("ログインの有効期限が切れました。もう一度ログインしてください"),
+                None,
+            );
+            return Response::from_html(html).map(|r| r.with_status(401));
+        }
+    };
 
-    if submitted_token != expected_token {
-        let html = templates::render_admin_login(Some("トークンが正しくありません"));
+    if !verify_second_factor(&db, &admin, &code, now).await? {
+        login_guard::record_failure(&kv, &client, now).await?;
+        audit::record_failed_login(&ctx.env, &req, &admin.username).await;
+        // 間違いが続いたトークンは破棄し、パスワードからやり直させる
+        if !auth::record_pending_login_failure(&ctx.env, &pending_token, &pending).await? {
+            let html = templates::render_admin_login(
+                Some("コードの誤りが続いたため、もう一度ログインしてください"),
+                None,
+            );
+            return Response::from_html(html).map(|r| r.with_status(401));
+        }
+        let html = templates::render_admin_login_totp(&pending_token, Some("コードが正しくありません"));
         return Response::from_html(html).map(|r| r.with_status(401));
     }
 
+    if let Err(e) = auth::delete_pending_login(&ctx.env, &pending_token).await {
+        worker::console_error!("Failed to delete pending login: {:?}", e);
+    }
+    if let Err(e) = login_guard::record_success(&kv, &client).await {
+        worker::console_error!("Failed to reset login failures: {:?}", e);
+    }
+
+    let identity = AdminIdentity {
+        admin_id: Some(admin.id),
+        username: admin.username,
+        role: admin.role,
+        csrf_token: None,
+    };
+    start_session(&req, &ctx, &identity).await
+}
+
+/// TOTPコードまたはリカバリーコードを検証
+///
+/// 使われたTOTPの時間ステップやリカバリーコードは再利用できないよう記録する。
+/// 同じコードが同時に送られても、時間ステップを先に記録できた1件だけが通る。
+async fn verify_second_factor(db: &D1Database, admin: &Admin, code: &str, now: i64) -> Result<bool> {
+    let Some(secret) = admin.totp_secret.as_deref() else {
+        return Ok(false);
+    };
+
+    if let Some(step) = totp::verify_totp(secret, code, now, admin.totp_last_step) {
+        return db::claim_admin_totp_step(db, admin.id, step).await;
+    }
+
+    db::consume_recovery_code(db, admin.id, &totp::hash_recovery_code(code)).await
+}
+
+/// ログインセッションを作成し、管理画面へリダイレクト
+async fn start_session(req: &Request, ctx: &RouteContext<()>, identity: &AdminIdentity) -> Result<Response> {
     // httpsかどうかをチェック
     let is_secure = req.url()?.scheme() == "https";
 
-    // 認証成功、Cookieをセット
-    let cookie = auth::create_auth_cookie(&expected_token, is_secure);
+    // 認証成功、セッションを作成してCookieをセット
+    let session_id = auth::create_session(&ctx.env, identity).await?;
+    audit::record(&ctx.env, req, identity, AuditEvent::new(AuditAction::Login)).await;
+    let cookie = auth::create_auth_cookie(&session_id, is_secure);
     let headers = Headers::new();
     headers.set("Set-Cookie", &cookie)?;
     headers.set("Location", "/admin/versions")?;
@@ -161,8 +298,81 @@ pub async fn admin_login_submit(mut req: Request, ctx: RouteContext<()>) -> Resu
     Ok(Response::empty()?.with_status(302).with_headers(headers))
 }
 
-/// GET /admin/logout - 管理者ログアウト
-pub async fn admin_logout(_req: Request, _ctx: RouteContext<()>) -> Result<Response> {
+/// ユーザー名とパスワードを照合し、管理者と二要素認証の要否を返す
+///
+/// ユーザー名が空の場合はADMIN_TOKENによるブートストラップログインとして扱う。
+async fn authenticate(
+    ctx: &RouteContext<()>,
+    username: &str,
+    submitted_password: &str,
+) -> Result<Option<(AdminIdentity, bool)>> {
+    if username.is_empty() {
+        let identity = ctx
+            .env
+            .secret("ADMIN_TOKEN")
+            .ok()
+            .filter(|secret| auth::constant_time_eq(submitted_password, &secret.to_string()))
+            .map(|_| (AdminIdentity::bootstrap(), false));
+        return Ok(identity);
+    }
+
+    let db = D1Store::from_env(&ctx.env)?;
+    match db::get_admin_by_username(&db, username).await? {
+        Some(admin) if password::verify_password(submitted_password, &admin.password_hash) => {
+            let totp_required = admin.totp_enabled();
+            let identity = AdminIdentity {
+                admin_id: Some(admin.id),
+                username: admin.username,
+                role: admin.role,
+                csrf_token: None,
+            };
+            Ok(Some((identity, totp_required)))
+        }
+        Some(_) => Ok(None),
+        None => {
+            password::dummy_verify(submitted_password);
+            Ok(None)
+        }
+    }
+}
+
+/// ログインフォームにTurnstileを表示する場合のサイトキー
+///
+/// Turnstileが設定されていない環境では、表示できないウィジェットを要求して
+/// 締め出さないようチャレンジ自体を省略する。
+fn login_challenge_site_key(ctx: &RouteContext<()>, challenge_required: bool) -> Option<String> {
+    if !challenge_required || ctx.env.secret("TURNSTILE_SECRET_KEY").is_err() {
+        return None;
+    }
+    ctx.env.var("TURNSTILE_SITE_KEY").ok().map(|v| v.to_string())
+}
+
+/// フォームのテキストフィールドを取得（存在しなければ空文字列）
+fn form_field(form_data: &FormData, name: &str) -> String {
+    form_data
+        .get(name)
+        .and_then(|v| match v {
+            worker::FormEntry::Field(s) => Some(s),
+            _ => None,
+        })
+        .unwrap_or_default()
+}
+
+/// POST /admin/logout - 管理者ログアウト
+pub async fn admin_logout(mut req: Request, ctx: RouteContext<()>) -> Result<Response> {
+    // 他サイトから強制的にログアウトさせられないようCSRFトークンを検証する
+    let admin = match auth::require_admin_form(&mut req, &ctx.env, AdminRole::Viewer).await? {
+        Ok((admin, _)) => admin,
+        Err(response) => return Ok(response),
+    };
+    audit::record(&ctx.env, &req, &admin, AuditEvent::new(AuditAction::Logout)).await;
+
+    if let Some(session_id) = auth::session_id_from_request(&req)? {
+        if let Err(e) = auth::delete_session(&ctx.env, &session_id).await {
+            worker::console_error!("Failed to delete session: {:?}", e);
+        }
+    }
+
     let cookie = auth::create_logout_cookie();
     let headers = Headers::new();
     headers.set("Set-Cookie", &cookie)?;
@@ -173,28 +383,25 @@ pub async fn admin_logout(_req: Request, _ctx: RouteContext<()>) -> Result<Respo
 
 /// GET /admin/versions - 管理者用：日付選択ページ
 pub async fn admin_versions_index(req: Request, ctx: RouteContext<()>) -> Result<Response> {
-    // 認証チェック
-    if !auth::verify_admin_token(&req, &ctx.env)? {
-        // 未認証の場合はログインページにリダイレクト
-        let headers = Headers::new();
-        headers.set("Location", "/admin/login")?;
-        return Ok(Response::empty()?.with_status(302).with_headers(headers));
-    }
+    // 認証チェック（未認証の場合はログインページにリダイレクト）
+    let admin = match auth::require_admin_page(&req, &ctx.env, AdminRole::Viewer).await? {
+        Ok(admin) => admin,
+        Err(response) => return Ok(response),
+    };
 
-    let html = templates::render_admin_versions_index();
+    let html = templates::render_admin_versions_index(&admin, &today_jst());
     Response::from_html(html)
 }
 
 /// GET /admin/entries/:date/versions - 管理者用：バージョン一覧ページ
 pub async fn admin_versions_list(req: Request, ctx: RouteContext<()>) -> Result<Response> {
     // 認証チェック
-    if !auth::verify_admin_token(&req, &ctx.env)? {
-        let headers = Headers::new();
-        headers.set("Location", "/admin/login")?;
-        return Ok(Response::empty()?.with_status(302).with_headers(headers));
-    }
+    let admin = match auth::require_admin_page(&req, &ctx.env, AdminRole::Viewer).await? {
+        Ok(admin) => admin,
+        Err(response) => return Ok(response),
+    };
 
-    let db: D1Database = ctx.env.d1("DB")?;
+    let db = D1Store::from_env(&ctx.env)?;
 
     let date = match ctx.param("date") {
         Some(d) => d,
@@ -210,13 +417,20 @@ pub async fn admin_versions_list(req: Request, ctx: RouteContext<()>) -> Result<
     }
 
     // 現在のエントリを取得
-    let current = db::get_entry(&db, date).await?;
+    let current = db.get_entry(date).await?;
 
     // バージョン一覧を取得
-    let versions = db::list_versions(&db, date).await?;
+    let versions = db.list_versions(date).await?;
     let summaries: Vec<VersionSummary> = versions.iter().map(VersionSummary::from_version).collect();
 
+    let event = AuditEvent {
+        target_date: Some(date.to_string()),
+        ..AuditEvent::new(AuditAction::ViewVersions)
+    };
+    audit::record(&ctx.env, &req, &admin, event).await;
+
     let html = templates::render_admin_versions_list(
+        &admin,
         date,
         current.as_ref().map(|e| e.content.as_str()),
         &summaries,
@@ -227,13 +441,12 @@ pub async fn admin_versions_list(req: Request, ctx: RouteContext<()>) -> Result<
 /// GET /admin/entries/:date/versions/:version - 管理者用：バージョン詳細ページ
 pub async fn admin_version_detail(req: Request, ctx: RouteContext<()>) -> Result<Response> {
     // 認証チェック
-    if !auth::verify_admin_token(&req, &ctx.env)? {
-        let headers = Headers::new();
-        headers.set("Location", "/admin/login")?;
-        return Ok(Response::empty()?.with_status(302).with_headers(headers));
-    }
+    let admin = match auth::require_admin_page(&req, &ctx.env, AdminRole::Viewer).await? {
+        Ok(admin) => admin,
+        Err(response) => return Ok(response),
+    };
 
-    let db: D1Database = ctx.env.d1("DB")?;
+    let db = D1Store::from_env(&ctx.env)?;
 
     let date = match ctx.param("date") {
         Some(d) => d,
@@ -256,9 +469,16 @@ pub async fn admin_version_detail(req: Request, ctx: RouteContext<()>) -> Result
         return Response::from_html(html).map(|r| r.with_status(404));
     }
 
-    match db::get_version(&db, date, version).await? {
+    match db.get_version(date, version).await? {
         Some(v) => {
-            let html = templates::render_admin_version_detail(&v);
+            let event = AuditEvent {
+                target_date: Some(v.entry_date.clone()),
+                target_version: Some(v.version_number),
+                ..AuditEvent::new(AuditAction::ViewVersion)
+            };
+            audit::record(&ctx.env, &req, &admin, event).await;
+
+            let html = templates::render_admin_version_detail(&admin, &v);
             Response::from_html(html)
         }
         None => {
@@ -267,3 +487,794 @@ pub async fn admin_version_detail(req: Request, ctx: RouteContext<()>) -> Result
         }
     }
 }
+
+/// POST /admin/entries/:date/revert - 管理者用：指定バージョンへの差し戻し
+pub async fn admin_revert(mut req: Request, ctx: RouteContext<()>) -> Result<Response> {
+    let (admin, form_data) =
+        match auth::require_admin_form(&mut req, &ctx.env, AdminRole::Moderator).await? {
+            Ok(result) => result,
+            Err(response) => return Ok(response),
+        };
+
+    let db = D1Store::from_env(&ctx.env)?;
+
+    let date = match ctx.param("date") {
+        Some(d) if is_valid_date(d) => d.to_string(),
+        _ => {
+            let html = templates::render_not_found();
+            return Response::from_html(html).map(|r| r.with_status(404));
+        }
+    };
+
+    let version = match form_field(&form_data, "version").parse::<i32>() {
+        Ok(v) => v,
+        Err(_) => {
+            let html = templates::render_not_found();
+            return Response::from_html(html).map(|r| r.with_status(404));
+        }
+    };
+
+    let target = match db.get_version(&date, version).await? {
+        Some(v) => v,
+        None => {
+            let html = templates::render_not_found();
+            return Response::from_html(html).map(|r| r.with_status(404));
+        }
+    };
+
+    // 差し戻し前の内容も新しいバージョンとして残る
+    let before = db.get_entry(&date).await?;
+    db.upsert_entry(&date, &target.content).await?;
+
+    let event = AuditEvent {
+        target_date: Some(date.clone()),
+        target_version: Some(version),
+        before_hash: before.map(|e| audit::content_hash(&e.content)),
+        after_hash: Some(audit::content_hash(&target.content)),
+        ..AuditEvent::new(AuditAction::Revert)
+    };
+    audit::record(&ctx.env, &req, &admin, event).await;
+
+    redirect(&format!("/admin/entries/{}/versions", date))
+}
+
+/// POST /admin/entries/:date/versions/:version/redact - 管理者用：バージョンの墨消し
+pub async fn admin_redact_version(mut req: Request, ctx: RouteContext<()>) -> Result<Response> {
+    let (admin, _) =
+        match auth::require_admin_form(&mut req, &ctx.env, AdminRole::Moderator).await? {
+            Ok(result) => result,
+            Err(response) => return Ok(response),
+        };
+
+    let db = D1Store::from_env(&ctx.env)?;
+
+    let date = match ctx.param("date") {
+        Some(d) if is_valid_date(d) => d.to_string(),
+        _ => {
+            let html = templates::render_not_found();
+            return Response::from_html(html).map(|r| r.with_status(404));
+        }
+    };
+
+    let version: i32 = match ctx.param("version").and_then(|v| v.parse().ok()) {
+        Some(v) => v,
+        None => {
+            let html = templates::render_not_found();
+            return Response::from_html(html).map(|r| r.with_status(404));
+        }
+    };
+
+    let target = match db.get_version(&date, version).await? {
+        Some(v) => v,
+        None => {
+            let html = templates::render_not_found();
+            return Response::from_html(html).map(|r| r.with_status(404));
+        }
+    };
+
+    db.redact_version(&date, version, REDACTED_CONTENT).await?;
+
+    let event = AuditEvent {
+        target_date: Some(date.clone()),
+        target_version: Some(version),
+        before_hash: Some(audit::content_hash(&target.content)),
+        after_hash: Some(audit::content_hash(REDACTED_CONTENT)),
+        ..AuditEvent::new(AuditAction::Redact)
+    };
+    audit::record(&ctx.env, &req, &admin, event).await;
+
+    redirect(&format!("/admin/entries/{}/versions/{}", date, version))
+}
+
+/// POST /admin/entries/:date/edit - 管理者用：日記の直接編集
+pub async fn admin_edit_entry(mut req: Request, ctx: RouteContext<()>) -> Result<Response> {
+    let (admin, form_data) =
+        match auth::require_admin_form(&mut req, &ctx.env, AdminRole::Superuser).await? {
+            Ok(result) => result,
+            Err(response) => return Ok(response),
+        };
+
+    let db = D1Store::from_env(&ctx.env)?;
+
+    let date = match ctx.param("date") {
+        Some(d) if is_valid_date(d) => d.to_string(),
+        _ => {
+            let html = templates::render_not_found();
+            return Response::from_html(html).map(|r| r.with_status(404));
+        }
+    };
+
+    let content = normalize_content(&form_field(&form_data, "content"));
+
+    if is_content_too_long(&content) {
+        let html = templates::render_bad_request(&format!(
+            "本文は{}文字以内にしてください",
+            MAX_CONTENT_LENGTH
+        ));
+        return Response::from_html(html).map(|r| r.with_status(400));
+    }
+
+    let before = db.get_entry(&date).await?;
+    db.upsert_entry(&date, &content).await?;
+
+    let event = AuditEvent {
+        target_date: Some(date.clone()),
+        before_hash: before.map(|e| audit::content_hash(&e.content)),
+        after_hash: Some(audit::content_hash(&content)),
+        ..AuditEvent::new(AuditAction::Edit)
+    };
+    audit::record(&ctx.env, &req, &admin, event).await;
+
+    redirect(&format!("/admin/entries/{}/versions", date))
+}
+
+/// GET /admin/admins - 管理者用：管理者アカウント一覧
+pub async fn admin_admins_page(req: Request, ctx: RouteContext<()>) -> Result<Response> {
+    let admin = match auth::require_admin_page(&req, &ctx.env, AdminRole::Superuser).await? {
+        Ok(admin) => admin,
+        Err(response) => return Ok(response),
+    };
+
+    render_admins_page(&ctx, &admin, None, 200).await
+}
+
+/// POST /admin/admins - 管理者用：管理者アカウントの作成
+pub async fn admin_create_admin(mut req: Request, ctx: RouteContext<()>) -> Result<Response> {
+    let (admin, form_data) =
+        match auth::require_admin_form(&mut req, &ctx.env, AdminRole::Superuser).await? {
+            Ok(result) => result,
+            Err(response) => return Ok(response),
+        };
+
+    let username = form_field(&form_data, "username");
+    let new_password = form_field(&form_data, "password");
+    let role = AdminRole::parse(&form_field(&form_data, "role"));
+
+    let error = if !is_valid_username(&username) {
+        Some("ユーザー名は英数字・ハイフン・アンダースコアで32文字以内にしてください".to_string())
+    } else if username == auth::BOOTSTRAP_USERNAME {
+        Some(format!("{}は予約されたユーザー名です", auth::BOOTSTRAP_USERNAME))
+    } else if new_password.chars().count() < MIN_PASSWORD_LENGTH {
+        Some(format!("パスワードは{}文字以上にしてください", MIN_PASSWORD_LENGTH))
+    } else if role.is_none() {
+        Some("権限が正しくありません".to_string())
+    } else {
+        None
+    };
+    if let Some(error) = error {
+        return render_admins_page(&ctx, &admin, Some(&error), 400).await;
+    }
+
+    let db = D1Store::from_env(&ctx.env)?;
+    if db::get_admin_by_username(&db, &username).await?.is_some() {
+        return render_admins_page(&ctx, &admin, Some("そのユーザー名は使われています"), 409).await;
+    }
+
+    let password_hash = password::hash_password(&new_password);
+    let role = role.unwrap_or(AdminRole::Viewer);
+    db::create_admin(&db, &username, &password_hash, role).await?;
+
+    let event = AuditEvent {
+        target: Some(format!("{} ({})", username, role.as_str())),
+        ..AuditEvent::new(AuditAction::AdminCreate)
+    };
+    audit::record(&ctx.env, &req, &admin, event).await;
+
+    redirect("/admin/admins")
+}
+
+/// POST /admin/admins/:id/role - 管理者用：権限の変更
+pub async fn admin_update_admin_role(mut req: Request, ctx: RouteContext<()>) -> Result<Response> {
+    let (admin, form_data) =
+        match auth::require_admin_form(&mut req, &ctx.env, AdminRole::Superuser).await? {
+            Ok(result) => result,
+            Err(response) => return Ok(response),
+        };
+
+    let id: i64 = match ctx.param("id").and_then(|v| v.parse::<i32>().ok()).map(i64::from) {
+        Some(id) => id,
+        None => {
+            let html = templates::render_not_found();
+            return Response::from_html(html).map(|r| r.with_status(404));
+        }
+    };
+
+    let role = match AdminRole::parse(&form_field(&form_data, "role")) {
+        Some(role) => role,
+        None => return render_admins_page(&ctx, &admin, Some("権限が正しくありません"), 400).await,
+    };
+
+    if admin.admin_id == Some(id) {
+        return render_admins_page(&ctx, &admin, Some("自分自身の権限は変更できません"), 400).await;
+    }
+
+    let db = D1Store::from_env(&ctx.env)?;
+    let Some(target) = db::get_admin(&db, id).await? else {
+        let html = templates::render_not_found();
+        return Response::from_html(html).map(|r| r.with_status(404));
+    };
+    db::update_admin_role(&db, id, role).await?;
+
+    let event = AuditEvent {
+        target: Some(format!(
+            "{} ({} -> {})",
+            target.username,
+            target.role.as_str(),
+            role.as_str()
+        )),
+        ..AuditEvent::new(AuditAction::AdminRoleChange)
+    };
+    audit::record(&ctx.env, &req, &admin, event).await;
+
+    redirect("/admin/admins")
+}
+
+/// POST /admin/admins/:id/email - 管理者用：Cloudflare Accessのメールアドレスの設定
+pub async fn admin_update_admin_email(mut req: Request, ctx: RouteContext<()>) -> Result<Response> {
+    let (admin, form_data) =
+        match auth::require_admin_form(&mut req, &ctx.env, AdminRole::Superuser).await? {
+            Ok(result) => result,
+            Err(response) => return Ok(response),
+        };
+
+    let id: i64 = match ctx.param("id").and_then(|v| v.parse::<i32>().ok()).map(i64::from) {
+        Some(id) => id,
+        None => {
+            let html = templates::render_not_found();
+            return Response::from_html(html).map(|r| r.with_status(404));
+        }
+    };
+
+    let email = form_field(&form_data, "email").trim().to_ascii_lowercase();
+    if !email.is_empty() && !is_valid_email(&email) {
+        return render_admins_page(&ctx, &admin, Some("メールアドレスが正しくありません"), 400).await;
+    }
+
+    let db = D1Store::from_env(&ctx.env)?;
+    let Some(target) = db::get_admin(&db, id).await? else {
+        let html = templates::render_not_found();
+        return Response::from_html(html).map(|r| r.with_status(404));
+    };
+    if !email.is_empty() {
+        if let Some(existing) = db::get_admin_by_email(&db, &email).await? {
+            if existing.id != id {
+                return render_admins_page(&ctx, &admin, Some("そのメールアドレスは使われています"), 409).await;
+            }
+        }
+    }
+    db::update_admin_email(&db, id, Some(email.as_str()).filter(|e| !e.is_empty())).await?;
+
+    let event = AuditEvent {
+        target: Some(target.username),
+        before_hash: target.email.as_deref().map(audit::content_hash),
+        after_hash: Some(email.as_str()).filter(|e| !e.is_empty()).map(audit::content_hash),
+        ..AuditEvent::new(AuditAction::AdminEmailChange)
+    };
+    audit::record(&ctx.env, &req, &admin, event).await;
+
+    redirect("/admin/admins")
+}
+
+/// POST /admin/admins/:id/delete - 管理者用：管理者アカウントの削除
+pub async fn admin_delete_admin(mut req: Request, ctx: RouteContext<()>) -> Result<Response> {
+    let (admin, _) =
+        match auth::require_admin_form(&mut req, &ctx.env, AdminRole::Superuser).await? {
+            Ok(result) => result,
+            Err(response) => return Ok(response),
+        };
+
+    let id: i64 = match ctx.param("id").and_then(|v| v.parse::<i32>().ok()).map(i64::from) {
+        Some(id) => id,
+        None => {
+            let html = templates::render_not_found();
+            return Response::from_html(html).map(|r| r.with_status(404));
+        }
+    };
+
+    if admin.admin_id == Some(id) {
+        return render_admins_page(&ctx, &admin, Some("自分自身は削除できません"), 400).await;
+    }
+
+    let db = D1Store::from_env(&ctx.env)?;
+    let Some(target) = db::get_admin(&db, id).await? else {
+        let html = templates::render_not_found();
+        return Response::from_html(html).map(|r| r.with_status(404));
+    };
+    db::delete_admin(&db, id).await?;
+
+    let event = AuditEvent {
+        target: Some(target.username),
+        ..AuditEvent::new(AuditAction::AdminDelete)
+    };
+    audit::record(&ctx.env, &req, &admin, event).await;
+
+    redirect("/admin/admins")
+}
+
+/// GET /admin/api-keys - 管理者用：APIキーの一覧と発行
+pub async fn admin_api_keys_page(req: Request, ctx: RouteContext<()>) -> Result<Response> {
+    let admin = match auth::require_admin_page(&req, &ctx.env, AdminRole::Superuser).await? {
+        Ok(admin) => admin,
+        Err(response) => return Ok(response),
+    };
+
+    render_api_keys_page(&ctx, &admin, None, None, 200).await
+}
+
+/// POST /admin/api-keys - 管理者用：APIキーの発行
+pub async fn admin_create_api_key(mut req: Request, ctx: RouteContext<()>) -> Result<Response> {
+    let (admin, form_data) =
+        match auth::require_admin_form(&mut req, &ctx.env, AdminRole::Superuser).await? {
+            Ok(result) => result,
+            Err(response) => return Ok(response),
+        };
+
+    let label = form_field(&form_data, "label").trim().to_string();
+    let daily_quota = api_keys::parse_daily_quota(
+        &form_field(&form_data, "daily_quota"),
+        MAX_API_KEY_DAILY_QUOTA,
+    );
+
+    let error = if label.is_empty() || label.chars().count() > MAX_API_KEY_LABEL_LENGTH {
+        Some(format!("名前は1〜{}文字にしてください", MAX_API_KEY_LABEL_LENGTH))
+    } else if daily_quota.is_none() {
+        Some(format!("1日の上限回数は1〜{}にしてください", MAX_API_KEY_DAILY_QUOTA))
+    } else {
+        None
+    };
+    if let Some(error) = error {
+        return render_api_keys_page(&ctx, &admin, None, Some(&error), 400).await;
+    }
+
+    let daily_quota = daily_quota.unwrap_or(DEFAULT_API_KEY_DAILY_QUOTA);
+    let key = api_keys::generate_key();
+    let key_prefix = api_keys::display_prefix(&key);
+    let db = D1Store::from_env(&ctx.env)?;
+    db::create_api_key(
+        &db,
+        &label,
+        &key_prefix,
+        &api_keys::hash_key(&key),
+        daily_quota,
+        &admin.username,
+    )
+    .await?;
+
+    let event = AuditEvent {
+        target: Some(format!("{} ({}…)", label, key_prefix)),
+        ..AuditEvent::new(AuditAction::ApiKeyCreate)
+    };
+    audit::record(&ctx.env, &req, &admin, event).await;
+
+    // リダイレクトすると平文のキーを表示できないため、そのまま一覧を返す
+    render_api_keys_page(&ctx, &admin, Some(&key), None, 201).await
+}
+
+/// POST /admin/api-keys/:id/revoke - 管理者用：APIキーの失効
+pub async fn admin_revoke_api_key(mut req: Request, ctx: RouteContext<()>) -> Result<Response> {
+    let (admin, _) =
+        match auth::require_admin_form(&mut req, &ctx.env, AdminRole::Superuser).await? {
+            Ok(result) => result,
+            Err(response) => return Ok(response),
+        };
+
+    let id: i64 = match ctx.param("id").and_then(|v| v.parse::<i32>().ok()).map(i64::from) {
+        Some(id) => id,
+        None => {
+            let html = templates::render_not_found();
+            return Response::from_html(html).map(|r| r.with_status(404));
+        }
+    };
+
+    let db = D1Store::from_env(&ctx.env)?;
+    if db::revoke_api_key(&db, id).await? {
+        let event = AuditEvent {
+            target: Some(format!("api_key:{}", id)),
+            ..AuditEvent::new(AuditAction::ApiKeyRevoke)
+        };
+        audit::record(&ctx.env, &req, &admin, event).await;
+    }
+
+    redirect("/admin/api-keys")
+}
+
+/// GET /admin/moderation - 管理者用：モデレーションキュー
+pub async fn admin_moderation_page(req: Request, ctx: RouteContext<()>) -> Result<Response> {
+    let admin = match auth::require_admin_page(&req, &ctx.env, AdminRole::Moderator).await? {
+        Ok(admin) => admin,
+        Err(response) => return Ok(response),
+    };
+
+    render_moderation_page(&ctx, &admin, None, 200).await
+}
+
+/// POST /admin/moderation/:id - 管理者用：モデレーションキューの項目の処理
+pub async fn admin_resolve_moderation(mut req: Request, ctx: RouteContext<()>) -> Result<Response> {
+    let (admin, form_data) =
+        match auth::require_admin_form(&mut req, &ctx.env, AdminRole::Moderator).await? {
+            Ok(result) => result,
+            Err(response) => return Ok(response),
+        };
+
+    let id: i64 = match ctx.param("id").and_then(|v| v.parse::<i32>().ok()).map(i64::from) {
+        Some(id) => id,
+        None => {
+            let html = templates::render_not_found();
+            return Response::from_html(html).map(|r| r.with_status(404));
+        }
+    };
+
+    let db = D1Store::from_env(&ctx.env)?;
+    let resolution = ModerationResolution::parse(&form_field(&form_data, "resolution"));
+    let resolved =
+        moderation::resolve(&db, id, resolution, &admin.username, &today_jst()).await?;
+    let resolved = match resolved {
+        Ok(resolved) => resolved,
+        Err(ResolveRejection::NotFound) => {
+            let html = templates::render_not_found();
+            return Response::from_html(html).map(|r| r.with_status(404));
+        }
+        Err(rejection @ ResolveRejection::NotApplicable) => {
+            let html = templates::render_bad_request(rejection.message());
+            return Response::from_html(html).map(|r| r.with_status(400));
+        }
+        Err(rejection @ ResolveRejection::Conflict(error)) => {
+            return render_moderation_page(&ctx, &admin, Some(error), rejection.status()).await;
+        }
+    };
+
+    let event = AuditEvent {
+        target_date: Some(resolved.item.entry_date.clone()),
+        target: Some(format!("moderation:{}", id)),
+        before_hash: resolved.before_hash,
+        after_hash: resolved.after_hash,
+        ..AuditEvent::new(resolved.action)
+    };
+    audit::record(&ctx.env, &req, &admin, event).await;
+
+    redirect("/admin/moderation")
+}
+
+/// GET /admin/filters - 管理者用：内容フィルターの一覧と登録
+pub async fn admin_filters_page(req: Request, ctx: RouteContext<()>) -> Result<Response> {
+    let admin = match auth::require_admin_page(&req, &ctx.env, AdminRole::Moderator).await? {
+        Ok(admin) => admin,
+        Err(response) => return Ok(response),
+    };
+
+    render_filters_page(&ctx, &admin, None, 200).await
+}
+
+/// POST /admin/filters - 管理者用：内容フィルターの登録
+pub async fn admin_create_filter(mut req: Request, ctx: RouteContext<()>) -> Result<Response> {
+    let (admin, form_data) =
+        match auth::require_admin_form(&mut req, &ctx.env, AdminRole::Moderator).await? {
+            Ok(result) => result,
+            Err(response) => return Ok(response),
+        };
+
+    let pattern = form_field(&form_data, "pattern");
+    let kind = FilterKind::parse(&form_field(&form_data, "kind"));
+    let action = FilterAction::parse(&form_field(&form_data, "action"));
+
+    let (Some(kind), Some(action)) = (kind, action) else {
+        let error = "種類または扱いが正しくありません";
+        return render_filters_page(&ctx, &admin, Some(error), 400).await;
+    };
+    // NGワードは前後の空白を除き、正規表現はそのまま使う
+    let pattern = match kind {
+        FilterKind::Word => pattern.trim().to_string(),
+        FilterKind::Regex => pattern,
+    };
+    if let Err(error) = content_filter::validate_pattern(kind, &pattern) {
+        return render_filters_page(&ctx, &admin, Some(&error), 400).await;
+    }
+
+    let db = D1Store::from_env(&ctx.env)?;
+    db::create_content_filter(&db, kind, &pattern, action, &admin.username).await?;
+
+    let event = AuditEvent {
+        target: Some(format!("{} {} ({})", kind.as_str(), pattern, action.as_str())),
+        ..AuditEvent::new(AuditAction::FilterCreate)
+    };
+    audit::record(&ctx.env, &req, &admin, event).await;
+
+    redirect("/admin/filters")
+}
+
+/// POST /admin/filters/:id/delete - 管理者用：内容フィルターの削除
+pub async fn admin_delete_filter(mut req: Request, ctx: RouteContext<()>) -> Result<Response> {
+    let (admin, _) =
+        match auth::require_admin_form(&mut req, &ctx.env, AdminRole::Moderator).await? {
+            Ok(result) => result,
+            Err(response) => return Ok(response),
+        };
+
+    let id: i64 = match ctx.param("id").and_then(|v| v.parse::<i32>().ok()).map(i64::from) {
+        Some(id) => id,
+        None => {
+            let html = templates::render_not_found();
+            return Response::from_html(html).map(|r| r.with_status(404));
+        }
+    };
+
+    let db = D1Store::from_env(&ctx.env)?;
+    let Some(filter) = db::delete_content_filter(&db, id).await? else {
+        let html = templates::render_not_found();
+        return Response::from_html(html).map(|r| r.with_status(404));
+    };
+
+    let event = AuditEvent {
+        target: Some(format!(
+            "{} {} ({})",
+            filter.kind.as_str(),
+            filter.pattern,
+            filter.action.as_str()
+        )),
+        ..AuditEvent::new(AuditAction::FilterDelete)
+    };
+    audit::record(&ctx.env, &req, &admin, event).await;
+
+    redirect("/admin/filters")
+}
+
+/// GET /admin/audit - 管理者用：監査ログ
+pub async fn admin_audit_page(req: Request, ctx: RouteContext<()>) -> Result<Response> {
+    let admin = match auth::require_admin_page(&req, &ctx.env, AdminRole::Superuser).await? {
+        Ok(admin) => admin,
+        Err(response) => return Ok(response),
+    };
+
+    let filter = audit::filter_from_request(&req)?;
+    let db = D1Store::from_env(&ctx.env)?;
+    let (entries, has_next) = audit::load_page(&db, &filter).await?;
+
+    let html = templates::render_admin_audit(&admin, &entries, &filter, has_next);
+    Response::from_html(html)
+}
+
+/// GET /admin/totp - 管理者用：二要素認証の設定
+pub async fn admin_totp_page(req: Request, ctx: RouteContext<()>) -> Result<Response> {
+    let identity = match auth::require_admin_page(&req, &ctx.env, AdminRole::Viewer).await? {
+        Ok(admin) => admin,
+        Err(response) => return Ok(response),
+    };
+    let admin = match current_admin(&ctx, &identity).await? {
+        Ok(admin) => admin,
+        Err(response) => return Ok(response),
+    };
+
+    if admin.totp_enabled() {
+        return render_totp_status(&ctx, &identity, &admin, None, 200).await;
+    }
+
+    // 登録用のシークレットを発行し、確認コードが入力されるまで保持する
+    let secret = totp::generate_secret();
+    auth::store_totp_enrollment(&ctx.env, admin.id, &secret).await?;
+    render_totp_setup(&identity, &secret, None, 200)
+}
+
+/// POST /admin/totp/enable - 管理者用：二要素認証の有効化
+pub async fn admin_totp_enable(mut req: Request, ctx: RouteContext<()>) -> Result<Response> {
+    let (identity, form_data) =
+        match auth::require_admin_form(&mut req, &ctx.env, AdminRole::Viewer).await? {
+            Ok(result) => result,
+            Err(response) => return Ok(response),
+        };
+    let admin = match current_admin(&ctx, &identity).await? {
+        Ok(admin) => admin,
+        Err(response) => return Ok(response),
+    };
+
+    let secret = match auth::get_totp_enrollment(&ctx.env, admin.id).await? {
+        Some(secret) => secret,
+        None => return redirect("/admin/totp"),
+    };
+
+    let code = form_field(&form_data, "code");
+    let step = match totp::verify_totp(&secret, &code, now_unix(), None) {
+        Some(step) => step,
+        None => {
+            return render_totp_setup(&identity, &secret, Some("コードが正しくありません"), 400);
+        }
+    };
+
+    let db = D1Store::from_env(&ctx.env)?;
+    db::set_admin_totp_secret(&db, admin.id, Some(&secret)).await?;
+    // 有効化に使ったコードはログインで再利用させない
+    db::claim_admin_totp_step(&db, admin.id, step).await?;
+    auth::delete_totp_enrollment(&ctx.env, admin.id).await?;
+    audit::record(&ctx.env, &req, &identity, AuditEvent::new(AuditAction::TotpEnable)).await;
+
+    issue_recovery_codes(&db, &identity, admin.id).await
+}
+
+/// POST /admin/totp/recovery-codes - 管理者用：リカバリーコードの再発行
+pub async fn admin_totp_regenerate_codes(mut req: Request, ctx: RouteContext<()>) -> Result<Response> {
+    let (identity, form_data) =
+        match auth::require_admin_form(&mut req, &ctx.env, AdminRole::Viewer).await? {
+            Ok(result) => result,
+            Err(response) => return Ok(response),
+        };
+    let admin = match current_admin(&ctx, &identity).await? {
+        Ok(admin) => admin,
+        Err(response) => return Ok(response),
+    };
+
+    let db = D1Store::from_env(&ctx.env)?;
+    let code = form_field(&form_data, "code");
+    if !verify_second_factor(&db, &admin, &code, now_unix()).await? {
+        return render_totp_status(&ctx, &identity, &admin, Some("コードが正しくありません"), 400).await;
+    }
+
+    let event = AuditEvent::new(AuditAction::RecoveryCodesRegenerate);
+    audit::record(&ctx.env, &req, &identity, event).await;
+
+    issue_recovery_codes(&db, &identity, admin.id).await
+}
+
+/// POST /admin/totp/disable - 管理者用：二要素認証の無効化
+pub async fn admin_totp_disable(mut req: Request, ctx: RouteContext<()>) -> Result<Response> {
+    let (identity, form_data) =
+        match auth::require_admin_form(&mut req, &ctx.env, AdminRole::Viewer).await? {
+            Ok(result) => result,
+            Err(response) => return Ok(response),
+        };
+    let admin = match current_admin(&ctx, &identity).await? {
+        Ok(admin) => admin,
+        Err(response) => return Ok(response),
+    };
+
+    let db = D1Store::from_env(&ctx.env)?;
+    let code = form_field(&form_data, "code");
+    if !verify_second_factor(&db, &admin, &code, now_unix()).await? {
+        return render_totp_status(&ctx, &identity, &admin, Some("コードが正しくありません"), 400).await;
+    }
+
+    db::set_admin_totp_secret(&db, admin.id, None).await?;
+    db::replace_recovery_codes(&db, admin.id, &[]).await?;
+    audit::record(&ctx.env, &req, &identity, AuditEvent::new(AuditAction::TotpDisable)).await;
+
+    redirect("/admin/totp")
+}
+
+/// ログイン中の管理者アカウントを取得
+///
+/// ADMIN_TOKENによるブートストラップ管理者はアカウントを持たないためエラーページを返す。
+async fn current_admin(
+    ctx: &RouteContext<()>,
+    identity: &AdminIdentity,
+) -> Result<std::result::Result<Admin, Response>> {
+    let admin = match identity.admin_id {
+        Some(id) => {
+            let db = D1Store::from_env(&ctx.env)?;
+            db::get_admin(&db, id).await?
+        }
+        None => None,
+    };
+
+    match admin {
+        Some(admin) => Ok(Ok(admin)),
+        None => {
+            let html = templates::render_bad_request(
+                "管理者トークンでログインしている場合は二要素認証を設定できません",
+            );
+            Response::from_html(html).map(|r| Err(r.with_status(400)))
+        }
+    }
+}
+
+/// 二要素認証の登録ページを描画
+fn render_totp_setup(
+    identity: &AdminIdentity,
+    secret: &str,
+    error: Option<&str>,
+    status: u16,
+) -> Result<Response> {
+    let uri = totp::provisioning_uri(TOTP_ISSUER, &identity.username, secret);
+    let qr_svg = totp::qr_svg(&uri);
+    let html = templates::render_admin_totp_setup(identity, secret, &uri, qr_svg.as_deref(), error);
+    Response::from_html(html).map(|r| r.with_status(status))
+}
+
+/// 二要素認証の状態ページを描画
+async fn render_totp_status(
+    ctx: &RouteContext<()>,
+    identity: &AdminIdentity,
+    admin: &Admin,
+    error: Option<&str>,
+    status: u16,
+) -> Result<Response> {
+    let db = D1Store::from_env(&ctx.env)?;
+    let remaining = db::count_unused_recovery_codes(&db, admin.id).await?;
+    let html = templates::render_admin_totp_status(identity, remaining, error);
+    Response::from_html(html).map(|r| r.with_status(status))
+}
+
+/// リカバリーコードを発行し、一度だけ表示する
+async fn issue_recovery_codes(db: &D1Database, identity: &AdminIdentity, admin_id: i64) -> Result<Response> {
+    let codes = totp::generate_recovery_codes();
+    let hashes: Vec<String> = codes.iter().map(|c| totp::hash_recovery_code(c)).collect();
+    db::replace_recovery_codes(db, admin_id, &hashes).await?;
+
+    let html = templates::render_admin_recovery_codes(identity, &codes);
+    Response::from_html(html)
+}
+
+/// 管理者一覧ページを描画
+async fn render_admins_page(
+    ctx: &RouteContext<()>,
+    admin: &AdminIdentity,
+    error: Option<&str>,
+    status: u16,
+) -> Result<Response> {
+    let db = D1Store::from_env(&ctx.env)?;
+    let admins = db::list_admins(&db).await?;
+    let html = templates::render_admin_admins(admin, &admins, error);
+    Response::from_html(html).map(|r| r.with_status(status))
+}
+
+async fn render_api_keys_page(
+    ctx: &RouteContext<()>,
+    admin: &AdminIdentity,
+    created_key: Option<&str>,
+    error: Option<&str>,
+    status: u16,
+) -> Result<Response> {
+    let db = D1Store::from_env(&ctx.env)?;
+    let keys = db::list_api_keys(&db, &today_jst()).await?;
+    let html = templates::render_admin_api_keys(admin, &keys, created_key, error);
+    Response::from_html(html).map(|r| r.with_status(status))
+}
+
+async fn render_moderation_page(
+    ctx: &RouteContext<()>,
+    admin: &AdminIdentity,
+    error: Option<&str>,
+    status: u16,
+) -> Result<Response> {
+    let db = D1Store::from_env(&ctx.env)?;
+    let items = db::list_pending_moderation(&db, MODERATION_PAGE_SIZE).await?;
+    let html = templates::render_admin_moderation(admin, &items, error);
+    Response::from_html(html).map(|r| r.with_status(status))
+}
+
+async fn render_filters_page(
+    ctx: &RouteContext<()>,
+    admin: &AdminIdentity,
+    error: Option<&str>,
+    status: u16,
+) -> Result<Response> {
+    let db = D1Store::from_env(&ctx.env)?;
+    let filters = db::list_content_filters(&db).await?;
+    let links = LinkPolicy::from_env(&ctx.env);
+    let html = templates::render_admin_filters(admin, &filters, &links, error);
+    Response::from_html(html).map(|r| r.with_status(status))
+}
+
+/// 指定パスへの302リダイレクト
+fn redirect(location: &str) -> Result<Response> {
+    let headers = Headers::new();
+    headers.set("Location", location)?;
+    Ok(Response::empty()?.with_status(302).with_headers(headers))
+}
@@ -0,0 +1,344 @@
+use serde::Deserialize;
+use worker::d1::D1Database;
+use worker::{Headers, Request, Response, Result, RouteContext};
+
+use crate::audit;
+use crate::auth::{self, AdminIdentity, WebAuthnChallenge};
+use crate::db;
+use crate::login_guard;
+use crate::models::{
+    AdminRole, AuditAction, AuditEvent, ErrorResponse, RedirectResponse, MAX_PASSKEY_LABEL_LENGTH,
+};
+use crate::rate_limit;
+use crate::templates;
+use crate::time::now_unix;
+use crate::webauthn::{self, b64url_decode, RelyingParty};
+
+/// 認証器に表示されるRelying Partyの名前
+const RP_NAME: &str = "誰かが書く日記";
+
+#[derive(Deserialize)]
+struct RegistrationRequest {
+    challenge: String,
+    client_data_json: String,
+    attestation_object: String,
+    label: String,
+}
+
+#[derive(Deserialize)]
+struct LoginRequest {
+    challenge: String,
+    credential_id: String,
+    client_data_json: String,
+    authenticator_data: String,
+    signature: String,
+}
+
+/// リクエストURLからRP IDとオリジンを取得
+fn relying_party_from(req: &Request) -> Result<(String, String)> {
+    let url = req.url()?;
+    let rp_id = url.host_str().unwrap_or("localhost").to_string();
+    let origin = url.origin().ascii_serialization();
+    Ok((rp_id, origin))
+}
+
+fn json_error(message: &str, code: &str, status: u16) -> Result<Response> {
+    Response::from_json(&ErrorResponse::new(message, code)).map(|r| r.with_status(status))
+}
+
+/// パスキーを登録できる管理者か確認（ブートストラップ管理者は不可）
+fn registrable_admin(
+    admin: AdminIdentity,
+) -> Result<std::result::Result<(AdminIdentity, i64), Response>> {
+    match admin.admin_id {
+        Some(id) => Ok(Ok((admin, id))),
+        None => {
+            let html = templates::render_bad_request(
+                "管理者トークンでログインしている場合はパスキーを登録できません",
+            );
+            Response::from_html(html).map(|r| Err(r.with_status(400)))
+        }
+    }
+}
+
+/// 管理画面の認証を行い、パスキーを登録できる管理者か確認
+async fn require_registrable_admin(
+    req: &Request,
+    ctx: &RouteContext<()>,
+) -> Result<std::result::Result<(AdminIdentity, i64), Response>> {
+    match auth::require_admin_page(req, &ctx.env, AdminRole::Viewer).await? {
+        Ok(admin) => registrable_admin(admin),
+        Err(response) => Ok(Err(response)),
+    }
+}
+
+/// GET /admin/passkeys - 管理者用：パスキー一覧
+pub async fn passkeys_page(req: Request, ctx: RouteContext<()>) -> Result<Response> {
+    let (admin, admin_id) = match require_registrable_admin(&req, &ctx).await? {
+        Ok(result) => result,
+        Err(response) => return Ok(response),
+    };
+
+    let db: D1Database = ctx.env.d1("DB")?;
+    let credentials = db::list_admin_credentials(&db, admin_id).await?;
+    let html = templates::render_admin_passkeys(&admin, &credentials);
+    Response::from_html(html)
+}
+
+/// POST /admin/passkeys/options - 管理者用：パスキー登録のオプション発行
+pub async fn registration_options(req: Request, ctx: RouteContext<()>) -> Result<Response> {
+    let (admin, admin_id) = match require_registrable_admin(&req, &ctx).await? {
+        Ok(result) => result,
+        Err(response) => return Ok(response),
+    };
+    if let Err(response) = auth::require_csrf_header(&req, &admin)? {
+        return Ok(response);
+    }
+
+    let db: D1Database = ctx.env.d1("DB")?;
+    let existing: Vec<String> = db::list_admin_credentials(&db, admin_id)
+        .await?
+        .into_iter()
+        .map(|c| c.credential_id)
+        .collect();
+
+    let challenge = webauthn::new_challenge();
+    auth::store_webauthn_challenge(
+        &ctx.env,
+        &challenge,
+        &WebAuthnChallenge::Register { admin_id },
+    )
+    .await?;
+
+    let (rp_id, _) = relying_party_from(&req)?;
+    let options = webauthn::creation_options(
+        &challenge,
+        &rp_id,
+        RP_NAME,
+        admin_id,
+        &admin.username,
+        &existing,
+    );
+    Response::from_json(&options)
+}
+
+/// POST /admin/passkeys - 管理者用：パスキーの登録
+pub async fn register(mut req: Request, ctx: RouteContext<()>) -> Result<Response> {
+    let (admin, admin_id) = match require_registrable_admin(&req, &ctx).await? {
+        Ok(result) => result,
+        Err(response) => return Ok(response),
+    };
+    if let Err(response) = auth::require_csrf_header(&req, &admin)? {
+        return Ok(response);
+    }
+
+    let body: RegistrationRequest = match req.json().await {
+        Ok(body) => body,
+        Err(_) => return json_error("Invalid JSON", "BAD_REQUEST", 400),
+    };
+
+    let label = body.label.trim();
+    if label.is_empty() || label.chars().count() > MAX_PASSKEY_LABEL_LENGTH {
+        return json_error("Invalid label", "BAD_REQUEST", 400);
+    }
+
+    // チャレンジは登録を開始した本人のものに限る
+    match auth::take_webauthn_challenge(&ctx.env, &body.challenge).await? {
+        Some(WebAuthnChallenge::Register {
+            admin_id: issued_to,
+        }) if issued_to == admin_id => {}
+        _ => return json_error("Challenge expired", "CHALLENGE_EXPIRED", 400),
+    }
+
+    let (Some(client_data_json), Some(attestation_object)) = (
+        b64url_decode(&body.client_data_json),
+        b64url_decode(&body.attestation_object),
+    ) else {
+        return json_error("Invalid encoding", "BAD_REQUEST", 400);
+    };
+
+    let (rp_id, origin) = relying_party_from(&req)?;
+    let rp = RelyingParty {
+        id: &rp_id,
+        origin: &origin,
+    };
+    let credential = match webauthn::verify_registration(
+        &client_data_json,
+        &attestation_object,
+        &body.challenge,
+        &rp,
+    ) {
+        Ok(credential) => credential,
+        Err(e) => {
+            worker::console_warn!("Passkey registration rejected: {:?}", e);
+            return json_error("Passkey verification failed", "WEBAUTHN_FAILED", 400);
+        }
+    };
+
+    let db: D1Database = ctx.env.d1("DB")?;
+    if db::get_admin_credential(&db, &credential.credential_id)
+        .await?
+        .is_some()
+    {
+        return json_error("Passkey already registered", "CONFLICT", 409);
+    }
+    db::create_admin_credential(
+        &db,
+        admin_id,
+        &credential.credential_id,
+        &credential.public_key,
+        credential.sign_count,
+        label,
+    )
+    .await?;
+
+    let event = AuditEvent {
+        target: Some(label.to_string()),
+        ..AuditEvent::new(AuditAction::PasskeyRegister)
+    };
+    audit::record(&ctx.env, &req, &admin, event).await;
+
+    Response::from_json(&RedirectResponse::new("/admin/passkeys")).map(|r| r.with_status(201))
+}
+
+/// POST /admin/passkeys/:id/delete - 管理者用：パスキーの削除
+pub async fn delete(mut req: Request, ctx: RouteContext<()>) -> Result<Response> {
+    let admin = match auth::require_admin_form(&mut req, &ctx.env, AdminRole::Viewer).await? {
+        Ok((admin, _)) => admin,
+        Err(response) => return Ok(response),
+    };
+    let (admin, admin_id) = match registrable_admin(admin)? {
+        Ok(result) => result,
+        Err(response) => return Ok(response),
+    };
+
+    let id: i64 = match ctx.param("id").and_then(|v| v.parse::<i32>().ok()).map(i64::from) {
+        Some(id) => id,
+        None => {
+            let html = templates::render_not_found();
+            return Response::from_html(html).map(|r| r.with_status(404));
+        }
+    };
+
+    let db: D1Database = ctx.env.d1("DB")?;
+    db::delete_admin_credential(&db, admin_id, id).await?;
+
+    let event = AuditEvent {
+        target: Some(id.to_string()),
+        ..AuditEvent::new(AuditAction::PasskeyDelete)
+    };
+    audit::record(&ctx.env, &req, &admin, event).await;
+
+    let headers = Headers::new();
+    headers.set("Location", "/admin/passkeys")?;
+    Ok(Response::empty()?.with_status(302).with_headers(headers))
+}
+
+/// POST /admin/login/passkey/options - パスキーログインのオプション発行
+pub async fn login_options(req: Request, ctx: RouteContext<()>) -> Result<Response> {
+    if !auth::check_same_origin(&req)? {
+        return json_error("Cross-origin request", "CSRF_FAILED", 403);
+    }
+    let challenge = webauthn::new_challenge();
+    auth::store_webauthn_challenge(&ctx.env, &challenge, &WebAuthnChallenge::Login).await?;
+
+    let (rp_id, _) = relying_party_from(&req)?;
+    Response::from_json(&webauthn::request_options(&challenge, &rp_id))
+}
+
+/// POST /admin/login/passkey - パスキーによるログイン
+///
+/// パスキーはそれ自体が所持と本人確認を兼ねるため、二要素認証のコード入力は求めない。
+pub async fn login(mut req: Request, ctx: RouteContext<()>) -> Result<Response> {
+    if !auth::check_same_origin(&req)? {
+        return json_error("Cross-origin request", "CSRF_FAILED", 403);
+    }
+    let kv = ctx.env.kv("RATE_LIMIT")?;
+    let client = rate_limit::login_client_key(&req, &ctx.env).await?;
+    let now = now_unix();
+
+    let status = login_guard::check(&kv, &client, now).await?;
+    if status.retry_after.is_some() {
+        return json_error("Too many failed attempts", "TOO_MANY_ATTEMPTS", 429);
+    }
+    login_guard::throttle(&status).await;
+
+    let body: LoginRequest = match req.json().await {
+        Ok(body) => body,
+        Err(_) => return json_error("Invalid JSON", "BAD_REQUEST", 400),
+    };
+
+    if auth::take_webauthn_challenge(&ctx.env, &body.challenge).await?
+        != Some(WebAuthnChallenge::Login)
+    {
+        return json_error("Challenge expired", "CHALLENGE_EXPIRED", 400);
+    }
+
+    let db: D1Database = ctx.env.d1("DB")?;
+    let (rp_id, origin) = relying_party_from(&req)?;
+    let rp = RelyingParty {
+        id: &rp_id,
+        origin: &origin,
+    };
+
+    let verified = match db::get_admin_credential(&db, &body.credential_id).await? {
+        Some(credential) => {
+            let decoded = (
+                b64url_decode(&body.client_data_json),
+                b64url_decode(&body.authenticator_data),
+                b64url_decode(&body.signature),
+                b64url_decode(&credential.public_key),
+            );
+            match decoded {
+                (Some(client_data), Some(auth_data), Some(signature), Some(public_key)) => {
+                    webauthn::verify_authentication(
+                        &client_data,
+                        &auth_data,
+                        &signature,
+                        &public_key,
+                        u32::try_from(credential.sign_count).unwrap_or(0),
+                        &body.challenge,
+                        &rp,
+                    )
+                    .map_err(|e| worker::console_warn!("Passkey login rejected: {:?}", e))
+                    .ok()
+                    .map(|sign_count| (credential, sign_count))
+                }
+                _ => None,
+            }
+        }
+        None => None,
+    };
+
+    let Some((credential, sign_count)) = verified else {
+        login_guard::record_failure(&kv, &client, now).await?;
+        return json_error("Passkey verification failed", "WEBAUTHN_FAILED", 401);
+    };
+
+    let Some(admin) = db::get_admin(&db, credential.admin_id).await? else {
+        login_guard::record_failure(&kv, &client, now).await?;
+        return json_error("Passkey verification failed", "WEBAUTHN_FAILED", 401);
+    };
+
+    db::update_admin_credential_usage(&db, credential.id, sign_count).await?;
+    if let Err(e) = login_guard::record_success(&kv, &client).await {
+        worker::console_error!("Failed to reset login failures: {:?}", e);
+    }
+
+    let identity = AdminIdentity {
+        admin_id: Some(admin.id),
+        username: admin.username,
+        role: admin.role,
+        csrf_token: None,
+    };
+    let session_id = auth::create_session(&ctx.env, &identity).await?;
+    audit::record(&ctx.env, &req, &identity, AuditEvent::new(AuditAction::Login)).await;
+    let is_secure = req.url()?.scheme() == "https";
+
+    let mut response = Response::from_json(&RedirectResponse::new("/admin/versions"))?;
+    response.headers_mut().set(
+        "Set-Cookie",
+        &auth::create_auth_cookie(&session_id, is_secure),
+    )?;
+    Ok(response)
+}
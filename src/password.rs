@@ -0,0 +1,109 @@
+use base64::engine::general_purpose::STANDARD_NO_PAD;
+use base64::Engine;
+use sha2::Sha256;
+
+use crate::auth::constant_time_eq;
+use crate::crypto::random_bytes;
+
+const SCHEME: &str = "pbkdf2-sha256";
+/// ハッシュの反復回数（保存形式に含めるため、後から変更しても既存ハッシュは検証できる）
+const ITERATIONS: u32 = 100_000;
+const SALT_LENGTH: usize = 16;
+const HASH_LENGTH: usize = 32;
+
+fn derive(password: &str, salt: &[u8], iterations: u32) -> [u8; HASH_LENGTH] {
+    let mut out = [0u8; HASH_LENGTH];
+    pbkdf2::pbkdf2_hmac::<Sha256>(password.as_bytes(), salt, iterations, &mut out);
+    out
+}
+
+/// 指定したソルトでパスワードをハッシュ化（純粋関数）
+///
+/// 形式: `pbkdf2-sha256$<反復回数>$<ソルト>$<ハッシュ>`（Base64）
+fn hash_password_with_salt(password: &str, salt: &[u8], iterations: u32) -> String {
+    format!(
+        "{}${}${}${}",
+        SCHEME,
+        iterations,
+        STANDARD_NO_PAD.encode(salt),
+        STANDARD_NO_PAD.encode(derive(password, salt, iterations))
+    )
+}
+
+/// ランダムなソルトでパスワードをハッシュ化
+pub fn hash_password(password: &str) -> String {
+    hash_password_with_salt(password, &random_bytes(SALT_LENGTH), ITERATIONS)
+}
+
+/// パスワードが保存済みハッシュと一致するか検証（純粋関数）
+pub fn verify_password(password: &str, stored: &str) -> bool {
+    let mut parts = stored.split('$');
+    let (Some(SCHEME), Some(iterations), Some(salt), Some(hash), None) = (
+        parts.next(),
+        parts.next(),
+        parts.next(),
+        parts.next(),
+        parts.next(),
+    ) else {
+        return false;
+    };
+
+    let Ok(iterations) = iterations.parse::<u32>() else {
+        return false;
+    };
+    let Ok(salt) = STANDARD_NO_PAD.decode(salt) else {
+        return false;
+    };
+
+    let derived = STANDARD_NO_PAD.encode(derive(password, &salt, iterations));
+    constant_time_eq(&derived, hash)
+}
+
+/// 存在しないユーザーでも処理時間を揃えるためのダミー検証
+pub fn dummy_verify(password: &str) {
+    let _ = derive(password, &[0u8; SALT_LENGTH], ITERATIONS);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_hash_password_format() {
+        let hash = hash_password_with_salt("password", b"0123456789abcdef", 1000);
+        let parts: Vec<&str> = hash.split('$').collect();
+        assert_eq!(parts.len(), 4);
+        assert_eq!(parts[0], "pbkdf2-sha256");
+        assert_eq!(parts[1], "1000");
+    }
+
+    #[test]
+    fn test_verify_password_roundtrip() {
+        let hash = hash_password_with_salt("correct horse", b"0123456789abcdef", 1000);
+        assert!(verify_password("correct horse", &hash));
+        assert!(!verify_password("wrong horse", &hash));
+    }
+
+    #[test]
+    fn test_verify_password_known_vector() {
+        // RFC 7914 のPBKDF2-HMAC-SHA256テストベクタ（P="passwd", S="salt", c=1）
+        let hash = format!(
+            "pbkdf2-sha256$1$c2FsdA${}",
+            STANDARD_NO_PAD.encode([
+                0x55, 0xac, 0x04, 0x6e, 0x56, 0xe3, 0x08, 0x9f, 0xec, 0x16, 0x91, 0xc2, 0x25,
+                0x44, 0xb6, 0x05, 0xf9, 0x41, 0x85, 0x21, 0x6d, 0xde, 0x04, 0x65, 0xe6, 0x8b,
+                0x9d, 0x57, 0xc2, 0x0d, 0xac, 0xbc,
+            ])
+        );
+        assert!(verify_password("passwd", &hash));
+    }
+
+    #[test]
+    fn test_verify_password_malformed() {
+        assert!(!verify_password("password", ""));
+        assert!(!verify_password("password", "plain-text"));
+        assert!(!verify_password("password", "md5$1$c2FsdA$AAAA"));
+        assert!(!verify_password("password", "pbkdf2-sha256$x$c2FsdA$AAAA"));
+        assert!(!verify_password("password", "pbkdf2-sha256$1$c2FsdA$AAAA$extra"));
+    }
+}
@@ -0,0 +1,32 @@
+use worker::{Request, Response, Result, RouteContext};
+
+pub use darekagakaku_core::pow::*;
+
+use crate::config::FromEnv;
+use crate::crypto::{random_bytes, to_hex};
+use crate::models::ErrorResponse;
+use crate::rate_limit::{self, KvStateStore, RateLimitPolicy, RateLimiter};
+use crate::reply::IntoResponse;
+use crate::store::D1Store;
+use crate::time::{now_unix, SystemClock};
+use crate::verifier::VerifierKind;
+
+/// POST /api/pow/challenge - プルーフ・オブ・ワークのチャレンジ発行
+///
+/// チャレンジはD1に保存する。確認方式がプルーフ・オブ・ワークでなければ404。
+pub async fn issue_challenge(req: Request, ctx: RouteContext<()>) -> Result<Response> {
+    if VerifierKind::from_env(&ctx.env) != VerifierKind::ProofOfWork {
+        return Response::from_json(&ErrorResponse::not_found()).map(|r| r.with_status(404));
+    }
+
+    let kv = KvStateStore::from_env(&ctx.env)?;
+    let policy = RateLimitPolicy::from_env(&ctx.env);
+    let client = rate_limit::client_key(&req, &ctx.env, &policy).await?;
+    let limiter = RateLimiter::new(&kv, SystemClock, policy);
+    let db = D1Store::from_env(&ctx.env)?;
+    let challenge = to_hex(&random_bytes(CHALLENGE_BYTES));
+    let policy = PowPolicy::from_env(&ctx.env);
+    issue_reply(&db, &limiter, &client, challenge, &policy, now_unix())
+        .await?
+        .into_response()
+}
@@ -1,38 +1,100 @@
+use std::ops::Deref;
+
 use worker::kv::KvStore;
-use worker::{Request, Result};
+use worker::{Env, Error, Request, Result};
+
+pub use darekagakaku_core::rate_limit::*;
 
-const MAX_REQUESTS: u32 = 60;
-const WINDOW_SECONDS: u64 = 3600;
+use crate::audit;
+use crate::config::FromEnv;
+use crate::time::today_jst;
+
+/// レート制限に使うクライアントのキー（純粋関数）
+///
+/// 生のIPアドレスをKVに残さないよう、日付と秘密の鍵でハッシュ化する。
+/// IPが分からないリクエストはUser-Agentで分け、全員が1つのバケツを共有しないようにする。
+fn derive_client_key(
+    ip: Option<&str>,
+    user_agent: &str,
+    key: &str,
+    day: &str,
+    ipv6_prefix_length: u8,
+) -> String {
+    let material = match ip.and_then(|ip| client_network(ip, ipv6_prefix_length)) {
+        Some(network) => format!("ip:{}", network),
+        None => format!("noip:{}", user_agent),
+    };
+    audit::hash_ip(&format!("{}|{}", day, material), key)
+}
 
-/// リクエスト数がレート制限に達しているかチェック（純粋関数）
-fn is_rate_limited(count: u32) -> bool {
-    count >= MAX_REQUESTS
+/// クライアントのキーを作るための秘密の鍵（IP_HASH_KEY）
+///
+/// 日付だけをソルトにするとIPv4の全数探索でキーからIPを逆算できてしまうため、必ず設定する。
+/// KVで日ごとのソルトを共有すると、結果整合のためにisolateごとに別のソルトになりうる。
+pub fn client_hash_key(env: &Env) -> Result<String> {
+    audit::ip_hash_key(env).ok_or_else(|| {
+        log::error!("IP_HASH_KEY is not set; rate limiting and writer counts need it");
+        Error::RustError("IP_HASH_KEY is not set".to_string())
+    })
 }
 
-async fn get_count(kv: &KvStore, key: &str) -> Result<u32> {
-    let count = kv
-        .get(key)
-        .text()
-        .await?
-        .and_then(|v| v.parse().ok())
-        .unwrap_or(0);
-    Ok(count)
+/// リクエストからレート制限に使うクライアントのキーを求める
+pub async fn client_key(req: &Request, env: &Env, policy: &RateLimitPolicy) -> Result<String> {
+    let headers = req.headers();
+    let ip = headers.get("CF-Connecting-IP").ok().flatten();
+    let user_agent = headers.get("User-Agent").ok().flatten().unwrap_or_default();
+    let day = today_jst();
+    let key = client_hash_key(env)?;
+    Ok(derive_client_key(
+        ip.as_deref(),
+        &user_agent,
+        &key,
+        &day,
+        policy.ipv6_prefix_length,
+    ))
 }
 
-pub async fn check_rate_limit(kv: &KvStore, ip: &str) -> Result<bool> {
-    let key = format!("rate:{}", ip);
-    let count = get_count(kv, &key).await?;
-    Ok(is_rate_limited(count))
+/// ログインの失敗を数えるためのクライアントのキー
+///
+/// 書き込みのレート制限と同じ方法で求め、KVのキーに生のIPを残さない。
+pub async fn login_client_key(req: &Request, env: &Env) -> Result<String> {
+    client_key(req, env, &RateLimitPolicy::from_env(env)).await
 }
 
-pub async fn increment_rate_limit(kv: &KvStore, ip: &str) -> Result<()> {
-    let key = format!("rate:{}", ip);
-    let count = get_count(kv, &key).await?;
-    kv.put(&key, (count + 1).to_string())?
-        .expiration_ttl(WINDOW_SECONDS)
-        .execute()
-        .await?;
-    Ok(())
+/// KV上のレート制限の状態
+///
+/// 共通部分のトレイトを実装するための包みで、KvStoreを取る関数にもそのまま渡せる。
+pub struct KvStateStore(KvStore);
+
+impl KvStateStore {
+    /// RATE_LIMITバインディングから作る
+    pub fn from_env(env: &Env) -> Result<Self> {
+        Ok(Self(env.kv("RATE_LIMIT")?))
+    }
+}
+
+impl Deref for KvStateStore {
+    type Target = KvStore;
+
+    fn deref(&self) -> &KvStore {
+        &self.0
+    }
+}
+
+impl RateLimitStore for KvStateStore {
+    type Error = Error;
+
+    async fn get_state(&self, key: &str) -> Result<Option<String>> {
+        Ok(self.get(key).text().await?)
+    }
+
+    async fn put_state(&self, key: &str, value: String, ttl_seconds: u64) -> Result<()> {
+        self.put(key, value)?
+            .expiration_ttl(ttl_seconds)
+            .execute()
+            .await?;
+        Ok(())
+    }
 }
 
 pub fn get_client_ip(req: &Request) -> String {
@@ -48,30 +110,29 @@ mod tests {
     use super::*;
 
     #[test]
-    fn test_max_requests_constant() {
-        assert_eq!(MAX_REQUESTS, 60);
-    }
+    fn test_derive_client_key() {
+        let key = "secret";
+        let a = derive_client_key(Some("2001:db8::1"), "", key, "2025-01-15", 64);
+        let b = derive_client_key(Some("2001:db8::ffff"), "", key, "2025-01-15", 64);
+        let c = derive_client_key(Some("2001:db8:0:1::1"), "", key, "2025-01-15", 64);
+        // 同じ/64は同じクライアント
+        assert_eq!(a, b);
+        assert_ne!(a, c);
+        assert!(!a.contains("2001"));
+        // 日付が変わるとキーも変わる
+        assert_ne!(
+            a,
+            derive_client_key(Some("2001:db8::1"), "", key, "2025-01-16", 64)
+        );
 
-    #[test]
-    fn test_window_seconds_constant() {
-        assert_eq!(WINDOW_SECONDS, 3600);
-    }
+        let ua1 = derive_client_key(None, "agent-1", key, "2025-01-15", 64);
+        let ua2 = derive_client_key(Some("unknown"), "agent-2", key, "2025-01-15", 64);
+        assert_ne!(ua1, ua2);
 
-    #[test]
-    fn test_is_rate_limited_under_limit() {
-        assert!(!is_rate_limited(0));
-        assert!(!is_rate_limited(59));
-    }
-
-    #[test]
-    fn test_is_rate_limited_at_limit() {
-        assert!(is_rate_limited(60));
-    }
-
-    #[test]
-    fn test_is_rate_limited_over_limit() {
-        assert!(is_rate_limited(61));
-        assert!(is_rate_limited(100));
-        assert!(is_rate_limited(u32::MAX));
+        // 鍵が違えば同じIPでも別のキーになる
+        assert_ne!(
+            a,
+            derive_client_key(Some("2001:db8::1"), "", "other", "2025-01-15", 64)
+        );
     }
 }
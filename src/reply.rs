@@ -0,0 +1,22 @@
+use worker::{Response, Result};
+
+pub use darekagakaku_core::reply::{Reply, ReplyBody};
+
+/// ルーティングの境界でWorkersのResponseに変換する
+pub trait IntoResponse {
+    fn into_response(self) -> Result<Response>;
+}
+
+impl IntoResponse for Reply {
+    fn into_response(self) -> Result<Response> {
+        let response = match self.body {
+            ReplyBody::Json(value) => Response::from_json(&value)?,
+            ReplyBody::Html(html) => Response::from_html(html)?,
+        };
+        let mut response = response.with_status(self.status);
+        for (name, value) in &self.headers {
+            response.headers_mut().set(name, value)?;
+        }
+        Ok(response)
+    }
+}
@@ -0,0 +1,79 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use worker::{Env, Method, Request, Response, Result};
+
+use crate::api;
+use crate::db;
+use crate::migrations::{self, SchemaStatus};
+use crate::reply::IntoResponse;
+
+/// 一度書き込めると確認できたら、isolateが生きている間は確かめ直さない
+static SCHEMA_WRITABLE: AtomicBool = AtomicBool::new(false);
+
+/// スキーマが古くても受け付ける書き込みのパス
+///
+/// ログアウトはKVのセッションを消すだけで、D1への書き込み（監査ログ）は失敗しても続ける。
+/// それ以外の書き込みはログインを含めてD1を書き換えるため、すべて止める。
+const SCHEMA_INDEPENDENT_PATHS: &[&str] = &["/admin/logout"];
+
+/// スキーマを確かめるべき書き込みのリクエストか
+fn writes_data(method: &Method, path: &str) -> bool {
+    if matches!(method, Method::Get | Method::Head | Method::Options) {
+        return false;
+    }
+    !SCHEMA_INDEPENDENT_PATHS.contains(&path)
+}
+
+/// データを書き換えるリクエストで、スキーマが古ければ503を返す
+///
+/// Workersには起動時の処理がないため、最初の書き込みで`wrangler d1 migrations apply`の
+/// 記録を確かめる。古い間は毎回確かめ直し、マイグレーションを当てればそのまま書き込める。
+/// 記録を読めないときも503にし、リクエストの処理ではスキーマを書き換えない。
+pub async fn guard_writes(req: &Request, env: &Env) -> Result<Option<Response>> {
+    if SCHEMA_WRITABLE.load(Ordering::Relaxed) || !writes_data(&req.method(), &req.path()) {
+        return Ok(None);
+    }
+
+    let db = env.d1("DB")?;
+    let status = match db::list_applied_migrations(&db).await {
+        Ok(applied) => migrations::status(&applied),
+        Err(e) => {
+            log::error!("Failed to read schema_migrations: {:?}", e);
+            return api::schema_outdated_reply()?.into_response().map(Some);
+        }
+    };
+    if !status.is_writable() {
+        log::error!("Schema is out of date: {:?}", status);
+        return api::schema_outdated_reply()?.into_response().map(Some);
+    }
+    if status != SchemaStatus::Current {
+        log::warn!("Schema is newer than this build: {:?}", status);
+    }
+    SCHEMA_WRITABLE.store(true, Ordering::Relaxed);
+    Ok(None)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_writes_data() {
+        assert!(writes_data(&Method::Post, "/api/today"));
+        assert!(!writes_data(&Method::Get, "/api/today"));
+        assert!(!writes_data(&Method::Head, "/api/today"));
+        assert!(writes_data(&Method::Post, "/admin/entries/2025-01-15/revert"));
+        assert!(writes_data(&Method::Post, "/admin/moderation/3"));
+        assert!(writes_data(&Method::Post, "/admin/api-keys/3/revoke"));
+        assert!(writes_data(&Method::Post, "/api/admin/restore"));
+        // ログインや管理者・認証手段の変更もD1を書き換える
+        assert!(writes_data(&Method::Post, "/admin/login"));
+        assert!(writes_data(&Method::Post, "/admin/login/passkey"));
+        assert!(writes_data(&Method::Post, "/admin/admins/3/delete"));
+        assert!(writes_data(&Method::Post, "/admin/totp/enable"));
+        assert!(writes_data(&Method::Post, "/admin/passkeys"));
+
+        // スキーマが古くてもログアウトはできる
+        assert!(!writes_data(&Method::Post, "/admin/logout"));
+    }
+}
@@ -0,0 +1,191 @@
+use std::ops::Deref;
+
+use worker::d1::D1Database;
+use worker::{Env, Error, Result};
+
+pub use darekagakaku_core::store::{
+    ApiKeyStore, ClearRange, DiaryStore, ImportWrite, ModerationStore, PowStore,
+};
+
+use crate::db;
+use crate::entry_stats::SaveActivity;
+use crate::models::{
+    ApiKey, ContentFilter, DiaryEntry, DiaryVersion, FilterAction, ModerationItem,
+    ModerationResolution,
+};
+use crate::stats::StatsTotals;
+
+/// D1上の日記の保存先
+///
+/// 共通部分のトレイトを実装するための包みで、D1Databaseを取るdb::の関数にもそのまま渡せる。
+pub struct D1Store(D1Database);
+
+impl D1Store {
+    /// DBバインディングから作る
+    pub fn from_env(env: &Env) -> Result<Self> {
+        Ok(Self(env.d1("DB")?))
+    }
+}
+
+impl Deref for D1Store {
+    type Target = D1Database;
+
+    fn deref(&self) -> &D1Database {
+        &self.0
+    }
+}
+
+impl DiaryStore for D1Store {
+    type Error = Error;
+
+    async fn get_entry(&self, date: &str) -> Result<Option<DiaryEntry>> {
+        db::get_entry(self, date).await
+    }
+
+    async fn upsert_entry(&self, date: &str, content: &str) -> Result<()> {
+        db::upsert_entry(self, date, content).await
+    }
+
+    async fn list_versions(&self, date: &str) -> Result<Vec<DiaryVersion>> {
+        db::list_versions(self, date).await
+    }
+
+    async fn get_version(&self, date: &str, version: i32) -> Result<Option<DiaryVersion>> {
+        db::get_version(self, date, version).await
+    }
+
+    async fn redact_version(&self, date: &str, version: i32, replacement: &str) -> Result<()> {
+        db::redact_version(self, date, version, replacement).await
+    }
+
+    async fn list_entries_before(&self, date: &str, limit: i32) -> Result<Vec<DiaryEntry>> {
+        db::list_past_entries(self, date, limit).await
+    }
+
+    async fn list_entries_after(&self, date: &str, limit: i32) -> Result<Vec<DiaryEntry>> {
+        db::list_entries_after(self, date, limit).await
+    }
+
+    async fn list_versions_after(&self, id: i64, limit: i32) -> Result<Vec<DiaryVersion>> {
+        db::list_versions_after(self, id, limit).await
+    }
+
+    async fn write_import(&self, writes: &[ImportWrite]) -> Result<()> {
+        db::write_import(self, writes).await
+    }
+
+    async fn stats_totals(&self, limit: i32) -> Result<StatsTotals> {
+        db::stats_totals(self, limit).await
+    }
+
+    async fn record_save(&self, date: &str, writer: &str) -> Result<()> {
+        db::record_save(self, date, writer).await
+    }
+
+    async fn save_activity(&self, date: &str) -> Result<SaveActivity> {
+        db::save_activity(self, date).await
+    }
+
+    async fn compact_saves(&self, before: &str) -> Result<()> {
+        db::compact_saves(self, before).await
+    }
+}
+
+impl ModerationStore for D1Store {
+    type Error = Error;
+
+    async fn list_content_filters(&self) -> Result<Vec<ContentFilter>> {
+        db::list_content_filters(self).await
+    }
+
+    async fn insert_moderation_item(
+        &self,
+        date: &str,
+        content: &str,
+        previous_content: Option<&str>,
+        action: FilterAction,
+        reasons: &str,
+    ) -> Result<()> {
+        db::insert_moderation_item(self, date, content, previous_content, action, reasons).await
+    }
+
+    async fn list_pending_moderation(&self, limit: i32) -> Result<Vec<ModerationItem>> {
+        db::list_pending_moderation(self, limit).await
+    }
+
+    async fn get_moderation_item(&self, id: i64) -> Result<Option<ModerationItem>> {
+        db::get_moderation_item(self, id).await
+    }
+
+    async fn resolve_moderation_item(
+        &self,
+        id: i64,
+        resolution: ModerationResolution,
+        resolved_by: &str,
+    ) -> Result<bool> {
+        db::resolve_moderation_item(self, id, resolution, resolved_by).await
+    }
+
+    async fn redact_moderation_content(&self, id: i64, replacement: &str) -> Result<()> {
+        db::redact_moderation_content(self, id, replacement).await
+    }
+
+    async fn redact_versions_with_content(
+        &self,
+        date: &str,
+        content: &str,
+        replacement: &str,
+    ) -> Result<()> {
+        db::redact_versions_with_content(self, date, content, replacement).await
+    }
+}
+
+impl ApiKeyStore for D1Store {
+    type Error = Error;
+
+    async fn get_api_key_by_hash(&self, key_hash: &str) -> Result<Option<ApiKey>> {
+        db::get_api_key_by_hash(self, key_hash).await
+    }
+
+    async fn consume_api_key_quota(
+        &self,
+        key_id: i64,
+        date: &str,
+        daily_quota: i64,
+    ) -> Result<bool> {
+        db::consume_api_key_quota(self, key_id, date, daily_quota).await
+    }
+
+    async fn record_api_key_write(
+        &self,
+        key_id: i64,
+        date: &str,
+        content_hash: &str,
+    ) -> Result<()> {
+        db::record_api_key_write(self, key_id, date, content_hash).await
+    }
+}
+
+impl PowStore for D1Store {
+    type Error = Error;
+
+    async fn insert_pow_challenge(
+        &self,
+        challenge: &str,
+        client: &str,
+        difficulty: u32,
+        expires_at: i64,
+        now: i64,
+    ) -> Result<()> {
+        db::insert_pow_challenge(self, challenge, client, difficulty, expires_at, now).await
+    }
+
+    async fn take_pow_challenge(
+        &self,
+        challenge: &str,
+        client: &str,
+        now: i64,
+    ) -> Result<Option<u32>> {
+        db::take_pow_challenge(self, challenge, client, now).await
+    }
+}
@@ -0,0 +1,266 @@
+use hmac::{Hmac, Mac};
+use sha1::Sha1;
+use sha2::{Digest, Sha256};
+
+use crate::auth::constant_time_eq;
+use crate::crypto::{random_bytes, to_hex};
+
+/// RFC 6238 の時間ステップ（秒）
+const STEP_SECONDS: i64 = 30;
+const DIGITS: u32 = 6;
+/// 時計のずれを許容する前後のステップ数
+const ALLOWED_DRIFT_STEPS: i64 = 1;
+const SECRET_LENGTH: usize = 20;
+const RECOVERY_CODE_COUNT: usize = 10;
+
+const BASE32_ALPHABET: &[u8; 32] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZ234567";
+
+/// RFC 4648 Base32でエンコード（パディングなし、純粋関数）
+pub fn base32_encode(data: &[u8]) -> String {
+    let mut out = String::new();
+    let mut buffer: u32 = 0;
+    let mut bits = 0;
+    for &byte in data {
+        buffer = (buffer << 8) | byte as u32;
+        bits += 8;
+        while bits >= 5 {
+            bits -= 5;
+            out.push(BASE32_ALPHABET[((buffer >> bits) & 0x1f) as usize] as char);
+        }
+    }
+    if bits > 0 {
+        out.push(BASE32_ALPHABET[((buffer << (5 - bits)) & 0x1f) as usize] as char);
+    }
+    out
+}
+
+/// RFC 4648 Base32をデコード（空白・パディング・小文字を許容、純粋関数）
+pub fn base32_decode(s: &str) -> Option<Vec<u8>> {
+    let mut out = Vec::new();
+    let mut buffer: u32 = 0;
+    let mut bits = 0;
+    for c in s.chars().filter(|c| !c.is_whitespace() && *c != '=') {
+        let value = BASE32_ALPHABET
+            .iter()
+            .position(|&a| a as char == c.to_ascii_uppercase())? as u32;
+        buffer = (buffer << 5) | value;
+        bits += 5;
+        if bits >= 8 {
+            bits -= 8;
+            out.push((buffer >> bits) as u8);
+        }
+    }
+    Some(out)
+}
+
+/// 新しいTOTPシークレットを生成（Base32）
+pub fn generate_secret() -> String {
+    base32_encode(&random_bytes(SECRET_LENGTH))
+}
+
+/// RFC 4226 HOTP値を計算（純粋関数）
+fn hotp(secret: &[u8], counter: u64, digits: u32) -> u32 {
+    let mut mac = Hmac::<Sha1>::new_from_slice(secret).expect("HMAC accepts any key length");
+    mac.update(&counter.to_be_bytes());
+    let hash = mac.finalize().into_bytes();
+
+    let offset = (hash[hash.len() - 1] & 0x0f) as usize;
+    let binary = u32::from_be_bytes([
+        hash[offset] & 0x7f,
+        hash[offset + 1],
+        hash[offset + 2],
+        hash[offset + 3],
+    ]);
+    binary % 10u32.pow(digits)
+}
+
+fn step_at(unix_time: i64) -> i64 {
+    unix_time.div_euclid(STEP_SECONDS)
+}
+
+fn format_code(code: u32, digits: u32) -> String {
+    format!("{:0width$}", code, width = digits as usize)
+}
+
+/// 指定した時間ステップのTOTPコードを計算（純粋関数）
+fn code_for_step(secret: &[u8], step: i64) -> String {
+    format_code(hotp(secret, step as u64, DIGITS), DIGITS)
+}
+
+/// TOTPコードを検証し、一致した時間ステップを返す（純粋関数）
+///
+/// `last_used_step` 以前のステップは再利用とみなして拒否する。
+pub fn verify_totp(
+    secret_base32: &str,
+    code: &str,
+    unix_time: i64,
+    last_used_step: Option<i64>,
+) -> Option<i64> {
+    let secret = base32_decode(secret_base32)?;
+    let code: String = code.chars().filter(|c| !c.is_whitespace()).collect();
+    if code.len() != DIGITS as usize || !code.chars().all(|c| c.is_ascii_digit()) {
+        return None;
+    }
+
+    let current = step_at(unix_time);
+    (-ALLOWED_DRIFT_STEPS..=ALLOWED_DRIFT_STEPS)
+        .map(|drift| current + drift)
+        .filter(|step| last_used_step.is_none_or(|last| *step > last))
+        .find(|step| constant_time_eq(&code_for_step(&secret, *step), &code))
+}
+
+/// 認証アプリに登録するための otpauth:// URI を生成（純粋関数）
+pub fn provisioning_uri(issuer: &str, account: &str, secret_base32: &str) -> String {
+    format!(
+        "otpauth://totp/{issuer}:{account}?secret={secret}&issuer={issuer}&algorithm=SHA1&digits={digits}&period={period}",
+        issuer = percent_encode(issuer),
+        account = percent_encode(account),
+        secret = secret_base32,
+        digits = DIGITS,
+        period = STEP_SECONDS,
+    )
+}
+
+fn percent_encode(s: &str) -> String {
+    s.bytes()
+        .map(|b| match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' => {
+                (b as char).to_string()
+            }
+            _ => format!("%{:02X}", b),
+        })
+        .collect()
+}
+
+/// provisioning URIのQRコードをSVGで生成
+pub fn qr_svg(uri: &str) -> Option<String> {
+    let code = qrcode::QrCode::new(uri.as_bytes()).ok()?;
+    Some(
+        code.render::<qrcode::render::svg::Color>()
+            .min_dimensions(200, 200)
+            .build(),
+    )
+}
+
+/// リカバリーコードを生成（`xxxx-xxxx-xxxx` 形式）
+pub fn generate_recovery_codes() -> Vec<String> {
+    (0..RECOVERY_CODE_COUNT)
+        .map(|_| {
+            let hex = to_hex(&random_bytes(6));
+            format!("{}-{}-{}", &hex[0..4], &hex[4..8], &hex[8..12])
+        })
+        .collect()
+}
+
+/// リカバリーコードを保存用にハッシュ化（純粋関数）
+///
+/// 十分なエントロピーを持つため、ソルトなしのSHA-256で保存する。
+pub fn hash_recovery_code(code: &str) -> String {
+    let normalized: String = code
+        .chars()
+        .filter(|c| c.is_ascii_alphanumeric())
+        .map(|c| c.to_ascii_lowercase())
+        .collect();
+    to_hex(&Sha256::digest(normalized.as_bytes()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // RFC 6238 付録B のSHA-1用シークレット
+    const RFC_SECRET: &[u8] = b"12345678901234567890";
+
+    #[test]
+    fn test_base32_roundtrip() {
+        assert_eq!(base32_encode(b"foobar"), "MZXW6YTBOI");
+        assert_eq!(base32_decode("MZXW6YTBOI").unwrap(), b"foobar");
+        assert_eq!(base32_decode("mzxw 6ytb oi======").unwrap(), b"foobar");
+        assert_eq!(base32_decode(&base32_encode(RFC_SECRET)).unwrap(), RFC_SECRET);
+    }
+
+    #[test]
+    fn test_base32_decode_invalid() {
+        assert!(base32_decode("MZXW1").is_none());
+    }
+
+    #[test]
+    fn test_hotp_rfc4226_vectors() {
+        let expected = [755224, 287082, 359152, 969429, 338314];
+        for (counter, code) in expected.iter().enumerate() {
+            assert_eq!(hotp(RFC_SECRET, counter as u64, 6), *code);
+        }
+    }
+
+    #[test]
+    fn test_totp_rfc6238_vectors() {
+        let vectors = [
+            (59, 94287082),
+            (1111111109, 7081804),
+            (1111111111, 14050471),
+            (1234567890, 89005924),
+            (2000000000, 69279037),
+        ];
+        for (time, code) in vectors {
+            assert_eq!(hotp(RFC_SECRET, step_at(time) as u64, 8), code);
+        }
+        assert_eq!(code_for_step(RFC_SECRET, step_at(59)), "287082");
+    }
+
+    #[test]
+    fn test_verify_totp_accepts_current_and_drift() {
+        let secret = base32_encode(RFC_SECRET);
+        let now = 1_700_000_000;
+        let current = code_for_step(RFC_SECRET, step_at(now));
+        let previous = code_for_step(RFC_SECRET, step_at(now - 30));
+        assert_eq!(verify_totp(&secret, &current, now, None), Some(step_at(now)));
+        assert_eq!(verify_totp(&secret, &previous, now, None), Some(step_at(now) - 1));
+        let stale = code_for_step(RFC_SECRET, step_at(now - 90));
+        assert_eq!(verify_totp(&secret, &stale, now, None), None);
+    }
+
+    #[test]
+    fn test_verify_totp_rejects_replay() {
+        let secret = base32_encode(RFC_SECRET);
+        let now = 1_700_000_000;
+        let code = code_for_step(RFC_SECRET, step_at(now));
+        assert_eq!(verify_totp(&secret, &code, now, Some(step_at(now))), None);
+    }
+
+    #[test]
+    fn test_verify_totp_rejects_malformed() {
+        let secret = base32_encode(RFC_SECRET);
+        assert_eq!(verify_totp(&secret, "12345", 59, None), None);
+        assert_eq!(verify_totp(&secret, "abcdef", 59, None), None);
+        assert_eq!(verify_totp("not base32!", "287082", 59, None), None);
+        assert_eq!(verify_totp(&secret, "287 082", 59, None), Some(1));
+    }
+
+    #[test]
+    fn test_provisioning_uri() {
+        let uri = provisioning_uri("誰かが書く日記", "alice", "JBSWY3DPEHPK3PXP");
+        assert!(uri.starts_with("otpauth://totp/%E8%AA%B0"));
+        assert!(uri.contains(":alice?secret=JBSWY3DPEHPK3PXP&"));
+        assert!(uri.contains("digits=6&period=30"));
+    }
+
+    #[test]
+    fn test_qr_svg() {
+        let svg = qr_svg("otpauth://totp/test:alice?secret=JBSWY3DPEHPK3PXP").unwrap();
+        assert!(svg.contains("<svg"));
+    }
+
+    #[test]
+    fn test_recovery_codes() {
+        let codes = generate_recovery_codes();
+        assert_eq!(codes.len(), RECOVERY_CODE_COUNT);
+        assert_eq!(codes[0].len(), 14);
+        assert_ne!(codes[0], codes[1]);
+    }
+
+    #[test]
+    fn test_hash_recovery_code_normalizes() {
+        assert_eq!(hash_recovery_code("abcd-ef01-2345"), hash_recovery_code("ABCD EF01 2345"));
+        assert_ne!(hash_recovery_code("abcd-ef01-2345"), hash_recovery_code("abcd-ef01-2346"));
+    }
+}
@@ -1,91 +1,125 @@
-use serde::{Deserialize, Serialize};
+use std::time::Duration;
+
 use worker::*;
 
-#[derive(Serialize)]
-#[cfg_attr(test, derive(Debug, PartialEq))]
-struct TurnstileRequest {
-    secret: String,
-    response: String,
-    remoteip: Option<String>,
-}
+pub use darekagakaku_core::turnstile::*;
+
+use crate::crypto::random_bytes;
+
+/// siteverifyの応答を待つ上限
+const TIMEOUT: Duration = Duration::from_secs(5);
+/// 通信エラー時は同じ冪等キーで1回だけ再試行する
+const MAX_ATTEMPTS: u32 = 2;
 
-#[derive(Deserialize)]
-#[cfg_attr(test, derive(Debug, PartialEq))]
-struct TurnstileResponse {
-    success: bool,
+/// トークンの発行元として照合する値（CANONICAL_HOSTが設定されていればホスト名も照合する）
+pub fn expectation_from_env<'a>(
+    env: &Env,
+    action: &'a str,
+    cdata: Option<&'a str>,
+) -> TurnstileExpectation<'a> {
+    TurnstileExpectation {
+        hostname: env.var("CANONICAL_HOST").ok().map(|v| v.to_string()),
+        action,
+        cdata,
+    }
 }
 
-pub async fn verify_turnstile(secret: &str, token: &str, ip: Option<&str>) -> Result<bool> {
-    let body = TurnstileRequest {
-        secret: secret.to_string(),
-        response: token.to_string(),
-        remoteip: ip.map(|s| s.to_string()),
-    };
+/// 再試行しても二重に検証されないよう、リクエストごとにUUID v4を振る
+fn new_idempotency_key() -> String {
+    let mut bytes = random_bytes(16);
+    bytes[6] = (bytes[6] & 0x0f) | 0x40;
+    bytes[8] = (bytes[8] & 0x3f) | 0x80;
+    let hex = crate::crypto::to_hex(&bytes);
+    format!(
+        "{}-{}-{}-{}-{}",
+        &hex[0..8],
+        &hex[8..12],
+        &hex[12..16],
+        &hex[16..20],
+        &hex[20..32]
+    )
+}
 
+async fn siteverify(body: &TurnstileRequest) -> Result<TurnstileResponse> {
     let headers = Headers::new();
     headers.set("Content-Type", "application/json")?;
 
     let req = Request::new_with_init(
-        "https://challenges.cloudflare.com/turnstile/v0/siteverify",
+        SITEVERIFY_URL,
         RequestInit::new()
             .with_method(Method::Post)
             .with_headers(headers)
-            .with_body(Some(serde_json::to_string(&body)?.into())),
+            .with_body(Some(serde_json::to_string(body)?.into())),
     )?;
 
-    let mut resp = Fetch::Request(req).send().await?;
-    let result: TurnstileResponse = resp.json().await?;
+    // 応答が遅い場合は中断する
+    let controller = AbortController::default();
+    let signal = controller.signal();
+    wasm_bindgen_futures::spawn_local(async move {
+        Delay::from(TIMEOUT).await;
+        controller.abort();
+    });
 
-    Ok(result.success)
+    let mut resp = Fetch::Request(req).send_with_signal(&signal).await?;
+    resp.json().await
 }
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-
-    #[test]
-    fn test_turnstile_request_serialization() {
-        let req = TurnstileRequest {
-            secret: "test-secret".to_string(),
-            response: "test-token".to_string(),
-            remoteip: Some("192.168.1.1".to_string()),
-        };
-        let json = serde_json::to_string(&req).unwrap();
-        assert!(json.contains("\"secret\":\"test-secret\""));
-        assert!(json.contains("\"response\":\"test-token\""));
-        assert!(json.contains("\"remoteip\":\"192.168.1.1\""));
+/// Turnstileのトークンを検証
+pub async fn verify_turnstile(
+    secret: &str,
+    token: &str,
+    ip: Option<&str>,
+    expected: &TurnstileExpectation<'_>,
+) -> TurnstileOutcome {
+    if token.is_empty() {
+        return TurnstileOutcome::InvalidToken;
     }
 
-    #[test]
-    fn test_turnstile_request_serialization_without_ip() {
-        let req = TurnstileRequest {
-            secret: "test-secret".to_string(),
-            response: "test-token".to_string(),
-            remoteip: None,
-        };
-        let json = serde_json::to_string(&req).unwrap();
-        assert!(json.contains("\"remoteip\":null"));
-    }
+    let body = TurnstileRequest {
+        secret: secret.to_string(),
+        response: token.to_string(),
+        remoteip: ip.map(|s| s.to_string()),
+        idempotency_key: new_idempotency_key(),
+    };
 
-    #[test]
-    fn test_turnstile_response_deserialization_success() {
-        let json = r#"{"success": true}"#;
-        let resp: TurnstileResponse = serde_json::from_str(json).unwrap();
-        assert!(resp.success);
+    for attempt in 1..=MAX_ATTEMPTS {
+        match siteverify(&body).await {
+            Ok(resp) => {
+                let outcome = outcome_from_response(&resp, expected);
+                if outcome == TurnstileOutcome::Unavailable && attempt < MAX_ATTEMPTS {
+                    continue;
+                }
+                if !outcome.is_success() {
+                    worker::console_warn!(
+                        "Turnstile rejected: {:?} error_codes={:?}",
+                        outcome,
+                        resp.error_codes
+                    );
+                }
+                return outcome;
+            }
+            Err(e) => {
+                worker::console_error!(
+                    "Turnstile verification error (attempt {}): {:?}",
+                    attempt,
+                    e
+                );
+            }
+        }
     }
+    TurnstileOutcome::Unavailable
+}
 
-    #[test]
-    fn test_turnstile_response_deserialization_failure() {
-        let json = r#"{"success": false}"#;
-        let resp: TurnstileResponse = serde_json::from_str(json).unwrap();
-        assert!(!resp.success);
-    }
+#[cfg(test)]
+mod tests {
+    use super::*;
 
     #[test]
-    fn test_turnstile_response_deserialization_with_extra_fields() {
-        // Turnstile APIは追加フィールドを返すことがある
-        let json = r#"{"success": true, "challenge_ts": "2025-01-15T00:00:00Z", "hostname": "example.com"}"#;
-        let resp: TurnstileResponse = serde_json::from_str(json).unwrap();
-        assert!(resp.success);
+    fn test_new_idempotency_key_is_uuid_v4() {
+        let key = new_idempotency_key();
+        assert_eq!(key.len(), 36);
+        assert_eq!(&key[14..15], "4");
+        assert!(matches!(&key[19..20], "8" | "9" | "a" | "b"));
+        assert_ne!(key, new_idempotency_key());
     }
 }
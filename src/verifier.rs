@@ -0,0 +1,167 @@
+use worker::{Env, Error, Result};
+
+pub use darekagakaku_core::verification::{
+    VerificationFailure, Verifier, VerifierKind, VerifierWidget,
+};
+
+use crate::config::FromEnv;
+use crate::hcaptcha;
+use crate::pow;
+use crate::store::D1Store;
+use crate::time::{now_unix, today_jst};
+use crate::turnstile::{self, TurnstileExpectation};
+
+/// 設定された確認方式に合わせて、トップページに埋め込むウィジェットを選ぶ
+pub fn widget_from_env(env: &Env) -> VerifierWidget {
+    let var = |name: &str| env.var(name).map(|v| v.to_string()).unwrap_or_default();
+    match VerifierKind::from_env(env) {
+        VerifierKind::Turnstile => VerifierWidget::Turnstile {
+            site_key: var("TURNSTILE_SITE_KEY"),
+        },
+        VerifierKind::HCaptcha => VerifierWidget::HCaptcha {
+            site_key: var("HCAPTCHA_SITE_KEY"),
+        },
+        VerifierKind::ProofOfWork => VerifierWidget::ProofOfWork,
+        VerifierKind::NoOp => VerifierWidget::NoOp,
+    }
+}
+
+pub struct TurnstileVerifier {
+    secret: String,
+    hostname: Option<String>,
+}
+
+impl Verifier for TurnstileVerifier {
+    type Error = Error;
+
+    async fn verify(
+        &self,
+        token: &str,
+        ip: Option<&str>,
+    ) -> Result<std::result::Result<(), VerificationFailure>> {
+        // ウィジェットには表示した日付をcdataとして埋め込んでいる
+        let today = today_jst();
+        let expected = TurnstileExpectation {
+            hostname: self.hostname.clone(),
+            action: turnstile::DIARY_ACTION,
+            cdata: Some(&today),
+        };
+        let outcome = turnstile::verify_turnstile(&self.secret, token, ip, &expected).await;
+        Ok(outcome.verification())
+    }
+}
+
+pub struct HCaptchaVerifier {
+    verify_url: String,
+    secret: String,
+    site_key: Option<String>,
+    hostname: Option<String>,
+}
+
+impl Verifier for HCaptchaVerifier {
+    type Error = Error;
+
+    async fn verify(
+        &self,
+        token: &str,
+        ip: Option<&str>,
+    ) -> Result<std::result::Result<(), VerificationFailure>> {
+        Ok(hcaptcha::verify_hcaptcha(
+            &self.verify_url,
+            &self.secret,
+            self.site_key.as_deref(),
+            token,
+            ip,
+            self.hostname.as_deref(),
+        )
+        .await)
+    }
+}
+
+pub struct ProofOfWorkVerifier {
+    db: D1Store,
+    /// チャレンジの発行先と照らし合わせるクライアントのキー
+    client: String,
+}
+
+impl Verifier for ProofOfWorkVerifier {
+    type Error = Error;
+
+    async fn verify(
+        &self,
+        token: &str,
+        _ip: Option<&str>,
+    ) -> Result<std::result::Result<(), VerificationFailure>> {
+        pow::verify_solution(&self.db, &self.client, token, now_unix()).await
+    }
+}
+
+pub struct NoOpVerifier;
+
+impl Verifier for NoOpVerifier {
+    type Error = Error;
+
+    async fn verify(
+        &self,
+        _token: &str,
+        _ip: Option<&str>,
+    ) -> Result<std::result::Result<(), VerificationFailure>> {
+        Ok(Ok(()))
+    }
+}
+
+/// 設定で選ばれた確認方式
+pub enum ConfiguredVerifier {
+    Turnstile(TurnstileVerifier),
+    HCaptcha(HCaptchaVerifier),
+    ProofOfWork(ProofOfWorkVerifier),
+    NoOp(NoOpVerifier),
+}
+
+impl ConfiguredVerifier {
+    /// `client` はレート制限と同じクライアントのキー（プルーフ・オブ・ワークで使う）
+    pub fn from_env(env: &Env, client: &str) -> Result<Self> {
+        let hostname = env.var("CANONICAL_HOST").ok().map(|v| v.to_string());
+        let verifier = match VerifierKind::from_env(env) {
+            VerifierKind::Turnstile => Self::Turnstile(TurnstileVerifier {
+                secret: env.secret("TURNSTILE_SECRET_KEY")?.to_string(),
+                hostname,
+            }),
+            VerifierKind::HCaptcha => Self::HCaptcha(HCaptchaVerifier {
+                verify_url: env
+                    .var("HCAPTCHA_VERIFY_URL")
+                    .map(|v| v.to_string())
+                    .unwrap_or_else(|_| hcaptcha::DEFAULT_VERIFY_URL.to_string()),
+                secret: env.secret("HCAPTCHA_SECRET_KEY")?.to_string(),
+                site_key: env.var("HCAPTCHA_SITE_KEY").ok().map(|v| v.to_string()),
+                hostname,
+            }),
+            VerifierKind::ProofOfWork => Self::ProofOfWork(ProofOfWorkVerifier {
+                db: D1Store::from_env(env)?,
+                client: client.to_string(),
+            }),
+            VerifierKind::NoOp => {
+                worker::console_warn!("VERIFIER=none: diary writes are not verified");
+                Self::NoOp(NoOpVerifier)
+            }
+        };
+        Ok(verifier)
+    }
+}
+
+impl Verifier for ConfiguredVerifier {
+    type Error = Error;
+
+    async fn verify(
+        &self,
+        token: &str,
+        ip: Option<&str>,
+    ) -> Result<std::result::Result<(), VerificationFailure>> {
+        match self {
+            Self::Turnstile(v) => v.verify(token, ip).await,
+            Self::HCaptcha(v) => v.verify(token, ip).await,
+            Self::ProofOfWork(v) => v.verify(token, ip).await,
+            Self::NoOp(v) => v.verify(token, ip).await,
+        }
+    }
+}
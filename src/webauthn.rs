@@ -0,0 +1,791 @@
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use base64::Engine;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::auth::constant_time_eq;
+use crate::crypto::random_bytes;
+
+/// COSEアルゴリズム識別子: ECDSA w/ SHA-256
+pub const COSE_ALG_ES256: i64 = -7;
+/// COSEアルゴリズム識別子: EdDSA
+pub const COSE_ALG_EDDSA: i64 = -8;
+
+/// ブラウザに渡すセレモニーのタイムアウト（ミリ秒）
+const CEREMONY_TIMEOUT_MS: u32 = 120_000;
+
+const FLAG_USER_PRESENT: u8 = 0x01;
+const FLAG_ATTESTED_CREDENTIAL_DATA: u8 = 0x40;
+
+/// WebAuthnの検証エラー
+#[derive(Debug, Clone, PartialEq)]
+pub enum WebAuthnError {
+    /// CBORやauthenticatorDataの形式が不正
+    Malformed(&'static str),
+    /// clientDataJSONのtypeが想定と異なる
+    WrongType,
+    ChallengeMismatch,
+    OriginMismatch,
+    RpIdMismatch,
+    UserNotPresent,
+    /// attestation "none" 以外の形式
+    UnsupportedAttestation,
+    UnsupportedAlgorithm,
+    InvalidSignature,
+    /// 署名カウンタが巻き戻った（認証器の複製の可能性）
+    CounterRegression,
+}
+
+/// 登録が完了したクレデンシャル
+#[derive(Debug, Clone, PartialEq)]
+pub struct RegisteredCredential {
+    /// Base64URLエンコードされたクレデンシャルID
+    pub credential_id: String,
+    /// Base64URLエンコードされたCOSE公開鍵
+    pub public_key: String,
+    pub sign_count: u32,
+}
+
+/// 検証に使うRelying Partyの情報
+#[derive(Debug, Clone, PartialEq)]
+pub struct RelyingParty<'a> {
+    pub id: &'a str,
+    pub origin: &'a str,
+}
+
+/// navigator.credentials.create に渡すオプション
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CreationOptions {
+    pub challenge: String,
+    pub rp: RpEntity,
+    pub user: UserEntity,
+    pub pub_key_cred_params: Vec<PubKeyCredParam>,
+    pub timeout: u32,
+    pub attestation: &'static str,
+    pub exclude_credentials: Vec<CredentialDescriptor>,
+    pub authenticator_selection: AuthenticatorSelection,
+}
+
+#[derive(Debug, Serialize)]
+pub struct RpEntity {
+    pub id: String,
+    pub name: String,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct UserEntity {
+    /// Base64URLエンコードされたユーザーハンドル
+    pub id: String,
+    pub name: String,
+    pub display_name: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct PubKeyCredParam {
+    #[serde(rename = "type")]
+    pub kind: &'static str,
+    pub alg: i64,
+}
+
+#[derive(Debug, Serialize)]
+pub struct CredentialDescriptor {
+    #[serde(rename = "type")]
+    pub kind: &'static str,
+    /// Base64URLエンコードされたクレデンシャルID
+    pub id: String,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AuthenticatorSelection {
+    pub resident_key: &'static str,
+    pub user_verification: &'static str,
+}
+
+/// navigator.credentials.get に渡すオプション
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RequestOptions {
+    pub challenge: String,
+    pub rp_id: String,
+    pub timeout: u32,
+    pub user_verification: &'static str,
+}
+
+/// 新しいチャレンジを生成（Base64URL）
+pub fn new_challenge() -> String {
+    b64url_encode(&random_bytes(32))
+}
+
+/// 登録用のオプションを生成（純粋関数）
+pub fn creation_options(
+    challenge: &str,
+    rp_id: &str,
+    rp_name: &str,
+    admin_id: i64,
+    username: &str,
+    existing_credential_ids: &[String],
+) -> CreationOptions {
+    CreationOptions {
+        challenge: challenge.to_string(),
+        rp: RpEntity {
+            id: rp_id.to_string(),
+            name: rp_name.to_string(),
+        },
+        user: UserEntity {
+            id: b64url_encode(&admin_id.to_be_bytes()),
+            name: username.to_string(),
+            display_name: username.to_string(),
+        },
+        pub_key_cred_params: [COSE_ALG_ES256, COSE_ALG_EDDSA]
+            .into_iter()
+            .map(|alg| PubKeyCredParam {
+                kind: "public-key",
+                alg,
+            })
+            .collect(),
+        timeout: CEREMONY_TIMEOUT_MS,
+        attestation: "none",
+        exclude_credentials: existing_credential_ids
+            .iter()
+            .map(|id| CredentialDescriptor {
+                kind: "public-key",
+                id: id.clone(),
+            })
+            .collect(),
+        authenticator_selection: AuthenticatorSelection {
+            resident_key: "preferred",
+            user_verification: "preferred",
+        },
+    }
+}
+
+/// 認証用のオプションを生成（純粋関数）
+///
+/// ユーザー名を入力させずに済むよう、allowCredentialsは空にして発見可能なクレデンシャルを使う。
+pub fn request_options(challenge: &str, rp_id: &str) -> RequestOptions {
+    RequestOptions {
+        challenge: challenge.to_string(),
+        rp_id: rp_id.to_string(),
+        timeout: CEREMONY_TIMEOUT_MS,
+        user_verification: "preferred",
+    }
+}
+
+pub fn b64url_encode(data: &[u8]) -> String {
+    URL_SAFE_NO_PAD.encode(data)
+}
+
+pub fn b64url_decode(s: &str) -> Option<Vec<u8>> {
+    URL_SAFE_NO_PAD.decode(s.trim_end_matches('=')).ok()
+}
+
+/// 最小限のCBOR値（WebAuthnで使う型のみ）
+#[derive(Debug, Clone, PartialEq)]
+enum Cbor {
+    Int(i64),
+    Bytes(Vec<u8>),
+    Text(String),
+    Array(Vec<Cbor>),
+    Map(Vec<(Cbor, Cbor)>),
+    Bool(bool),
+    Null,
+}
+
+impl Cbor {
+    fn get(&self, key: &Cbor) -> Option<&Cbor> {
+        match self {
+            Cbor::Map(entries) => entries.iter().find(|(k, _)| k == key).map(|(_, v)| v),
+            _ => None,
+        }
+    }
+
+    fn get_int_key(&self, key: i64) -> Option<&Cbor> {
+        self.get(&Cbor::Int(key))
+    }
+
+    fn get_text_key(&self, key: &str) -> Option<&Cbor> {
+        self.get(&Cbor::Text(key.to_string()))
+    }
+
+    fn as_int(&self) -> Option<i64> {
+        match self {
+            Cbor::Int(v) => Some(*v),
+            _ => None,
+        }
+    }
+
+    fn as_bytes(&self) -> Option<&[u8]> {
+        match self {
+            Cbor::Bytes(v) => Some(v),
+            _ => None,
+        }
+    }
+
+    fn as_text(&self) -> Option<&str> {
+        match self {
+            Cbor::Text(v) => Some(v),
+            _ => None,
+        }
+    }
+}
+
+/// CBORを1要素デコードし、値と読み込んだバイト数を返す（純粋関数）
+fn decode_cbor(data: &[u8]) -> Result<(Cbor, usize), WebAuthnError> {
+    const MAX_DEPTH: usize = 16;
+    decode_cbor_at(data, 0, MAX_DEPTH)
+}
+
+fn decode_cbor_at(data: &[u8], pos: usize, depth: usize) -> Result<(Cbor, usize), WebAuthnError> {
+    let malformed = WebAuthnError::Malformed("invalid CBOR");
+    if depth == 0 {
+        return Err(WebAuthnError::Malformed("CBOR nested too deeply"));
+    }
+
+    let initial = *data.get(pos).ok_or(malformed.clone())?;
+    let major = initial >> 5;
+    let info = initial & 0x1f;
+    let mut cursor = pos + 1;
+
+    let argument = match info {
+        0..=23 => info as u64,
+        24..=27 => {
+            let len = 1usize << (info - 24);
+            let bytes = data.get(cursor..cursor + len).ok_or(malformed.clone())?;
+            cursor += len;
+            bytes.iter().fold(0u64, |acc, b| (acc << 8) | *b as u64)
+        }
+        _ => return Err(WebAuthnError::Malformed("unsupported CBOR length")),
+    };
+
+    let value = match major {
+        0 => Cbor::Int(i64::try_from(argument).map_err(|_| malformed.clone())?),
+        1 => Cbor::Int(-1 - i64::try_from(argument).map_err(|_| malformed.clone())?),
+        2 | 3 => {
+            let len = usize::try_from(argument).map_err(|_| malformed.clone())?;
+            let end = cursor.checked_add(len).ok_or(malformed.clone())?;
+            let bytes = data.get(cursor..end).ok_or(malformed.clone())?.to_vec();
+            cursor = end;
+            if major == 2 {
+                Cbor::Bytes(bytes)
+            } else {
+                Cbor::Text(String::from_utf8(bytes).map_err(|_| malformed.clone())?)
+            }
+        }
+        4 => {
+            let mut items = Vec::new();
+            for _ in 0..argument {
+                let (item, next) = decode_cbor_at(data, cursor, depth - 1)?;
+                items.push(item);
+                cursor = next;
+            }
+            Cbor::Array(items)
+        }
+        5 => {
+            let mut entries = Vec::new();
+            for _ in 0..argument {
+                let (key, next) = decode_cbor_at(data, cursor, depth - 1)?;
+                let (value, next) = decode_cbor_at(data, next, depth - 1)?;
+                entries.push((key, value));
+                cursor = next;
+            }
+            Cbor::Map(entries)
+        }
+        7 => match info {
+            20 => Cbor::Bool(false),
+            21 => Cbor::Bool(true),
+            22 => Cbor::Null,
+            _ => return Err(WebAuthnError::Malformed("unsupported CBOR simple value")),
+        },
+        _ => return Err(WebAuthnError::Malformed("unsupported CBOR major type")),
+    };
+
+    Ok((value, cursor))
+}
+
+#[derive(Deserialize)]
+struct ClientData {
+    #[serde(rename = "type")]
+    kind: String,
+    challenge: String,
+    origin: String,
+}
+
+/// clientDataJSONの type / challenge / origin を検証（純粋関数）
+fn verify_client_data(
+    client_data_json: &[u8],
+    expected_type: &str,
+    expected_challenge: &str,
+    rp: &RelyingParty,
+) -> Result<(), WebAuthnError> {
+    let client_data: ClientData = serde_json::from_slice(client_data_json)
+        .map_err(|_| WebAuthnError::Malformed("invalid clientDataJSON"))?;
+
+    if client_data.kind != expected_type {
+        return Err(WebAuthnError::WrongType);
+    }
+    if !constant_time_eq(
+        client_data.challenge.trim_end_matches('='),
+        expected_challenge,
+    ) {
+        return Err(WebAuthnError::ChallengeMismatch);
+    }
+    if client_data.origin != rp.origin {
+        return Err(WebAuthnError::OriginMismatch);
+    }
+    Ok(())
+}
+
+/// authenticatorDataの固定長部分
+struct AuthenticatorData<'a> {
+    rp_id_hash: &'a [u8],
+    flags: u8,
+    sign_count: u32,
+    /// attestedCredentialData以降
+    rest: &'a [u8],
+}
+
+fn parse_authenticator_data(data: &[u8]) -> Result<AuthenticatorData<'_>, WebAuthnError> {
+    if data.len() < 37 {
+        return Err(WebAuthnError::Malformed("authenticatorData too short"));
+    }
+    Ok(AuthenticatorData {
+        rp_id_hash: &data[0..32],
+        flags: data[32],
+        sign_count: u32::from_be_bytes([data[33], data[34], data[35], data[36]]),
+        rest: &data[37..],
+    })
+}
+
+fn verify_rp_and_presence(
+    auth_data: &AuthenticatorData,
+    rp: &RelyingParty,
+) -> Result<(), WebAuthnError> {
+    if auth_data.rp_id_hash != Sha256::digest(rp.id.as_bytes()).as_slice() {
+        return Err(WebAuthnError::RpIdMismatch);
+    }
+    if auth_data.flags & FLAG_USER_PRESENT == 0 {
+        return Err(WebAuthnError::UserNotPresent);
+    }
+    Ok(())
+}
+
+/// COSE公開鍵のアルゴリズムを確認（純粋関数）
+fn cose_algorithm(cose_key: &Cbor) -> Result<i64, WebAuthnError> {
+    let alg = cose_key
+        .get_int_key(3)
+        .and_then(Cbor::as_int)
+        .ok_or(WebAuthnError::Malformed("COSE key without alg"))?;
+    match alg {
+        COSE_ALG_ES256 | COSE_ALG_EDDSA => Ok(alg),
+        _ => Err(WebAuthnError::UnsupportedAlgorithm),
+    }
+}
+
+/// 登録（navigator.credentials.create）の応答を検証（純粋関数）
+pub fn verify_registration(
+    client_data_json: &[u8],
+    attestation_object: &[u8],
+    expected_challenge: &str,
+    rp: &RelyingParty,
+) -> Result<RegisteredCredential, WebAuthnError> {
+    verify_client_data(client_data_json, "webauthn.create", expected_challenge, rp)?;
+
+    let (attestation, _) = decode_cbor(attestation_object)?;
+    if attestation.get_text_key("fmt").and_then(Cbor::as_text) != Some("none") {
+        return Err(WebAuthnError::UnsupportedAttestation);
+    }
+    let auth_data_bytes = attestation
+        .get_text_key("authData")
+        .and_then(Cbor::as_bytes)
+        .ok_or(WebAuthnError::Malformed(
+            "attestationObject without authData",
+        ))?;
+
+    let auth_data = parse_authenticator_data(auth_data_bytes)?;
+    verify_rp_and_presence(&auth_data, rp)?;
+    if auth_data.flags & FLAG_ATTESTED_CREDENTIAL_DATA == 0 {
+        return Err(WebAuthnError::Malformed("no attested credential data"));
+    }
+
+    // aaguid(16) + credentialIdLength(2) + credentialId + credentialPublicKey
+    let rest = auth_data.rest;
+    if rest.len() < 18 {
+        return Err(WebAuthnError::Malformed(
+            "attested credential data too short",
+        ));
+    }
+    let id_len = u16::from_be_bytes([rest[16], rest[17]]) as usize;
+    let credential_id = rest
+        .get(18..18 + id_len)
+        .ok_or(WebAuthnError::Malformed("credential id truncated"))?;
+    let key_bytes = &rest[18 + id_len..];
+    let (cose_key, key_len) = decode_cbor(key_bytes)?;
+    cose_algorithm(&cose_key)?;
+
+    Ok(RegisteredCredential {
+        credential_id: b64url_encode(credential_id),
+        public_key: b64url_encode(&key_bytes[..key_len]),
+        sign_count: auth_data.sign_count,
+    })
+}
+
+/// 認証（navigator.credentials.get）の応答を検証し、新しい署名カウンタを返す（純粋関数）
+pub fn verify_authentication(
+    client_data_json: &[u8],
+    authenticator_data: &[u8],
+    signature: &[u8],
+    public_key_cose: &[u8],
+    stored_sign_count: u32,
+    expected_challenge: &str,
+    rp: &RelyingParty,
+) -> Result<u32, WebAuthnError> {
+    verify_client_data(client_data_json, "webauthn.get", expected_challenge, rp)?;
+
+    let auth_data = parse_authenticator_data(authenticator_data)?;
+    verify_rp_and_presence(&auth_data, rp)?;
+
+    let mut signed = authenticator_data.to_vec();
+    signed.extend_from_slice(&Sha256::digest(client_data_json));
+
+    let (cose_key, _) = decode_cbor(public_key_cose)?;
+    verify_signature(&cose_key, &signed, signature)?;
+
+    // カウンタを実装していない認証器は常に0を返す
+    if (auth_data.sign_count != 0 || stored_sign_count != 0)
+        && auth_data.sign_count <= stored_sign_count
+    {
+        return Err(WebAuthnError::CounterRegression);
+    }
+    Ok(auth_data.sign_count)
+}
+
+fn verify_signature(
+    cose_key: &Cbor,
+    message: &[u8],
+    signature: &[u8],
+) -> Result<(), WebAuthnError> {
+    let coordinate = |label: i64| {
+        cose_key
+            .get_int_key(label)
+            .and_then(Cbor::as_bytes)
+            .ok_or(WebAuthnError::Malformed("COSE key without coordinates"))
+    };
+
+    match cose_algorithm(cose_key)? {
+        COSE_ALG_ES256 => {
+            use p256::ecdsa::signature::Verifier;
+            use p256::ecdsa::{Signature, VerifyingKey};
+
+            let (x, y) = (coordinate(-2)?, coordinate(-3)?);
+            if x.len() != 32 || y.len() != 32 {
+                return Err(WebAuthnError::Malformed("invalid P-256 coordinates"));
+            }
+            let mut sec1 = vec![0x04];
+            sec1.extend_from_slice(x);
+            sec1.extend_from_slice(y);
+
+            let key = VerifyingKey::from_sec1_bytes(&sec1)
+                .map_err(|_| WebAuthnError::Malformed("invalid P-256 key"))?;
+            let signature =
+                Signature::from_der(signature).map_err(|_| WebAuthnError::InvalidSignature)?;
+            key.verify(message, &signature)
+                .map_err(|_| WebAuthnError::InvalidSignature)
+        }
+        _ => {
+            use ed25519_dalek::{Signature, VerifyingKey};
+
+            let x: [u8; 32] = coordinate(-2)?
+                .try_into()
+                .map_err(|_| WebAuthnError::Malformed("invalid Ed25519 key"))?;
+            let key = VerifyingKey::from_bytes(&x)
+                .map_err(|_| WebAuthnError::Malformed("invalid Ed25519 key"))?;
+            let signature =
+                Signature::from_slice(signature).map_err(|_| WebAuthnError::InvalidSignature)?;
+            key.verify_strict(message, &signature)
+                .map_err(|_| WebAuthnError::InvalidSignature)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const RP: RelyingParty = RelyingParty {
+        id: "darekagakaku.day",
+        origin: "https://darekagakaku.day",
+    };
+    const CHALLENGE: &str = "dGVzdC1jaGFsbGVuZ2U";
+
+    /// テスト用の簡易CBORエンコーダ
+    fn cbor_head(major: u8, value: u64) -> Vec<u8> {
+        match value {
+            0..=23 => vec![(major << 5) | value as u8],
+            24..=0xff => vec![(major << 5) | 24, value as u8],
+            _ => {
+                let mut out = vec![(major << 5) | 25];
+                out.extend_from_slice(&(value as u16).to_be_bytes());
+                out
+            }
+        }
+    }
+
+    fn cbor_int(v: i64) -> Vec<u8> {
+        if v >= 0 {
+            cbor_head(0, v as u64)
+        } else {
+            cbor_head(1, (-1 - v) as u64)
+        }
+    }
+
+    fn cbor_bytes(b: &[u8]) -> Vec<u8> {
+        let mut out = cbor_head(2, b.len() as u64);
+        out.extend_from_slice(b);
+        out
+    }
+
+    fn cbor_text(s: &str) -> Vec<u8> {
+        let mut out = cbor_head(3, s.len() as u64);
+        out.extend_from_slice(s.as_bytes());
+        out
+    }
+
+    fn cbor_map(entries: Vec<(Vec<u8>, Vec<u8>)>) -> Vec<u8> {
+        let mut out = cbor_head(5, entries.len() as u64);
+        for (k, v) in entries {
+            out.extend(k);
+            out.extend(v);
+        }
+        out
+    }
+
+    fn client_data(kind: &str, challenge: &str, origin: &str) -> Vec<u8> {
+        format!(
+            r#"{{"type":"{}","challenge":"{}","origin":"{}","crossOrigin":false}}"#,
+            kind, challenge, origin
+        )
+        .into_bytes()
+    }
+
+    fn auth_data(
+        rp_id: &str,
+        flags: u8,
+        sign_count: u32,
+        attested: Option<(&[u8], &[u8])>,
+    ) -> Vec<u8> {
+        let mut out = Sha256::digest(rp_id.as_bytes()).to_vec();
+        out.push(flags);
+        out.extend_from_slice(&sign_count.to_be_bytes());
+        if let Some((credential_id, cose_key)) = attested {
+            out.extend_from_slice(&[0u8; 16]);
+            out.extend_from_slice(&(credential_id.len() as u16).to_be_bytes());
+            out.extend_from_slice(credential_id);
+            out.extend_from_slice(cose_key);
+        }
+        out
+    }
+
+    fn es256_key() -> (p256::ecdsa::SigningKey, Vec<u8>) {
+        let signing = p256::ecdsa::SigningKey::from_slice(&[7u8; 32]).unwrap();
+        let point = signing.verifying_key().to_encoded_point(false);
+        let cose = cbor_map(vec![
+            (cbor_int(1), cbor_int(2)),
+            (cbor_int(3), cbor_int(COSE_ALG_ES256)),
+            (cbor_int(-1), cbor_int(1)),
+            (cbor_int(-2), cbor_bytes(point.x().unwrap())),
+            (cbor_int(-3), cbor_bytes(point.y().unwrap())),
+        ]);
+        (signing, cose)
+    }
+
+    fn ed25519_key() -> (ed25519_dalek::SigningKey, Vec<u8>) {
+        let signing = ed25519_dalek::SigningKey::from_bytes(&[9u8; 32]);
+        let cose = cbor_map(vec![
+            (cbor_int(1), cbor_int(1)),
+            (cbor_int(3), cbor_int(COSE_ALG_EDDSA)),
+            (cbor_int(-1), cbor_int(6)),
+            (cbor_int(-2), cbor_bytes(signing.verifying_key().as_bytes())),
+        ]);
+        (signing, cose)
+    }
+
+    fn attestation_object(fmt: &str, auth_data: &[u8]) -> Vec<u8> {
+        cbor_map(vec![
+            (cbor_text("fmt"), cbor_text(fmt)),
+            (cbor_text("attStmt"), cbor_map(vec![])),
+            (cbor_text("authData"), cbor_bytes(auth_data)),
+        ])
+    }
+
+    #[test]
+    fn test_creation_options_serialization() {
+        let options = creation_options(
+            CHALLENGE,
+            RP.id,
+            "誰かが書く日記",
+            1,
+            "alice",
+            &["abc".to_string()],
+        );
+        let json = serde_json::to_value(&options).unwrap();
+        assert_eq!(json["challenge"], CHALLENGE);
+        assert_eq!(json["rp"]["id"], "darekagakaku.day");
+        assert_eq!(json["user"]["displayName"], "alice");
+        assert_eq!(json["attestation"], "none");
+        assert_eq!(json["pubKeyCredParams"][0]["type"], "public-key");
+        assert_eq!(json["pubKeyCredParams"][0]["alg"], -7);
+        assert_eq!(json["pubKeyCredParams"][1]["alg"], -8);
+        assert_eq!(json["excludeCredentials"][0]["id"], "abc");
+    }
+
+    #[test]
+    fn test_request_options_serialization() {
+        let json = serde_json::to_value(request_options(CHALLENGE, RP.id)).unwrap();
+        assert_eq!(json["rpId"], "darekagakaku.day");
+        assert_eq!(json["userVerification"], "preferred");
+    }
+
+    #[test]
+    fn test_decode_cbor_basic_types() {
+        let data = cbor_map(vec![
+            (cbor_int(-7), cbor_text("a")),
+            (cbor_int(300), cbor_bytes(&[1, 2])),
+        ]);
+        let (value, len) = decode_cbor(&data).unwrap();
+        assert_eq!(len, data.len());
+        assert_eq!(value.get_int_key(-7).and_then(Cbor::as_text), Some("a"));
+        assert_eq!(
+            value.get_int_key(300).and_then(Cbor::as_bytes),
+            Some(&[1u8, 2][..])
+        );
+    }
+
+    #[test]
+    fn test_decode_cbor_truncated() {
+        assert!(decode_cbor(&[0x58, 0x05, 0x01]).is_err());
+        assert!(decode_cbor(&[]).is_err());
+    }
+
+    #[test]
+    fn test_verify_registration_es256() {
+        let (_, cose) = es256_key();
+        let auth = auth_data(RP.id, 0x41, 0, Some((b"cred-1", &cose)));
+        let client = client_data("webauthn.create", CHALLENGE, RP.origin);
+
+        let credential =
+            verify_registration(&client, &attestation_object("none", &auth), CHALLENGE, &RP)
+                .unwrap();
+        assert_eq!(credential.credential_id, b64url_encode(b"cred-1"));
+        assert_eq!(b64url_decode(&credential.public_key).unwrap(), cose);
+        assert_eq!(credential.sign_count, 0);
+    }
+
+    #[test]
+    fn test_verify_registration_rejects_mismatches() {
+        let (_, cose) = es256_key();
+        let auth = auth_data(RP.id, 0x41, 0, Some((b"cred-1", &cose)));
+        let attestation = attestation_object("none", &auth);
+
+        let wrong_challenge = client_data("webauthn.create", "b3RoZXI", RP.origin);
+        assert_eq!(
+            verify_registration(&wrong_challenge, &attestation, CHALLENGE, &RP),
+            Err(WebAuthnError::ChallengeMismatch)
+        );
+
+        let wrong_origin = client_data("webauthn.create", CHALLENGE, "https://evil.example");
+        assert_eq!(
+            verify_registration(&wrong_origin, &attestation, CHALLENGE, &RP),
+            Err(WebAuthnError::OriginMismatch)
+        );
+
+        let wrong_type = client_data("webauthn.get", CHALLENGE, RP.origin);
+        assert_eq!(
+            verify_registration(&wrong_type, &attestation, CHALLENGE, &RP),
+            Err(WebAuthnError::WrongType)
+        );
+
+        let client = client_data("webauthn.create", CHALLENGE, RP.origin);
+        let other_rp = auth_data("evil.example", 0x41, 0, Some((b"cred-1", &cose)));
+        assert_eq!(
+            verify_registration(
+                &client,
+                &attestation_object("none", &other_rp),
+                CHALLENGE,
+                &RP
+            ),
+            Err(WebAuthnError::RpIdMismatch)
+        );
+
+        assert_eq!(
+            verify_registration(
+                &client,
+                &attestation_object("packed", &auth),
+                CHALLENGE,
+                &RP
+            ),
+            Err(WebAuthnError::UnsupportedAttestation)
+        );
+    }
+
+    #[test]
+    fn test_verify_authentication_es256() {
+        use p256::ecdsa::signature::Signer;
+
+        let (signing, cose) = es256_key();
+        let auth = auth_data(RP.id, 0x05, 3, None);
+        let client = client_data("webauthn.get", CHALLENGE, RP.origin);
+
+        let mut signed = auth.clone();
+        signed.extend_from_slice(&Sha256::digest(&client));
+        let signature: p256::ecdsa::Signature = signing.sign(&signed);
+        let der = signature.to_der();
+
+        let count = verify_authentication(&client, &auth, der.as_bytes(), &cose, 2, CHALLENGE, &RP)
+            .unwrap();
+        assert_eq!(count, 3);
+
+        assert_eq!(
+            verify_authentication(&client, &auth, der.as_bytes(), &cose, 3, CHALLENGE, &RP),
+            Err(WebAuthnError::CounterRegression)
+        );
+    }
+
+    #[test]
+    fn test_verify_authentication_eddsa() {
+        use ed25519_dalek::Signer;
+
+        let (signing, cose) = ed25519_key();
+        let auth = auth_data(RP.id, 0x01, 0, None);
+        let client = client_data("webauthn.get", CHALLENGE, RP.origin);
+
+        let mut signed = auth.clone();
+        signed.extend_from_slice(&Sha256::digest(&client));
+        let signature = signing.sign(&signed).to_bytes();
+
+        assert_eq!(
+            verify_authentication(&client, &auth, &signature, &cose, 0, CHALLENGE, &RP),
+            Ok(0)
+        );
+
+        let mut tampered = signature;
+        tampered[0] ^= 0xff;
+        assert_eq!(
+            verify_authentication(&client, &auth, &tampered, &cose, 0, CHALLENGE, &RP),
+            Err(WebAuthnError::InvalidSignature)
+        );
+    }
+
+    #[test]
+    fn test_verify_authentication_requires_user_presence() {
+        let (_, cose) = ed25519_key();
+        let auth = auth_data(RP.id, 0x00, 0, None);
+        let client = client_data("webauthn.get", CHALLENGE, RP.origin);
+        assert_eq!(
+            verify_authentication(&client, &auth, &[0u8; 64], &cose, 0, CHALLENGE, &RP),
+            Err(WebAuthnError::UserNotPresent)
+        );
+    }
+}